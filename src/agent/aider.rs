@@ -0,0 +1,66 @@
+use super::{AgentBackend, AgentCommand, SessionOptions};
+
+/// Aider in single-message mode; the prompt is passed as an argument.
+pub struct Aider;
+
+impl AgentBackend for Aider {
+    fn name(&self) -> &str {
+        "aider"
+    }
+
+    fn command(&self, prompt: &str, opts: &SessionOptions<'_>) -> AgentCommand {
+        let mut cmd = AgentCommand::new("aider").arg("--no-pretty");
+        if opts.permission_mode == "plan" && !opts.dangerously_skip_permissions {
+            cmd = cmd.arg("--chat-mode").arg("ask");
+        } else if opts.permission_mode == "acceptEdits" || opts.dangerously_skip_permissions {
+            cmd = cmd.arg("--yes-always");
+        }
+        if opts.continue_session {
+            cmd = cmd.arg("--restore-chat-history");
+        }
+        cmd.model("--model", opts.model).arg("--message").arg(prompt)
+    }
+
+    fn version_command(&self) -> Option<AgentCommand> {
+        Some(AgentCommand::new("aider").arg("--version"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_prompt_as_message() {
+        let opts = SessionOptions {
+            permission_mode: "acceptEdits",
+            continue_session: true,
+            dangerously_skip_permissions: false,
+            model: None,
+            allowed_tools: &[],
+            disallowed_tools: &[],
+        };
+        let cmd = Aider.command("do it", &opts);
+        assert_eq!(
+            cmd.args,
+            ["--no-pretty", "--yes-always", "--restore-chat-history", "--message", "do it"]
+        );
+        assert_eq!(cmd.stdin, None);
+    }
+
+    #[test]
+    fn plan_uses_ask_mode() {
+        let opts = SessionOptions {
+            permission_mode: "plan",
+            continue_session: false,
+            dangerously_skip_permissions: false,
+            model: None,
+            allowed_tools: &[],
+            disallowed_tools: &[],
+        };
+        assert_eq!(
+            Aider.command("p", &opts).args,
+            ["--no-pretty", "--chat-mode", "ask", "--message", "p"]
+        );
+    }
+}
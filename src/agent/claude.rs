@@ -0,0 +1,82 @@
+use super::{AgentBackend, AgentCommand, SessionOptions};
+
+pub struct Claude;
+
+impl AgentBackend for Claude {
+    fn name(&self) -> &str {
+        "claude"
+    }
+
+    fn command(&self, prompt: &str, opts: &SessionOptions<'_>) -> AgentCommand {
+        let mut cmd = AgentCommand::new("claude")
+            .arg("--permission-mode")
+            .arg(opts.permission_mode);
+        if opts.dangerously_skip_permissions {
+            cmd = cmd.arg("--dangerously-skip-permissions");
+        }
+        cmd = cmd
+            .model("--model", opts.model)
+            .list("--allowedTools", opts.allowed_tools)
+            .list("--disallowedTools", opts.disallowed_tools);
+        cmd = if opts.continue_session {
+            cmd.arg("--continue")
+        } else {
+            // One JSON event per message and tool call, ending with the result, usage, and cost;
+            // --print needs --verbose for stream-json
+            cmd.arg("--print").arg("--output-format").arg("stream-json").arg("--verbose")
+        };
+        cmd.stdin(prompt)
+    }
+
+    fn version_command(&self) -> Option<AgentCommand> {
+        Some(AgentCommand::new("claude").arg("--version"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_flags_and_pipes_prompt() {
+        let opts = SessionOptions {
+            permission_mode: "plan",
+            continue_session: false,
+            dangerously_skip_permissions: true,
+            model: Some("opus"),
+            allowed_tools: &[],
+            disallowed_tools: &["WebFetch".into(), "Bash(rm:*)".into()],
+        };
+        let cmd = Claude.command("do it", &opts);
+        assert_eq!(
+            cmd.args,
+            [
+                "--permission-mode",
+                "plan",
+                "--dangerously-skip-permissions",
+                "--model",
+                "opus",
+                "--disallowedTools",
+                "WebFetch,Bash(rm:*)",
+                "--print",
+                "--output-format",
+                "stream-json",
+                "--verbose"
+            ]
+        );
+        assert_eq!(cmd.stdin.as_deref(), Some("do it"));
+    }
+
+    #[test]
+    fn continue_session_replaces_print() {
+        let opts = SessionOptions {
+            permission_mode: "acceptEdits",
+            continue_session: true,
+            dangerously_skip_permissions: false,
+            model: None,
+            allowed_tools: &[],
+            disallowed_tools: &[],
+        };
+        assert_eq!(Claude.command("p", &opts).args.last().unwrap(), "--continue");
+    }
+}
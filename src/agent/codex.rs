@@ -0,0 +1,79 @@
+use super::{AgentBackend, AgentCommand, SessionOptions};
+
+/// OpenAI Codex CLI in non-interactive `exec` mode.
+pub struct Codex;
+
+impl AgentBackend for Codex {
+    fn name(&self) -> &str {
+        "codex"
+    }
+
+    fn command(&self, prompt: &str, opts: &SessionOptions<'_>) -> AgentCommand {
+        let mut cmd = AgentCommand::new("codex").arg("exec");
+        if opts.continue_session {
+            cmd = cmd.arg("resume").arg("--last");
+        }
+        cmd = if opts.dangerously_skip_permissions {
+            cmd.arg("--dangerously-bypass-approvals-and-sandbox")
+        } else {
+            match opts.permission_mode {
+                "plan" => cmd.arg("--sandbox").arg("read-only"),
+                "acceptEdits" => cmd.arg("--full-auto"),
+                _ => cmd.arg("--sandbox").arg("workspace-write"),
+            }
+        };
+        // `-` reads the prompt from stdin
+        cmd.model("--model", opts.model).arg("-").stdin(prompt)
+    }
+
+    fn version_command(&self) -> Option<AgentCommand> {
+        Some(AgentCommand::new("codex").arg("--version"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts(permission_mode: &str) -> SessionOptions<'_> {
+        SessionOptions {
+            permission_mode,
+            continue_session: false,
+            dangerously_skip_permissions: false,
+            model: None,
+            allowed_tools: &[],
+            disallowed_tools: &[],
+        }
+    }
+
+    #[test]
+    fn plan_is_read_only() {
+        let cmd = Codex.command("p", &opts("plan"));
+        assert_eq!(cmd.args, ["exec", "--sandbox", "read-only", "-"]);
+        assert_eq!(cmd.stdin.as_deref(), Some("p"));
+    }
+
+    #[test]
+    fn accept_edits_is_full_auto() {
+        assert_eq!(Codex.command("p", &opts("acceptEdits")).args, ["exec", "--full-auto", "-"]);
+    }
+
+    #[test]
+    fn skip_permissions_and_resume() {
+        let o = SessionOptions {
+            continue_session: true,
+            dangerously_skip_permissions: true,
+            ..opts("default")
+        };
+        assert_eq!(
+            Codex.command("p", &o).args,
+            ["exec", "resume", "--last", "--dangerously-bypass-approvals-and-sandbox", "-"]
+        );
+    }
+
+    #[test]
+    fn passes_model() {
+        let o = SessionOptions { model: Some("o4-mini"), ..opts("acceptEdits") };
+        assert_eq!(Codex.command("p", &o).args, ["exec", "--full-auto", "--model", "o4-mini", "-"]);
+    }
+}
@@ -0,0 +1,56 @@
+use super::{AgentBackend, AgentCommand, SessionOptions};
+
+/// Google Gemini CLI; reads the prompt from stdin when not attached to a terminal.
+pub struct Gemini;
+
+impl AgentBackend for Gemini {
+    fn name(&self) -> &str {
+        "gemini"
+    }
+
+    fn command(&self, prompt: &str, opts: &SessionOptions<'_>) -> AgentCommand {
+        // Gemini has no read-only mode; plan falls back to prompting for every tool
+        let approval = if opts.dangerously_skip_permissions {
+            "yolo"
+        } else if opts.permission_mode == "acceptEdits" {
+            "auto_edit"
+        } else {
+            "default"
+        };
+        AgentCommand::new("gemini")
+            .arg("--approval-mode")
+            .arg(approval)
+            .model("--model", opts.model)
+            .stdin(prompt)
+    }
+
+    fn version_command(&self) -> Option<AgentCommand> {
+        Some(AgentCommand::new("gemini").arg("--version"))
+    }
+
+    fn supports_continue(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_approval_modes() {
+        let mut opts = SessionOptions {
+            permission_mode: "acceptEdits",
+            continue_session: false,
+            dangerously_skip_permissions: false,
+            model: None,
+            allowed_tools: &[],
+            disallowed_tools: &[],
+        };
+        assert_eq!(Gemini.command("p", &opts).args, ["--approval-mode", "auto_edit"]);
+        opts.permission_mode = "plan";
+        assert_eq!(Gemini.command("p", &opts).args, ["--approval-mode", "default"]);
+        opts.dangerously_skip_permissions = true;
+        assert_eq!(Gemini.command("p", &opts).args, ["--approval-mode", "yolo"]);
+    }
+}
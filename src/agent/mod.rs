@@ -0,0 +1,144 @@
+//! Agent backends: how each supported CLI is invoked for a prompt.
+//!
+//! Backends only build the command line; spawning, streaming, timeouts, and logging are
+//! shared in `claude::run_claude`, so every backend returns the same `ClaudeResult`.
+
+mod aider;
+mod claude;
+mod codex;
+mod gemini;
+mod shell;
+
+use crate::config::{AgentKind, Args};
+use anyhow::{bail, Result};
+
+/// Session flags from ralph's CLI, in Claude's vocabulary; backends translate them.
+#[derive(Debug, Clone, Copy)]
+pub struct SessionOptions<'a> {
+    /// `default`, `acceptEdits`, or `plan`
+    pub permission_mode: &'a str,
+    pub continue_session: bool,
+    pub dangerously_skip_permissions: bool,
+    /// `--model`, or the feature's `model`; None leaves the CLI's default
+    pub model: Option<&'a str>,
+    /// `--allowed-tools` / `--disallowed-tools`; only Claude takes them
+    pub allowed_tools: &'a [String],
+    pub disallowed_tools: &'a [String],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentCommand {
+    pub program: String,
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    /// Written to the agent's stdin; None when the prompt is passed as an argument
+    pub stdin: Option<String>,
+}
+
+impl AgentCommand {
+    fn new(program: &str) -> Self {
+        Self {
+            program: program.to_string(),
+            args: Vec::new(),
+            env: Vec::new(),
+            stdin: None,
+        }
+    }
+
+    fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// `flag a,b,c` if the list isn't empty.
+    fn list(self, flag: &str, items: &[String]) -> Self {
+        if items.is_empty() {
+            return self;
+        }
+        self.arg(flag).arg(items.join(","))
+    }
+
+    /// `flag <model>` if a model was chosen.
+    fn model(self, flag: &str, model: Option<&str>) -> Self {
+        match model {
+            Some(model) => self.arg(flag).arg(model),
+            None => self,
+        }
+    }
+
+    fn stdin(mut self, prompt: &str) -> Self {
+        self.stdin = Some(prompt.to_string());
+        self
+    }
+}
+
+pub trait AgentBackend: Send + Sync {
+    /// Display name, used in logs and the config digest
+    fn name(&self) -> &str;
+
+    fn command(&self, prompt: &str, opts: &SessionOptions<'_>) -> AgentCommand;
+
+    /// Command printing the backend's version, if it has one
+    fn version_command(&self) -> Option<AgentCommand>;
+
+    /// Whether `--continue-session` maps to anything for this backend
+    fn supports_continue(&self) -> bool {
+        true
+    }
+}
+
+pub fn backend(args: &Args) -> Result<Box<dyn AgentBackend>> {
+    if args.agent_command.is_some() && args.agent != AgentKind::Shell {
+        bail!("--agent-command requires --agent shell");
+    }
+    if args.agent != AgentKind::Claude && !(args.allowed_tools.is_empty() && args.disallowed_tools.is_empty()) {
+        bail!("--allowed-tools and --disallowed-tools require --agent claude");
+    }
+    Ok(match args.agent {
+        AgentKind::Claude => Box::new(claude::Claude),
+        AgentKind::Codex => Box::new(codex::Codex),
+        AgentKind::Gemini => Box::new(gemini::Gemini),
+        AgentKind::Aider => Box::new(aider::Aider),
+        AgentKind::Shell => match args.agent_command {
+            Some(ref command) => Box::new(shell::Shell::new(command)),
+            None => bail!("--agent shell requires --agent-command"),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn parse_args(args: &[&str]) -> Args {
+        Args::try_parse_from(std::iter::once("ralph").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn defaults_to_claude() {
+        assert_eq!(backend(&parse_args(&[])).unwrap().name(), "claude");
+    }
+
+    #[test]
+    fn selects_named_backend() {
+        assert_eq!(backend(&parse_args(&["--agent", "codex"])).unwrap().name(), "codex");
+    }
+
+    #[test]
+    fn shell_requires_command() {
+        assert!(backend(&parse_args(&["--agent", "shell"])).is_err());
+        assert!(backend(&parse_args(&["--agent", "shell", "--agent-command", "./agent.sh"])).is_ok());
+    }
+
+    #[test]
+    fn agent_command_requires_shell() {
+        assert!(backend(&parse_args(&["--agent-command", "./agent.sh"])).is_err());
+    }
+
+    #[test]
+    fn tool_rules_require_claude() {
+        assert!(backend(&parse_args(&["--disallowed-tools", "WebFetch"])).is_ok());
+        assert!(backend(&parse_args(&["--agent", "codex", "--disallowed-tools", "WebFetch"])).is_err());
+    }
+}
@@ -0,0 +1,67 @@
+use super::{AgentBackend, AgentCommand, SessionOptions};
+
+/// Any command run by the platform's shell (`sh -c`, or PowerShell on Windows); the prompt arrives on stdin and session flags as
+/// `RALPH_PERMISSION_MODE`, `RALPH_CONTINUE_SESSION`, `RALPH_SKIP_PERMISSIONS`, and
+/// `RALPH_MODEL` (when a model was chosen).
+pub struct Shell {
+    command: String,
+}
+
+impl Shell {
+    pub fn new(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+        }
+    }
+}
+
+impl AgentBackend for Shell {
+    fn name(&self) -> &str {
+        "shell"
+    }
+
+    fn command(&self, prompt: &str, opts: &SessionOptions<'_>) -> AgentCommand {
+        let argv = crate::shell::Shell::default().argv(&self.command);
+        let mut cmd = AgentCommand::new(argv[0]).stdin(prompt);
+        cmd.args = argv[1..].iter().map(|a| a.to_string()).collect();
+        cmd.env = vec![
+            ("RALPH_PERMISSION_MODE".into(), opts.permission_mode.into()),
+            ("RALPH_CONTINUE_SESSION".into(), opts.continue_session.to_string()),
+            ("RALPH_SKIP_PERMISSIONS".into(), opts.dangerously_skip_permissions.to_string()),
+        ];
+        if let Some(model) = opts.model {
+            cmd.env.push(("RALPH_MODEL".into(), model.into()));
+        }
+        cmd
+    }
+
+    fn version_command(&self) -> Option<AgentCommand> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_command_with_env() {
+        let opts = SessionOptions {
+            permission_mode: "plan",
+            continue_session: false,
+            dangerously_skip_permissions: false,
+            model: None,
+            allowed_tools: &[],
+            disallowed_tools: &[],
+        };
+        let cmd = Shell::new("./agent.sh --fast").command("p", &opts);
+        assert_eq!(cmd.program, "sh");
+        assert_eq!(cmd.args, ["-c", "./agent.sh --fast"]);
+        assert_eq!(cmd.stdin.as_deref(), Some("p"));
+        assert!(cmd.env.contains(&("RALPH_PERMISSION_MODE".into(), "plan".into())));
+        assert!(!cmd.env.iter().any(|(k, _)| k == "RALPH_MODEL"));
+
+        let cmd = Shell::new("./agent.sh").command("p", &SessionOptions { model: Some("local"), ..opts });
+        assert!(cmd.env.contains(&("RALPH_MODEL".into(), "local".into())));
+    }
+}
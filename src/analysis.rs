@@ -1,3 +1,23 @@
+use crate::prd::{Prd, Status};
+use anyhow::{Context, Result};
+use regex::{RegexSet, RegexSetBuilder};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+
+/// Phrases of an agent that is stuck, looked for near the start of its output
+const LOOP_PATTERNS: [&str; 4] = [
+    "i cannot proceed",
+    "i'm unable to continue",
+    "i don't have access to",
+    "cannot complete this task",
+];
+
+/// Phrases of a rate-limit error, looked for near the end of the output
+const RATE_LIMIT_PATTERNS: [&str; 2] = ["rate limit", "too many requests"];
+
+static BUILT_IN: LazyLock<Patterns> = LazyLock::new(Patterns::default);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum IterationResult {
     Continue,
@@ -5,19 +25,94 @@ pub enum IterationResult {
     RateLimit,
     LoopDetected,
     Failed,
+    /// The agent printed nothing for `--stall-timeout` and was stopped
+    Stalled,
+}
+
+impl IterationResult {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Continue => "continue",
+            Self::Complete => "complete",
+            Self::RateLimit => "rate_limit",
+            Self::LoopDetected => "loop_detected",
+            Self::Failed => "failed",
+            Self::Stalled => "stalled",
+        }
+    }
 }
 
 pub struct OutputAnalysisContext<'a> {
     pub success: bool,
     pub completion_marker: &'a str,
+    pub patterns: &'a Patterns,
+}
+
+/// Loop and rate-limit patterns, matched case-insensitively and compiled once per run.
+#[derive(Debug, Clone)]
+pub struct Patterns {
+    loops: RegexSet,
+    rate_limits: RegexSet,
+}
+
+impl Default for Patterns {
+    /// The built-in phrases.
+    fn default() -> Self {
+        let set = |phrases: &[&str]| {
+            compile(phrases.iter().map(|p| regex::escape(p))).unwrap_or_else(|_| RegexSet::empty())
+        };
+        Self { loops: set(&LOOP_PATTERNS), rate_limits: set(&RATE_LIMIT_PATTERNS) }
+    }
+}
+
+impl Patterns {
+    /// Compiles custom regexes; an empty list keeps the built-in phrases for that kind.
+    pub fn new(loops: &[String], rate_limits: &[String]) -> Result<Self> {
+        let defaults = Self::default();
+        Ok(Self {
+            loops: if loops.is_empty() {
+                defaults.loops
+            } else {
+                compile(loops.iter().cloned()).context("Invalid loop pattern")?
+            },
+            rate_limits: if rate_limits.is_empty() {
+                defaults.rate_limits
+            } else {
+                compile(rate_limits.iter().cloned()).context("Invalid rate-limit pattern")?
+            },
+        })
+    }
+
+    #[must_use]
+    pub fn is_loop(&self, output: &str) -> bool {
+        // Only check first 500 chars - stuck messages appear at start
+        let check_region = output.char_indices().nth(500).map_or(output, |(i, _)| &output[..i]);
+        self.loops.is_match(check_region)
+    }
+
+    #[must_use]
+    pub fn is_rate_limit(&self, output: &str) -> bool {
+        // Check last 1000 chars where error messages appear
+        let tail = output
+            .char_indices()
+            .rev()
+            .nth(999)
+            .map_or(output, |(i, _)| &output[i..]);
+        self.rate_limits.is_match(tail)
+    }
+}
+
+fn compile(patterns: impl IntoIterator<Item = String>) -> Result<RegexSet, regex::Error> {
+    RegexSetBuilder::new(patterns).case_insensitive(true).build()
 }
 
 #[must_use]
 pub fn analyze_iteration_output(output: &str, ctx: &OutputAnalysisContext<'_>) -> IterationResult {
-    if !ctx.success && detect_rate_limit(output) {
+    if !ctx.success && ctx.patterns.is_rate_limit(output) {
         return IterationResult::RateLimit;
     }
-    if detect_loop_pattern(output) {
+    if ctx.patterns.is_loop(output) {
         return IterationResult::LoopDetected;
     }
     if output.contains(ctx.completion_marker) {
@@ -30,33 +125,134 @@ pub fn analyze_iteration_output(output: &str, ctx: &OutputAnalysisContext<'_>) -
     }
 }
 
+/// Why a completion marker is premature: the features still pending or in progress, as
+/// `'id' is status`. Empty when it isn't.
+#[must_use]
+pub fn premature_completion(prd: &Prd) -> Vec<String> {
+    prd.features
+        .iter()
+        .filter(|f| matches!(f.status, Status::Pending | Status::InProgress))
+        .map(|f| format!("'{}' is {}", f.id, f.status.as_str()))
+        .collect()
+}
+
+/// Phases whose `phaseMarkers` entry appears in the output, in phase-name order.
+#[must_use]
+pub fn detect_milestones<'a>(output: &str, phase_markers: &'a BTreeMap<String, String>) -> Vec<&'a str> {
+    phase_markers
+        .iter()
+        .filter(|(_, marker)| !marker.is_empty() && output.contains(marker.as_str()))
+        .map(|(phase, _)| phase.as_str())
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reopen {
+    pub feature_id: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditVerdict {
+    Confirmed,
+    Reopen(Vec<Reopen>),
+    Inconclusive,
+}
+
+const AUDIT_REOPEN_PREFIX: &str = "REOPEN ";
+
+/// Interprets the output of a `--final-audit` iteration.
+#[must_use]
+pub fn analyze_audit_output(output: &str, completion_marker: &str) -> AuditVerdict {
+    let reopens: Vec<Reopen> = output
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim().trim_start_matches(['-', '*', '`', ' ']);
+            let rest = line.strip_prefix(AUDIT_REOPEN_PREFIX)?;
+            let (id, reason) = rest.split_once(':').unwrap_or((rest, ""));
+            let feature_id = id.trim().trim_matches('`').to_string();
+            (!feature_id.is_empty()).then(|| Reopen {
+                feature_id,
+                reason: reason.trim().trim_end_matches('`').to_string(),
+            })
+        })
+        .collect();
+
+    if !reopens.is_empty() {
+        AuditVerdict::Reopen(reopens)
+    } else if output.contains(completion_marker) {
+        AuditVerdict::Confirmed
+    } else {
+        AuditVerdict::Inconclusive
+    }
+}
+
+/// The diffs of recent iterations, to catch an agent that keeps making the same change, or
+/// none, without printing anything that sounds stuck.
+#[derive(Debug, Clone, Default)]
+pub struct DiffHistory {
+    limit: usize,
+    last: Option<[u8; 32]>,
+    repeats: usize,
+}
+
+impl DiffHistory {
+    /// Detects `limit` identical diffs in a row; 0 never detects.
+    #[must_use]
+    pub fn new(limit: usize) -> Self {
+        Self { limit, ..Self::default() }
+    }
+
+    /// Records an iteration's diff. True once the same diff has come up `limit` times in a row.
+    pub fn record(&mut self, diff: &str) -> bool {
+        let hash: [u8; 32] = Sha256::digest(diff.as_bytes()).into();
+        self.repeats = if self.last == Some(hash) { self.repeats + 1 } else { 1 };
+        self.last = Some(hash);
+        self.limit > 0 && self.repeats >= self.limit
+    }
+}
+
+/// Iterations in a row that neither committed nor changed a feature status, to catch an agent
+/// that talks about the work without doing it.
+#[derive(Debug, Clone, Default)]
+pub struct IdleStreak {
+    limit: u32,
+    idle: u32,
+}
+
+impl IdleStreak {
+    /// Gives up after `limit` idle iterations; 0 never does.
+    #[must_use]
+    pub fn new(limit: u32) -> Self {
+        Self { limit, idle: 0 }
+    }
+
+    /// Records whether an iteration made progress and returns the streak of idle ones.
+    pub fn record(&mut self, progressed: bool) -> u32 {
+        self.idle = if progressed { 0 } else { self.idle + 1 };
+        self.idle
+    }
+
+    #[must_use]
+    pub fn exhausted(&self) -> bool {
+        self.limit > 0 && self.idle >= self.limit
+    }
+
+    pub fn reset(&mut self) {
+        self.idle = 0;
+    }
+}
+
+/// [`Patterns::is_loop`] with the built-in phrases.
 #[must_use]
 pub fn detect_loop_pattern(output: &str) -> bool {
-    // Only check first 500 chars - stuck messages appear at start
-    let check_region: String = output.chars().take(500).collect();
-    let lower = check_region.to_lowercase();
-
-    let patterns = [
-        "i cannot proceed",
-        "i'm unable to continue",
-        "i don't have access to",
-        "cannot complete this task",
-    ];
-
-    patterns.iter().any(|p| lower.contains(p))
+    BUILT_IN.is_loop(output)
 }
 
+/// [`Patterns::is_rate_limit`] with the built-in phrases.
 #[must_use]
 pub fn detect_rate_limit(output: &str) -> bool {
-    // Check last 1000 chars where error messages appear
-    let tail = output
-        .char_indices()
-        .rev()
-        .nth(999)
-        .map_or(output, |(i, _)| &output[i..]);
-    let lower = tail.to_lowercase();
-
-    lower.contains("rate limit") || lower.contains("too many requests")
+    BUILT_IN.is_rate_limit(output)
 }
 
 #[cfg(test)]
@@ -118,6 +314,60 @@ mod tests {
         }
     }
 
+    mod idle_streak_tests {
+        use super::*;
+
+        #[test]
+        fn progress_resets_the_streak() {
+            let mut streak = IdleStreak::new(2);
+            assert_eq!(streak.record(false), 1);
+            assert!(!streak.exhausted());
+            assert_eq!(streak.record(true), 0);
+            assert_eq!(streak.record(false), 1);
+            assert_eq!(streak.record(false), 2);
+            assert!(streak.exhausted());
+            streak.reset();
+            assert!(!streak.exhausted());
+        }
+
+        #[test]
+        fn zero_disables() {
+            let mut streak = IdleStreak::new(0);
+            (0..5).for_each(|_| {
+                streak.record(false);
+            });
+            assert!(!streak.exhausted());
+        }
+    }
+
+    mod diff_history_tests {
+        use super::*;
+
+        #[test]
+        fn detects_the_same_diff_in_a_row() {
+            let mut history = DiffHistory::new(3);
+            assert!(!history.record("a"));
+            assert!(!history.record("a"));
+            assert!(history.record("a"));
+            assert!(history.record("a"));
+        }
+
+        #[test]
+        fn a_different_diff_restarts_the_count() {
+            let mut history = DiffHistory::new(2);
+            assert!(!history.record(""));
+            assert!(!history.record("a"));
+            assert!(!history.record(""));
+            assert!(history.record(""));
+        }
+
+        #[test]
+        fn zero_disables() {
+            let mut history = DiffHistory::new(0);
+            assert!((0..5).all(|_| !history.record("")));
+        }
+    }
+
     mod detect_rate_limit_tests {
         use super::*;
 
@@ -176,6 +426,7 @@ mod tests {
             OutputAnalysisContext {
                 success,
                 completion_marker: marker,
+                patterns: &BUILT_IN,
             }
         }
 
@@ -236,6 +487,122 @@ mod tests {
         }
     }
 
+    mod patterns_tests {
+        use super::*;
+
+        #[test]
+        fn custom_patterns_replace_the_built_in_ones() {
+            let patterns = Patterns::new(&[r"stuck on \w+".into()], &[]).unwrap();
+            assert!(patterns.is_loop("Stuck on migrations"));
+            assert!(!patterns.is_loop("I cannot proceed"));
+            assert!(patterns.is_rate_limit("too many requests"));
+        }
+
+        #[test]
+        fn rate_limit_patterns() {
+            let patterns = Patterns::new(&[], &["overloaded_error".into(), r"\b529\b".into()]).unwrap();
+            assert!(patterns.is_rate_limit("API Error: 529"));
+            assert!(!patterns.is_rate_limit("rate limit"));
+        }
+
+        #[test]
+        fn invalid_pattern_is_an_error() {
+            let err = Patterns::new(&["(unclosed".into()], &[]).unwrap_err();
+            assert!(format!("{err:#}").starts_with("Invalid loop pattern"));
+        }
+    }
+
+    mod analyze_audit_output_tests {
+        use super::*;
+
+        #[test]
+        fn confirmed_when_marker_without_reopens() {
+            assert_eq!(analyze_audit_output("All good\nDONE", "DONE"), AuditVerdict::Confirmed);
+        }
+
+        #[test]
+        fn inconclusive_without_marker_or_reopens() {
+            assert_eq!(analyze_audit_output("Looked around", "DONE"), AuditVerdict::Inconclusive);
+        }
+
+        #[test]
+        fn parses_reopen_lines() {
+            let output = "REOPEN feat-1: missing tests\n- REOPEN feat-2: not wired up\nREOPEN feat-3";
+            assert_eq!(
+                analyze_audit_output(output, "DONE"),
+                AuditVerdict::Reopen(vec![
+                    Reopen { feature_id: "feat-1".into(), reason: "missing tests".into() },
+                    Reopen { feature_id: "feat-2".into(), reason: "not wired up".into() },
+                    Reopen { feature_id: "feat-3".into(), reason: String::new() },
+                ])
+            );
+        }
+
+        #[test]
+        fn reopens_take_priority_over_marker() {
+            let output = "REOPEN feat-1: broken\nDONE";
+            assert!(matches!(analyze_audit_output(output, "DONE"), AuditVerdict::Reopen(_)));
+        }
+
+        #[test]
+        fn ignores_placeholder_reopen_without_id() {
+            assert_eq!(analyze_audit_output("REOPEN : nothing", "DONE"), AuditVerdict::Inconclusive);
+        }
+    }
+
+    mod detect_milestones_tests {
+        use super::*;
+
+        fn markers() -> BTreeMap<String, String> {
+            BTreeMap::from([
+                ("MVP".to_string(), "<promise>MVP</promise>".to_string()),
+                ("Beta".to_string(), "<promise>BETA</promise>".to_string()),
+            ])
+        }
+
+        #[test]
+        fn finds_printed_markers() {
+            let output = "done\n<promise>MVP</promise>\n<promise>BETA</promise>";
+            assert_eq!(detect_milestones(output, &markers()), vec!["Beta", "MVP"]);
+        }
+
+        #[test]
+        fn none_without_markers_in_output() {
+            assert!(detect_milestones("<promise>COMPLETE</promise>", &markers()).is_empty());
+        }
+
+        #[test]
+        fn ignores_empty_markers() {
+            let markers = BTreeMap::from([("MVP".to_string(), String::new())]);
+            assert!(detect_milestones("anything", &markers).is_empty());
+        }
+    }
+
+    mod premature_completion_tests {
+        use super::*;
+        use crate::testing::{FeatureBuilder, PrdBuilder};
+
+        #[test]
+        fn lists_pending_and_in_progress_features() {
+            let prd = PrdBuilder::new()
+                .feature(FeatureBuilder::new("done").status(Status::Complete))
+                .feature(FeatureBuilder::new("stuck").status(Status::Blocked))
+                .feature(FeatureBuilder::new("wip").status(Status::InProgress))
+                .feature(FeatureBuilder::new("todo"))
+                .build();
+            assert_eq!(premature_completion(&prd), ["'wip' is in-progress", "'todo' is pending"]);
+        }
+
+        #[test]
+        fn nothing_once_features_are_finished_or_blocked() {
+            let prd = PrdBuilder::new()
+                .feature(FeatureBuilder::new("done").status(Status::Complete))
+                .feature(FeatureBuilder::new("stuck").status(Status::Blocked))
+                .build();
+            assert!(premature_completion(&prd).is_empty());
+        }
+    }
+
     mod boundary_tests {
         use super::*;
 
@@ -0,0 +1,361 @@
+//! Library API: runs the loop from Rust, without spawning the CLI.
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use ralph_rs::{Ralph, RunOptions};
+//!
+//! let summary = Ralph::new("prd.jsonc")
+//!     .options(RunOptions { max_iterations: Some(5), ..Default::default() })
+//!     .on_event(|kind, fields| eprintln!("{kind}: {fields}"))
+//!     .run()
+//!     .await?;
+//! println!("{}/{} features complete", summary.features_complete, summary.features_total);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Output, the run id, and webhooks are process-wide, so only one [`Ralph::run`] can be in
+//! progress per process; a second one fails instead of taking over the first one's output.
+
+use crate::{
+    artifact::IterationArtifact,
+    config::{AgentKind, Args},
+    events::{self, EventSink},
+    output::{self, Console, Level, Reporter},
+    prd, run_id, runner, validation, webhook,
+};
+use anyhow::{Context, Result};
+use clap::{Parser, ValueEnum};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A [`Ralph::run`] is in progress.
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+type EventCallback = Box<dyn Fn(&str, &Value) + Send + Sync>;
+
+/// The common run options; anything else goes in `args` as `ralph` flags. Unset fields
+/// keep the CLI defaults. The config file is not read.
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    pub project_dir: Option<PathBuf>,
+    /// 0 is unlimited [default: 10]
+    pub max_iterations: Option<u32>,
+    pub agent: Option<AgentKind>,
+    pub model: Option<String>,
+    pub permission_mode: Option<String>,
+    /// Extra flags, e.g. `["--parallel", "2"]`
+    pub args: Vec<String>,
+}
+
+impl RunOptions {
+    /// The CLI arguments for a run of `prd`.
+    pub fn to_args(&self, prd: &Path) -> Result<Args> {
+        let mut argv: Vec<String> = vec!["ralph".into(), "--prd".into(), prd.display().to_string()];
+        if let Some(ref dir) = self.project_dir {
+            argv.extend(["--project-dir".into(), dir.display().to_string()]);
+        }
+        if let Some(max) = self.max_iterations {
+            argv.extend(["--max-iterations".into(), max.to_string()]);
+        }
+        if let Some(agent) = self.agent.and_then(|a| a.to_possible_value()) {
+            argv.extend(["--agent".into(), agent.get_name().to_string()]);
+        }
+        if let Some(ref model) = self.model {
+            argv.extend(["--model".into(), model.clone()]);
+        }
+        if let Some(ref mode) = self.permission_mode {
+            argv.extend(["--permission-mode".into(), mode.clone()]);
+        }
+        argv.extend(self.args.iter().cloned());
+        Args::try_parse_from(argv).context("Invalid run options")
+    }
+}
+
+/// How a run ended, from its iteration artifacts and the PRD afterwards.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunSummary {
+    pub run_id: String,
+    /// Iterations in this run, including earlier ones when resumed
+    pub iterations: usize,
+    /// Iteration count per result, e.g. `continue`, `complete`, `failed`
+    pub results: BTreeMap<String, usize>,
+    /// Agent-reported cost; 0 when the agent doesn't report it
+    pub cost_usd: f64,
+    pub features_complete: usize,
+    pub features_total: usize,
+}
+
+impl RunSummary {
+    /// Every feature in the PRD is complete.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.features_complete == self.features_total
+    }
+}
+
+/// Builder for an embedded run.
+pub struct Ralph {
+    prd: PathBuf,
+    options: RunOptions,
+    reporter: Option<Arc<dyn Reporter>>,
+    callbacks: Vec<EventCallback>,
+    sinks: Vec<Arc<dyn EventSink>>,
+}
+
+impl Ralph {
+    pub fn new(prd: impl Into<PathBuf>) -> Self {
+        Self {
+            prd: prd.into(),
+            options: RunOptions::default(),
+            reporter: None,
+            callbacks: Vec::new(),
+            sinks: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn options(mut self, options: RunOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Where messages and agent output go [default: the terminal, as the CLI prints them]
+    #[must_use]
+    pub fn reporter(mut self, reporter: impl Reporter + 'static) -> Self {
+        self.reporter = Some(Arc::new(reporter));
+        self
+    }
+
+    /// Called with each structured event, e.g. `iteration_end`, and its fields.
+    #[must_use]
+    pub fn on_event(mut self, callback: impl Fn(&str, &Value) + Send + Sync + 'static) -> Self {
+        self.callbacks.push(Box::new(callback));
+        self
+    }
+
+    /// Receives every [`events::RunEvent`] of the run, typed.
+    #[must_use]
+    pub fn sink(mut self, sink: impl EventSink + 'static) -> Self {
+        self.sinks.push(Arc::new(sink));
+        self
+    }
+
+    /// Runs the loop to completion, the iteration limit, or an error.
+    ///
+    /// Concurrent runs aren't supported: this fails while another run is in progress in the
+    /// same process. The reporter and sinks are removed when the run ends or its future is dropped.
+    pub async fn run(self) -> Result<RunSummary> {
+        let args = self.options.to_args(&self.prd)?;
+        let prd_path = args.prd.clone();
+        let _installed = Installed::new(
+            Arc::new(Callbacks {
+                inner: self.reporter.unwrap_or_else(|| Arc::new(Console)),
+                callbacks: self.callbacks,
+            }),
+            self.sinks,
+        )?;
+        let result = runner::run(args).await;
+        webhook::flush().await;
+        result?;
+        summarize(&prd_path)
+    }
+}
+
+/// The reporter and sinks of the run in progress, uninstalled on drop.
+struct Installed {
+    sinks: Vec<Arc<dyn EventSink>>,
+}
+
+impl Installed {
+    fn new(reporter: Arc<dyn Reporter>, sinks: Vec<Arc<dyn EventSink>>) -> Result<Self> {
+        if RUNNING.swap(true, Ordering::SeqCst) {
+            anyhow::bail!("Another ralph run is in progress in this process; concurrent runs aren't supported");
+        }
+        output::set_reporter(Some(reporter));
+        for sink in &sinks {
+            events::add_sink(Arc::clone(sink));
+        }
+        Ok(Self { sinks })
+    }
+}
+
+impl Drop for Installed {
+    fn drop(&mut self) {
+        output::set_reporter(None);
+        for sink in &self.sinks {
+            events::remove_sink(sink);
+        }
+        RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Passes everything to `inner`, and events to the callbacks too.
+struct Callbacks {
+    inner: Arc<dyn Reporter>,
+    callbacks: Vec<EventCallback>,
+}
+
+impl Reporter for Callbacks {
+    fn message(&self, level: Level, msg: &str) {
+        self.inner.message(level, msg);
+    }
+
+    fn event(&self, kind: &str, fields: &Value) {
+        self.inner.event(kind, fields);
+        for callback in &self.callbacks {
+            callback(kind, fields);
+        }
+    }
+
+    fn agent_line(&self, line: &str, stderr: bool) {
+        self.inner.agent_line(line, stderr);
+    }
+
+    fn rule(&self, bold: bool) {
+        self.inner.rule(bold);
+    }
+
+    fn blank(&self) {
+        self.inner.blank();
+    }
+}
+
+fn summarize(prd_path: &Path) -> Result<RunSummary> {
+    let run_id = run_id::current().unwrap_or_default();
+    let logs_dir = validation::prd_dir(prd_path).join(".ralph").join("logs");
+    let artifacts: Vec<IterationArtifact> = IterationArtifact::load_all(&logs_dir)
+        .into_iter()
+        .filter(|a| a.run_id.as_deref() == Some(run_id.as_str()))
+        .collect();
+    let mut results = BTreeMap::new();
+    for artifact in &artifacts {
+        *results.entry(artifact.result.clone()).or_insert(0) += 1;
+    }
+    let prd = prd::Prd::load(prd_path)?;
+    Ok(RunSummary {
+        iterations: artifacts.len(),
+        results,
+        cost_usd: artifacts.iter().filter_map(|a| a.cost_usd).sum(),
+        features_complete: prd.status_counts().complete,
+        features_total: prd.features.len(),
+        run_id,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::RunEvent;
+    use std::sync::Mutex;
+
+    const PRD: &str = r#"{
+  "project": { "name": "embed", "description": "d" },
+  "verification": { "commands": [], "runAfterEachFeature": false },
+  "features": [
+    { "id": "feat-1", "category": "functional", "description": "d", "steps": [], "status": "in-progress" }
+  ],
+  "completion": { "allFeaturesComplete": true, "allVerificationsPassing": true, "marker": "<promise>DONE</promise>" }
+}"#;
+
+    /// Runs install process-wide state; one test at a time.
+    static INSTALL: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    #[derive(Default)]
+    struct Collect(Mutex<Vec<String>>);
+
+    impl Reporter for Arc<Collect> {
+        fn message(&self, _level: Level, msg: &str) {
+            self.0.lock().unwrap().push(msg.to_string());
+        }
+
+        fn event(&self, _kind: &str, _fields: &Value) {}
+
+        fn agent_line(&self, line: &str, _stderr: bool) {
+            self.0.lock().unwrap().push(line.to_string());
+        }
+    }
+
+    #[derive(Default)]
+    struct Typed(Mutex<Vec<RunEvent>>);
+
+    impl EventSink for Arc<Typed> {
+        fn handle(&self, event: &RunEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    #[test]
+    fn one_run_at_a_time_and_uninstalled_on_drop() {
+        let _lock = INSTALL.blocking_lock();
+        let typed: Arc<dyn EventSink> = Arc::new(Arc::new(Typed::default()));
+        let first = Installed::new(Arc::new(Console), vec![Arc::clone(&typed)]).unwrap();
+        let err = Installed::new(Arc::new(Console), Vec::new()).err().unwrap();
+        assert!(err.to_string().contains("in progress"), "{err}");
+        assert_eq!(Arc::strong_count(&typed), 3);
+        drop(first);
+        assert_eq!(Arc::strong_count(&typed), 1);
+        drop(Installed::new(Arc::new(Console), Vec::new()).unwrap());
+    }
+
+    #[test]
+    fn options_become_cli_args() {
+        let options = RunOptions {
+            max_iterations: Some(3),
+            agent: Some(AgentKind::Codex),
+            args: vec!["--parallel".into(), "2".into()],
+            ..Default::default()
+        };
+        let args = options.to_args(Path::new("x/prd.jsonc")).unwrap();
+        assert_eq!((args.max_iterations, args.agent, args.parallel), (3, AgentKind::Codex, 2));
+        assert_eq!(args.prd, Path::new("x/prd.jsonc"));
+        let bad = RunOptions { args: vec!["--no-such-flag".into()], ..Default::default() };
+        assert!(bad.to_args(Path::new("prd.jsonc")).is_err());
+    }
+
+    #[tokio::test]
+    async fn runs_the_loop_and_summarizes() {
+        let _lock = INSTALL.lock().await;
+        let dir = tempfile::tempdir().unwrap();
+        let prd = dir.path().join("prd.jsonc");
+        std::fs::write(&prd, PRD).unwrap();
+        let script = r#"{ "steps": [
+            { "output": "working" },
+            { "output": "<promise>DONE</promise>", "run": "sed -i 's/in-progress/complete/' prd.jsonc" }
+        ] }"#;
+        std::fs::write(dir.path().join("script.json"), script).unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&events);
+        let collected = Arc::new(Collect::default());
+        let typed = Arc::new(Typed::default());
+        let summary = Ralph::new(&prd)
+            .options(RunOptions {
+                args: ["--simulate", &dir.path().join("script.json").display().to_string(), "-d", "0", "--skip-init"]
+                    .map(String::from)
+                    .to_vec(),
+                ..Default::default()
+            })
+            .reporter(Arc::clone(&collected))
+            .on_event(move |kind, fields| seen.lock().unwrap().push((kind.to_string(), fields.clone())))
+            .sink(Arc::clone(&typed))
+            .run()
+            .await
+            .unwrap();
+
+        assert_eq!(summary.iterations, 2);
+        assert_eq!(summary.results, BTreeMap::from([("complete".into(), 1), ("continue".into(), 1)]));
+        assert!(summary.is_complete());
+        let events = events.lock().unwrap();
+        assert!(events.iter().any(|(kind, fields)| kind == "iteration_end" && fields["result"] == "complete"));
+        assert!(collected.0.lock().unwrap().iter().any(|line| line == "working"));
+        assert!(typed.0.lock().unwrap().contains(&RunEvent::FeatureStatusChanged {
+            iteration: 2,
+            feature: "feat-1".into(),
+            status: prd::Status::Complete,
+        }));
+    }
+}
@@ -0,0 +1,147 @@
+//! Approval gates: with `--approve-each-iteration`, or before a feature with `requiresApproval`,
+//! the loop shows the planned feature and the latest commit's diff, then waits for `y`/`n` on
+//! the terminal or an authenticated `POST /approve` or `/reject` to the `--serve` address.
+
+use crate::{
+    config::Args,
+    events, git, output,
+    prd::{Feature, Status},
+    serve::Endpoint,
+};
+use anyhow::{bail, Result};
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::sync::{LazyLock, Mutex};
+use tokio::io::{AsyncBufReadExt, BufReader, Lines, Stdin};
+use tokio::sync::oneshot;
+
+/// Diff lines shown before the rest is summarized.
+const DIFF_LINES: usize = 60;
+
+/// The request waiting for an answer over HTTP, if any.
+static PENDING: Mutex<Option<oneshot::Sender<bool>>> = Mutex::new(None);
+
+/// One reader for the whole run: a new one per question would drop what the last one buffered.
+static STDIN: LazyLock<tokio::sync::Mutex<Lines<BufReader<Stdin>>>> =
+    LazyLock::new(|| tokio::sync::Mutex::new(BufReader::new(tokio::io::stdin()).lines()));
+
+#[must_use]
+pub fn required(args: &Args, feature: &Feature) -> bool {
+    args.approve_each_iteration || feature.requires_approval
+}
+
+/// Fails when approvals may be needed but nobody could give them: stdin isn't a terminal
+/// and there is no `--serve` endpoint.
+pub fn check_answerable(args: &Args, features: &[Feature]) -> Result<()> {
+    let unfinished = |f: &&Feature| matches!(f.status, Status::Pending | Status::InProgress);
+    let needed = args.approve_each_iteration || features.iter().filter(unfinished).any(|f| f.requires_approval);
+    if needed && args.serve.is_none() && !std::io::stdin().is_terminal() {
+        bail!("Approval gates need a terminal to answer on, or --serve to answer over HTTP");
+    }
+    Ok(())
+}
+
+/// Answers the waiting request. False if nothing is waiting.
+pub fn decide(approved: bool) -> bool {
+    let sender = PENDING.lock().unwrap_or_else(|e| e.into_inner()).take();
+    sender.is_some_and(|tx| tx.send(approved).is_ok())
+}
+
+/// Shows what `iteration` will work on and waits for the operator. True if approved.
+pub async fn request(iteration: u32, feature: &Feature, project_dir: &Path, serve: Option<&Endpoint>) -> bool {
+    output::blank();
+    output::section(&format!("Approval needed: iteration {iteration}"));
+    output::log(&format!("Feature: {} - {}", feature.id, feature.description));
+    for step in &feature.steps {
+        output::dim(&format!("  - {step}"));
+    }
+    if let Some(diff) = git::is_git_repo(project_dir).then(|| git::last_commit_diff(project_dir).ok()).flatten() {
+        output::log("Latest changes:");
+        for line in shorten(&diff, DIFF_LINES) {
+            output::dim(&format!("  {line}"));
+        }
+    }
+
+    let (tx, rx) = oneshot::channel();
+    *PENDING.lock().unwrap_or_else(|e| e.into_inner()) = Some(tx);
+    let mut ways = Vec::new();
+    let terminal = std::io::stdin().is_terminal();
+    if terminal {
+        ways.push("answer y/n in the terminal".to_string());
+    }
+    if let Some(Endpoint { addr, token }) = serve {
+        ways.push(format!(
+            "POST to http://{addr}/approve or http://{addr}/reject with the header 'Authorization: Bearer {token}'"
+        ));
+    }
+    events::action_required(
+        &format!("Iteration {iteration} on feature '{}' is waiting for approval", feature.id),
+        &capitalize(&ways.join(", or ")),
+    );
+
+    let approved = tokio::select! {
+        answer = rx => answer.unwrap_or(false),
+        answer = ask(), if terminal => answer,
+    };
+    PENDING.lock().unwrap_or_else(|e| e.into_inner()).take();
+    if approved {
+        output::success(&format!("Approved '{}'", feature.id));
+    }
+    approved
+}
+
+/// Asks on the terminal until the answer is yes or no; end of input is no.
+async fn ask() -> bool {
+    let mut lines = STDIN.lock().await;
+    loop {
+        output::warn("Run this iteration? [y/n]");
+        let _ = std::io::stdout().flush();
+        match lines.next_line().await {
+            Ok(Some(line)) => match line.trim().to_ascii_lowercase().as_str() {
+                "y" | "yes" => return true,
+                "n" | "no" => return false,
+                _ => {}
+            },
+            _ => return false,
+        }
+    }
+}
+
+/// The first `max` lines of `diff`, then how many more there are.
+fn shorten(diff: &str, max: usize) -> Vec<String> {
+    let lines: Vec<&str> = diff.lines().collect();
+    let mut shown: Vec<String> = lines.iter().take(max).map(|line| line.to_string()).collect();
+    if lines.len() > max {
+        shown.push(format!("... {} more lines", lines.len() - max));
+    }
+    shown
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    chars.next().map(|c| c.to_uppercase().chain(chars).collect()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn required_by_flag_or_feature() {
+        let args = Args::try_parse_from(["ralph"]).unwrap();
+        let mut feature = crate::testing::FeatureBuilder::new("a").build();
+        assert!(!required(&args, &feature));
+        feature.requires_approval = true;
+        assert!(required(&args, &feature));
+        let args = Args::try_parse_from(["ralph", "--approve-each-iteration"]).unwrap();
+        assert!(required(&args, &crate::testing::FeatureBuilder::new("b").build()));
+    }
+
+    #[test]
+    fn long_diffs_are_shortened() {
+        let diff = (1..=5).map(|n| format!("+line {n}")).collect::<Vec<_>>().join("\n");
+        assert_eq!(shorten(&diff, 10).len(), 5);
+        assert_eq!(shorten(&diff, 2), ["+line 1", "+line 2", "... 3 more lines"]);
+    }
+}
@@ -0,0 +1,146 @@
+//! Per-iteration metadata written beside each iteration log as `<log>.json`.
+
+use crate::{atomic, resources::ResourceUsage};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct IterationArtifact {
+    pub iteration: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    pub started_at: String,
+    pub duration_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feature_id: Option<String>,
+    pub permission_mode: String,
+    /// Permission mode change applied after this iteration, e.g. `plan -> acceptEdits`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub escalation: Option<String>,
+    pub result: String,
+    /// Line coverage percentage measured by the coverage gate after this iteration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coverage: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+    /// Sampled CPU and memory of the agent process tree
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resources: Option<ResourceUsage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub log_path: PathBuf,
+}
+
+impl IterationArtifact {
+    #[must_use]
+    pub fn path_for(log_path: &Path) -> PathBuf {
+        log_path.with_extension("json")
+    }
+
+    pub fn write(&self) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        atomic::write(&Self::path_for(&self.log_path), json + "\n")
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read artifact: {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse artifact: {}", path.display()))
+    }
+
+    /// Loads every artifact in `logs_dir`, oldest first. Unreadable files are skipped.
+    /// File names start with the run id, which needn't sort by time, so this orders by
+    /// start time and then iteration.
+    #[must_use]
+    pub fn load_all(logs_dir: &Path) -> Vec<Self> {
+        let Ok(entries) = std::fs::read_dir(logs_dir) else {
+            return Vec::new();
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|e| e == "json"))
+            .collect();
+        paths.sort();
+        let mut artifacts: Vec<Self> = paths.iter().filter_map(|p| Self::load(p).ok()).collect();
+        artifacts.sort_by_cached_key(|a| (chrono::DateTime::parse_from_rfc3339(&a.started_at).ok(), a.iteration));
+        artifacts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn artifact(dir: &Path, iteration: u32) -> IterationArtifact {
+        IterationArtifact {
+            iteration,
+            run_id: None,
+            started_at: "2024-01-15T10:30:00+00:00".into(),
+            duration_secs: 42,
+            feature_id: Some("feat-1".into()),
+            permission_mode: "plan".into(),
+            escalation: Some("plan -> acceptEdits".into()),
+            result: "continue".into(),
+            coverage: None,
+            cost_usd: None,
+            resources: None,
+            error: None,
+            log_path: dir.join(format!("20240115-103000-iteration-{iteration}.log")),
+        }
+    }
+
+    #[test]
+    fn path_replaces_log_extension() {
+        assert_eq!(
+            IterationArtifact::path_for(Path::new("logs/x-iteration-1.log")),
+            PathBuf::from("logs/x-iteration-1.json")
+        );
+    }
+
+    #[test]
+    fn write_and_load_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = artifact(dir.path(), 1);
+        a.write().unwrap();
+        let loaded = IterationArtifact::load(&IterationArtifact::path_for(&a.log_path)).unwrap();
+        assert_eq!(loaded, a);
+    }
+
+    #[test]
+    fn serializes_camel_case_and_omits_none() {
+        let mut a = artifact(Path::new("logs"), 1);
+        a.escalation = None;
+        let json = serde_json::to_string(&a).unwrap();
+        assert!(json.contains("\"permissionMode\":\"plan\""));
+        assert!(!json.contains("escalation"));
+        assert!(!json.contains("error"));
+    }
+
+    #[test]
+    fn load_all_sorted_and_skips_garbage() {
+        let dir = tempfile::tempdir().unwrap();
+        artifact(dir.path(), 2).write().unwrap();
+        artifact(dir.path(), 1).write().unwrap();
+        std::fs::write(dir.path().join("zzz.json"), "not json").unwrap();
+        let all = IterationArtifact::load_all(dir.path());
+        assert_eq!(all.iter().map(|a| a.iteration).collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn load_all_orders_by_start_time_not_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut later = artifact(dir.path(), 1);
+        later.log_path = dir.path().join("a-run-iteration-1.log");
+        later.started_at = "2024-01-16T09:00:00+00:00".into();
+        later.write().unwrap();
+        let mut earlier = artifact(dir.path(), 1);
+        earlier.log_path = dir.path().join("b-run-iteration-1.log");
+        earlier.write().unwrap();
+        let all = IterationArtifact::load_all(dir.path());
+        assert_eq!(all[0].log_path, earlier.log_path);
+        assert_eq!(all[1].log_path, later.log_path);
+    }
+}
@@ -0,0 +1,98 @@
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::Path;
+
+/// Writes `contents` to a temp file beside `path`, syncs it, then renames it over `path`,
+/// so readers (and a crash) only ever see the old or the new file, never a partial one.
+/// An existing file keeps its permissions, and a symlink keeps pointing at it: the file it
+/// links to is the one replaced.
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let existing = std::fs::metadata(path).ok();
+    let resolved = match existing {
+        Some(_) => std::fs::canonicalize(path).with_context(|| format!("Failed to resolve {}", path.display()))?,
+        None => path.to_path_buf(),
+    };
+    let path = resolved.as_path();
+    let dir = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)
+        .with_context(|| format!("Failed to create temp file in {}", dir.display()))?;
+    tmp.write_all(contents.as_ref())?;
+    if let Some(metadata) = existing {
+        tmp.as_file()
+            .set_permissions(metadata.permissions())
+            .with_context(|| format!("Failed to keep the permissions of {}", path.display()))?;
+    }
+    tmp.as_file().sync_all()?;
+    tmp.persist(path)
+        .with_context(|| format!("Failed to replace {}", path.display()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn creates_new_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.txt");
+        write(&path, "hello").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello");
+    }
+
+    #[test]
+    fn replaces_existing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.txt");
+        std::fs::write(&path, "old content that is longer").unwrap();
+        write(&path, "new").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+    }
+
+    #[test]
+    fn leaves_no_temp_files_behind() {
+        let dir = tempfile::tempdir().unwrap();
+        write(&dir.path().join("a.txt"), "x").unwrap();
+        write(&dir.path().join("a.txt"), "y").unwrap();
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_the_permissions_of_an_existing_file() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("prd.jsonc");
+        std::fs::write(&path, "old").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        write(&path, "new").unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o644);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn replaces_the_target_of_a_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("shared")).unwrap();
+        let target = dir.path().join("shared/prd.jsonc");
+        std::fs::write(&target, "old").unwrap();
+        let link = dir.path().join("prd.jsonc");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        write(&link, "new").unwrap();
+        assert!(std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "new");
+        // The temp file went beside the target, not the link
+        assert_eq!(std::fs::read_dir(dir.path().join("shared")).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn fails_when_directory_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(write(&dir.path().join("missing/out.txt"), "x").is_err());
+    }
+}
@@ -0,0 +1,202 @@
+//! Waits between rate-limited iterations: exponential backoff with jitter, or the delay
+//! the agent's output asked for.
+
+use tokio::time::Duration;
+
+/// Phrases that precede a wait in rate-limit messages, e.g. `Retry-After: 30` or
+/// `try again in 2 minutes`.
+const HINT_PREFIXES: [&str; 4] = ["retry-after", "retry after", "try again in", "retry in"];
+
+#[derive(Debug, Clone)]
+pub struct RateLimitBackoff {
+    base: Duration,
+    max: Duration,
+    attempts: u32,
+    rng: fastrand::Rng,
+}
+
+impl RateLimitBackoff {
+    #[must_use]
+    pub fn new(base_secs: u64, max_secs: u64) -> Self {
+        Self {
+            base: Duration::from_secs(base_secs),
+            max: Duration::from_secs(max_secs.max(base_secs)),
+            attempts: 0,
+            rng: fastrand::Rng::new(),
+        }
+    }
+
+    /// Draws jitter from `seed`, so a run replays the same delays.
+    #[must_use]
+    pub fn seeded(mut self, seed: u64) -> Self {
+        self.rng = fastrand::Rng::with_seed(seed);
+        self
+    }
+
+    /// Longest wait for the next consecutive rate limit: `base * 2^n`, capped at `max`.
+    #[must_use]
+    pub fn ceiling(&self) -> Duration {
+        let factor = 2u32.saturating_pow(self.attempts.min(31));
+        self.base.saturating_mul(factor).min(self.max)
+    }
+
+    /// Records a rate limit and returns how long to wait: a random point in the upper half
+    /// of the ceiling, so concurrent sessions don't retry in lockstep. A hint from the
+    /// agent's output wins over the computed delay, but never exceeds `max`.
+    pub fn next_delay(&mut self, hint: Option<Duration>) -> Duration {
+        let ceiling = self.ceiling();
+        self.attempts = self.attempts.saturating_add(1);
+        if let Some(hint) = hint {
+            return hint.min(self.max);
+        }
+        let half = ceiling / 2;
+        half + half.mul_f64(self.rng.f64())
+    }
+
+    /// Called after an iteration that wasn't rate limited.
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+}
+
+/// The wait a rate-limit message asks for, from its last `Retry-After`-style phrase.
+/// A bare number is seconds.
+#[must_use]
+pub fn retry_after_hint(output: &str) -> Option<Duration> {
+    // Same window as `analysis::detect_rate_limit`: the error is at the end
+    let tail = output
+        .char_indices()
+        .rev()
+        .nth(999)
+        .map_or(output, |(i, _)| &output[i..]);
+    let lower = tail.to_lowercase();
+    HINT_PREFIXES
+        .iter()
+        .filter_map(|prefix| {
+            let at = lower.rfind(prefix)?;
+            Some((at, parse_wait(&lower[at + prefix.len()..])?))
+        })
+        .max_by_key(|(at, _)| *at)
+        .map(|(_, wait)| wait)
+}
+
+fn parse_wait(text: &str) -> Option<Duration> {
+    let text = text.trim_start_matches([':', '=', ' ', '\t']);
+    let number_len = text.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(text.len());
+    let value: f64 = text[..number_len].parse().ok()?;
+    let unit: String = text[number_len..]
+        .trim_start()
+        .chars()
+        .take_while(char::is_ascii_alphabetic)
+        .collect();
+    let secs = match unit.as_str() {
+        "" | "s" | "sec" | "secs" | "second" | "seconds" => value,
+        "m" | "min" | "mins" | "minute" | "minutes" => value * 60.0,
+        "h" | "hr" | "hrs" | "hour" | "hours" => value * 3600.0,
+        "ms" | "millisecond" | "milliseconds" => value / 1000.0,
+        _ => return None,
+    };
+    Duration::try_from_secs_f64(secs).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod backoff_tests {
+        use super::*;
+
+        #[test]
+        fn doubles_up_to_max() {
+            let mut backoff = RateLimitBackoff::new(10, 45);
+            let ceilings: Vec<u64> = (0..4)
+                .map(|_| {
+                    let ceiling = backoff.ceiling().as_secs();
+                    backoff.next_delay(None);
+                    ceiling
+                })
+                .collect();
+            assert_eq!(ceilings, vec![10, 20, 40, 45]);
+        }
+
+        #[test]
+        fn jitter_stays_in_upper_half() {
+            let mut backoff = RateLimitBackoff::new(60, 600);
+            for _ in 0..20 {
+                let ceiling = backoff.ceiling();
+                let delay = backoff.next_delay(None);
+                assert!(delay >= ceiling / 2 && delay <= ceiling, "{delay:?} outside {ceiling:?}");
+            }
+        }
+
+        #[test]
+        fn hint_wins_but_is_capped() {
+            let mut backoff = RateLimitBackoff::new(60, 300);
+            assert_eq!(backoff.next_delay(Some(Duration::from_secs(5))), Duration::from_secs(5));
+            assert_eq!(backoff.next_delay(Some(Duration::from_secs(3600))), Duration::from_secs(300));
+            assert_eq!(backoff.ceiling(), Duration::from_secs(240));
+        }
+
+        #[test]
+        fn reset_starts_over() {
+            let mut backoff = RateLimitBackoff::new(30, 600);
+            backoff.next_delay(None);
+            backoff.next_delay(None);
+            backoff.reset();
+            assert_eq!(backoff.ceiling(), Duration::from_secs(30));
+        }
+
+        #[test]
+        fn same_seed_same_delays() {
+            let delays = |seed| {
+                let mut backoff = RateLimitBackoff::new(60, 600).seeded(seed);
+                (0..3).map(|_| backoff.next_delay(None)).collect::<Vec<_>>()
+            };
+            assert_eq!(delays(7), delays(7));
+            assert_ne!(delays(7), delays(8));
+        }
+
+        #[test]
+        fn max_below_base_uses_base() {
+            assert_eq!(RateLimitBackoff::new(60, 10).ceiling(), Duration::from_secs(60));
+        }
+    }
+
+    mod retry_after_hint_tests {
+        use super::*;
+
+        #[test]
+        fn header_style() {
+            assert_eq!(retry_after_hint("HTTP 429\nRetry-After: 30"), Some(Duration::from_secs(30)));
+        }
+
+        #[test]
+        fn prose_with_units() {
+            assert_eq!(
+                retry_after_hint("Rate limit exceeded. Please try again in 2 minutes."),
+                Some(Duration::from_secs(120))
+            );
+            assert_eq!(retry_after_hint("retry after 1.5s"), Some(Duration::from_millis(1500)));
+            assert_eq!(retry_after_hint("retry in 500ms"), Some(Duration::from_millis(500)));
+        }
+
+        #[test]
+        fn last_hint_wins() {
+            assert_eq!(
+                retry_after_hint("retry after 10 seconds\n...\ntry again in 1 hour"),
+                Some(Duration::from_secs(3600))
+            );
+        }
+
+        #[test]
+        fn none_without_a_number() {
+            assert_eq!(retry_after_hint("Too many requests, try again in a moment"), None);
+            assert_eq!(retry_after_hint("rate limit reached"), None);
+        }
+
+        #[test]
+        fn unknown_unit_is_ignored() {
+            assert_eq!(retry_after_hint("retry after 3 days"), None);
+        }
+    }
+}
@@ -0,0 +1,339 @@
+//! `ralph bench`: runs the PRD once per combination of a matrix of prompts, models, and
+//! permission modes, each in a fresh worktree, and compares how they did.
+
+use crate::{
+    config::{Args, BenchArgs},
+    git, history, output, report, run_id, runner, stop, validation,
+};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// Stands for the built-in prompt or the agent's own model in a matrix.
+const DEFAULT: &str = "default";
+
+/// `bench.toml`. An empty list means the value from the command line.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Matrix {
+    /// Prompt files, relative to the matrix file; "default" is the built-in prompt
+    #[serde(default)]
+    pub prompts: Vec<String>,
+    /// "default" leaves the model to the agent CLI
+    #[serde(default)]
+    pub models: Vec<String>,
+    #[serde(default)]
+    pub permission_modes: Vec<String>,
+    /// Runs per combination
+    #[serde(default = "one")]
+    pub repeat: u32,
+}
+
+fn one() -> u32 {
+    1
+}
+
+/// One combination of the matrix.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cell {
+    pub prompt: Option<PathBuf>,
+    pub model: Option<String>,
+    pub permission_mode: String,
+}
+
+/// How one run of a cell went.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Trial {
+    pub run_id: String,
+    pub result: String,
+    pub iterations: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+}
+
+impl Matrix {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let matrix: Self = toml::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))?;
+        if matrix.repeat == 0 {
+            bail!("repeat in {} must be at least 1", path.display());
+        }
+        Ok(matrix)
+    }
+
+    /// Every combination, prompts varying slowest; `base` fills in the dimensions left empty.
+    /// Prompt paths are resolved against `dir`.
+    #[must_use]
+    pub fn cells(&self, base: &Args, dir: &Path) -> Vec<Cell> {
+        let prompts: Vec<Option<PathBuf>> = match self.prompts.as_slice() {
+            [] => vec![base.prompt.clone()],
+            prompts => prompts.iter().map(|p| (p != DEFAULT).then(|| dir.join(p))).collect(),
+        };
+        let models: Vec<Option<String>> = match self.models.as_slice() {
+            [] => vec![base.model.clone()],
+            models => models.iter().map(|m| (m != DEFAULT).then(|| m.clone())).collect(),
+        };
+        let modes = match self.permission_modes.as_slice() {
+            [] => vec![base.permission_mode.clone()],
+            modes => modes.to_vec(),
+        };
+        let mut cells = Vec::new();
+        for prompt in &prompts {
+            for model in &models {
+                for mode in &modes {
+                    cells.push(Cell { prompt: prompt.clone(), model: model.clone(), permission_mode: mode.clone() });
+                }
+            }
+        }
+        cells
+    }
+}
+
+impl Cell {
+    fn prompt_name(&self) -> String {
+        self.prompt.as_deref().and_then(Path::file_name).map_or(DEFAULT.into(), |n| n.to_string_lossy().into_owned())
+    }
+}
+
+/// Runs every cell of the matrix `repeat` times, then prints and saves the comparison.
+pub async fn run(args: &Args, opts: &BenchArgs) -> Result<()> {
+    let matrix = Matrix::load(&opts.matrix)?;
+    let matrix_dir = std::path::absolute(opts.matrix.parent().unwrap_or(Path::new(".")))?;
+    let cells = matrix.cells(args, &matrix_dir);
+    for prompt in cells.iter().filter_map(|c| c.prompt.as_deref()) {
+        if !prompt.exists() {
+            bail!("Prompt file not found: {}", prompt.display());
+        }
+    }
+
+    let prd_dir = validation::prd_dir(&args.prd);
+    let repo_root = std::fs::canonicalize(git::toplevel(prd_dir).context("ralph bench needs a git repository")?)
+        .context("Failed to resolve repository root")?;
+    let prd_rel = std::fs::canonicalize(&args.prd)
+        .context("Failed to resolve PRD path")?
+        .strip_prefix(&repo_root)
+        .context("ralph bench needs the PRD inside the git repository")?
+        .to_path_buf();
+    let project_rel = match args.project_dir {
+        Some(ref dir) => Some(
+            std::fs::canonicalize(dir)
+                .context("Failed to resolve project directory")?
+                .strip_prefix(&repo_root)
+                .context("ralph bench needs the project directory inside the git repository")?
+                .to_path_buf(),
+        ),
+        None => None,
+    };
+    let bench_id = run_id::generate();
+    let ralph_dir = prd_dir.join(".ralph");
+    let worktrees_dir = ralph_dir.join("bench").join(&bench_id);
+
+    let total = cells.len() * matrix.repeat as usize;
+    output::log(&format!("Bench {bench_id}: {} combinations x {} runs", cells.len(), matrix.repeat));
+    let mut results: Vec<(Cell, Vec<Trial>)> = Vec::new();
+    for (at, cell) in cells.into_iter().enumerate() {
+        let mut trials = Vec::new();
+        for n in 1..=matrix.repeat {
+            let id = format!("{bench_id}-{}-{n}", at + 1);
+            output::section(&format!(
+                "Bench run {}/{total}: {}, {}, {}",
+                at * matrix.repeat as usize + n as usize,
+                cell.prompt_name(),
+                cell.model.as_deref().unwrap_or(DEFAULT),
+                cell.permission_mode
+            ));
+            let worktree = worktrees_dir.join(&id);
+            let branch = format!("ralph/bench-{id}");
+            git::add_worktree(&repo_root, &worktree, &branch)
+                .with_context(|| format!("Failed to create worktree for bench run {id}"))?;
+            let mut cell_args = args.clone();
+            cell_args.command = None;
+            cell_args.prd = worktree.join(&prd_rel);
+            if !cell_args.prd.exists() {
+                let _ = git::remove_worktree(&repo_root, &worktree);
+                let _ = git::delete_branch(&repo_root, &branch);
+                bail!("{} is not committed; bench runs only see committed files", args.prd.display());
+            }
+            cell_args.project_dir = project_rel.as_ref().map(|rel| worktree.join(rel));
+            cell_args.prompt = cell.prompt.clone();
+            cell_args.model = cell.model.clone();
+            cell_args.permission_mode = cell.permission_mode.clone();
+            cell_args.run_id = Some(id.clone());
+            cell_args.resume = false;
+
+            let cell_ralph_dir = validation::prd_dir(&cell_args.prd).join(".ralph");
+            let result = Box::pin(runner::run(cell_args)).await;
+            let trial = trial(&id, &cell_ralph_dir, &result);
+            // The agent's commits go with the branch; the table and saved results keep the outcome
+            let removed = git::remove_worktree(&repo_root, &worktree).and_then(|()| git::delete_branch(&repo_root, &branch));
+            if let Err(e) = removed {
+                output::warn(&format!("{e:#}"));
+            }
+            match result {
+                Err(e) if e.is::<stop::OperatorStop>() => return Err(e),
+                Err(e) => output::warn(&format!("Bench run {id} failed: {e:#}")),
+                Ok(()) => {}
+            }
+            trials.push(trial);
+        }
+        results.push((cell, trials));
+    }
+    let _ = std::fs::remove_dir(&worktrees_dir);
+
+    let table = comparison(&results);
+    output::blank();
+    print!("{table}");
+    let saved = ralph_dir.join("bench").join(format!("{bench_id}.json"));
+    std::fs::create_dir_all(ralph_dir.join("bench")).context("Failed to create .ralph/bench directory")?;
+    let json: Vec<_> = results.iter().map(|(cell, trials)| serde_json::json!({ "cell": cell, "trials": trials })).collect();
+    std::fs::write(&saved, serde_json::to_string_pretty(&json)?)
+        .with_context(|| format!("Failed to write {}", saved.display()))?;
+    output::log(&format!("Results saved to {}", saved.display()));
+    Ok(())
+}
+
+/// The run's outcome from its run index; a run that never got that far failed.
+fn trial(id: &str, ralph_dir: &Path, result: &Result<()>) -> Trial {
+    let outcome = history::load(ralph_dir)
+        .ok()
+        .and_then(|records| records.into_iter().rev().find(|r| r.run_id.as_deref() == Some(id)))
+        .and_then(|r| r.outcome);
+    match outcome {
+        Some(o) => Trial { run_id: id.into(), result: o.result, iterations: o.iterations, cost_usd: o.cost_usd },
+        None => Trial {
+            run_id: id.into(),
+            result: if result.is_ok() { "incomplete" } else { "failed" }.into(),
+            iterations: 0,
+            cost_usd: None,
+        },
+    }
+}
+
+/// One row per cell: how many runs completed, and the mean iterations and cost.
+#[must_use]
+pub fn comparison(results: &[(Cell, Vec<Trial>)]) -> String {
+    let rows: Vec<Vec<String>> = results
+        .iter()
+        .map(|(cell, trials)| {
+            let runs = trials.len().max(1) as f64;
+            let completed = trials.iter().filter(|t| t.result == "complete").count();
+            let iterations = trials.iter().map(|t| f64::from(t.iterations)).sum::<f64>() / runs;
+            let costs: Vec<f64> = trials.iter().filter_map(|t| t.cost_usd).collect();
+            let cost = match costs.len() {
+                0 => "-".to_string(),
+                n => format!("${:.2}", costs.iter().sum::<f64>() / n as f64),
+            };
+            vec![
+                cell.prompt_name(),
+                cell.model.clone().unwrap_or_else(|| DEFAULT.into()),
+                cell.permission_mode.clone(),
+                format!("{completed}/{} ({:.0}%)", trials.len(), completed as f64 / runs * 100.0),
+                format!("{iterations:.1}"),
+                cost,
+            ]
+        })
+        .collect();
+    let mut out = String::new();
+    let _ = writeln!(out, "## Bench results\n");
+    report::markdown_table(&mut out, &["Prompt", "Model", "Permission mode", "Completed", "Iterations", "Cost"], &rows);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn args(extra: &[&str]) -> Args {
+        Args::try_parse_from(std::iter::once("ralph").chain(extra.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn parses_a_matrix() {
+        let matrix: Matrix = toml::from_str(
+            r#"
+            prompts = ["default", "prompts/terse.md"]
+            models = ["sonnet", "opus"]
+            permission-modes = ["acceptEdits"]
+            repeat = 3
+            "#,
+        )
+        .unwrap();
+        assert_eq!(matrix.prompts, ["default", "prompts/terse.md"]);
+        assert_eq!(matrix.repeat, 3);
+        assert!(toml::from_str::<Matrix>("model = [\"opus\"]").is_err());
+        assert_eq!(toml::from_str::<Matrix>("").unwrap().repeat, 1);
+    }
+
+    #[test]
+    fn cells_cover_every_combination() {
+        let matrix = Matrix {
+            prompts: vec!["default".into(), "terse.md".into()],
+            models: vec!["sonnet".into(), "default".into()],
+            permission_modes: Vec::new(),
+            repeat: 1,
+        };
+        let cells = matrix.cells(&args(&["--permission-mode", "plan"]), Path::new("/bench"));
+        assert_eq!(cells.len(), 4);
+        assert_eq!(cells[0], Cell { prompt: None, model: Some("sonnet".into()), permission_mode: "plan".into() });
+        assert_eq!(cells[3], Cell { prompt: Some("/bench/terse.md".into()), model: None, permission_mode: "plan".into() });
+
+        let empty = Matrix { prompts: Vec::new(), models: Vec::new(), permission_modes: Vec::new(), repeat: 1 };
+        let cells = empty.cells(&args(&["--model", "opus"]), Path::new("/bench"));
+        assert_eq!(cells, [Cell { prompt: None, model: Some("opus".into()), permission_mode: "acceptEdits".into() }]);
+    }
+
+    #[test]
+    fn compares_success_iterations_and_cost() {
+        let cell = Cell { prompt: Some("/b/terse.md".into()), model: Some("opus".into()), permission_mode: "plan".into() };
+        let trial = |result: &str, iterations, cost_usd| Trial { run_id: "r".into(), result: result.into(), iterations, cost_usd };
+        let table = comparison(&[(
+            cell,
+            vec![trial("complete", 3, Some(1.0)), trial("incomplete", 10, Some(2.0)), trial("failed", 2, None)],
+        )]);
+        assert!(table.contains("| Prompt | Model | Permission mode | Completed | Iterations | Cost |"), "{table}");
+        assert!(table.contains("| terse.md | opus | plan | 1/3 (33%) | 5.0 | $1.50 |"), "{table}");
+    }
+
+    #[tokio::test]
+    async fn runs_each_cell_in_a_worktree() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let prd = r#"{
+  "project": { "name": "bench", "description": "d" },
+  "verification": { "commands": [], "runAfterEachFeature": false },
+  "features": [ { "id": "feat-1", "category": "functional", "description": "d", "steps": [], "status": "in-progress" } ],
+  "completion": { "allFeaturesComplete": false, "allVerificationsPassing": false, "marker": "<promise>DONE</promise>" }
+}"#;
+        std::fs::write(root.join("prd.jsonc"), prd).unwrap();
+        std::fs::write(root.join(".gitignore"), ".ralph/\n").unwrap();
+        std::fs::write(root.join("script.json"), r#"{ "steps": [ { "output": "<promise>DONE</promise>" } ] }"#).unwrap();
+        std::fs::write(root.join("bench.toml"), "models = [\"a\", \"b\"]\n").unwrap();
+        for git_args in [
+            &["init", "-q", "-b", "main"][..],
+            &["config", "user.email", "t@example.com"],
+            &["config", "user.name", "t"],
+            &["add", "-A"],
+            &["commit", "-qm", "base"],
+        ] {
+            assert!(std::process::Command::new("git").current_dir(root).args(git_args).status().unwrap().success());
+        }
+
+        let prd_path = root.join("prd.jsonc").display().to_string();
+        let script = root.join("script.json").display().to_string();
+        let args = args(&["-p", &prd_path, "--simulate", &script, "-d", "0", "--skip-init", "--premature-completion", "accept"]);
+        run(&args, &BenchArgs { matrix: root.join("bench.toml") }).await.unwrap();
+
+        let bench_dir = root.join(".ralph/bench");
+        let saved: Vec<PathBuf> = std::fs::read_dir(&bench_dir).unwrap().flatten().map(|e| e.path()).collect();
+        // The worktrees are gone; only the results remain
+        assert_eq!(saved.len(), 1, "{saved:?}");
+        let results: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&saved[0]).unwrap()).unwrap();
+        let models: Vec<&str> = results.as_array().unwrap().iter().map(|r| r["cell"]["model"].as_str().unwrap()).collect();
+        assert_eq!(models, ["a", "b"]);
+        assert!(results.as_array().unwrap().iter().all(|r| r["trials"][0]["result"] == "complete"), "{results}");
+    }
+}
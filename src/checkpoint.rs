@@ -0,0 +1,89 @@
+//! `--rollback-on-failure`: the repository and PRD as they were before an iteration, so an
+//! iteration that fails PRD validation or verification can be undone instead of built on.
+
+use crate::{atomic, git};
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    dir: PathBuf,
+    head: String,
+    /// Uncommitted changes at the time, from `git stash create`
+    uncommitted: Option<String>,
+    prd_path: PathBuf,
+    prd: String,
+}
+
+impl Checkpoint {
+    /// Records HEAD and the uncommitted changes of the repository at `dir`, and the PRD.
+    pub fn take(dir: &Path, prd_path: &Path) -> Result<Self> {
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            head: git::head_sha(dir).context("Failed to record HEAD")?,
+            uncommitted: git::stash_create(dir).context("Failed to record uncommitted changes")?,
+            prd_path: prd_path.to_path_buf(),
+            prd: std::fs::read_to_string(prd_path).context("Failed to read PRD file")?,
+        })
+    }
+
+    #[must_use]
+    pub fn head(&self) -> &str {
+        &self.head
+    }
+
+    /// Resets to the recorded HEAD, reapplies the changes that were uncommitted then, and
+    /// restores the PRD. Files in `keep` (such as the progress file) keep their current content.
+    pub fn restore(&self, keep: &[&Path]) -> Result<()> {
+        let kept: Vec<(&Path, Option<Vec<u8>>)> = keep.iter().map(|path| (*path, std::fs::read(path).ok())).collect();
+        git::reset_hard(&self.dir, &self.head)?;
+        if let Some(ref sha) = self.uncommitted {
+            git::stash_apply(&self.dir, sha).context("Failed to reapply uncommitted changes")?;
+        }
+        atomic::write(&self.prd_path, &self.prd)?;
+        for (path, content) in kept {
+            if let Some(content) = content {
+                atomic::write(path, content)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = std::process::Command::new("git").current_dir(dir).args(args).output().unwrap();
+        assert!(output.status.success(), "git {args:?}: {}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    #[test]
+    fn restore_undoes_commits_and_keeps_earlier_uncommitted_work() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        git(dir.path(), &["init", "-q", "-b", "main"]);
+        git(dir.path(), &["config", "user.email", "t@example.com"]);
+        git(dir.path(), &["config", "user.name", "t"]);
+        for name in ["prd.jsonc", "progress.txt", "src.rs"] {
+            std::fs::write(path(name), "base\n").unwrap();
+        }
+        git(dir.path(), &["add", "-A"]);
+        git(dir.path(), &["commit", "-qm", "base"]);
+        std::fs::write(path("src.rs"), "user edit\n").unwrap();
+
+        let checkpoint = Checkpoint::take(dir.path(), &path("prd.jsonc")).unwrap();
+        std::fs::write(path("src.rs"), "agent edit\n").unwrap();
+        std::fs::write(path("prd.jsonc"), "complete\n").unwrap();
+        std::fs::write(path("progress.txt"), "base\nlearned something\n").unwrap();
+        git(dir.path(), &["commit", "-qam", "agent"]);
+        checkpoint.restore(&[&path("progress.txt")]).unwrap();
+
+        assert_eq!(git(dir.path(), &["rev-parse", "HEAD"]), checkpoint.head());
+        assert_eq!(std::fs::read_to_string(path("src.rs")).unwrap(), "user edit\n");
+        assert_eq!(std::fs::read_to_string(path("prd.jsonc")).unwrap(), "base\n");
+        assert_eq!(std::fs::read_to_string(path("progress.txt")).unwrap(), "base\nlearned something\n");
+    }
+}
@@ -1,5 +1,16 @@
+use crate::{
+    agent::{AgentBackend, SessionOptions},
+    control,
+    events::{self, RunEvent},
+    metrics::Usage,
+    redact,
+    resources::{self, ResourceUsage, Sampler},
+    serve::{LiveEvent, LiveFeed},
+    simulate::Simulator,
+    stream::{self, StreamEvent},
+};
 use anyhow::{Context, Result};
-use std::fmt::Write as FmtWrite;
+use std::collections::{BTreeMap, VecDeque};
 use std::io::Write;
 use std::process::Stdio;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
@@ -8,65 +19,215 @@ use tokio::time::Duration;
 use tokio_util::sync::CancellationToken;
 
 pub struct ClaudeArgs<'a> {
+    pub backend: &'a dyn AgentBackend,
     pub permission_mode: String,
     pub continue_session: bool,
     pub dangerously_skip_permissions: bool,
+    pub model: Option<String>,
+    pub allowed_tools: &'a [String],
+    pub disallowed_tools: &'a [String],
     pub timeout_secs: u64,
+    /// Stop the agent once it has printed nothing for this long
+    pub stall_timeout: Option<Duration>,
     pub project_dir: &'a std::path::Path,
+    pub output_buffer_bytes: usize,
+    pub live: Option<&'a LiveFeed>,
+    pub simulator: Option<&'a Simulator>,
+    /// Variables from the PRD's `env` and `--env`
+    pub env: &'a BTreeMap<String, String>,
 }
 
 pub struct ClaudeResult {
     pub output: String,
     pub success: bool,
+    /// Token usage and cost, when the agent reported them (Claude's result event)
+    pub usage: Option<Usage>,
+    /// CPU and memory of the agent process tree, when it ran long enough to be sampled
+    pub resources: Option<ResourceUsage>,
+    /// Stopped by the stall watchdog rather than exiting or timing out
+    pub stalled: bool,
 }
 
+/// How long the agent gets to exit after SIGINT (a stall or a second Ctrl-C) before its process group is killed.
+const STALL_GRACE: Duration = Duration::from_secs(5);
+
+/// `<name> <version>` for the backend, e.g. for the config digest, if its CLI is installed.
+pub async fn cli_version(backend: &dyn AgentBackend) -> Option<String> {
+    let spec = backend.version_command()?;
+    let output = Command::new(&spec.program).args(&spec.args).output().await.ok()?;
+    output.status.success().then(|| {
+        format!("{} {}", backend.name(), String::from_utf8_lossy(&output.stdout).trim())
+    })
+}
+
+#[tracing::instrument(
+    name = "agent",
+    skip_all,
+    fields(
+        backend = args.backend.name(),
+        model = args.model.as_deref(),
+        success = tracing::field::Empty,
+        stalled = tracing::field::Empty,
+        cost_usd = tracing::field::Empty,
+    )
+)]
 pub async fn run_claude(
     prompt: &str,
     args: &ClaudeArgs<'_>,
     log_path: &std::path::Path,
     cancel_token: &CancellationToken,
+) -> Result<ClaudeResult> {
+    let result = match args.simulator {
+        Some(simulator) => simulator.run(args.project_dir, log_path, args.live, args.stall_timeout, args.env, cancel_token).await,
+        None => spawn_agent(prompt, args, log_path, cancel_token).await,
+    };
+    if let Ok(ref r) = result {
+        let span = tracing::Span::current();
+        span.record("success", r.success);
+        span.record("stalled", r.stalled);
+        if let Some(ref usage) = r.usage {
+            span.record("cost_usd", usage.cost_usd);
+        }
+    }
+    result
+}
+
+async fn spawn_agent(
+    prompt: &str,
+    args: &ClaudeArgs<'_>,
+    log_path: &std::path::Path,
+    cancel_token: &CancellationToken,
 ) -> Result<ClaudeResult> {
     let duration = Duration::from_secs(args.timeout_secs);
 
-    let mut cmd = Command::new("claude");
+    let spec = args.backend.command(
+        prompt,
+        &SessionOptions {
+            permission_mode: &args.permission_mode,
+            continue_session: args.continue_session,
+            dangerously_skip_permissions: args.dangerously_skip_permissions,
+            model: args.model.as_deref(),
+            allowed_tools: args.allowed_tools,
+            disallowed_tools: args.disallowed_tools,
+        },
+    );
+    let shown: Vec<&str> = spec.args.iter().map(|a| if a == prompt { "<prompt>" } else { a.as_str() }).collect();
+    crate::output::debug(&format!("Agent command: {} {}", spec.program, shown.join(" ")));
+    let mut cmd = Command::new(&spec.program);
     cmd.current_dir(args.project_dir);
-    cmd.arg("--permission-mode").arg(&args.permission_mode);
-    if args.dangerously_skip_permissions {
-        cmd.arg("--dangerously-skip-permissions");
+    cmd.args(&spec.args);
+    cmd.envs(spec.env.iter().map(|(k, v)| (k, v)));
+    cmd.envs(args.env);
+    if let Some(id) = crate::run_id::current() {
+        cmd.env(crate::run_id::ENV_VAR, id);
     }
-    if args.continue_session {
-        cmd.arg("--continue");
-    } else {
-        cmd.arg("--print");
-    }
-    cmd.stdin(Stdio::piped());
+    cmd.stdin(if spec.stdin.is_some() { Stdio::piped() } else { Stdio::null() });
     cmd.stdout(Stdio::piped());
     cmd.stderr(Stdio::piped());
+    control::own_process_group(&mut cmd);
 
-    let mut child = cmd.spawn().context("Failed to spawn claude CLI")?;
+    let mut child = cmd
+        .spawn()
+        .with_context(|| format!("Failed to spawn {} CLI", args.backend.name()))?;
+    let pid = child.id();
 
-    tokio::select! {
-        result = run_claude_inner(&mut child, prompt, log_path) => result,
+    let sampler = child.id().map(Sampler::start);
+    let result = tokio::select! {
+        result = run_claude_inner(&mut child, spec.stdin.as_deref(), log_path, args.output_buffer_bytes, args.stall_timeout, args.live) => result,
         _ = tokio::time::sleep(duration) => {
             let _ = child.kill().await;
+            if let Some(pid) = pid {
+                control::kill_group(pid);
+            }
             Ok(ClaudeResult {
-                output: "Timeout: Claude execution exceeded time limit".to_string(),
+                output: "Timeout: agent execution exceeded time limit".to_string(),
                 success: false,
+                usage: None,
+                resources: None,
+                stalled: false,
             })
         }
         _ = cancel_token.cancelled() => {
-            let _ = child.kill().await;
+            // In its own group the agent missed the terminal's Ctrl-C; pass it on, then kill what's left
+            let interrupted = pid.is_some_and(control::interrupt_group);
+            if !interrupted || tokio::time::timeout(STALL_GRACE, child.wait()).await.is_err() {
+                let _ = child.kill().await;
+            }
+            if let Some(pid) = pid {
+                control::kill_group(pid);
+            }
             Ok(ClaudeResult {
-                output: "Cancelled: Claude execution was interrupted".to_string(),
+                output: "Cancelled: agent execution was interrupted".to_string(),
                 success: false,
+                usage: None,
+                resources: None,
+                stalled: false,
             })
         }
+    };
+    let result = match result {
+        Ok(r) if r.stalled => stop_stalled(&mut child, r, args.stall_timeout.unwrap_or_default(), log_path).await,
+        other => other,
+    };
+    let resources = sampler.and_then(Sampler::finish);
+    result.map(|r| ClaudeResult { resources, ..r })
+}
+
+/// Records what the stalled agent's processes were doing, then interrupts its process group
+/// and kills whatever is left of it after [`STALL_GRACE`].
+async fn stop_stalled(
+    child: &mut tokio::process::Child,
+    mut result: ClaudeResult,
+    stall: Duration,
+    log_path: &std::path::Path,
+) -> Result<ClaudeResult> {
+    let message = format!("Stalled: no agent output for {}s", stall.as_secs());
+    crate::output::warn(&message);
+    let snapshot = child.id().map(resources::process_snapshot).unwrap_or_default();
+    let mut log_file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(log_path)
+        .context("Failed to open log file")?;
+    writeln!(log_file, "[ralph: {message}; processes:]")?;
+    for line in &snapshot {
+        // Command lines can carry credentials
+        let line = redact::text(line);
+        crate::output::dim(&format!("  {line}"));
+        writeln!(log_file, "[ralph:   {line}]")?;
+    }
+
+    let pid = child.id();
+    let interrupted = pid.is_some_and(control::interrupt_group);
+    if !interrupted || tokio::time::timeout(STALL_GRACE, child.wait()).await.is_err() {
+        let _ = child.kill().await;
+    }
+    if let Some(pid) = pid {
+        control::kill_group(pid);
     }
+
+    result.output.push_str(&message);
+    result.output.push('\n');
+    Ok(result)
 }
 
-async fn run_claude_inner(child: &mut tokio::process::Child, prompt: &str, log_path: &std::path::Path) -> Result<ClaudeResult> {
+/// Completes after `stall` without agent output; never without a stall timeout.
+async fn stall_timer(stall: Option<Duration>) {
+    match stall {
+        Some(stall) => tokio::time::sleep(stall).await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn run_claude_inner(
+    child: &mut tokio::process::Child,
+    prompt: Option<&str>,
+    log_path: &std::path::Path,
+    output_buffer_bytes: usize,
+    stall: Option<Duration>,
+    live: Option<&LiveFeed>,
+) -> Result<ClaudeResult> {
     // Write prompt to stdin
-    if let Some(mut stdin) = child.stdin.take() {
+    if let (Some(prompt), Some(mut stdin)) = (prompt, child.stdin.take()) {
         stdin.write_all(prompt.as_bytes()).await?;
         stdin.shutdown().await?;
     }
@@ -77,7 +238,10 @@ async fn run_claude_inner(child: &mut tokio::process::Child, prompt: &str, log_p
     let mut log_file = std::fs::File::create(log_path)
         .context("Failed to create log file")?;
 
-    let mut output = String::new();
+    let mut output = OutputBuffer::new(output_buffer_bytes);
+    let mut usage = None;
+    let mut reported_error = false;
+    let mut seen_text = false;
 
     // Stream stdout
     let mut stdout_reader = BufReader::new(stdout).lines();
@@ -95,13 +259,35 @@ async fn run_claude_inner(child: &mut tokio::process::Child, prompt: &str, log_p
             line = stdout_reader.next_line(), if !stdout_done => {
                 match line {
                     Ok(Some(line)) => {
-                        println!("{line}");
-                        writeln!(log_file, "{line}")?;
-                        let _ = writeln!(output, "{line}");
+                        // stream-json: readable lines per event, keeping the result's usage
+                        let lines = match stream::parse_line(&line) {
+                            Some(event) => {
+                                let lines = event.log_lines(seen_text);
+                                match event {
+                                    StreamEvent::Result(result) => {
+                                        usage = Some(result.usage);
+                                        reported_error |= result.is_error;
+                                    }
+                                    StreamEvent::Assistant { ref text, .. } => seen_text |= !text.is_empty(),
+                                    _ => {}
+                                }
+                                lines
+                            }
+                            None => vec![line],
+                        };
+                        for line in lines {
+                            let line = redact::text(&line).into_owned();
+                            events::emit(&RunEvent::AgentOutputChunk { line: line.clone(), stderr: false });
+                            writeln!(log_file, "{line}")?;
+                            output.push_line(&line);
+                            if let Some(live) = live {
+                                live.send(LiveEvent::Line(line));
+                            }
+                        }
                     }
                     Ok(None) => stdout_done = true,
                     Err(e) => {
-                        eprintln!("Error reading stdout: {e}");
+                        crate::output::error(&format!("Error reading stdout: {e}"));
                         stdout_done = true;
                     }
                 }
@@ -109,24 +295,257 @@ async fn run_claude_inner(child: &mut tokio::process::Child, prompt: &str, log_p
             line = stderr_reader.next_line(), if !stderr_done => {
                 match line {
                     Ok(Some(line)) => {
-                        eprintln!("{line}");
+                        let line = redact::text(&line).into_owned();
+                        events::emit(&RunEvent::AgentOutputChunk { line: line.clone(), stderr: true });
                         writeln!(log_file, "[stderr] {line}")?;
-                        let _ = writeln!(output, "{line}");
+                        output.push_line(&line);
+                        if let Some(live) = live {
+                            live.send(LiveEvent::Line(line));
+                        }
                     }
                     Ok(None) => stderr_done = true,
                     Err(e) => {
-                        eprintln!("Error reading stderr: {e}");
+                        crate::output::error(&format!("Error reading stderr: {e}"));
                         stderr_done = true;
                     }
                 }
             }
+            // Restarted on every loop, so any line resets it
+            () = stall_timer(stall) => {
+                return Ok(ClaudeResult {
+                    output: output.into_string(),
+                    success: false,
+                    usage,
+                    resources: None,
+                    stalled: true,
+                });
+            }
         }
     }
 
     let status = child.wait().await?;
+    if let Some(ref usage) = usage {
+        writeln!(
+            log_file,
+            "[ralph: usage {} input / {} output tokens, ${:.4}]",
+            usage.total_input_tokens(),
+            usage.output_tokens,
+            usage.cost_usd
+        )?;
+    }
 
     Ok(ClaudeResult {
-        output,
-        success: status.success(),
+        output: output.into_string(),
+        success: status.success() && !reported_error,
+        usage,
+        resources: None,
+        stalled: false,
     })
 }
+
+/// Bytes always kept from the start of the output, where stuck-agent messages appear.
+const HEAD_BYTES: usize = 4096;
+
+/// Bounded in-memory copy of agent output used for analysis.
+///
+/// Keeps the first `HEAD_BYTES` and a ring of the most recent `capacity` bytes;
+/// everything in between is dropped (the log file still receives every line).
+pub struct OutputBuffer {
+    head: String,
+    tail: VecDeque<String>,
+    tail_bytes: usize,
+    capacity: usize,
+    dropped_bytes: usize,
+}
+
+impl OutputBuffer {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            head: String::new(),
+            tail: VecDeque::new(),
+            tail_bytes: 0,
+            capacity: capacity.max(1),
+            dropped_bytes: 0,
+        }
+    }
+
+    pub fn push_line(&mut self, line: &str) {
+        let len = line.len() + 1;
+        if self.tail.is_empty() && self.head.len() + len <= HEAD_BYTES {
+            self.head.push_str(line);
+            self.head.push('\n');
+            return;
+        }
+
+        let mut entry = format!("{line}\n");
+        if entry.len() > self.capacity {
+            let cut = ceil_char_boundary(&entry, entry.len() - self.capacity);
+            self.dropped_bytes += cut;
+            entry.drain(..cut);
+        }
+        self.tail_bytes += entry.len();
+        self.tail.push_back(entry);
+
+        while self.tail_bytes > self.capacity {
+            let Some(front) = self.tail.pop_front() else { break };
+            self.tail_bytes -= front.len();
+            self.dropped_bytes += front.len();
+        }
+    }
+
+    #[must_use]
+    pub fn dropped_bytes(&self) -> usize {
+        self.dropped_bytes
+    }
+
+    #[must_use]
+    pub fn into_string(self) -> String {
+        let mut out = self.head;
+        if self.dropped_bytes > 0 {
+            out.push_str(&format!(
+                "[ralph: {} bytes of output omitted, see iteration log]\n",
+                self.dropped_bytes
+            ));
+        }
+        out.extend(self.tail);
+        out
+    }
+}
+
+fn ceil_char_boundary(s: &str, mut i: usize) -> usize {
+    while !s.is_char_boundary(i) {
+        i += 1;
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_output_is_kept_verbatim() {
+        let mut buf = OutputBuffer::new(1024);
+        buf.push_line("first");
+        buf.push_line("second");
+        assert_eq!(buf.dropped_bytes(), 0);
+        assert_eq!(buf.into_string(), "first\nsecond\n");
+    }
+
+    #[test]
+    fn keeps_head_and_most_recent_tail() {
+        let mut buf = OutputBuffer::new(100);
+        buf.push_line("I cannot proceed");
+        for i in 0..10_000 {
+            buf.push_line(&format!("line {i:05} {}", "x".repeat(50)));
+        }
+        buf.push_line("<promise>COMPLETE</promise>");
+
+        assert!(buf.dropped_bytes() > 0);
+        let out = buf.into_string();
+        assert!(out.starts_with("I cannot proceed\n"));
+        assert!(out.ends_with("<promise>COMPLETE</promise>\n"));
+        assert!(out.contains("bytes of output omitted"));
+        assert!(out.len() < HEAD_BYTES + 200);
+    }
+
+    #[test]
+    fn tail_stays_within_capacity() {
+        let mut buf = OutputBuffer::new(64);
+        for _ in 0..1000 {
+            buf.push_line(&"y".repeat(200));
+        }
+        assert!(buf.tail_bytes <= 64);
+    }
+
+    #[test]
+    fn oversized_line_truncated_on_char_boundary() {
+        let mut buf = OutputBuffer::new(10);
+        buf.push_line(&"x".repeat(HEAD_BYTES));
+        buf.push_line(&"é".repeat(100));
+        let out = buf.into_string();
+        assert!(out.ends_with("éééé\n"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn silent_agent_is_stopped_as_stalled() {
+        use crate::config::Args;
+        use clap::Parser;
+
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("it.log");
+        let cli = Args::try_parse_from(["ralph", "--agent", "shell", "--agent-command", "echo started; exec sleep 30"]).unwrap();
+        let backend = crate::agent::backend(&cli).unwrap();
+        let args = ClaudeArgs {
+            backend: backend.as_ref(),
+            permission_mode: "default".into(),
+            continue_session: false,
+            dangerously_skip_permissions: false,
+            model: None,
+            allowed_tools: &[],
+            disallowed_tools: &[],
+            timeout_secs: 60,
+            stall_timeout: Some(Duration::from_secs(1)),
+            project_dir: dir.path(),
+            output_buffer_bytes: 1024,
+            live: None,
+            simulator: None,
+            env: &BTreeMap::new(),
+        };
+
+        let started = std::time::Instant::now();
+        let result = run_claude("", &args, &log, &CancellationToken::new()).await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(result.stalled && !result.success);
+        assert!(result.output.starts_with("started\n"));
+        assert!(result.output.contains("Stalled: no agent output for 1s"));
+        let log = std::fs::read_to_string(&log).unwrap();
+        assert!(log.contains("sleep"), "{log}");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn stalled_agent_takes_its_children_with_it() {
+        use crate::config::Args;
+        use clap::Parser;
+
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("it.log");
+        // The shell waits on its child rather than exec'ing it
+        let command = r#"echo started; sh -c 'echo $$ > child.pid; exec sleep 30'; echo done"#;
+        let cli = Args::try_parse_from(["ralph", "--agent", "shell", "--agent-command", command]).unwrap();
+        let backend = crate::agent::backend(&cli).unwrap();
+        let args = ClaudeArgs {
+            backend: backend.as_ref(),
+            permission_mode: "default".into(),
+            continue_session: false,
+            dangerously_skip_permissions: false,
+            model: None,
+            allowed_tools: &[],
+            disallowed_tools: &[],
+            timeout_secs: 60,
+            stall_timeout: Some(Duration::from_secs(1)),
+            project_dir: dir.path(),
+            output_buffer_bytes: 1024,
+            live: None,
+            simulator: None,
+            env: &BTreeMap::new(),
+        };
+
+        let result = run_claude("", &args, &log, &CancellationToken::new()).await.unwrap();
+        assert!(result.stalled);
+        let child = std::fs::read_to_string(dir.path().join("child.pid")).unwrap();
+        let stat = format!("/proc/{}/stat", child.trim());
+        // Gone, or a zombie waiting for a reaper
+        let running = || std::fs::read_to_string(&stat).is_ok_and(|s| !s.contains(") Z "));
+        for _ in 0..20 {
+            if !running() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("the agent's child {} outlived it", child.trim());
+    }
+}
@@ -0,0 +1,234 @@
+//! Retention for `.ralph`: `ralph clean`, and `--retain-runs`/`--retain-for` before each run,
+//! delete old runs' iteration logs, artifacts, and sessions, and the saved state and metrics
+//! when they belong to one of them.
+
+use crate::{
+    artifact::IterationArtifact,
+    config::{Args, CleanArgs},
+    output, session, state, timing, validation,
+};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, FixedOffset};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Retention {
+    pub keep_last: Option<usize>,
+    pub older_than: Option<std::time::Duration>,
+}
+
+impl Retention {
+    #[must_use]
+    pub fn from_args(args: &Args) -> Self {
+        Self { keep_last: args.retain_runs, older_than: args.retain_for.map(std::time::Duration::from_secs) }
+    }
+
+    #[must_use]
+    pub fn is_set(&self) -> bool {
+        self.keep_last.is_some() || self.older_than.is_some()
+    }
+}
+
+/// The files of one run; artifacts from before run ids share the `None` run.
+#[derive(Debug)]
+struct Run {
+    id: Option<String>,
+    last_started: Option<DateTime<FixedOffset>>,
+    files: Vec<PathBuf>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Cleaned {
+    pub runs: usize,
+    pub files: usize,
+}
+
+/// Runs with iteration artifacts in `ralph_dir`, oldest first.
+fn runs(ralph_dir: &Path) -> Vec<Run> {
+    let logs_dir = ralph_dir.join("logs");
+    let names: Vec<String> = std::fs::read_dir(&logs_dir)
+        .map(|entries| entries.flatten().map(|e| e.file_name().to_string_lossy().into_owned()).collect())
+        .unwrap_or_default();
+    let mut runs: BTreeMap<Option<String>, Run> = BTreeMap::new();
+    for artifact in IterationArtifact::load_all(&logs_dir) {
+        let run = runs.entry(artifact.run_id.clone()).or_insert_with(|| Run {
+            id: artifact.run_id.clone(),
+            last_started: None,
+            files: artifact.run_id.iter().map(|id| logs_dir.join(format!("{id}-ralph.jsonl"))).collect(),
+        });
+        run.last_started = run.last_started.max(DateTime::parse_from_rfc3339(&artifact.started_at).ok());
+        // Artifacts record the path as the run saw it; the review, audit, and summary logs share its stem
+        let Some(stem) = artifact.log_path.file_stem().map(|s| s.to_string_lossy().into_owned()) else {
+            continue;
+        };
+        let (artifact_name, extra) = (format!("{stem}.json"), format!("{stem}-"));
+        let belongs = |name: &&String| {
+            **name == artifact_name || name.strip_suffix(".log").is_some_and(|n| n == stem || n.starts_with(&extra))
+        };
+        run.files.extend(names.iter().filter(belongs).map(|n| logs_dir.join(n)));
+        if let Some(ref id) = artifact.run_id {
+            run.files.push(session::path(ralph_dir, id, artifact.iteration));
+        }
+    }
+    let mut runs: Vec<Run> = runs.into_values().collect();
+    runs.sort_by_key(|r| r.last_started);
+    runs
+}
+
+/// Runs to delete: all but the newest `keep_last`, and those last active before `now - older_than`.
+fn expired(runs: &[Run], retention: Retention, now: DateTime<FixedOffset>) -> Vec<&Run> {
+    let keep_from = retention.keep_last.map_or(0, |n| runs.len().saturating_sub(n));
+    let cutoff = retention.older_than.and_then(|d| chrono::Duration::from_std(d).ok()).map(|d| now - d);
+    runs.iter()
+        .enumerate()
+        .filter(|(at, run)| *at < keep_from || cutoff.is_some_and(|c| run.last_started.is_none_or(|t| t < c)))
+        .map(|(_, run)| run)
+        .collect()
+}
+
+/// Deletes the runs `retention` expires, except `current`.
+pub fn clean(ralph_dir: &Path, retention: Retention, current: Option<&str>) -> Result<Cleaned> {
+    let runs = runs(ralph_dir);
+    let expired: Vec<&Run> = expired(&runs, retention, chrono::Local::now().fixed_offset())
+        .into_iter()
+        .filter(|run| current.is_none() || run.id.as_deref() != current)
+        .collect();
+
+    let mut cleaned = Cleaned::default();
+    for run in &expired {
+        for file in &run.files {
+            match std::fs::remove_file(file) {
+                Ok(()) => cleaned.files += 1,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e).with_context(|| format!("Failed to delete {}", file.display())),
+            }
+        }
+        cleaned.runs += 1;
+    }
+
+    // Saved state and metrics only describe the last run; they go with it
+    let removed = |id: Option<String>| id.is_some() && expired.iter().any(|run| run.id == id);
+    let state_run = state::load(ralph_dir).ok().flatten().and_then(|s| s.run_id);
+    let metrics_run = timing::load(ralph_dir).ok().flatten().and_then(|m| m.run_id);
+    for (path, id) in [(state::path(ralph_dir), state_run), (timing::path(ralph_dir), metrics_run)] {
+        if removed(id) {
+            std::fs::remove_file(&path).with_context(|| format!("Failed to delete {}", path.display()))?;
+            cleaned.files += 1;
+        }
+    }
+    Ok(cleaned)
+}
+
+/// `ralph clean`: the options given, else the configured retention.
+pub fn run(args: &Args, opts: &CleanArgs) -> Result<()> {
+    let retention = match (opts.keep_last, opts.older_than) {
+        (None, None) => Retention::from_args(args),
+        (keep_last, older_than) => Retention { keep_last, older_than: older_than.map(std::time::Duration::from_secs) },
+    };
+    if !retention.is_set() {
+        bail!("Nothing to clean by: pass --keep-last or --older-than, or set retain-runs or retain-for in ralph.toml");
+    }
+    let ralph_dir = validation::prd_dir(&args.prd).join(".ralph");
+    let cleaned = clean(&ralph_dir, retention, None)?;
+    output::success(&format!("Deleted {} runs ({} files) from {}", cleaned.runs, cleaned.files, ralph_dir.display()));
+    Ok(())
+}
+
+/// Applies `--retain-runs`/`--retain-for` before a run; failures only warn.
+pub fn apply_retention(args: &Args, ralph_dir: &Path, current: &str) {
+    let retention = Retention::from_args(args);
+    if !retention.is_set() {
+        return;
+    }
+    match clean(ralph_dir, retention, Some(current)) {
+        Ok(cleaned) if cleaned.runs > 0 => {
+            output::dim(&format!("Retention: deleted {} old runs ({} files)", cleaned.runs, cleaned.files));
+        }
+        Ok(_) => {}
+        Err(e) => output::warn(&format!("Retention cleanup failed: {e:#}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ArtifactBuilder;
+    use std::time::Duration;
+
+    /// One iteration of run `id` with its log, review log, and session.
+    fn iteration(ralph_dir: &Path, id: &str, iteration: u32, started_at: &str) {
+        let logs_dir = ralph_dir.join("logs");
+        let artifact = ArtifactBuilder::new(iteration).run_id(id).started_at(started_at).in_dir(&logs_dir).write().unwrap();
+        std::fs::write(&artifact.log_path, "log").unwrap();
+        std::fs::write(logs_dir.join(format!("{id}-iteration-{iteration}-review.log")), "review").unwrap();
+        std::fs::create_dir_all(session::sessions_dir(ralph_dir)).unwrap();
+        std::fs::write(session::path(ralph_dir, id, iteration), "{}").unwrap();
+    }
+
+    fn setup() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("logs")).unwrap();
+        iteration(dir.path(), "ci", 1, "2026-01-01T10:00:00+00:00");
+        iteration(dir.path(), "ci", 2, "2026-01-01T11:00:00+00:00");
+        iteration(dir.path(), "ci-7", 1, "2026-01-05T10:00:00+00:00");
+        iteration(dir.path(), "new", 1, "2026-01-09T10:00:00+00:00");
+        std::fs::write(dir.path().join("logs/ci-ralph.jsonl"), "").unwrap();
+        dir
+    }
+
+    fn remaining(dir: &Path, sub: &str) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir.join(sub))
+            .unwrap()
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn groups_files_by_run_oldest_first() {
+        let dir = setup();
+        let runs = runs(dir.path());
+        assert_eq!(runs.iter().map(|r| r.id.as_deref()).collect::<Vec<_>>(), [Some("ci"), Some("ci-7"), Some("new")]);
+        // Two iterations of four files each, plus the structured log
+        assert_eq!(runs[0].files.len(), 9);
+        assert!(runs[1].files.iter().all(|f| f.to_string_lossy().contains("ci-7-")));
+    }
+
+    #[test]
+    fn expires_by_count_and_age() {
+        let dir = setup();
+        let runs = runs(dir.path());
+        let now = DateTime::parse_from_rfc3339("2026-01-10T10:00:00+00:00").unwrap();
+        let ids = |retention| expired(&runs, retention, now).iter().map(|r| r.id.clone().unwrap()).collect::<Vec<_>>();
+        assert_eq!(ids(Retention { keep_last: Some(1), older_than: None }), ["ci", "ci-7"]);
+        assert_eq!(ids(Retention { keep_last: Some(5), older_than: None }), Vec::<String>::new());
+        let week = Some(Duration::from_secs(7 * 86400));
+        assert_eq!(ids(Retention { keep_last: None, older_than: week }), ["ci"]);
+        assert_eq!(ids(Retention { keep_last: Some(2), older_than: week }), ["ci"]);
+    }
+
+    #[test]
+    fn deletes_expired_runs_and_their_state() {
+        let dir = setup();
+        state::save(dir.path(), &state::LoopState { run_id: Some("ci-7".into()), ..Default::default() }).unwrap();
+        let cleaned = clean(dir.path(), Retention { keep_last: Some(1), older_than: None }, None).unwrap();
+        assert_eq!(cleaned, Cleaned { runs: 2, files: 14 });
+        assert_eq!(
+            remaining(dir.path(), "logs"),
+            ["new-iteration-1-review.log", "new-iteration-1.json", "new-iteration-1.log"]
+        );
+        assert_eq!(remaining(dir.path(), "sessions"), ["new-1.json"]);
+        assert!(!state::path(dir.path()).exists());
+    }
+
+    #[test]
+    fn keeps_the_current_run() {
+        let dir = setup();
+        let cleaned = clean(dir.path(), Retention { keep_last: Some(0), older_than: None }, Some("ci-7")).unwrap();
+        assert_eq!(cleaned.runs, 2);
+        assert_eq!(remaining(dir.path(), "sessions"), ["ci-7-1.json"]);
+    }
+}
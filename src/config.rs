@@ -1,70 +1,782 @@
-use clap::Parser;
-use std::path::PathBuf;
-
-#[derive(Parser, Debug)]
+use crate::source::SourceSpec;
+use crate::webhook::{EventType, Target};
+use anyhow::{bail, Context, Result};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, Parser, Subcommand, ValueEnum};
+use serde::{Serialize, Serializer};
+use std::ffi::OsString;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+/// Per-project config files, in lookup order. Keys are long flag names.
+pub const CONFIG_FILES: [&str; 2] = ["ralph.toml", ".ralphrc"];
+
+#[derive(Parser, Debug, Clone, Serialize)]
 #[command(name = "ralph")]
 #[command(about = "Autonomous AI agent loop for iterative development")]
 #[command(version)]
 pub struct Args {
+    #[command(subcommand)]
+    #[serde(skip)]
+    pub command: Option<Command>,
+
     /// Path to PRD file
-    #[arg(short, long, default_value = "prd.jsonc")]
+    #[arg(short, long, default_value = "prd.jsonc", global = true)]
     pub prd: PathBuf,
 
+    /// Run every *.jsonc PRD in this directory, one after another
+    #[arg(long, value_name = "DIR", conflicts_with = "prd_manifest", global = true)]
+    pub prd_dir: Option<PathBuf>,
+
+    /// Run the PRDs listed in this TOML file (`prds = [...]`), one after another
+    #[arg(long, value_name = "PATH", global = true)]
+    pub prd_manifest: Option<PathBuf>,
+
+    /// Build the PRD's features from an issue tracker: github:<owner>/<repo>, jira:<host>?..., or linear:<team>?...
+    #[arg(long, value_name = "SOURCE", global = true)]
+    pub prd_source: Option<SourceSpec>,
+
+    /// Working directory for the agent, git, and verification commands [default: PRD's directory]
+    #[arg(long, global = true)]
+    pub project_dir: Option<PathBuf>,
+
+    /// Progress file path (overrides the PRD's `progressPath`) [default: progress.txt beside the PRD]
+    #[arg(long, value_name = "PATH", global = true)]
+    pub progress_path: Option<PathBuf>,
+
     /// Path to custom system prompt file (uses built-in if not specified)
-    #[arg(short = 'P', long)]
+    #[arg(short = 'P', long, global = true)]
     pub prompt: Option<PathBuf>,
 
+    /// Prompt file for features of a category, e.g. test=prompts/test.md (repeatable; overrides the PRD's)
+    #[arg(long, value_name = "CATEGORY=PATH", value_parser = parse_category_prompt, global = true)]
+    pub category_prompt: Vec<(String, PathBuf)>,
+
     /// Maximum iterations (0 = unlimited)
-    #[arg(short = 'm', long, default_value_t = 10)]
+    #[arg(short = 'm', long, default_value_t = 10, global = true)]
     pub max_iterations: u32,
 
     /// Delay between iterations in seconds
-    #[arg(short, long, default_value_t = 2)]
+    #[arg(short, long, default_value_t = 2, global = true)]
     pub delay: u64,
 
+    /// First wait in seconds after a rate limit; doubles on each consecutive one
+    #[arg(long, default_value_t = 60, value_name = "SECS", global = true)]
+    pub rate_limit_base: u64,
+
+    /// Longest wait in seconds after a rate limit, including waits the agent asks for
+    #[arg(long, default_value_t = 900, value_name = "SECS", global = true)]
+    pub rate_limit_max: u64,
+
     /// Completion marker text (overrides PRD)
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     pub completion_marker: Option<String>,
 
+    /// Agent CLI to drive
+    #[arg(long, value_enum, default_value_t = AgentKind::Claude, global = true)]
+    pub agent: AgentKind,
+
+    /// Command for `--agent shell`, run via `sh -c` with the prompt on stdin
+    #[arg(long, value_name = "CMD", global = true)]
+    pub agent_command: Option<String>,
+
+    /// Model for the agent CLI, e.g. sonnet; a feature's `model` in the PRD takes precedence
+    #[arg(long, value_name = "MODEL", global = true)]
+    pub model: Option<String>,
+
+    /// Tools Claude may use without asking, e.g. `Bash(git:*)`; repeatable or comma-separated
+    #[arg(long, value_name = "TOOL", value_delimiter = ',', global = true)]
+    pub allowed_tools: Vec<String>,
+
+    /// Tools Claude may not use, e.g. `WebFetch` or `Bash(rm:*)`; repeatable or comma-separated
+    #[arg(long, value_name = "TOOL", value_delimiter = ',', global = true)]
+    pub disallowed_tools: Vec<String>,
+
     /// Claude permission mode: default, acceptEdits, plan
-    #[arg(long, default_value = "acceptEdits")]
+    #[arg(long, default_value = "acceptEdits", global = true)]
     pub permission_mode: String,
 
+    /// Start in this restricted mode (plan or default) and escalate to acceptEdits after a successful iteration
+    #[arg(long, value_name = "MODE", global = true)]
+    pub escalate_from: Option<String>,
+
     /// Use --continue mode (preserves session context)
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub continue_session: bool,
 
     /// Skip all permission prompts
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub dangerously_skip_permissions: bool,
 
     /// Skip initialization phase
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub skip_init: bool,
 
-    /// Initialize a new prd.jsonc template
-    #[arg(long)]
+    /// Initialize a new prd.jsonc template (alias for `ralph init`)
+    #[arg(long, hide = true, global = true)]
+    #[serde(skip)]
     pub init: bool,
 
-    /// Initialize a new custom prompt template
-    #[arg(long)]
+    /// Initialize a new custom prompt template (alias for `ralph init prompt`)
+    #[arg(long, hide = true, global = true)]
+    #[serde(skip)]
     pub init_prompt: bool,
 
     /// Dry run: validate PRD, run verifications, show plan, exit without Claude
-    #[arg(long)]
+    #[arg(long, global = true)]
+    #[serde(skip)]
     pub dry_run: bool,
 
-    /// Webhook URL for session event notifications (start, complete, failed)
-    #[arg(long)]
+    /// Verification commands run at once by --dry-run and `ralph watch`
+    #[arg(short = 'j', long, default_value_t = 1, value_name = "N", global = true)]
+    #[serde(skip)]
+    pub jobs: usize,
+
+    /// Webhook URL for event notifications; see --webhook-events
+    #[arg(long, visible_alias = "webhook-url", global = true)]
+    #[serde(serialize_with = "redact")]
     pub webhook: Option<String>,
 
-    /// Experimental: auto-block feature after N iteration errors (0 = disabled)
-    #[arg(long, default_value_t = 0)]
+    /// Also notify a Slack or Discord webhook: slack:<URL> or discord:<URL> (repeatable)
+    #[arg(long, value_name = "KIND:URL", global = true)]
+    pub notify: Vec<Target>,
+
+    /// Show a desktop notification when the run completes, fails, needs action, or is rate limited
+    #[arg(long, global = true)]
+    #[serde(skip)]
+    pub notify_desktop: bool,
+
+    /// Comma-separated webhook events to send [default: all]
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "EVENTS", global = true)]
+    pub webhook_events: Vec<EventType>,
+
+    /// Append run events (the JSON output records, agent output included) to this file
+    #[arg(long, value_name = "PATH", global = true)]
+    pub events_file: Option<PathBuf>,
+
+    /// Export iteration, agent, and verification spans to this OpenTelemetry collector over OTLP/HTTP
+    #[arg(long, value_name = "URL", global = true)]
+    pub otel_endpoint: Option<String>,
+
+    /// Serve live iteration output as server-sent events at http://ADDR/events
+    #[arg(long, value_name = "ADDR", global = true)]
+    pub serve: Option<SocketAddr>,
+
+    /// Token that `POST /approve` and `/reject` on --serve must send as `Authorization: Bearer <TOKEN>`
+    /// [default: random per run, shown when an approval is waiting]
+    #[arg(long, value_name = "TOKEN", requires = "serve", global = true)]
+    #[serde(serialize_with = "redact")]
+    pub serve_token: Option<String>,
+
+    /// Upload iteration logs and the progress file to s3://bucket/prefix or gs://bucket/prefix (repeatable)
+    #[arg(long = "log-sink", value_name = "URI", global = true)]
+    pub log_sink: Vec<String>,
+
+    /// Regex for agent output that means it is stuck, replacing the built-in phrases and the
+    /// PRD's `analysis.loopPatterns` (repeatable)
+    #[arg(long = "loop-pattern", value_name = "REGEX", global = true)]
+    pub loop_patterns: Vec<String>,
+
+    /// Regex for agent errors that mean a rate limit, replacing the built-in phrases and the
+    /// PRD's `analysis.rateLimitPatterns` (repeatable)
+    #[arg(long = "rate-limit-pattern", value_name = "REGEX", global = true)]
+    pub rate_limit_patterns: Vec<String>,
+
+    /// Treat N iterations in a row with the same (or no) git diff as a loop (0 = disabled)
+    #[arg(long, default_value_t = 3, value_name = "N", global = true)]
+    pub loop_diff_repeats: usize,
+
+    /// Block the in-progress feature after N iterations in a row without a commit or status change (0 = disabled)
+    #[arg(long, default_value_t = 0, value_name = "N", global = true)]
+    pub max_idle_iterations: u32,
+
+    /// Auto-block the in-progress feature after N failed iterations on it and move on (0 = disabled)
+    #[arg(long, visible_alias = "max-feature-retries", default_value_t = 0, value_name = "N", global = true)]
     pub max_iteration_errors: u32,
 
-    /// Timeout per Claude execution in seconds
-    #[arg(short = 't', long, default_value_t = 1800)]
+    /// Timeout per agent execution in seconds
+    #[arg(short = 't', long, default_value_t = 1800, global = true)]
     pub timeout: u64,
+
+    /// Stop the agent if it prints nothing for this long, e.g. 300, 90s, 5m, 1h
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, global = true)]
+    pub stall_timeout: Option<u64>,
+
+    /// Show the planned feature and latest changes before each iteration and wait for approval
+    #[arg(long, global = true)]
+    pub approve_each_iteration: bool,
+
+    /// After the completion marker, run a read-only audit that confirms or reopens features
+    #[arg(long, global = true)]
+    pub final_audit: bool,
+
+    /// A completion marker while features are pending or in progress: reject it (the run goes on),
+    /// warn and accept it, or accept it
+    #[arg(long, value_enum, default_value_t = PrematureCompletion::Reject, value_name = "MODE", global = true)]
+    pub premature_completion: PrematureCompletion,
+
+    /// Also let the agent append to a feature's `agentNotes` list (validated append-only)
+    #[arg(long, global = true)]
+    pub allow_agent_notes: bool,
+
+    /// Ralph marks features complete: once a commit mentions the feature id and its verification passes
+    #[arg(long, global = true)]
+    pub auto_complete: bool,
+
+    /// Commit what the agent left uncommitted instead of failing the iteration
+    #[arg(long, global = true)]
+    pub auto_commit: bool,
+
+    /// Fail iterations that change files matching this glob, like the PRD's `protectedPaths`; repeatable
+    #[arg(long, value_name = "GLOB", global = true)]
+    pub protected_path: Vec<String>,
+
+    /// Let iterations remove tests whose file path or function name matches this glob; repeatable
+    #[arg(long, value_name = "GLOB", global = true)]
+    pub allow_test_removal: Vec<String>,
+
+    /// Show the PRD and progress paths in the prompt relative to the project directory
+    #[arg(long, global = true)]
+    pub relative_paths: bool,
+
+    /// Replace the Claude CLI with a scripted fake (see README: Simulation)
+    #[arg(long, hide = true, value_name = "SCRIPT", global = true)]
+    pub simulate: Option<PathBuf>,
+
+    /// Abort in-progress merges/rebases/cherry-picks and leave detached HEAD before iterations
+    #[arg(long, global = true)]
+    pub auto_recover_git: bool,
+
+    /// Have a model condense the last progress entry and iteration output into {recent_context}
+    #[arg(long, global = true)]
+    pub summarize_context: bool,
+
+    /// Model for --summarize-context, e.g. haiku [default: --model]
+    #[arg(long, value_name = "MODEL", global = true)]
+    pub summary_model: Option<String>,
+
+    /// Have a second, read-only agent review each iteration's diff; its feedback goes into the next prompt
+    #[arg(long, global = true)]
+    pub review: bool,
+
+    /// Model for --review [default: --model]
+    #[arg(long, value_name = "MODEL", global = true)]
+    pub review_model: Option<String>,
+
+    /// Verification runs summarized in the prompt; 0 stops ralph running verifications after iterations
+    #[arg(long, default_value_t = 5, value_name = "N", global = true)]
+    pub verification_history: usize,
+
+    /// Where PRD verification commands run: none, docker, firejail
+    #[arg(long, value_enum, default_value_t = SandboxKind::None, global = true)]
+    pub sandbox: SandboxKind,
+
+    /// Image for `--sandbox docker`; the project is mounted at /workspace
+    #[arg(long, value_name = "IMAGE", required_if_eq("sandbox", "docker"), global = true)]
+    pub sandbox_image: Option<String>,
+
+    /// Variable for the agent and verification commands, e.g. API_URL=http://localhost:8080 (repeatable;
+    /// overrides the PRD's `env`)
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_env_var, global = true)]
+    #[serde(serialize_with = "redact_env")]
+    pub env: Vec<(String, String)>,
+
+    /// `.env` file of variables for the agent and verification commands, loaded after the PRD's `envFile`
+    #[arg(long, value_name = "PATH", global = true)]
+    pub env_file: Option<PathBuf>,
+
+    /// Regex whose matches are masked in agent output, logs, sessions, and webhook payloads (repeatable;
+    /// secret-looking variables and common token formats are always masked)
+    #[arg(long, value_name = "REGEX", global = true)]
+    pub redact_pattern: Vec<String>,
+
+    /// Stop the loop once the agent's reported cost reaches this many USD
+    #[arg(long, value_name = "USD", global = true)]
+    pub max_cost: Option<f64>,
+
+    /// Run up to N ready features at once, each in its own git worktree, merging branches back
+    #[arg(long, default_value_t = 1, value_name = "N", global = true)]
+    pub parallel: usize,
+
+    /// Check out a new branch ralph/<run id> before the loop, leaving the current branch untouched
+    #[arg(long, global = true)]
+    pub branch_per_run: bool,
+
+    /// Check out a branch ralph/<run id>-<feature id> when work moves to a new feature,
+    /// each starting where the previous one ended
+    #[arg(long, global = true)]
+    pub branch_per_feature: bool,
+
+    /// Reset to the pre-iteration commit and restore the PRD when an iteration fails PRD
+    /// validation or verification
+    #[arg(long, global = true)]
+    pub rollback_on_failure: bool,
+
+    /// Without a git repository, hash the project tree around each iteration to validate the PRD and list changed files
+    #[arg(long, global = true)]
+    pub snapshot: bool,
+
+    /// Continue the iteration count, failure counters, and runtime saved in .ralph/state.json
+    #[arg(long, global = true)]
+    #[serde(skip)]
+    pub resume: bool,
+
+    /// Id for this run, used in log names, events, and commit trailers [default: generated]
+    #[arg(long, value_name = "ID", global = true)]
+    #[serde(skip)]
+    pub run_id: Option<String>,
+
+    /// Inline files matching the next feature's `context` globs into the prompt
+    #[arg(long, global = true)]
+    pub pack_context: bool,
+
+    /// Default packed-context budget in bytes (features can override with `contextBudget`)
+    #[arg(long, default_value_t = 32 * 1024, global = true)]
+    pub context_budget: usize,
+
+    /// Max lines per entry ralph writes to the progress file
+    #[arg(long, default_value_t = 20, global = true)]
+    pub progress_entry_lines: usize,
+
+    /// Max bytes per entry ralph writes to the progress file
+    #[arg(long, default_value_t = 2048, global = true)]
+    pub progress_entry_bytes: usize,
+
+    /// Archive the progress file once it exceeds this many bytes, restarting it with a summary (0 = never)
+    #[arg(long, value_name = "BYTES", default_value_t = 65536, global = true)]
+    pub progress_max_bytes: u64,
+
+    /// Before each run, delete the logs, sessions, and state of all but this many recent runs
+    #[arg(long, value_name = "N", global = true)]
+    pub retain_runs: Option<usize>,
+
+    /// Before each run, delete the logs, sessions, and state of runs older than this, e.g. 7d
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, global = true)]
+    pub retain_for: Option<u64>,
+
+    /// Confirm before running if the projected cost exceeds this many USD
+    #[arg(long, value_name = "USD", global = true)]
+    pub budget: Option<f64>,
+
+    /// Model whose list prices are used for cost estimates without recorded history
+    #[arg(long, value_name = "MODEL", default_value = "sonnet", global = true)]
+    pub pricing_model: String,
+
+    /// Agent output kept in memory for analysis, in KB (full output always goes to the log)
+    #[arg(long, default_value_t = 1024, global = true)]
+    pub output_buffer_kb: usize,
+
+    /// How ralph reports progress: colored text, or one JSON record per line
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text, global = true)]
+    #[serde(skip)]
+    pub output_format: OutputFormat,
+
+    /// When to color ralph's messages
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto, global = true)]
+    #[serde(skip)]
+    pub color: ColorChoice,
+
+    /// Same as --color never
+    #[arg(long, global = true)]
+    #[serde(skip)]
+    pub no_color: bool,
+
+    /// More detail from ralph: -v adds debug messages, -vv also trace messages such as the full prompt
+    #[arg(short, long, action = clap::ArgAction::Count, global = true, conflicts_with = "quiet")]
+    #[serde(skip)]
+    pub verbose: u8,
+
+    /// Only warnings and errors from ralph; the agent's output is still shown
+    #[arg(short, long, global = true)]
+    #[serde(skip)]
+    pub quiet: bool,
+
+    /// Prefix ralph's messages with the time
+    #[arg(long, global = true)]
+    #[serde(skip)]
+    pub timestamps: bool,
+
+    /// Write every ralph message, debug and trace included, as JSON lines to .ralph/logs/<run id>-ralph.jsonl
+    #[arg(long, global = true)]
+    #[serde(skip)]
+    pub structured_log: bool,
+}
+
+impl Args {
+    /// `--color`, with `--no-color` taking precedence.
+    #[must_use]
+    pub fn color_choice(&self) -> ColorChoice {
+        if self.no_color {
+            ColorChoice::Never
+        } else {
+            self.color
+        }
+    }
+
+    /// Parses the command line, taking flags it leaves unset from the first of
+    /// [`CONFIG_FILES`] in the current directory.
+    pub fn load() -> Result<Self> {
+        let dir = std::env::current_dir().context("Failed to read current directory")?;
+        Self::load_from(std::env::args_os().collect(), &dir)
+    }
+
+    /// Like [`Args::load`], reading the config file from `dir`. Exits on invalid arguments,
+    /// like [`Parser::parse`].
+    pub fn load_from(argv: Vec<OsString>, dir: &Path) -> Result<Self> {
+        let Some(path) = CONFIG_FILES.iter().map(|name| dir.join(name)).find(|p| p.is_file()) else {
+            return Ok(Self::parse_from(argv));
+        };
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let argv = with_config(argv, &content)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        Ok(Self::parse_from(argv))
+    }
+}
+
+/// Inserts flags from a TOML config after the program name, skipping any the command
+/// line already sets, so the command line wins and list flags are replaced rather than
+/// extended.
+pub fn with_config(argv: Vec<OsString>, content: &str) -> Result<Vec<OsString>> {
+    let table: toml::Table = content.parse()?;
+    let matches = Args::command().try_get_matches_from(&argv).unwrap_or_else(|e| e.exit());
+    let mut merged: Vec<OsString> = argv.iter().take(1).cloned().collect();
+    for (key, value) in &table {
+        merged.extend(config_flag(&matches, key, value)?);
+    }
+    merged.extend(argv.into_iter().skip(1));
+    Ok(merged)
+}
+
+fn config_flag(matches: &ArgMatches, key: &str, value: &toml::Value) -> Result<Vec<OsString>> {
+    let long = key.replace('_', "-");
+    let command = Args::command();
+    let Some(arg) = command.get_arguments().find(|arg| {
+        arg.get_long() == Some(long.as_str()) || arg.get_all_aliases().is_some_and(|a| a.contains(&long.as_str()))
+    }) else {
+        bail!("Unknown option '{key}'");
+    };
+    if matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine) {
+        return Ok(Vec::new());
+    }
+    let flag = OsString::from(format!("--{}", arg.get_long().unwrap_or(&long)));
+    if !arg.get_action().takes_values() {
+        return match value {
+            toml::Value::Boolean(true) => Ok(vec![flag]),
+            toml::Value::Boolean(false) => Ok(Vec::new()),
+            _ => bail!("'{key}' must be true or false"),
+        };
+    }
+    let values = match value {
+        toml::Value::Array(items) => items.iter().collect(),
+        value => vec![value],
+    };
+    let mut flags = Vec::new();
+    for value in values {
+        let text = match value {
+            toml::Value::String(s) => s.clone(),
+            toml::Value::Integer(_) | toml::Value::Float(_) | toml::Value::Boolean(_) => value.to_string(),
+            _ => bail!("'{key}' must be a string, number, or list of them"),
+        };
+        flags.push(flag.clone());
+        flags.push(text.into());
+    }
+    Ok(flags)
+}
+
+/// Seconds in `300`, `90s`, `5m`, `1h`, or `7d`.
+fn parse_duration(value: &str) -> std::result::Result<u64, String> {
+    let value = value.trim();
+    let (number, unit) = value.split_at(value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len()));
+    let number: u64 = number.parse().map_err(|_| format!("expected a duration like 300, 90s, 5m, 1h, or 7d, got '{value}'"))?;
+    let scale = match unit.trim() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        other => return Err(format!("unknown duration unit '{other}', expected s, m, h, or d")),
+    };
+    match number.checked_mul(scale) {
+        Some(0) => Err("duration must be greater than zero".to_string()),
+        Some(secs) => Ok(secs),
+        None => Err(format!("duration '{value}' is too large")),
+    }
+}
+
+fn parse_env_var(value: &str) -> std::result::Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((name, value)) if crate::environment::validate_name(name).is_ok() => {
+            Ok((name.to_string(), value.to_string()))
+        }
+        _ => Err(format!("expected KEY=VALUE with a valid variable name, got '{value}'")),
+    }
+}
+
+fn parse_category_prompt(value: &str) -> std::result::Result<(String, PathBuf), String> {
+    match value.split_once('=') {
+        Some((category, path)) if !category.is_empty() && !path.is_empty() => Ok((category.to_string(), path.into())),
+        _ => Err(format!("expected CATEGORY=PATH, got '{value}'")),
+    }
+}
+
+/// Webhook URLs often embed tokens, so only their presence is recorded.
+fn redact<S: Serializer>(value: &Option<String>, s: S) -> Result<S::Ok, S::Error> {
+    value.as_ref().map(|_| "<redacted>").serialize(s)
+}
+
+/// `--env` values are often secrets, so only the names are recorded.
+fn redact_env<S: Serializer>(value: &[(String, String)], s: S) -> Result<S::Ok, S::Error> {
+    value.iter().map(|(name, _)| name).collect::<Vec<_>>().serialize(s)
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Run the loop (the default); `ralph run [OPTIONS]` is the same as `ralph [OPTIONS]`
+    Run,
+    /// Create a template PRD, or a custom prompt template
+    Init(InitArgs),
+    /// Show iteration logs
+    Logs(LogsArgs),
+    /// Delete the logs, sessions, and state of old runs from .ralph
+    Clean(CleanArgs),
+    /// List past runs with their outcome, or show one
+    History {
+        #[command(subcommand)]
+        action: Option<HistoryAction>,
+    },
+    /// Print an iteration's archived session: prompt, agent output, diff, and verification
+    Replay(ReplayArgs),
+    /// Show feature counts, current work, and the latest progress without running anything
+    Status,
+    /// Summarize the PRD, iteration logs, and progress records as a Markdown or HTML report
+    Report(ReportArgs),
+    /// Generate a PRD from an external source
+    Import {
+        #[command(subcommand)]
+        source: ImportSource,
+    },
+    /// Project the cost and duration of finishing the PRD
+    Estimate,
+    /// Run the PRD against a matrix of prompts, models, and permission modes and compare them
+    Bench(BenchArgs),
+    /// Render the feature dependency graph
+    Graph(GraphArgs),
+    /// Print the PRD JSON Schema
+    Schema,
+    /// Check the PRD for mistakes and show where they are
+    Validate,
+    /// Re-run the PRD's verification commands when project files change, or follow another
+    /// ralph instance with --remote
+    Watch(WatchArgs),
+    /// Move a feature and its iteration logs from the PRD to another PRD
+    MoveFeature(MoveFeatureArgs),
+    /// Convert a Markdown PRD to prd.jsonc
+    Convert(ConvertArgs),
+    /// Serve PRD status, the progress log, and run control to other agents and IDEs over the
+    /// Model Context Protocol on stdin/stdout
+    Mcp,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ConvertArgs {
+    /// Where to write the PRD [default: --prd with a .jsonc extension]
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+
+    /// Overwrite the output file if it exists
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct GraphArgs {
+    /// Output format
+    #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+    pub format: GraphFormat,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct InitArgs {
+    /// What to create
+    #[arg(value_enum, default_value_t = InitTarget::Prd)]
+    pub target: InitTarget,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitTarget {
+    /// The PRD at --prd
+    Prd,
+    /// prompt.md
+    Prompt,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct LogsArgs {
+    /// List iterations instead of printing a log
+    #[arg(long)]
+    pub list: bool,
+
+    /// Iteration to show (default: the latest)
+    #[arg(long, value_name = "N")]
+    pub iteration: Option<u32>,
+
+    /// Only consider iterations of this run
+    #[arg(long = "run", value_name = "ID")]
+    pub run_id: Option<String>,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct BenchArgs {
+    /// TOML file listing the prompts, models, and permission modes to try
+    #[arg(long, value_name = "PATH", default_value = "bench.toml")]
+    pub matrix: PathBuf,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct CleanArgs {
+    /// Keep the N most recent runs [default: --retain-runs]
+    #[arg(long, value_name = "N")]
+    pub keep_last: Option<usize>,
+
+    /// Delete runs older than this, e.g. 7d or 12h [default: --retain-for]
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub older_than: Option<u64>,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ReplayArgs {
+    /// Iteration to show; the latest run's if several have one
+    #[arg(value_name = "N")]
+    pub iteration: u32,
+
+    /// Only consider sessions of this run
+    #[arg(long = "run", value_name = "ID")]
+    pub run_id: Option<String>,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ReportArgs {
+    /// Output format
+    #[arg(long, value_enum, default_value_t = ReportFormat::Markdown)]
+    pub format: ReportFormat,
+
+    /// Write the report here instead of stdout
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct WatchArgs {
+    /// Follow the live output of a ralph instance started with --serve, e.g. http://host:7777
+    #[arg(long, value_name = "URL")]
+    pub remote: Option<String>,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct MoveFeatureArgs {
+    /// Id of the feature to move
+    pub id: String,
+
+    /// PRD to move the feature into
+    #[arg(long, value_name = "PATH")]
+    pub to: PathBuf,
+
+    /// New id for the feature, if its id is already taken in the target PRD
+    #[arg(long = "as", value_name = "ID")]
+    pub new_id: Option<String>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum HistoryAction {
+    /// Show a run's outcome, config digest, and the flags it changed
+    Show {
+        /// Run id, as listed by `ralph history`
+        #[arg(value_name = "ID")]
+        run_id: String,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ImportSource {
+    /// Seed features from TODO/FIXME/HACK comments in the codebase
+    Todos(TodoImportArgs),
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct TodoImportArgs {
+    /// Directory to scan
+    #[arg(long, default_value = ".")]
+    pub dir: PathBuf,
+
+    /// Create one feature per file or per module (directory)
+    #[arg(long, value_enum, default_value_t = GroupBy::File)]
+    pub group_by: GroupBy,
+
+    /// Overwrite the PRD file if it already exists
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// When the stream is a terminal and NO_COLOR isn't set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AgentKind {
+    Claude,
+    Codex,
+    Gemini,
+    Aider,
+    Shell,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrematureCompletion {
+    #[default]
+    Reject,
+    Warn,
+    Accept,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxKind {
+    #[default]
+    None,
+    Docker,
+    Firejail,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    File,
+    Module,
 }
 
 #[cfg(test)]
@@ -150,6 +862,11 @@ mod tests {
             assert_eq!(args.timeout, 1800);
         }
 
+        #[test]
+        fn stall_timeout_defaults_to_off() {
+            assert_eq!(parse_args(&[]).stall_timeout, None);
+        }
+
         #[test]
         fn prompt_defaults_to_none() {
             let args = parse_args(&[]);
@@ -162,11 +879,150 @@ mod tests {
             assert!(args.webhook.is_none());
         }
 
+        #[test]
+        fn model_flag() {
+            assert_eq!(parse_args(&[]).model, None);
+            assert_eq!(parse_args(&["--model", "opus"]).model.as_deref(), Some("opus"));
+        }
+
+        #[test]
+        fn tool_flags_are_repeatable_and_comma_separated() {
+            assert!(parse_args(&[]).allowed_tools.is_empty());
+            let args = parse_args(&["--disallowed-tools", "WebFetch,Bash(rm:*)", "--disallowed-tools", "WebSearch"]);
+            assert_eq!(args.disallowed_tools, ["WebFetch", "Bash(rm:*)", "WebSearch"]);
+            assert_eq!(parse_args(&["--allowed-tools", "Edit"]).allowed_tools, ["Edit"]);
+        }
+
+        #[test]
+        fn max_idle_iterations_defaults_to_0() {
+            assert_eq!(parse_args(&[]).max_idle_iterations, 0);
+            assert_eq!(parse_args(&["--max-idle-iterations", "4"]).max_idle_iterations, 4);
+        }
+
+        #[test]
+        fn loop_diff_repeats_defaults_to_3() {
+            assert_eq!(parse_args(&[]).loop_diff_repeats, 3);
+            assert_eq!(parse_args(&["--loop-diff-repeats", "0"]).loop_diff_repeats, 0);
+        }
+
         #[test]
         fn max_iteration_errors_defaults_to_0() {
             let args = parse_args(&[]);
             assert_eq!(args.max_iteration_errors, 0);
         }
+
+        #[test]
+        fn output_buffer_kb_defaults_to_1024() {
+            let args = parse_args(&[]);
+            assert_eq!(args.output_buffer_kb, 1024);
+        }
+
+        #[test]
+        fn progress_entry_limits_default() {
+            let args = parse_args(&[]);
+            assert_eq!(args.progress_entry_lines, 20);
+            assert_eq!(args.progress_entry_bytes, 2048);
+            assert_eq!(args.progress_max_bytes, 65536);
+            assert_eq!(parse_args(&["--progress-max-bytes", "0"]).progress_max_bytes, 0);
+        }
+
+        #[test]
+        fn agent_defaults_to_claude() {
+            let args = parse_args(&[]);
+            assert_eq!(args.agent, AgentKind::Claude);
+            assert!(args.agent_command.is_none());
+        }
+
+        #[test]
+        fn relative_paths_flag() {
+            assert!(!parse_args(&[]).relative_paths);
+            assert!(parse_args(&["--relative-paths"]).relative_paths);
+        }
+
+        #[test]
+        fn allow_agent_notes_defaults_to_false() {
+            let args = parse_args(&[]);
+            assert!(!args.allow_agent_notes);
+        }
+
+        #[test]
+        fn prd_source_parses_github() {
+            let args = parse_args(&["--prd-source", "github:acme/shop?label=ralph"]);
+            assert_eq!(args.prd_source.map(|s| s.to_string()).as_deref(), Some("github:acme/shop?label=ralph"));
+            assert!(Args::try_parse_from(["ralph", "--prd-source", "acme/shop"]).is_err());
+            let args = parse_args(&["--prd-source", "jira:acme.atlassian.net?sprint=42"]);
+            assert!(matches!(args.prd_source, Some(SourceSpec::Jira(_))));
+        }
+
+        #[test]
+        fn auto_complete_defaults_to_false() {
+            assert!(!parse_args(&[]).auto_complete);
+            assert!(parse_args(&["--auto-complete"]).auto_complete);
+        }
+
+        #[test]
+        fn auto_commit_defaults_to_false() {
+            assert!(!parse_args(&[]).auto_commit);
+            assert!(parse_args(&["--auto-commit"]).auto_commit);
+        }
+
+        #[test]
+        fn protected_path_repeats() {
+            assert!(parse_args(&[]).protected_path.is_empty());
+            let args = parse_args(&["--protected-path", "Cargo.lock", "--protected-path", ".github/{a,b}/**"]);
+            assert_eq!(args.protected_path, ["Cargo.lock", ".github/{a,b}/**"]);
+        }
+
+        #[test]
+        fn allow_test_removal_repeats() {
+            assert!(parse_args(&[]).allow_test_removal.is_empty());
+            let args = parse_args(&["--allow-test-removal", "tests/legacy/**", "--allow-test-removal", "flaky_*"]);
+            assert_eq!(args.allow_test_removal, ["tests/legacy/**", "flaky_*"]);
+        }
+
+        #[test]
+        fn premature_completion_modes() {
+            assert_eq!(parse_args(&[]).premature_completion, PrematureCompletion::Reject);
+            assert_eq!(parse_args(&["--premature-completion", "warn"]).premature_completion, PrematureCompletion::Warn);
+            assert!(Args::try_parse_from(["ralph", "--premature-completion", "ignore"]).is_err());
+        }
+
+        #[test]
+        fn final_audit_defaults_to_false() {
+            let args = parse_args(&[]);
+            assert!(!args.final_audit);
+        }
+
+        #[test]
+        fn project_dir_defaults_to_none() {
+            let args = parse_args(&[]);
+            assert!(args.project_dir.is_none());
+        }
+
+        #[test]
+        fn log_sink_defaults_to_empty() {
+            let args = parse_args(&[]);
+            assert!(args.log_sink.is_empty());
+        }
+
+        #[test]
+        fn pack_context_defaults_to_false() {
+            let args = parse_args(&[]);
+            assert!(!args.pack_context);
+            assert_eq!(args.context_budget, 32 * 1024);
+        }
+
+        #[test]
+        fn escalate_from_defaults_to_none() {
+            let args = parse_args(&[]);
+            assert!(args.escalate_from.is_none());
+        }
+
+        #[test]
+        fn command_defaults_to_none() {
+            let args = parse_args(&[]);
+            assert!(args.command.is_none());
+        }
     }
 
     mod argument_overrides {
@@ -176,6 +1032,45 @@ mod tests {
             Args::try_parse_from(std::iter::once("ralph").chain(args.iter().copied())).unwrap()
         }
 
+        #[test]
+        fn stall_timeout_units() {
+            assert_eq!(parse_args(&["--stall-timeout", "300"]).stall_timeout, Some(300));
+            assert_eq!(parse_args(&["--stall-timeout", "90s"]).stall_timeout, Some(90));
+            assert_eq!(parse_args(&["--stall-timeout", "5m"]).stall_timeout, Some(300));
+            assert_eq!(parse_args(&["--stall-timeout", "1h"]).stall_timeout, Some(3600));
+            assert_eq!(parse_args(&["--stall-timeout", "1d"]).stall_timeout, Some(86400));
+        }
+
+        #[test]
+        fn stall_timeout_rejects_bad_durations() {
+            for bad in ["5w", "m", "0", "-1"] {
+                let args = std::iter::once("ralph").chain(["--stall-timeout", bad]);
+                assert!(Args::try_parse_from(args).is_err(), "{bad}");
+            }
+        }
+
+        #[test]
+        fn color_flags() {
+            assert_eq!(parse_args(&[]).color_choice(), ColorChoice::Auto);
+            assert_eq!(parse_args(&["--color", "always"]).color_choice(), ColorChoice::Always);
+            assert_eq!(parse_args(&["status", "--color", "always", "--no-color"]).color_choice(), ColorChoice::Never);
+        }
+
+        #[test]
+        fn verbosity_flags() {
+            assert_eq!((parse_args(&[]).verbose, parse_args(&[]).quiet), (0, false));
+            assert_eq!(parse_args(&["-vv"]).verbose, 2);
+            assert!(parse_args(&["status", "-q"]).quiet);
+            assert!(Args::try_parse_from(["ralph", "-v", "-q"]).is_err());
+        }
+
+        #[test]
+        fn output_format_json() {
+            assert_eq!(parse_args(&[]).output_format, OutputFormat::Text);
+            assert_eq!(parse_args(&["--output-format", "json"]).output_format, OutputFormat::Json);
+            assert_eq!(parse_args(&["estimate", "--output-format", "json"]).output_format, OutputFormat::Json);
+        }
+
         #[test]
         fn prd_short_flag() {
             let args = parse_args(&["-p", "custom.json"]);
@@ -218,6 +1113,14 @@ mod tests {
             assert_eq!(args.delay, 10);
         }
 
+        #[test]
+        fn rate_limit_backoff_flags() {
+            let args = parse_args(&[]);
+            assert_eq!((args.rate_limit_base, args.rate_limit_max), (60, 900));
+            let args = parse_args(&["--rate-limit-base", "5", "--rate-limit-max", "120"]);
+            assert_eq!((args.rate_limit_base, args.rate_limit_max), (5, 120));
+        }
+
         #[test]
         fn completion_marker_short_flag() {
             let args = parse_args(&["-c", "DONE"]);
@@ -272,6 +1175,12 @@ mod tests {
             assert!(args.dry_run);
         }
 
+        #[test]
+        fn jobs_flag() {
+            assert_eq!(parse_args(&[]).jobs, 1);
+            assert_eq!(parse_args(&["--dry-run", "-j", "4"]).jobs, 4);
+        }
+
         #[test]
         fn timeout_short_flag() {
             let args = parse_args(&["-t", "3600"]);
@@ -302,10 +1211,81 @@ mod tests {
             assert_eq!(args.webhook, Some("https://example.com/webhook".to_string()));
         }
 
+        #[test]
+        fn webhook_url_alias_and_events() {
+            let args = parse_args(&[
+                "--webhook-url",
+                "https://example.com/webhook",
+                "--webhook-events",
+                "feature_complete,session_failed",
+            ]);
+            assert_eq!(args.webhook, Some("https://example.com/webhook".to_string()));
+            assert_eq!(args.webhook_events, vec![EventType::FeatureComplete, EventType::SessionFailed]);
+            assert!(parse_args(&[]).webhook_events.is_empty());
+        }
+
+        #[test]
+        fn notify_is_repeatable() {
+            let args = parse_args(&["--notify", "slack:https://hooks.slack.com/x", "--notify", "discord:https://discord.com/y"]);
+            assert_eq!(args.notify.len(), 2);
+            assert!(Args::try_parse_from(["ralph", "--notify", "teams:https://x"]).is_err());
+        }
+
+        #[test]
+        fn notify_desktop_flag() {
+            assert!(!parse_args(&[]).notify_desktop);
+            assert!(parse_args(&["--notify-desktop"]).notify_desktop);
+        }
+
+        #[test]
+        fn events_file_flag() {
+            assert_eq!(parse_args(&[]).events_file, None);
+            assert_eq!(parse_args(&["--events-file", "events.jsonl"]).events_file, Some(PathBuf::from("events.jsonl")));
+        }
+
         #[test]
         fn max_iteration_errors_long_flag() {
             let args = parse_args(&["--max-iteration-errors", "5"]);
             assert_eq!(args.max_iteration_errors, 5);
+            let args = parse_args(&["--max-feature-retries", "2"]);
+            assert_eq!(args.max_iteration_errors, 2);
+        }
+
+        #[test]
+        fn project_dir_long_flag() {
+            let args = parse_args(&["--project-dir", "../app"]);
+            assert_eq!(args.project_dir, Some(PathBuf::from("../app")));
+        }
+
+        #[test]
+        fn log_sink_repeatable() {
+            let args = parse_args(&["--log-sink", "s3://a/b", "--log-sink", "gs://c"]);
+            assert_eq!(args.log_sink, vec!["s3://a/b".to_string(), "gs://c".to_string()]);
+        }
+
+        #[test]
+        fn pack_context_with_budget() {
+            let args = parse_args(&["--pack-context", "--context-budget", "4096"]);
+            assert!(args.pack_context);
+            assert_eq!(args.context_budget, 4096);
+        }
+
+        #[test]
+        fn escalate_from_flag() {
+            let args = parse_args(&["--escalate-from", "plan"]);
+            assert_eq!(args.escalate_from, Some("plan".to_string()));
+        }
+
+        #[test]
+        fn allow_agent_notes_flag() {
+            let args = parse_args(&["--allow-agent-notes"]);
+            assert!(args.allow_agent_notes);
+        }
+
+        #[test]
+        fn final_audit_flag() {
+            let args = parse_args(&["--final-audit"]);
+            assert!(args.final_audit);
         }
 
         #[test]
@@ -315,6 +1295,372 @@ mod tests {
         }
     }
 
+    mod subcommands {
+        use super::*;
+
+        fn parse_args(args: &[&str]) -> Args {
+            Args::try_parse_from(std::iter::once("ralph").chain(args.iter().copied())).unwrap()
+        }
+
+        #[test]
+        fn run_options_go_before_or_after_run() {
+            let args = parse_args(&["run", "-m", "5", "--dry-run"]);
+            assert_eq!(args.max_iterations, 5);
+            assert!(args.dry_run);
+            assert!(matches!(args.command, Some(Command::Run)));
+            let args = parse_args(&["--prd", "x.jsonc", "run", "-m", "5"]);
+            assert_eq!((args.prd.as_path(), args.max_iterations), (Path::new("x.jsonc"), 5));
+            assert_eq!(parse_args(&["-m", "5", "run"]).max_iterations, 5);
+            assert_eq!(parse_args(&["--prd", "run"]).prd, Path::new("run"));
+            Args::command().debug_assert();
+        }
+
+        #[test]
+        fn init_defaults_to_prd() {
+            match parse_args(&["init"]).command {
+                Some(Command::Init(opts)) => assert_eq!(opts.target, InitTarget::Prd),
+                other => panic!("unexpected command: {other:?}"),
+            }
+            match parse_args(&["init", "prompt"]).command {
+                Some(Command::Init(opts)) => assert_eq!(opts.target, InitTarget::Prompt),
+                other => panic!("unexpected command: {other:?}"),
+            }
+        }
+
+        #[test]
+        fn init_flags_are_hidden_aliases() {
+            let help = Args::command().render_long_help().to_string();
+            assert!(!help.contains("--init"));
+            assert!(parse_args(&["--init"]).init);
+        }
+
+        #[test]
+        fn status_subcommand() {
+            assert!(matches!(parse_args(&["--prd", "other.jsonc", "status"]).command, Some(Command::Status)));
+        }
+
+        #[test]
+        fn report_defaults_to_markdown_on_stdout() {
+            match parse_args(&["report"]).command {
+                Some(Command::Report(opts)) => assert_eq!((opts.format, opts.output), (ReportFormat::Markdown, None)),
+                other => panic!("unexpected command: {other:?}"),
+            }
+            match parse_args(&["report", "--format", "html", "-o", "report.html"]).command {
+                Some(Command::Report(opts)) => {
+                    assert_eq!(opts.format, ReportFormat::Html);
+                    assert_eq!(opts.output, Some(PathBuf::from("report.html")));
+                }
+                other => panic!("unexpected command: {other:?}"),
+            }
+        }
+
+        #[test]
+        fn bench_matrix() {
+            match parse_args(&["bench", "--matrix", "experiments.toml"]).command {
+                Some(Command::Bench(opts)) => assert_eq!(opts.matrix, PathBuf::from("experiments.toml")),
+                other => panic!("unexpected command: {other:?}"),
+            }
+        }
+
+        #[test]
+        fn prd_dir_and_manifest_conflict() {
+            assert_eq!(parse_args(&["--prd-dir", "reqs"]).prd_dir, Some(PathBuf::from("reqs")));
+            let both = ["ralph", "--prd-dir", "reqs", "--prd-manifest", "prds.toml"];
+            assert!(Args::try_parse_from(both).is_err());
+        }
+
+        #[test]
+        fn clean_options() {
+            match parse_args(&["clean", "--keep-last", "5", "--older-than", "7d"]).command {
+                Some(Command::Clean(opts)) => assert_eq!((opts.keep_last, opts.older_than), (Some(5), Some(7 * 86400))),
+                other => panic!("unexpected command: {other:?}"),
+            }
+            let args = parse_args(&["--retain-runs", "20", "--retain-for", "30d"]);
+            assert_eq!((args.retain_runs, args.retain_for), (Some(20), Some(30 * 86400)));
+        }
+
+        #[test]
+        fn history_actions() {
+            assert!(matches!(parse_args(&["history"]).command, Some(Command::History { action: None })));
+            match parse_args(&["history", "show", "r1"]).command {
+                Some(Command::History { action: Some(HistoryAction::Show { run_id }) }) => assert_eq!(run_id, "r1"),
+                other => panic!("unexpected command: {other:?}"),
+            }
+        }
+
+        #[test]
+        fn replay_options() {
+            match parse_args(&["replay", "4", "--run", "ci-7"]).command {
+                Some(Command::Replay(opts)) => assert_eq!((opts.iteration, opts.run_id.as_deref()), (4, Some("ci-7"))),
+                other => panic!("unexpected command: {other:?}"),
+            }
+        }
+
+        #[test]
+        fn logs_options() {
+            match parse_args(&["logs", "--list", "--iteration", "3", "--run", "ci-7"]).command {
+                Some(Command::Logs(opts)) => {
+                    assert!(opts.list);
+                    assert_eq!(opts.iteration, Some(3));
+                    assert_eq!(opts.run_id.as_deref(), Some("ci-7"));
+                }
+                other => panic!("unexpected command: {other:?}"),
+            }
+        }
+
+        #[test]
+        fn import_todos_defaults() {
+            let args = parse_args(&["import", "todos"]);
+            match args.command {
+                Some(Command::Import { source: ImportSource::Todos(opts) }) => {
+                    assert_eq!(opts.dir, PathBuf::from("."));
+                    assert_eq!(opts.group_by, GroupBy::File);
+                    assert!(!opts.force);
+                }
+                other => panic!("unexpected command: {other:?}"),
+            }
+        }
+
+        #[test]
+        fn import_todos_group_by_module() {
+            let args = parse_args(&["import", "todos", "--group-by", "module", "--force"]);
+            match args.command {
+                Some(Command::Import { source: ImportSource::Todos(opts) }) => {
+                    assert_eq!(opts.group_by, GroupBy::Module);
+                    assert!(opts.force);
+                }
+                other => panic!("unexpected command: {other:?}"),
+            }
+        }
+
+        #[test]
+        fn graph_defaults_to_dot() {
+            let args = parse_args(&["graph"]);
+            match args.command {
+                Some(Command::Graph(opts)) => assert_eq!(opts.format, GraphFormat::Dot),
+                other => panic!("unexpected command: {other:?}"),
+            }
+        }
+
+        #[test]
+        fn graph_mermaid() {
+            let args = parse_args(&["graph", "--format", "mermaid"]);
+            match args.command {
+                Some(Command::Graph(opts)) => assert_eq!(opts.format, GraphFormat::Mermaid),
+                other => panic!("unexpected command: {other:?}"),
+            }
+        }
+
+        #[test]
+        fn estimate_with_budget() {
+            let args = parse_args(&["estimate", "--budget", "25", "--pricing-model", "opus"]);
+            assert!(matches!(args.command, Some(Command::Estimate)));
+            assert_eq!(args.budget, Some(25.0));
+            assert_eq!(args.pricing_model, "opus");
+        }
+
+        #[test]
+        fn schema_command() {
+            let args = parse_args(&["schema"]);
+            assert!(matches!(args.command, Some(Command::Schema)));
+        }
+
+        #[test]
+        fn watch_remote() {
+            let args = parse_args(&["watch", "--remote", "http://host:7777"]);
+            match args.command {
+                Some(Command::Watch(opts)) => assert_eq!(opts.remote.as_deref(), Some("http://host:7777")),
+                other => panic!("unexpected command: {other:?}"),
+            }
+        }
+
+        #[test]
+        fn watch_without_remote_watches_the_project() {
+            match parse_args(&["watch", "-j", "2"]).command {
+                Some(Command::Watch(opts)) => assert!(opts.remote.is_none()),
+                other => panic!("unexpected command: {other:?}"),
+            }
+        }
+
+        #[test]
+        fn move_feature_with_rename() {
+            let args = parse_args(&["move-feature", "login", "--to", "web/prd.jsonc", "--as", "web-login"]);
+            match args.command {
+                Some(Command::MoveFeature(opts)) => {
+                    assert_eq!(opts.id, "login");
+                    assert_eq!(opts.to, PathBuf::from("web/prd.jsonc"));
+                    assert_eq!(opts.new_id.as_deref(), Some("web-login"));
+                }
+                other => panic!("unexpected command: {other:?}"),
+            }
+        }
+
+        #[test]
+        fn move_feature_requires_target() {
+            assert!(Args::try_parse_from(["ralph", "move-feature", "login"]).is_err());
+        }
+
+        #[test]
+        fn convert_with_output() {
+            let args = parse_args(&["-p", "PRD.md", "convert", "-o", "prd.jsonc", "--force"]);
+            match args.command {
+                Some(Command::Convert(opts)) => {
+                    assert_eq!(opts.output, Some(PathBuf::from("prd.jsonc")));
+                    assert!(opts.force);
+                }
+                other => panic!("unexpected command: {other:?}"),
+            }
+            assert_eq!(args.prd, PathBuf::from("PRD.md"));
+        }
+
+        #[test]
+        fn agent_shell_with_command() {
+            let args = parse_args(&["--agent", "shell", "--agent-command", "./my-agent"]);
+            assert_eq!(args.agent, AgentKind::Shell);
+            assert_eq!(args.agent_command.as_deref(), Some("./my-agent"));
+        }
+
+        #[test]
+        fn branch_flags() {
+            let args = parse_args(&["--branch-per-run", "--branch-per-feature"]);
+            assert!(args.branch_per_run);
+            assert!(args.branch_per_feature);
+            assert!(!parse_args(&[]).branch_per_run);
+        }
+
+        #[test]
+        fn rollback_on_failure_flag() {
+            assert!(parse_args(&["--rollback-on-failure"]).rollback_on_failure);
+            assert!(!parse_args(&[]).rollback_on_failure);
+        }
+
+        #[test]
+        fn auto_recover_git_flag() {
+            let args = parse_args(&["--auto-recover-git"]);
+            assert!(args.auto_recover_git);
+        }
+
+        #[test]
+        fn sandbox_docker_requires_an_image() {
+            assert_eq!(parse_args(&[]).sandbox, SandboxKind::None);
+            assert!(Args::try_parse_from(["ralph", "--sandbox", "docker"]).is_err());
+            let args = parse_args(&["--sandbox", "docker", "--sandbox-image", "rust:1"]);
+            assert_eq!((args.sandbox, args.sandbox_image.as_deref()), (SandboxKind::Docker, Some("rust:1")));
+            assert_eq!(parse_args(&["--sandbox", "firejail"]).sandbox, SandboxKind::Firejail);
+        }
+
+        #[test]
+        fn env_pairs() {
+            let args = parse_args(&["--env", "API_URL=http://x?a=b", "--env", "EMPTY="]);
+            assert_eq!(
+                args.env,
+                vec![("API_URL".to_string(), "http://x?a=b".to_string()), ("EMPTY".to_string(), String::new())]
+            );
+            for bad in ["API_URL", "=x", "1X=y", "A B=c"] {
+                assert!(Args::try_parse_from(["ralph", "--env", bad]).is_err(), "{bad}");
+            }
+            let recorded = serde_json::to_value(&args).unwrap();
+            assert_eq!(recorded["env"], serde_json::json!(["API_URL", "EMPTY"]));
+        }
+
+        #[test]
+        fn redact_patterns() {
+            let args = parse_args(&["--redact-pattern", r"acct-\d+", "--redact-pattern", r"internal\.corp"]);
+            assert_eq!(args.redact_pattern, [r"acct-\d+", r"internal\.corp"]);
+            assert!(parse_args(&[]).redact_pattern.is_empty());
+        }
+
+        #[test]
+        fn category_prompt_pairs() {
+            let args = parse_args(&["--category-prompt", "test=prompts/test.md", "--category-prompt", "docs=d.md"]);
+            assert_eq!(
+                args.category_prompt,
+                vec![("test".to_string(), PathBuf::from("prompts/test.md")), ("docs".to_string(), PathBuf::from("d.md"))]
+            );
+            for bad in ["test", "=x.md", "test="] {
+                assert!(Args::try_parse_from(["ralph", "--category-prompt", bad]).is_err(), "{bad}");
+            }
+        }
+
+        #[test]
+        fn review_flags() {
+            let args = parse_args(&[]);
+            assert!(!args.review);
+            assert_eq!(args.review_model, None);
+            let args = parse_args(&["--review", "--review-model", "opus"]);
+            assert!(args.review);
+            assert_eq!(args.review_model.as_deref(), Some("opus"));
+        }
+
+        #[test]
+        fn summarize_context_flags() {
+            let args = parse_args(&[]);
+            assert!(!args.summarize_context);
+            assert_eq!(args.summary_model, None);
+            let args = parse_args(&["--summarize-context", "--summary-model", "haiku"]);
+            assert!(args.summarize_context);
+            assert_eq!(args.summary_model.as_deref(), Some("haiku"));
+        }
+
+        #[test]
+        fn verification_history_flag() {
+            assert_eq!(parse_args(&[]).verification_history, 5);
+            assert_eq!(parse_args(&["--verification-history", "0"]).verification_history, 0);
+        }
+
+        #[test]
+        fn max_cost_flag() {
+            assert_eq!(parse_args(&[]).max_cost, None);
+            assert_eq!(parse_args(&["--max-cost", "12.5"]).max_cost, Some(12.5));
+        }
+
+        #[test]
+        fn parallel_flag() {
+            assert_eq!(parse_args(&[]).parallel, 1);
+            assert_eq!(parse_args(&["--parallel", "4"]).parallel, 4);
+        }
+
+        #[test]
+        fn snapshot_flag() {
+            assert!(!parse_args(&[]).snapshot);
+            assert!(parse_args(&["--snapshot"]).snapshot);
+        }
+
+        #[test]
+        fn resume_flag() {
+            let args = parse_args(&["--resume"]);
+            assert!(args.resume);
+        }
+
+        #[test]
+        fn progress_path_flag() {
+            let args = parse_args(&["--progress-path", "docs/progress.md"]);
+            assert_eq!(args.progress_path, Some(PathBuf::from("docs/progress.md")));
+        }
+
+        #[test]
+        fn simulate_script() {
+            let args = parse_args(&["--simulate", "script.json"]);
+            assert_eq!(args.simulate, Some(PathBuf::from("script.json")));
+        }
+
+        #[test]
+        fn serve_address() {
+            let args = parse_args(&["--serve", "0.0.0.0:7777"]);
+            assert_eq!(args.serve, Some("0.0.0.0:7777".parse().unwrap()));
+            let args = parse_args(&["--serve", "127.0.0.1:7777", "--serve-token", "s3cret"]);
+            assert_eq!(args.serve_token.as_deref(), Some("s3cret"));
+            assert_eq!(serde_json::to_value(&args).unwrap()["serve_token"], "<redacted>");
+            assert!(Args::try_parse_from(["ralph", "--serve-token", "s3cret"]).is_err());
+        }
+
+        #[test]
+        fn prd_flag_accepted_after_subcommand() {
+            let args = parse_args(&["import", "todos", "-p", "cleanup.jsonc"]);
+            assert_eq!(args.prd, PathBuf::from("cleanup.jsonc"));
+        }
+    }
+
     mod edge_cases {
         use super::*;
 
@@ -447,4 +1793,98 @@ mod tests {
             assert_eq!(args.prompt, Some(PathBuf::from("/home/user/prompts/custom.md")));
         }
     }
+
+    mod config_file {
+        use super::*;
+
+        fn load(config: &str, cli: &[&str]) -> Args {
+            let argv = std::iter::once("ralph").chain(cli.iter().copied()).map(OsString::from).collect();
+            Args::try_parse_from(with_config(argv, config).unwrap()).unwrap()
+        }
+
+        fn load_err(config: &str) -> String {
+            let argv = vec![OsString::from("ralph")];
+            format!("{:#}", with_config(argv, config).unwrap_err())
+        }
+
+        #[test]
+        fn supplies_defaults() {
+            let args = load(
+                "prd = \"docs/prd.jsonc\"\ndelay = 5\ntimeout = 600\npermission-mode = \"plan\"\nwebhook = \"https://hooks.example\"",
+                &[],
+            );
+            assert_eq!(args.prd, PathBuf::from("docs/prd.jsonc"));
+            assert_eq!(args.delay, 5);
+            assert_eq!(args.timeout, 600);
+            assert_eq!(args.permission_mode, "plan");
+            assert_eq!(args.webhook.as_deref(), Some("https://hooks.example"));
+        }
+
+        #[test]
+        fn command_line_wins() {
+            let args = load("delay = 5\nprd = \"a.jsonc\"", &["--delay", "9", "-p", "b.jsonc"]);
+            assert_eq!(args.delay, 9);
+            assert_eq!(args.prd, PathBuf::from("b.jsonc"));
+        }
+
+        #[test]
+        fn snake_case_keys_and_aliases() {
+            let args = load("max_iterations = 3\nwebhook-url = \"https://x\"", &[]);
+            assert_eq!(args.max_iterations, 3);
+            assert_eq!(args.webhook.as_deref(), Some("https://x"));
+        }
+
+        #[test]
+        fn boolean_flags() {
+            let args = load("snapshot = true\nfinal-audit = false", &[]);
+            assert!(args.snapshot);
+            assert!(!args.final_audit);
+        }
+
+        #[test]
+        fn lists_are_replaced_by_the_command_line() {
+            let config = "webhook-events = [\"error\", \"complete\"]\nlog-sink = [\"s3://a/b\"]";
+            let args = load(config, &["--webhook-events", "iteration_failed"]);
+            assert_eq!(args.webhook_events, vec![EventType::IterationFailed]);
+            assert_eq!(args.log_sink, vec!["s3://a/b"]);
+        }
+
+        #[test]
+        fn applies_to_subcommands() {
+            let args = load("budget = 2.5", &["estimate"]);
+            assert_eq!(args.budget, Some(2.5));
+            assert!(matches!(args.command, Some(Command::Estimate)));
+        }
+
+        #[test]
+        fn rejects_unknown_keys() {
+            assert!(load_err("dleay = 5").contains("Unknown option 'dleay'"));
+        }
+
+        #[test]
+        fn rejects_non_boolean_flags() {
+            assert!(load_err("snapshot = \"yes\"").contains("'snapshot' must be true or false"));
+        }
+
+        #[test]
+        fn rejects_invalid_toml() {
+            assert!(load_err("delay = ").contains("TOML parse error"));
+        }
+
+        #[test]
+        fn ralph_toml_is_preferred() {
+            let dir = tempfile::tempdir().unwrap();
+            std::fs::write(dir.path().join(".ralphrc"), "delay = 1").unwrap();
+            std::fs::write(dir.path().join("ralph.toml"), "delay = 7").unwrap();
+            let args = Args::load_from(vec!["ralph".into()], dir.path()).unwrap();
+            assert_eq!(args.delay, 7);
+        }
+
+        #[test]
+        fn no_config_file_uses_builtin_defaults() {
+            let dir = tempfile::tempdir().unwrap();
+            let args = Args::load_from(vec!["ralph".into()], dir.path()).unwrap();
+            assert_eq!(args.delay, 2);
+        }
+    }
 }
@@ -0,0 +1,210 @@
+//! Inlines feature-relevant source files into the system prompt (`--pack-context`).
+
+use crate::prd::Feature;
+use anyhow::{Context, Result};
+use globset::{Glob, GlobMatcher};
+use ignore::WalkBuilder;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Rough bytes-per-token ratio used to convert token budgets.
+pub const BYTES_PER_TOKEN: usize = 4;
+
+#[derive(Debug)]
+pub struct PackedContext {
+    pub section: String,
+    pub included: Vec<String>,
+    pub omitted: Vec<String>,
+    pub bytes: usize,
+    pub budget: usize,
+}
+
+/// Effective byte budget for `feature`: the tighter of its byte/token limits, else `default_bytes`.
+#[must_use]
+pub fn budget_bytes(feature: &Feature, default_bytes: usize) -> usize {
+    feature
+        .context_budget
+        .and_then(|b| {
+            [b.bytes, b.tokens.map(|t| t.saturating_mul(BYTES_PER_TOKEN))]
+                .into_iter()
+                .flatten()
+                .min()
+        })
+        .unwrap_or(default_bytes)
+}
+
+/// Packs files matching the feature's `context` globs, in priority order, until the budget is spent.
+/// Files that don't fit are skipped (smaller later files may still fit). Returns `None` if the
+/// feature declares no context.
+pub fn pack(feature: &Feature, root: &Path, default_budget: usize) -> Result<Option<PackedContext>> {
+    if feature.context.is_empty() {
+        return Ok(None);
+    }
+
+    let matchers = feature
+        .context
+        .iter()
+        .map(|p| {
+            Glob::new(p)
+                .map(|g| g.compile_matcher())
+                .with_context(|| format!("Invalid context glob '{p}' in feature '{}'", feature.id))
+        })
+        .collect::<Result<Vec<GlobMatcher>>>()?;
+
+    let files = list_files(root);
+    let budget = budget_bytes(feature, default_budget);
+    let mut seen = HashSet::new();
+    let mut body = String::new();
+    let mut packed = PackedContext {
+        section: String::new(),
+        included: Vec::new(),
+        omitted: Vec::new(),
+        bytes: 0,
+        budget,
+    };
+
+    for matcher in &matchers {
+        for rel in files.iter().filter(|f| matcher.is_match(f.as_str())) {
+            if !seen.insert(rel.as_str()) {
+                continue;
+            }
+            let Ok(content) = std::fs::read_to_string(root.join(rel)) else {
+                continue;
+            };
+            if packed.bytes + content.len() > budget {
+                packed.omitted.push(rel.clone());
+                continue;
+            }
+            packed.bytes += content.len();
+            let fence = fence_for(&content);
+            let _ = write!(body, "### {rel}\n\n{fence}\n{content}");
+            if !content.ends_with('\n') {
+                body.push('\n');
+            }
+            let _ = writeln!(body, "{fence}\n");
+            packed.included.push(rel.clone());
+        }
+    }
+
+    let _ = write!(
+        packed.section,
+        "## Included Context\n\nFiles relevant to feature `{}` ({} of {} bytes budget):\n\n{body}",
+        feature.id, packed.bytes, budget
+    );
+    if !packed.omitted.is_empty() {
+        let _ = writeln!(
+            packed.section,
+            "Omitted (over budget, read them yourself if needed): {}",
+            packed.omitted.join(", ")
+        );
+    }
+
+    Ok(Some(packed))
+}
+
+fn list_files(root: &Path) -> Vec<String> {
+    let mut files: Vec<String> = WalkBuilder::new(root)
+        .build()
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
+        .filter_map(|e| {
+            e.path()
+                .strip_prefix(root)
+                .ok()
+                .map(|p| p.to_string_lossy().replace('\\', "/"))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// A backtick fence longer than any backtick run inside `content`.
+fn fence_for(content: &str) -> String {
+    let longest = content
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prd::ContextBudget;
+
+    fn feature(context: &[&str], budget: Option<ContextBudget>) -> Feature {
+        Feature {
+            id: "feat-1".into(),
+            context: context.iter().map(|s| s.to_string()).collect(),
+            context_budget: budget,
+            ..Default::default()
+        }
+    }
+
+    fn project() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src/nested")).unwrap();
+        std::fs::write(dir.path().join("src/lib.rs"), "pub mod a;\n").unwrap();
+        std::fs::write(dir.path().join("src/a.rs"), "x".repeat(100)).unwrap();
+        std::fs::write(dir.path().join("src/nested/b.rs"), "fn b() {}\n").unwrap();
+        std::fs::write(dir.path().join("README.md"), "# readme\n").unwrap();
+        dir
+    }
+
+    #[test]
+    fn budget_defaults_when_unset() {
+        assert_eq!(budget_bytes(&feature(&[], None), 500), 500);
+    }
+
+    #[test]
+    fn budget_uses_tighter_limit() {
+        let b = ContextBudget { bytes: Some(1000), tokens: Some(100) };
+        assert_eq!(budget_bytes(&feature(&[], Some(b)), 5), 400);
+        let b = ContextBudget { bytes: Some(10), tokens: Some(100) };
+        assert_eq!(budget_bytes(&feature(&[], Some(b)), 5), 10);
+    }
+
+    #[test]
+    fn no_context_returns_none() {
+        let dir = project();
+        assert!(pack(&feature(&[], None), dir.path(), 1000).unwrap().is_none());
+    }
+
+    #[test]
+    fn includes_files_in_priority_order() {
+        let dir = project();
+        let packed = pack(&feature(&["src/lib.rs", "src/**/*.rs"], None), dir.path(), 10_000)
+            .unwrap()
+            .unwrap();
+        assert_eq!(packed.included, vec!["src/lib.rs", "src/a.rs", "src/nested/b.rs"]);
+        assert!(packed.section.contains("### src/lib.rs\n\n```\npub mod a;\n```"));
+        assert!(packed.omitted.is_empty());
+    }
+
+    #[test]
+    fn skips_files_over_budget() {
+        let dir = project();
+        let b = ContextBudget { bytes: Some(30), tokens: None };
+        let packed = pack(&feature(&["src/**/*.rs"], Some(b)), dir.path(), 10_000)
+            .unwrap()
+            .unwrap();
+        assert_eq!(packed.included, vec!["src/lib.rs", "src/nested/b.rs"]);
+        assert_eq!(packed.omitted, vec!["src/a.rs"]);
+        assert!(packed.bytes <= 30);
+        assert!(packed.section.contains("Omitted (over budget"));
+    }
+
+    #[test]
+    fn invalid_glob_is_an_error() {
+        let dir = project();
+        assert!(pack(&feature(&["src/[.rs"], None), dir.path(), 100).is_err());
+    }
+
+    #[test]
+    fn fence_outgrows_backticks_in_content() {
+        assert_eq!(fence_for("plain"), "```");
+        assert_eq!(fence_for("has ``` inside"), "````");
+    }
+}
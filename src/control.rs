@@ -0,0 +1,350 @@
+//! Operator control of a running loop: commands written to `.ralph/control`, one per line,
+//! are picked up between iterations and the file is removed. `pause` holds the loop before
+//! the next iteration until `resume`, `skip-feature` blocks the feature the next iteration
+//! would work on, and `stop-after-iteration` ends the run once the current iteration is
+//! done. On Unix, `SIGUSR1` toggles pause. Ctrl-C during an iteration asks for the same
+//! stop; a second Ctrl-C cancels the iteration.
+
+use crate::{output, stop};
+use anyhow::{bail, Context, Result};
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::time::Duration;
+
+/// Set by the `SIGUSR1` listener, cleared when [`Control::poll`] sees it.
+static PAUSE_TOGGLED: AtomicBool = AtomicBool::new(false);
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Pause,
+    Resume,
+    SkipFeature,
+    StopAfterIteration,
+}
+
+impl FromStr for Command {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "pause" => Self::Pause,
+            "resume" => Self::Resume,
+            "skip-feature" => Self::SkipFeature,
+            "stop-after-iteration" => Self::StopAfterIteration,
+            _ => bail!("unknown control command '{s}' (expected pause, resume, skip-feature, or stop-after-iteration)"),
+        })
+    }
+}
+
+impl Command {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pause => "pause",
+            Self::Resume => "resume",
+            Self::SkipFeature => "skip-feature",
+            Self::StopAfterIteration => "stop-after-iteration",
+        }
+    }
+}
+
+#[must_use]
+pub fn path(ralph_dir: &Path) -> PathBuf {
+    ralph_dir.join("control")
+}
+
+/// Appends `command` to the control file for the running loop to pick up.
+pub fn send(ralph_dir: &Path, command: Command) -> Result<()> {
+    std::fs::create_dir_all(ralph_dir).with_context(|| format!("Failed to create {}", ralph_dir.display()))?;
+    let path = path(ralph_dir);
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    writeln!(file, "{}", command.as_str()).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Reads and removes the control file. Blank lines and `#` comments are ignored; unknown
+/// commands are reported and skipped.
+pub fn take(ralph_dir: &Path) -> Result<Vec<Command>> {
+    let path = path(ralph_dir);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    std::fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.parse().map_err(|e| output::warn(&format!("{e:#}"))).ok())
+        .collect())
+}
+
+/// Starts `command` in its own process group on Unix. The terminal sends Ctrl-C to ralph's
+/// group, so the first press no longer reaches the agent or a verification command; ralph
+/// passes it on with [`interrupt_group`] only when it gives up on the iteration.
+pub fn own_process_group(command: &mut tokio::process::Command) {
+    #[cfg(unix)]
+    command.process_group(0);
+    #[cfg(not(unix))]
+    let _ = command;
+}
+
+#[cfg(unix)]
+fn signal_group(pid: u32, signal: libc::c_int) -> bool {
+    let Ok(pgid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // SAFETY: kill(2) only sends a signal; a negative pid addresses the process group
+    unsafe { libc::kill(-pgid, signal) == 0 }
+}
+
+/// Sends SIGINT to the group [`own_process_group`] started `pid` in; false if it couldn't
+/// be sent, and always off Unix.
+#[must_use]
+pub fn interrupt_group(pid: u32) -> bool {
+    #[cfg(unix)]
+    return signal_group(pid, libc::SIGINT);
+    #[cfg(not(unix))]
+    {
+        let _ = pid;
+        false
+    }
+}
+
+/// Kills whatever is left of the group [`own_process_group`] started `pid` in.
+pub fn kill_group(pid: u32) {
+    #[cfg(unix)]
+    signal_group(pid, libc::SIGKILL);
+    #[cfg(not(unix))]
+    let _ = pid;
+}
+
+/// Kills a child's process group when dropped, unless [`release`](Self::release)d, so
+/// abandoning the work running it (a second Ctrl-C) takes its descendants with it.
+pub struct GroupGuard(Option<u32>);
+
+impl GroupGuard {
+    #[must_use]
+    pub fn new(pid: Option<u32>) -> Self {
+        Self(pid)
+    }
+
+    /// The child has exited on its own; leave the group alone.
+    pub fn release(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for GroupGuard {
+    fn drop(&mut self) {
+        if let Some(pid) = self.0 {
+            kill_group(pid);
+        }
+    }
+}
+
+/// Toggles pause on every `SIGUSR1` until dropped. Does nothing off Unix.
+pub struct SignalListener(Option<tokio::task::JoinHandle<()>>);
+
+impl SignalListener {
+    #[must_use]
+    pub fn start() -> Self {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            match signal(SignalKind::user_defined1()) {
+                Ok(mut signals) => {
+                    return Self(Some(tokio::spawn(async move {
+                        while signals.recv().await.is_some() {
+                            PAUSE_TOGGLED.fetch_xor(true, Ordering::SeqCst);
+                        }
+                    })));
+                }
+                Err(e) => output::warn(&format!("SIGUSR1 pause toggle unavailable: {e}")),
+            }
+        }
+        Self(None)
+    }
+}
+
+impl Drop for SignalListener {
+    fn drop(&mut self) {
+        if let Some(ref task) = self.0 {
+            task.abort();
+        }
+    }
+}
+
+/// What the operator has asked for so far.
+#[derive(Debug, Default)]
+pub struct Control {
+    pub paused: bool,
+    /// Block the next feature before the next iteration
+    pub skip_feature: bool,
+    pub stop_after_iteration: bool,
+}
+
+impl Control {
+    /// Applies new commands and a pending `SIGUSR1`, announcing changes.
+    pub fn poll(&mut self, ralph_dir: &Path) {
+        let mut commands = take(ralph_dir).unwrap_or_else(|e| {
+            output::warn(&format!("{e:#}"));
+            Vec::new()
+        });
+        if PAUSE_TOGGLED.swap(false, Ordering::SeqCst) {
+            commands.push(if self.paused { Command::Resume } else { Command::Pause });
+        }
+        for command in commands {
+            self.apply(command, ralph_dir);
+        }
+    }
+
+    /// Waits while paused, polling for commands; returns early once the stop file exists or
+    /// on Ctrl-C, which asks for a stop.
+    pub async fn hold(&mut self, ralph_dir: &Path) {
+        self.poll(ralph_dir);
+        while self.paused && !stop::requested(ralph_dir) {
+            tokio::select! {
+                () = tokio::time::sleep(POLL_INTERVAL) => {}
+                _ = tokio::signal::ctrl_c() => {
+                    self.stop_after_iteration = true;
+                    return;
+                }
+            }
+            self.poll(ralph_dir);
+        }
+    }
+
+    /// Runs `work` to completion. The first Ctrl-C asks for a stop once it is done; the
+    /// second gives up on it and returns None, for the caller to cancel.
+    pub async fn interruptible<T>(&mut self, work: impl Future<Output = T>) -> Option<T> {
+        tokio::pin!(work);
+        let mut interrupted = false;
+        loop {
+            tokio::select! {
+                result = &mut work => return Some(result),
+                _ = tokio::signal::ctrl_c() => {
+                    if interrupted {
+                        return None;
+                    }
+                    interrupted = true;
+                    self.stop_after_iteration = true;
+                    output::blank();
+                    output::warn("Finishing the current iteration, then stopping (Ctrl-C again to cancel it)");
+                }
+            }
+        }
+    }
+
+    fn apply(&mut self, command: Command, ralph_dir: &Path) {
+        match command {
+            Command::Pause if !self.paused => {
+                self.paused = true;
+                output::warn(&format!(
+                    "Paused; write 'resume' to {} (or send SIGUSR1) to continue",
+                    path(ralph_dir).display()
+                ));
+            }
+            Command::Resume if self.paused => {
+                self.paused = false;
+                output::log("Resumed");
+            }
+            Command::SkipFeature => {
+                self.skip_feature = true;
+                output::log("The next feature will be skipped");
+            }
+            Command::StopAfterIteration if !self.stop_after_iteration => {
+                self.stop_after_iteration = true;
+                output::log("Stopping after the current iteration");
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn take_reads_and_removes_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(take(dir.path()).unwrap().is_empty());
+        std::fs::write(path(dir.path()), "pause\n# note\n\n skip-feature \nbogus\nstop-after-iteration\n").unwrap();
+        assert_eq!(
+            take(dir.path()).unwrap(),
+            vec![Command::Pause, Command::SkipFeature, Command::StopAfterIteration]
+        );
+        assert!(!path(dir.path()).exists());
+    }
+
+    #[test]
+    fn sent_commands_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let ralph_dir = dir.path().join(".ralph");
+        send(&ralph_dir, Command::Pause).unwrap();
+        send(&ralph_dir, Command::SkipFeature).unwrap();
+        assert_eq!(take(&ralph_dir).unwrap(), vec![Command::Pause, Command::SkipFeature]);
+        for command in [Command::Pause, Command::Resume, Command::SkipFeature, Command::StopAfterIteration] {
+            assert_eq!(command.as_str().parse::<Command>().unwrap(), command);
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn children_get_their_own_group_and_interrupts_on_request() {
+        let mut command = tokio::process::Command::new("sh");
+        command.args(["-c", "trap 'exit 7' INT; sleep 5 & wait"]);
+        own_process_group(&mut command);
+        let mut child = command.spawn().unwrap();
+        let pid = child.id().unwrap();
+        // Give the shell a moment to install its trap
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).unwrap();
+        let pgid: u32 = stat.rsplit(')').next().unwrap().split_whitespace().nth(2).unwrap().parse().unwrap();
+        assert_eq!(pgid, pid);
+
+        assert!(interrupt_group(pid));
+        let status = tokio::time::timeout(Duration::from_secs(3), child.wait()).await.unwrap().unwrap();
+        assert_eq!(status.code(), Some(7));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn group_guard_kills_the_group_unless_released() {
+        let mut command = tokio::process::Command::new("sleep");
+        command.arg("5");
+        own_process_group(&mut command);
+        let mut child = command.spawn().unwrap();
+        drop(GroupGuard::new(child.id()));
+        let status = tokio::time::timeout(Duration::from_secs(3), child.wait()).await.unwrap().unwrap();
+        assert!(!status.success());
+
+        let mut done = tokio::process::Command::new("true").spawn().unwrap();
+        let guard = GroupGuard::new(done.id());
+        done.wait().await.unwrap();
+        guard.release();
+    }
+
+    #[test]
+    fn poll_applies_commands_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut control = Control::default();
+        std::fs::write(path(dir.path()), "pause\n").unwrap();
+        control.poll(dir.path());
+        assert!(control.paused);
+        std::fs::write(path(dir.path()), "resume\nskip-feature\n").unwrap();
+        control.poll(dir.path());
+        assert!(!control.paused);
+        assert!(control.skip_feature);
+        assert!(!control.stop_after_iteration);
+    }
+}
@@ -0,0 +1,277 @@
+//! Coverage delta gate: runs the PRD's coverage command after each successful iteration
+//! and fails the iteration if line coverage drops below the baseline by more than `minDelta`.
+
+use crate::prd::{CoverageConfig, VerifyCommand};
+use crate::sandbox::Sandbox;
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CoverageCheck {
+    Passed { previous: Option<f64>, current: f64 },
+    Regressed { previous: f64, current: f64 },
+}
+
+impl CoverageCheck {
+    #[must_use]
+    pub fn current(self) -> f64 {
+        match self {
+            Self::Passed { current, .. } | Self::Regressed { current, .. } => current,
+        }
+    }
+}
+
+pub struct CoverageGate<'a> {
+    config: &'a CoverageConfig,
+    project_dir: &'a Path,
+    /// Runs the command like the verification commands: same environment, timeout, and cleanup
+    sandbox: Sandbox,
+    timeout: Duration,
+    /// Highest coverage seen so far; only ratchets up so repeated small drops can't accumulate
+    baseline: Option<f64>,
+}
+
+impl<'a> CoverageGate<'a> {
+    #[must_use]
+    pub fn new(config: &'a CoverageConfig, project_dir: &'a Path, sandbox: Sandbox, timeout: Duration) -> Self {
+        Self {
+            config,
+            project_dir,
+            sandbox,
+            timeout,
+            baseline: None,
+        }
+    }
+
+    #[must_use]
+    pub fn baseline(&self) -> Option<f64> {
+        self.baseline
+    }
+
+    pub async fn check(&mut self) -> Result<CoverageCheck> {
+        let current = measure(self.config, self.project_dir, &self.sandbox, self.timeout).await?;
+        let check = evaluate(self.baseline, current, self.config.min_delta);
+        if let CoverageCheck::Passed { .. } = check {
+            self.baseline = Some(self.baseline.map_or(current, |b| b.max(current)));
+        }
+        Ok(check)
+    }
+}
+
+#[must_use]
+pub fn evaluate(baseline: Option<f64>, current: f64, min_delta: f64) -> CoverageCheck {
+    match baseline {
+        // Small epsilon so float noise in reports doesn't fail a zero-tolerance gate
+        Some(previous) if current - previous < min_delta - 1e-9 => {
+            CoverageCheck::Regressed { previous, current }
+        }
+        previous => CoverageCheck::Passed { previous, current },
+    }
+}
+
+/// Runs the coverage command in `sandbox`, as the verification command `coverage`, and
+/// returns line coverage as a percentage.
+pub async fn measure(config: &CoverageConfig, project_dir: &Path, sandbox: &Sandbox, timeout: Duration) -> Result<f64> {
+    let cmd = VerifyCommand {
+        name: "coverage".into(),
+        command: config.command.clone(),
+        description: "Coverage gate".into(),
+        ..VerifyCommand::default()
+    };
+    let passed = sandbox
+        .run(&cmd, project_dir, timeout)
+        .await
+        .with_context(|| format!("Failed to run coverage command: {}", config.command))?;
+    if !passed {
+        bail!("Coverage command failed: {}", config.command);
+    }
+
+    let report_path = project_dir.join(&config.report);
+    let report = std::fs::read_to_string(&report_path)
+        .with_context(|| format!("Failed to read coverage report: {}", report_path.display()))?;
+    parse_report(&report)
+        .with_context(|| format!("Failed to parse coverage report: {}", report_path.display()))
+}
+
+/// Parses an lcov tracefile or a Cobertura XML report, detected by content.
+pub(crate) fn parse_report(report: &str) -> Result<f64> {
+    if report.trim_start().starts_with('<') {
+        parse_cobertura(report)
+    } else {
+        parse_lcov(report)
+    }
+}
+
+fn parse_lcov(report: &str) -> Result<f64> {
+    let (mut found, mut hit) = (0u64, 0u64);
+    for line in report.lines() {
+        if let Some(n) = line.strip_prefix("LF:") {
+            found += n.trim().parse::<u64>().context("Invalid LF line")?;
+        } else if let Some(n) = line.strip_prefix("LH:") {
+            hit += n.trim().parse::<u64>().context("Invalid LH line")?;
+        }
+    }
+    if found == 0 {
+        bail!("No LF/LH records in lcov report");
+    }
+    Ok(hit as f64 * 100.0 / found as f64)
+}
+
+fn parse_cobertura(report: &str) -> Result<f64> {
+    let start = report.find("<coverage").context("No <coverage> element")?;
+    let tag = &report[start..start + report[start..].find('>').context("Unterminated <coverage> element")?];
+
+    if let (Some(covered), Some(valid)) = (attr(tag, "lines-covered"), attr(tag, "lines-valid")) {
+        let covered: f64 = covered.parse().context("Invalid lines-covered")?;
+        let valid: f64 = valid.parse().context("Invalid lines-valid")?;
+        if valid > 0.0 {
+            return Ok(covered * 100.0 / valid);
+        }
+    }
+    let rate: f64 = attr(tag, "line-rate")
+        .context("No line-rate attribute on <coverage>")?
+        .parse()
+        .context("Invalid line-rate")?;
+    Ok(rate * 100.0)
+}
+
+fn attr<'t>(tag: &'t str, name: &str) -> Option<&'t str> {
+    let needle = format!(" {name}=\"");
+    let start = tag.find(&needle)? + needle.len();
+    tag[start..].split('"').next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod parse_report_tests {
+        use super::*;
+
+        #[test]
+        fn sums_lcov_records() {
+            let report = "TN:\nSF:src/a.rs\nLF:10\nLH:5\nend_of_record\nSF:src/b.rs\nLF:30\nLH:25\nend_of_record\n";
+            assert_eq!(parse_report(report).unwrap(), 75.0);
+        }
+
+        #[test]
+        fn empty_lcov_is_error() {
+            assert!(parse_report("TN:\n").is_err());
+        }
+
+        #[test]
+        fn cobertura_prefers_line_counts() {
+            let report = r#"<?xml version="1.0" ?>
+<coverage line-rate="0.9" lines-covered="3" lines-valid="4" version="1">
+</coverage>"#;
+            assert_eq!(parse_report(report).unwrap(), 75.0);
+        }
+
+        #[test]
+        fn cobertura_falls_back_to_line_rate() {
+            let report = r#"<coverage branch-rate="0.1" line-rate="0.5"></coverage>"#;
+            assert_eq!(parse_report(report).unwrap(), 50.0);
+        }
+
+        #[test]
+        fn cobertura_without_coverage_element_is_error() {
+            assert!(parse_report("<report/>").is_err());
+        }
+    }
+
+    mod evaluate_tests {
+        use super::*;
+
+        #[test]
+        fn first_measurement_passes() {
+            assert_eq!(
+                evaluate(None, 40.0, 0.0),
+                CoverageCheck::Passed { previous: None, current: 40.0 }
+            );
+        }
+
+        #[test]
+        fn drop_fails_with_zero_tolerance() {
+            assert_eq!(
+                evaluate(Some(80.0), 79.9, 0.0),
+                CoverageCheck::Regressed { previous: 80.0, current: 79.9 }
+            );
+        }
+
+        #[test]
+        fn drop_within_tolerance_passes() {
+            assert!(matches!(evaluate(Some(80.0), 79.5, -0.5), CoverageCheck::Passed { .. }));
+        }
+
+        #[test]
+        fn positive_min_delta_requires_growth() {
+            assert!(matches!(evaluate(Some(80.0), 80.0, 1.0), CoverageCheck::Regressed { .. }));
+            assert!(matches!(evaluate(Some(80.0), 81.0, 1.0), CoverageCheck::Passed { .. }));
+        }
+    }
+
+    mod gate_tests {
+        use super::*;
+
+        fn config(dir: &Path, value: &str, min_delta: f64) -> CoverageConfig {
+            std::fs::write(dir.join("value"), value).unwrap();
+            CoverageConfig {
+                command: "printf 'LF:100\\nLH:%s\\n' \"$(cat value)\" > lcov.info".into(),
+                report: "lcov.info".into(),
+                min_delta,
+            }
+        }
+
+        #[tokio::test]
+        async fn baseline_ratchets_up_only() {
+            let dir = tempfile::tempdir().unwrap();
+            let cfg = config(dir.path(), "80", -5.0);
+            let mut gate = CoverageGate::new(&cfg, dir.path(), Sandbox::default(), Duration::from_secs(5));
+
+            assert!(matches!(gate.check().await.unwrap(), CoverageCheck::Passed { .. }));
+            std::fs::write(dir.path().join("value"), "77").unwrap();
+            assert!(matches!(gate.check().await.unwrap(), CoverageCheck::Passed { .. }));
+            assert_eq!(gate.baseline(), Some(80.0));
+            std::fs::write(dir.path().join("value"), "74").unwrap();
+            assert_eq!(
+                gate.check().await.unwrap(),
+                CoverageCheck::Regressed { previous: 80.0, current: 74.0 }
+            );
+        }
+
+        #[tokio::test]
+        async fn failing_command_is_error() {
+            let dir = tempfile::tempdir().unwrap();
+            let cfg = CoverageConfig {
+                command: "exit 1".into(),
+                report: "lcov.info".into(),
+                min_delta: 0.0,
+            };
+            assert!(measure(&cfg, dir.path(), &Sandbox::default(), Duration::from_secs(5)).await.is_err());
+        }
+
+        #[tokio::test]
+        async fn runs_with_the_environment_and_timeout_of_verification() {
+            use clap::Parser;
+
+            let dir = tempfile::tempdir().unwrap();
+            let prd = dir.path().join("prd.jsonc").display().to_string();
+            let args = crate::config::Args::try_parse_from(["ralph", "--prd", &prd]).unwrap();
+            let env = std::collections::BTreeMap::from([("COVERED".to_string(), "90".to_string())]);
+            let sandbox = Sandbox::from_args(&args, &env);
+            let cfg = CoverageConfig {
+                command: "printf 'LF:100\\nLH:%s\\n' \"$COVERED\" > lcov.info".into(),
+                report: "lcov.info".into(),
+                min_delta: 0.0,
+            };
+            assert_eq!(measure(&cfg, dir.path(), &sandbox, Duration::from_secs(5)).await.unwrap(), 90.0);
+            assert!(dir.path().join(".ralph/logs/verify-coverage.log").exists());
+
+            let slow = CoverageConfig { command: "sleep 30".into(), ..cfg };
+            let started = std::time::Instant::now();
+            assert!(measure(&slow, dir.path(), &sandbox, Duration::from_millis(200)).await.is_err());
+            assert!(started.elapsed() < Duration::from_secs(5));
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Native desktop notifications (`--notify-desktop`) for runs left in a background terminal:
+//! one when the loop completes, one when it gives up after too many consecutive failures,
+//! one whenever it needs the operator, and one for every rate-limited iteration.
+
+use crate::events::{EventSink, RunEvent};
+use crate::{output, webhook};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+const APP_NAME: &str = "ralph";
+
+pub fn configure(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Summary and body of the notification for `event`, if it gets one.
+#[must_use]
+pub fn notification(event: &RunEvent) -> Option<(&'static str, String)> {
+    match event {
+        RunEvent::Completed { iterations } => {
+            Some(("ralph: complete", format!("All features complete after {iterations} iterations")))
+        }
+        RunEvent::Failed { iterations, reason } => {
+            Some(("ralph: failed", format!("Stopped after {iterations} iterations: {reason}")))
+        }
+        RunEvent::ActionRequired { message, action } => {
+            Some(("ralph: action required", format!("{message}\n{action}")))
+        }
+        RunEvent::IterationFinished(artifact) if artifact.result == "rate_limit" => Some((
+            "ralph: rate limited",
+            format!("Iteration {} hit a rate limit; waiting before the next one", artifact.iteration),
+        )),
+        _ => None,
+    }
+}
+
+pub struct DesktopSink;
+
+impl EventSink for DesktopSink {
+    fn handle(&self, event: &RunEvent) {
+        if !ENABLED.load(Ordering::Relaxed) {
+            return;
+        }
+        let Some((summary, body)) = notification(event) else {
+            return;
+        };
+        // Showing one can block on the notification daemon; the webhook flush waits for it
+        webhook::track(tokio::task::spawn_blocking(move || {
+            let shown = notify_rust::Notification::new().appname(APP_NAME).summary(summary).body(&body).show();
+            if let Err(e) = shown {
+                output::warn(&format!("Desktop notification failed: {e}"));
+            }
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ArtifactBuilder;
+
+    #[test]
+    fn notifies_on_completion_failure_action_and_rate_limit() {
+        let (summary, body) = notification(&RunEvent::Completed { iterations: 4 }).unwrap();
+        assert_eq!(summary, "ralph: complete");
+        assert_eq!(body, "All features complete after 4 iterations");
+
+        let failed = RunEvent::Failed { iterations: 3, reason: "too many consecutive failures".into() };
+        assert_eq!(notification(&failed).unwrap().1, "Stopped after 3 iterations: too many consecutive failures");
+
+        let action = RunEvent::ActionRequired { message: "Approval needed".into(), action: "Answer y/n".into() };
+        let (summary, body) = notification(&action).unwrap();
+        assert_eq!(summary, "ralph: action required");
+        assert_eq!(body, "Approval needed\nAnswer y/n");
+
+        let limited = ArtifactBuilder::new(2).result("rate_limit").build();
+        let (summary, _) = notification(&RunEvent::IterationFinished(Box::new(limited))).unwrap();
+        assert_eq!(summary, "ralph: rate limited");
+        let finished = ArtifactBuilder::new(2).build();
+        assert!(notification(&RunEvent::IterationFinished(Box::new(finished))).is_none());
+        assert!(notification(&RunEvent::Stopped { iterations: 1 }).is_none());
+    }
+}
@@ -0,0 +1,237 @@
+//! Readable JSON5 parse errors: position, a caret-annotated snippet, and hints for the
+//! mistakes hand-edited PRDs usually contain.
+
+use std::fmt;
+
+/// Lines of context shown above the offending line.
+const CONTEXT_LINES: usize = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    /// 1-based line and column, when the parser reported one
+    pub location: Option<(usize, usize)>,
+    snippet: Vec<String>,
+    pub hint: Option<String>,
+}
+
+impl Diagnostic {
+    #[must_use]
+    pub fn new(content: &str, err: &json5::Error) -> Self {
+        let json5::Error::Message { msg, location } = err;
+        let message = summarize(msg);
+        let location = location.as_ref().map(|l| (l.line, l.column));
+        let snippet = location.map_or_else(Vec::new, |(line, column)| snippet(content, line, column));
+        let hint = hint(content, &message, location);
+        Self {
+            message,
+            location,
+            snippet,
+            hint,
+        }
+    }
+
+    /// A problem found in a document that parsed, at byte `offset` when it's known.
+    #[must_use]
+    pub fn at(content: &str, offset: Option<usize>, message: String, hint: Option<String>) -> Self {
+        let location = offset.map(|offset| {
+            let before = &content[..offset];
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+        });
+        let snippet = location.map_or_else(Vec::new, |(line, column)| snippet(content, line, column));
+        Self {
+            message,
+            location,
+            snippet,
+            hint,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location {
+            Some((line, column)) => write!(f, "{} at line {line}, column {column}", self.message)?,
+            None => f.write_str(&self.message)?,
+        }
+        for line in &self.snippet {
+            write!(f, "\n{line}")?;
+        }
+        if let Some(ref hint) = self.hint {
+            write!(f, "\nhint: {hint}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+/// The parser's own message without its ASCII-art snippet: pest errors end with
+/// `= expected ...`, serde errors are a single line already.
+fn summarize(msg: &str) -> String {
+    let message = msg
+        .lines()
+        .rev()
+        .find_map(|l| l.trim().strip_prefix("= "))
+        .unwrap_or(msg)
+        .trim();
+    match message {
+        "expected EOI" => "unexpected text after the end of the document".to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn snippet(content: &str, line: usize, column: usize) -> Vec<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    if line == 0 || line > lines.len() {
+        return Vec::new();
+    }
+    let width = line.to_string().len();
+    let first = line.saturating_sub(CONTEXT_LINES).max(1);
+    let mut out = vec![format!("{:width$} |", "")];
+    for n in first..=line {
+        out.push(format!("{n:>width$} | {}", lines[n - 1]));
+    }
+    // Tabs keep their width so the caret lines up
+    let pad: String = lines[line - 1]
+        .chars()
+        .take(column.saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    out.push(format!("{:width$} | {pad}^", ""));
+    out
+}
+
+fn hint(content: &str, message: &str, location: Option<(usize, usize)>) -> Option<String> {
+    let line = location.and_then(|(line, _)| content.lines().nth(line.saturating_sub(1)));
+    if line.is_some_and(|l| l.contains(['\u{201c}', '\u{201d}', '\u{2018}', '\u{2019}'])) {
+        return Some("curly quotes aren't string delimiters; use straight \" or ' quotes".into());
+    }
+    if let Some(key) = unquoted_key_with_dash(content) {
+        return Some(format!(
+            "unquoted keys may only contain letters, digits, _ and $; quote it as \"{key}\""
+        ));
+    }
+    if message.starts_with("unexpected text after the end") {
+        return Some("remove the trailing text, or look for an extra closing brace before it".into());
+    }
+    if message.starts_with("missing field") {
+        return Some("`ralph schema` prints every required field".into());
+    }
+    if message.starts_with("unknown variant") {
+        return Some("status is one of pending, in-progress, complete, blocked".into());
+    }
+    if message.contains("char_literal") || (message.contains("string") && unbalanced_quotes(line?)) {
+        return Some("a string isn't closed; check for a missing quote".into());
+    }
+    if message.starts_with("expected") && missing_comma(content, location?) {
+        return Some("a comma is probably missing before this entry".into());
+    }
+    None
+}
+
+/// An unquoted object key like `project-name:` outside strings and comments.
+fn unquoted_key_with_dash(content: &str) -> Option<String> {
+    content.lines().find_map(|line| {
+        let line = line.trim_start().trim_start_matches(['{', ',']).trim_start();
+        if line.starts_with(['"', '\'', '/']) {
+            return None;
+        }
+        let (key, _) = line.split_once(':')?;
+        let key = key.trim_end();
+        let valid_start = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '$');
+        let key_chars = key.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '$' | '-'));
+        (valid_start && key_chars && key.contains('-')).then(|| key.to_string())
+    })
+}
+
+fn unbalanced_quotes(line: &str) -> bool {
+    line.matches('"').count() % 2 == 1 || line.matches('\'').count() % 2 == 1
+}
+
+/// The error line ends a value without a comma and the next line starts another entry.
+/// The parser reports the position of the last value it matched, not the gap after it.
+fn missing_comma(content: &str, (line, _): (usize, usize)) -> bool {
+    let mut lines = content.lines().skip(line.saturating_sub(1)).map(str::trim).filter(|l| !l.is_empty());
+    let (Some(current), Some(next)) = (lines.next(), lines.next()) else {
+        return false;
+    };
+    let ends_value = current.ends_with(|c: char| matches!(c, '"' | '\'' | '}' | ']') || c.is_ascii_alphanumeric());
+    let starts_entry = next.starts_with(|c: char| matches!(c, '"' | '\'' | '{' | '[') || c.is_ascii_alphanumeric());
+    ends_value && starts_entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prd::Prd;
+
+    fn diagnose(content: &str) -> Diagnostic {
+        Diagnostic::new(content, &json5::from_str::<Prd>(content).unwrap_err())
+    }
+
+    #[test]
+    fn trailing_text_points_at_it() {
+        let d = diagnose("{ \"project\": {} }\noops");
+        assert_eq!(d.message, "unexpected text after the end of the document");
+        assert_eq!(d.location, Some((2, 1)));
+        assert_eq!(
+            d.to_string(),
+            "unexpected text after the end of the document at line 2, column 1\n  |\n1 | { \"project\": {} }\n2 | oops\n  | ^\nhint: remove the trailing text, or look for an extra closing brace before it"
+        );
+    }
+
+    #[test]
+    fn at_offset_converts_to_line_and_column() {
+        let content = "{\n  \"ids\": [\"a\", \"a\"]\n}";
+        let d = Diagnostic::at(content, content.rfind("\"a\""), "duplicate".into(), None);
+        assert_eq!(d.location, Some((2, 16)));
+        assert!(d.to_string().contains("2 |   \"ids\": [\"a\", \"a\"]\n  |                ^"));
+        assert_eq!(Diagnostic::at(content, None, "x".into(), None).to_string(), "x");
+    }
+
+    #[test]
+    fn caret_under_column() {
+        let d = diagnose("{\n  \"a\": [1, 2 }\n}");
+        assert_eq!(d.location, Some((2, 12)));
+        assert!(d.to_string().contains("2 |   \"a\": [1, 2 }\n  |            ^"));
+    }
+
+    #[test]
+    fn unquoted_key_with_dash() {
+        let d = diagnose("{\n  project-name: \"x\"\n}");
+        assert_eq!(d.hint.as_deref(), Some("unquoted keys may only contain letters, digits, _ and $; quote it as \"project-name\""));
+    }
+
+    #[test]
+    fn missing_comma_between_entries() {
+        let d = diagnose("{\n  \"a\": \"x\"\n  \"b\": \"y\"\n}");
+        assert_eq!(d.hint.as_deref(), Some("a comma is probably missing before this entry"));
+    }
+
+    #[test]
+    fn curly_quotes() {
+        let d = diagnose("{ \"a\": \u{201c}x\u{201d} }");
+        assert!(d.hint.unwrap().contains("curly quotes"));
+    }
+
+    #[test]
+    fn serde_errors_keep_their_message() {
+        let d = diagnose("{ \"project\": { \"name\": \"x\" } }");
+        assert_eq!(d.message, "missing field `description`");
+        assert_eq!(d.hint.as_deref(), Some("`ralph schema` prints every required field"));
+    }
+
+    #[test]
+    fn unknown_status_lists_values() {
+        let d = diagnose(
+            r#"{ "project": { "name": "p", "description": "d" },
+                "verification": { "commands": [], "runAfterEachFeature": true },
+                "features": [{ "id": "f", "category": "c", "description": "d", "steps": [], "status": "done" }],
+                "completion": { "allFeaturesComplete": true, "allVerificationsPassing": true, "marker": "X" } }"#,
+        );
+        assert!(d.message.starts_with("unknown variant `done`"));
+        assert!(d.hint.unwrap().contains("in-progress"));
+    }
+}
@@ -0,0 +1,111 @@
+//! Configuration digest: the inputs that determine a run, hashed so runs can be correlated later.
+
+use crate::config::Args;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigDigest {
+    pub ralph_version: String,
+    /// Agent backend and its CLI version, e.g. `claude 1.0.0`
+    pub agent_version: String,
+    pub prd_sha256: String,
+    pub prompt_sha256: String,
+    /// Resolved command-line flags, including defaults; secrets are redacted
+    pub flags: serde_json::Value,
+    /// SHA-256 over all of the above
+    pub hash: String,
+}
+
+impl ConfigDigest {
+    /// `prompt` is the raw system prompt template: the custom file or the built-in one.
+    pub fn new(args: &Args, agent_version: &str, prd: &str, prompt: &str) -> Result<Self> {
+        let mut digest = Self {
+            ralph_version: env!("CARGO_PKG_VERSION").to_string(),
+            agent_version: agent_version.to_string(),
+            prd_sha256: sha256_hex(prd.as_bytes()),
+            prompt_sha256: sha256_hex(prompt.as_bytes()),
+            flags: serde_json::to_value(args).context("Failed to serialize flags")?,
+            hash: String::new(),
+        };
+        // serde_json objects are key-sorted, so this serialization is stable
+        digest.hash = sha256_hex(&serde_json::to_vec(&digest)?);
+        Ok(digest)
+    }
+
+    #[must_use]
+    pub fn short_hash(&self) -> &str {
+        &self.hash[..self.hash.len().min(12)]
+    }
+}
+
+#[must_use]
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn parse_args(args: &[&str]) -> Args {
+        Args::try_parse_from(std::iter::once("ralph").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn sha256_of_empty_input() {
+        assert_eq!(
+            sha256_hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn same_inputs_same_hash() {
+        let a = ConfigDigest::new(&parse_args(&[]), "1.0.0", "prd", "prompt").unwrap();
+        let b = ConfigDigest::new(&parse_args(&[]), "1.0.0", "prd", "prompt").unwrap();
+        assert_eq!(a.hash, b.hash);
+        assert_eq!(a.short_hash().len(), 12);
+    }
+
+    #[test]
+    fn any_input_changes_hash() {
+        let base = ConfigDigest::new(&parse_args(&[]), "1.0.0", "prd", "prompt").unwrap();
+        let variants = [
+            ConfigDigest::new(&parse_args(&["-m", "3"]), "1.0.0", "prd", "prompt").unwrap(),
+            ConfigDigest::new(&parse_args(&[]), "1.0.1", "prd", "prompt").unwrap(),
+            ConfigDigest::new(&parse_args(&[]), "1.0.0", "prd2", "prompt").unwrap(),
+            ConfigDigest::new(&parse_args(&[]), "1.0.0", "prd", "prompt2").unwrap(),
+        ];
+        for v in variants {
+            assert_ne!(v.hash, base.hash);
+        }
+    }
+
+    #[test]
+    fn flags_include_defaults_and_redact_webhook() {
+        let d = ConfigDigest::new(
+            &parse_args(&["--webhook", "https://hooks.example.com/secret"]),
+            "1.0.0",
+            "prd",
+            "prompt",
+        )
+        .unwrap();
+        assert_eq!(d.flags["max_iterations"], 10);
+        assert_eq!(d.flags["webhook"], "<redacted>");
+        assert!(d.flags.get("init").is_none());
+    }
+
+    #[test]
+    fn notify_urls_are_redacted() {
+        let d = ConfigDigest::new(&parse_args(&["--notify", "slack:https://hooks.slack.com/secret"]), "1.0.0", "prd", "prompt")
+            .unwrap();
+        assert_eq!(d.flags["notify"], serde_json::json!(["slack:<redacted>"]));
+    }
+}
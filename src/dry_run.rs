@@ -1,14 +1,17 @@
-use crate::{config::Args, git, output, prd::Prd};
+use crate::{config::Args, environment, git, output, prd::{Prd, VerifyCommand}, sandbox::Sandbox, verification};
 use anyhow::Result;
-use std::process::Command;
+use futures::StreamExt;
+use std::path::Path;
+use std::time::Duration;
 
-pub fn run(args: &Args, prd: &Prd) -> Result<()> {
+pub async fn run(args: &Args, prd: &Prd, project_dir: &Path) -> Result<()> {
     output::section("Dry Run Mode");
 
     output::header("PRD Summary");
     output::log(&format!("Project: {}", prd.project.name));
     output::log(&format!("PRD file: {}", args.prd.display()));
-    println!();
+    output::log(&format!("Project dir: {}", project_dir.display()));
+    output::blank();
 
     let counts = prd.status_counts();
     let total = prd.features.len();
@@ -18,10 +21,10 @@ pub fn run(args: &Args, prd: &Prd) -> Result<()> {
     output::log(&format!("  In-progress: {}", counts.in_progress));
     output::log(&format!("  Complete:    {}", counts.complete));
     output::log(&format!("  Blocked:     {}", counts.blocked));
-    println!();
+    output::blank();
 
     output::header("Git Status");
-    if let Some(status) = git::get_git_status() {
+    if let Some(status) = git::get_git_status(project_dir) {
         output::log(&format!("Branch: {}", status.branch));
         output::log(&format!(
             "Uncommitted changes: {}",
@@ -33,28 +36,11 @@ pub fn run(args: &Args, prd: &Prd) -> Result<()> {
     } else {
         output::warn("Not a git repository");
     }
-    println!();
+    output::blank();
 
     output::header("Verification Commands");
-    let mut all_passed = true;
-    for cmd in &prd.verification.commands {
-        let result = Command::new("sh").args(["-c", &cmd.command]).output();
-
-        match result {
-            Ok(output) if output.status.success() => {
-                output::success(&format!("{}: PASS", cmd.name));
-            }
-            Ok(_) => {
-                output::error(&format!("{}: FAIL", cmd.name));
-                all_passed = false;
-            }
-            Err(e) => {
-                output::error(&format!("{}: ERROR ({})", cmd.name, e));
-                all_passed = false;
-            }
-        }
-    }
-    println!();
+    let all_passed = verify_all(args, prd, project_dir).await;
+    output::blank();
 
     output::separator();
     if all_passed {
@@ -66,3 +52,49 @@ pub fn run(args: &Args, prd: &Prd) -> Result<()> {
 
     Ok(())
 }
+
+/// Runs the PRD's verification commands, `--jobs` at a time, printing PASS or FAIL for each
+/// as it finishes. Also used by `ralph watch`.
+pub async fn verify_all(args: &Args, prd: &Prd, project_dir: &Path) -> bool {
+    let env = match environment::resolve(args, prd) {
+        Ok(env) => env,
+        Err(e) => {
+            output::error(&format!("Environment: {e:#}"));
+            return false;
+        }
+    };
+    let mut all_passed = true;
+    let sandbox = Sandbox::from_args(args, &env);
+    let timeout = Duration::from_secs(args.timeout);
+    // Statuses print as commands finish, which with --jobs needn't be PRD order
+    let checks: Vec<_> =
+        prd.verification.commands.iter().map(|cmd| verify(cmd, &sandbox, project_dir, timeout)).collect();
+    let mut finished = futures::stream::iter(checks).buffer_unordered(args.jobs.max(1));
+    while let Some((cmd, outcome, attempts)) = finished.next().await {
+        let tries = if attempts > 1 { format!(" after {attempts} attempts") } else { String::new() };
+        match outcome {
+            Ok(true) => {
+                output::success(&format!("{}: PASS{tries}", cmd.name));
+            }
+            Ok(false) => {
+                output::error(&format!("{}: FAIL{tries}", cmd.name));
+                all_passed = false;
+            }
+            Err(e) => {
+                output::error(&format!("{}: ERROR ({e:#})", cmd.name));
+                all_passed = false;
+            }
+        }
+    }
+    all_passed
+}
+
+async fn verify<'a>(
+    cmd: &'a VerifyCommand,
+    sandbox: &Sandbox,
+    project_dir: &Path,
+    timeout: Duration,
+) -> (&'a VerifyCommand, Result<bool>, u32) {
+    let (outcome, attempts) = verification::run_with_retries(cmd, sandbox, project_dir, timeout).await;
+    (cmd, outcome, attempts)
+}
@@ -0,0 +1,209 @@
+//! Variables set for the agent and verification commands, so API keys and test settings
+//! don't depend on the shell ralph runs in: `.env` files (the PRD's `envFile`, then
+//! `--env-file`), the PRD's `env`, then `--env`, each overriding the ones before.
+
+use crate::{config::Args, prd::Prd, validation};
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// One `KEY=VALUE` line of a `.env` file.
+#[derive(Debug, PartialEq, Eq)]
+struct Entry {
+    key: String,
+    value: String,
+    /// False for single-quoted values, which are taken literally
+    expand: bool,
+}
+
+/// Names a shell would accept: letters, digits, and `_`, not starting with a digit.
+pub fn validate_name(name: &str) -> Result<()> {
+    let valid = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        bail!("'{name}' is not a valid environment variable name");
+    }
+    Ok(())
+}
+
+/// `KEY=VALUE` lines; blank lines, `#` comments, and an `export ` prefix are allowed.
+/// Double-quoted values may use `\n`, `\"`, and `\\`; unquoted ones end at ` #`.
+fn parse_dotenv(content: &str) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for (at, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            bail!("line {}: expected KEY=VALUE", at + 1);
+        };
+        let key = key.trim();
+        validate_name(key).with_context(|| format!("line {}", at + 1))?;
+        let value = value.trim();
+        let entry = if let Some(quoted) = value.strip_prefix('\'') {
+            let Some(end) = quoted.find('\'') else {
+                bail!("line {}: unterminated quote", at + 1);
+            };
+            Entry { key: key.to_string(), value: quoted[..end].to_string(), expand: false }
+        } else if let Some(quoted) = value.strip_prefix('"') {
+            Entry { key: key.to_string(), value: unescape(quoted).with_context(|| format!("line {}", at + 1))?, expand: true }
+        } else {
+            let value = value.split_once(" #").map_or(value, |(v, _)| v).trim_end();
+            Entry { key: key.to_string(), value: value.to_string(), expand: true }
+        };
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// The inside of a double-quoted value, up to its closing quote.
+fn unescape(quoted: &str) -> Result<String> {
+    let mut value = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Ok(value),
+            '\\' => match chars.next() {
+                Some('n') => value.push('\n'),
+                Some(other) => value.push(other),
+                None => break,
+            },
+            c => value.push(c),
+        }
+    }
+    bail!("unterminated quote")
+}
+
+/// Replaces each `${NAME}` with its value in `vars`, else in ralph's environment.
+/// A name set in neither is an error rather than silently empty.
+pub fn expand(value: &str, vars: &BTreeMap<String, String>) -> Result<String> {
+    let mut expanded = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let Some(len) = rest[start + 2..].find('}') else {
+            bail!("unterminated ${{ in '{value}'");
+        };
+        let name = &rest[start + 2..start + 2 + len];
+        let resolved = vars.get(name).cloned().or_else(|| std::env::var(name).ok());
+        let Some(resolved) = resolved else {
+            bail!("${{{name}}} is not set");
+        };
+        expanded.push_str(&resolved);
+        rest = &rest[start + 3 + len..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+fn load_file(path: &Path, vars: &mut BTreeMap<String, String>) -> Result<()> {
+    let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    for entry in parse_dotenv(&content).with_context(|| format!("Failed to parse {}", path.display()))? {
+        let value = if entry.expand {
+            expand(&entry.value, vars).with_context(|| format!("{} in {}", entry.key, path.display()))?
+        } else {
+            entry.value
+        };
+        vars.insert(entry.key, value);
+    }
+    Ok(())
+}
+
+/// The variables for this run. `${NAME}` in the PRD's `env` sees the `.env` files, and in
+/// `--env` the PRD's `env` too, before ralph's own environment.
+pub fn resolve(args: &Args, prd: &Prd) -> Result<BTreeMap<String, String>> {
+    let mut vars = BTreeMap::new();
+    let prd_file = prd.project.env_file.as_ref().map(|path| validation::prd_dir(&args.prd).join(path));
+    for path in prd_file.iter().chain(&args.env_file) {
+        load_file(path, &mut vars)?;
+    }
+    let seen = vars.clone();
+    for (key, value) in &prd.project.env {
+        validate_name(key)?;
+        vars.insert(key.clone(), expand(value, &seen).with_context(|| format!("{key} in the PRD's env"))?);
+    }
+    let seen = vars.clone();
+    for (key, value) in &args.env {
+        vars.insert(key.clone(), expand(value, &seen).with_context(|| format!("--env {key}"))?);
+    }
+    Ok(vars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::PrdBuilder;
+    use clap::Parser;
+
+    fn vars(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn parses_dotenv_lines() {
+        let content = r#"
+# comment
+export API_URL=http://localhost:8080 # local
+TOKEN="a \"quoted\"\nvalue"
+LITERAL='${NOT_EXPANDED}'
+EMPTY=
+"#;
+        let entries = parse_dotenv(content).unwrap();
+        let pairs: Vec<(&str, &str, bool)> =
+            entries.iter().map(|e| (e.key.as_str(), e.value.as_str(), e.expand)).collect();
+        assert_eq!(
+            pairs,
+            [
+                ("API_URL", "http://localhost:8080", true),
+                ("TOKEN", "a \"quoted\"\nvalue", true),
+                ("LITERAL", "${NOT_EXPANDED}", false),
+                ("EMPTY", "", true),
+            ]
+        );
+        for bad in ["NO_EQUALS", "1BAD=x", "OPEN=\"x", "OPEN='x"] {
+            assert!(parse_dotenv(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn expands_from_vars_then_the_environment() {
+        let known = vars(&[("HOST", "db"), ("PATH", "shadowed")]);
+        assert_eq!(expand("postgres://${HOST}:5432", &known).unwrap(), "postgres://db:5432");
+        assert_eq!(expand("${PATH}", &known).unwrap(), "shadowed");
+        assert_eq!(expand("$HOST and $", &known).unwrap(), "$HOST and $");
+        assert!(std::env::var("HOME").is_ok_and(|home| expand("${HOME}/x", &known).unwrap() == format!("{home}/x")));
+        assert!(expand("${RALPH_TEST_UNSET_VARIABLE}", &known).unwrap_err().to_string().contains("is not set"));
+        assert!(expand("${HOST", &known).is_err());
+    }
+
+    #[test]
+    fn later_sources_override_earlier_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(".env"), "DB_HOST=localhost\nDB_PORT=5432\nMODE=file\n").unwrap();
+        std::fs::write(dir.path().join("ci.env"), "DB_HOST=ci-db\n").unwrap();
+        let mut prd = PrdBuilder::new().build();
+        prd.project.env_file = Some(".env".into());
+        prd.project.env.insert("DATABASE_URL".into(), "postgres://${DB_HOST}:${DB_PORT}".into());
+        prd.project.env.insert("MODE".into(), "prd".into());
+
+        let prd_path = dir.path().join("prd.jsonc").display().to_string();
+        let env_file = dir.path().join("ci.env").display().to_string();
+        let args = Args::try_parse_from(["ralph", "-p", &prd_path, "--env-file", &env_file, "--env", "MODE=${MODE}-flag"])
+            .unwrap();
+        let resolved = resolve(&args, &prd).unwrap();
+        assert_eq!(
+            resolved,
+            vars(&[
+                ("DATABASE_URL", "postgres://ci-db:5432"),
+                ("DB_HOST", "ci-db"),
+                ("DB_PORT", "5432"),
+                ("MODE", "prd-flag")
+            ])
+        );
+
+        prd.project.env.insert("bad name".into(), "x".into());
+        assert!(resolve(&args, &prd).is_err());
+    }
+}
@@ -0,0 +1,243 @@
+//! Pre-flight projection of the cost and duration of finishing the PRD.
+//!
+//! Iterations per feature come from the feature's `estimate`, else from the history of
+//! completed features in the iteration artifacts, else a default. Cost per iteration is
+//! the historical average when artifacts record cost, else a token guess priced from
+//! the model table.
+
+use crate::{
+    artifact::IterationArtifact,
+    config::Args,
+    output,
+    prd::{Prd, Status},
+    validation,
+};
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::io::{BufRead, IsTerminal, Write};
+use std::time::Duration;
+
+const DEFAULT_ITERATIONS_PER_FEATURE: f64 = 2.0;
+const DEFAULT_ITERATION_SECS: f64 = 600.0;
+/// Rough per-iteration token usage for an agent reading the PRD, progress, and code
+const INPUT_TOKENS_PER_ITERATION: f64 = 150_000.0;
+const OUTPUT_TOKENS_PER_ITERATION: f64 = 8_000.0;
+
+/// List prices in USD per million input/output tokens.
+const PRICING: [(&str, f64, f64); 3] = [
+    ("opus", 5.0, 25.0),
+    ("sonnet", 3.0, 15.0),
+    ("haiku", 1.0, 5.0),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Estimate {
+    pub remaining_features: usize,
+    pub iterations: f64,
+    pub cost_usd: f64,
+    pub duration_secs: f64,
+    /// Whether per-iteration cost came from recorded history rather than the pricing table
+    pub cost_from_history: bool,
+}
+
+#[derive(Debug, Default)]
+struct History {
+    iterations_per_feature: Option<f64>,
+    cost_per_iteration: Option<f64>,
+    secs_per_iteration: Option<f64>,
+}
+
+/// Price per iteration for a model name containing `opus`, `sonnet`, or `haiku`.
+pub fn model_cost_per_iteration(model: &str) -> Result<f64> {
+    let model = model.to_ascii_lowercase();
+    let Some((_, input, output)) = PRICING.iter().find(|(name, _, _)| model.contains(name)) else {
+        bail!(
+            "Unknown pricing model '{model}' (expected one of: {})",
+            PRICING.map(|(n, _, _)| n).join(", ")
+        );
+    };
+    Ok((INPUT_TOKENS_PER_ITERATION * input + OUTPUT_TOKENS_PER_ITERATION * output) / 1_000_000.0)
+}
+
+pub fn estimate(prd: &Prd, artifacts: &[IterationArtifact], model: &str, delay_secs: u64) -> Result<Estimate> {
+    let history = history(prd, artifacts);
+    let default_iterations = history
+        .iterations_per_feature
+        .unwrap_or(DEFAULT_ITERATIONS_PER_FEATURE);
+
+    let remaining: Vec<_> = prd
+        .features
+        .iter()
+        .filter(|f| matches!(f.status, Status::Pending | Status::InProgress))
+        .collect();
+    let iterations: f64 = remaining
+        .iter()
+        .map(|f| f.estimate.map_or(default_iterations, f64::from))
+        .sum();
+
+    let (cost_per_iteration, cost_from_history) = match history.cost_per_iteration {
+        Some(cost) => (cost, true),
+        None => (model_cost_per_iteration(model)?, false),
+    };
+    let secs_per_iteration = history.secs_per_iteration.unwrap_or(DEFAULT_ITERATION_SECS) + delay_secs as f64;
+
+    Ok(Estimate {
+        remaining_features: remaining.len(),
+        iterations,
+        cost_usd: iterations * cost_per_iteration,
+        duration_secs: iterations * secs_per_iteration,
+        cost_from_history,
+    })
+}
+
+/// Estimates the PRD at `args.prd` using the run history in its `.ralph/logs`, and prints it.
+pub fn run(args: &Args) -> Result<Estimate> {
+    let prd = Prd::load(&args.prd)?;
+    let logs_dir = validation::prd_dir(&args.prd).join(".ralph").join("logs");
+    let artifacts = IterationArtifact::load_all(&logs_dir);
+    let estimate = estimate(&prd, &artifacts, &args.pricing_model, args.delay)?;
+
+    output::header("Estimate");
+    output::log(&format!("Remaining features: {}", estimate.remaining_features));
+    output::log(&format!("Projected iterations: {:.1}", estimate.iterations));
+    output::log(&format!(
+        "Projected cost: ${:.2} ({})",
+        estimate.cost_usd,
+        if estimate.cost_from_history {
+            format!("from {} recorded iterations", artifacts.iter().filter(|a| a.cost_usd.is_some()).count())
+        } else {
+            format!("{} list prices, rough token guess", args.pricing_model)
+        }
+    ));
+    output::log(&format!(
+        "Projected duration: {}",
+        output::format_duration(Duration::from_secs_f64(estimate.duration_secs))
+    ));
+    if let Some(budget) = args.budget {
+        output::log(&format!("Budget: ${budget:.2}"));
+    }
+    Ok(estimate)
+}
+
+/// Asks the operator to confirm a run projected to exceed `budget`.
+/// Non-interactive sessions can't confirm, so they fail instead.
+pub fn confirm_budget(estimate: &Estimate, budget: f64) -> Result<()> {
+    if estimate.cost_usd <= budget {
+        return Ok(());
+    }
+    let message = format!(
+        "Projected cost ${:.2} exceeds budget ${budget:.2}",
+        estimate.cost_usd
+    );
+    if !std::io::stdin().is_terminal() {
+        bail!("{message}; raise --budget to proceed");
+    }
+
+    output::warn(&format!("{message}. Continue? [y/N]"));
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    if !matches!(answer.trim(), "y" | "Y" | "yes") {
+        bail!("Run cancelled: {message}");
+    }
+    Ok(())
+}
+
+fn history(prd: &Prd, artifacts: &[IterationArtifact]) -> History {
+    let complete: Vec<&str> = prd
+        .features
+        .iter()
+        .filter(|f| f.status == Status::Complete)
+        .map(|f| f.id.as_str())
+        .collect();
+
+    let mut per_feature: HashMap<&str, u32> = HashMap::new();
+    for a in artifacts {
+        if let Some(id) = a.feature_id.as_deref().filter(|id| complete.contains(id)) {
+            *per_feature.entry(id).or_default() += 1;
+        }
+    }
+
+    let costs: Vec<f64> = artifacts.iter().filter_map(|a| a.cost_usd).collect();
+    History {
+        iterations_per_feature: (!per_feature.is_empty())
+            .then(|| per_feature.values().sum::<u32>() as f64 / per_feature.len() as f64),
+        cost_per_iteration: (!costs.is_empty()).then(|| costs.iter().sum::<f64>() / costs.len() as f64),
+        secs_per_iteration: (!artifacts.is_empty()).then(|| {
+            artifacts.iter().map(|a| a.duration_secs as f64).sum::<f64>() / artifacts.len() as f64
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prd::Feature;
+    use crate::testing::{ArtifactBuilder, FeatureBuilder, PrdBuilder};
+
+    fn feature(id: &str, status: Status, estimate: Option<u32>) -> Feature {
+        let feature = FeatureBuilder::new(id).status(status);
+        match estimate {
+            Some(n) => feature.estimate(n).build(),
+            None => feature.build(),
+        }
+    }
+
+    fn artifact(feature_id: &str, duration_secs: u64, cost_usd: Option<f64>) -> IterationArtifact {
+        let artifact = ArtifactBuilder::new(1).feature(feature_id).duration_secs(duration_secs);
+        match cost_usd {
+            Some(cost) => artifact.cost_usd(cost).build(),
+            None => artifact.build(),
+        }
+    }
+
+    fn prd(features: Vec<Feature>) -> Prd {
+        PrdBuilder::new().features(features).build()
+    }
+
+    #[test]
+    fn within_budget_needs_no_confirmation() {
+        let e = estimate(&prd(vec![feature("a", Status::Pending, Some(1))]), &[], "haiku", 0).unwrap();
+        assert!(confirm_budget(&e, 100.0).is_ok());
+    }
+
+    #[test]
+    fn prices_known_models() {
+        assert!((model_cost_per_iteration("sonnet").unwrap() - 0.57).abs() < 1e-9);
+        assert!(model_cost_per_iteration("claude-opus-4").unwrap() > model_cost_per_iteration("sonnet").unwrap());
+        assert!(model_cost_per_iteration("gpt").is_err());
+    }
+
+    #[test]
+    fn defaults_without_history() {
+        let prd = prd(vec![
+            feature("a", Status::Complete, None),
+            feature("b", Status::Pending, None),
+            feature("c", Status::InProgress, Some(5)),
+            feature("d", Status::Blocked, None),
+        ]);
+        let e = estimate(&prd, &[], "haiku", 0).unwrap();
+        assert_eq!(e.remaining_features, 2);
+        assert_eq!(e.iterations, 7.0);
+        assert_eq!(e.duration_secs, 7.0 * DEFAULT_ITERATION_SECS);
+        assert!(!e.cost_from_history);
+    }
+
+    #[test]
+    fn uses_history_of_completed_features() {
+        let prd = prd(vec![
+            feature("a", Status::Complete, None),
+            feature("b", Status::Pending, None),
+        ]);
+        let artifacts = [
+            artifact("a", 100, Some(1.0)),
+            artifact("a", 300, Some(3.0)),
+            artifact("a", 200, None),
+        ];
+        let e = estimate(&prd, &artifacts, "sonnet", 10).unwrap();
+        assert_eq!(e.iterations, 3.0);
+        assert!(e.cost_from_history);
+        assert_eq!(e.cost_usd, 6.0);
+        assert_eq!(e.duration_secs, 3.0 * 210.0);
+    }
+}
@@ -0,0 +1,192 @@
+//! Run events: what happens during a run, published to every sink. The terminal (or JSON
+//! output), webhooks, desktop notifications, and the `--prd-source` tracker always get them;
+//! `--events-file` and embedders add more sinks.
+
+use crate::{artifact::IterationArtifact, desktop, output, prd::Status, source, verification::CommandResult, webhook};
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// The `--events-file` sink, replaced by each [`configure`].
+static FILE: RwLock<Option<Arc<JsonlFile>>> = RwLock::new(None);
+/// Sinks added with [`add_sink`].
+static SINKS: RwLock<Vec<Arc<dyn EventSink>>> = RwLock::new(Vec::new());
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunEvent {
+    SessionStarted { project: String },
+    /// `feature` is set in `--parallel` mode
+    IterationStarted { iteration: u32, feature: Option<String>, log_path: PathBuf },
+    /// A line of the agent's output
+    AgentOutputChunk { line: String, stderr: bool },
+    VerificationResult { iteration: u32, results: Vec<CommandResult> },
+    IterationFinished(Box<IterationArtifact>),
+    FeatureStatusChanged { iteration: u32, feature: String, status: Status },
+    MilestoneReached { iteration: u32, phase: String },
+    /// The operator needs to step in; `action` says what to do
+    ActionRequired { message: String, action: String },
+    Completed { iterations: u32 },
+    Stopped { iterations: u32 },
+    Failed { iterations: u32, reason: String },
+}
+
+impl RunEvent {
+    /// The `type` of the event's JSON record.
+    #[must_use]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::SessionStarted { .. } => "session_start",
+            Self::IterationStarted { .. } => "iteration_start",
+            Self::AgentOutputChunk { .. } => "agent_output",
+            Self::VerificationResult { .. } => "verification",
+            Self::IterationFinished(_) => "iteration_end",
+            Self::FeatureStatusChanged { .. } => "feature_status",
+            Self::MilestoneReached { .. } => "milestone",
+            Self::ActionRequired { .. } => "action_required",
+            Self::Completed { .. } => "complete",
+            Self::Stopped { .. } => "stopped",
+            Self::Failed { .. } => "failed",
+        }
+    }
+
+    /// The rest of the JSON record.
+    #[must_use]
+    pub fn fields(&self) -> Value {
+        match self {
+            Self::SessionStarted { project } => json!({ "project": project }),
+            Self::IterationStarted { iteration, feature, log_path } => {
+                json!({ "iteration": iteration, "feature": feature, "logPath": log_path })
+            }
+            Self::AgentOutputChunk { line, stderr: false } => json!({ "line": line }),
+            Self::AgentOutputChunk { line, stderr: true } => json!({ "line": line, "stream": "stderr" }),
+            Self::VerificationResult { iteration, results } => json!({ "iteration": iteration, "results": results }),
+            Self::IterationFinished(artifact) => serde_json::to_value(artifact).unwrap_or_default(),
+            Self::FeatureStatusChanged { iteration, feature, status } => {
+                json!({ "iteration": iteration, "feature": feature, "status": status })
+            }
+            Self::MilestoneReached { iteration, phase } => json!({ "iteration": iteration, "phase": phase }),
+            Self::ActionRequired { message, action } => json!({ "message": message, "action": action }),
+            Self::Completed { iterations } | Self::Stopped { iterations } => json!({ "iterations": iterations }),
+            Self::Failed { iterations, reason } => json!({ "iterations": iterations, "reason": reason }),
+        }
+    }
+}
+
+pub trait EventSink: Send + Sync {
+    fn handle(&self, event: &RunEvent);
+}
+
+/// Publishes `event` to every sink.
+pub fn emit(event: &RunEvent) {
+    output::ConsoleSink.handle(event);
+    webhook::WebhookSink.handle(event);
+    desktop::DesktopSink.handle(event);
+    source::SourceSink.handle(event);
+    let file = FILE.read().unwrap_or_else(|e| e.into_inner()).clone();
+    if let Some(file) = file {
+        file.handle(event);
+    }
+    let sinks = SINKS.read().unwrap_or_else(|e| e.into_inner()).clone();
+    for sink in sinks {
+        sink.handle(event);
+    }
+}
+
+/// Shorthand for [`RunEvent::ActionRequired`].
+pub fn action_required(message: &str, action: &str) {
+    emit(&RunEvent::ActionRequired {
+        message: message.to_string(),
+        action: action.to_string(),
+    });
+}
+
+/// Sets the `--events-file` sink, or removes it.
+pub fn configure(events_file: Option<&Path>) -> Result<()> {
+    let file = events_file.map(JsonlFile::open).transpose()?.map(Arc::new);
+    *FILE.write().unwrap_or_else(|e| e.into_inner()) = file;
+    Ok(())
+}
+
+/// Sends events to `sink` too, until it's removed.
+pub fn add_sink(sink: Arc<dyn EventSink>) {
+    SINKS.write().unwrap_or_else(|e| e.into_inner()).push(sink);
+}
+
+pub fn remove_sink(sink: &Arc<dyn EventSink>) {
+    SINKS.write().unwrap_or_else(|e| e.into_inner()).retain(|s| !Arc::ptr_eq(s, sink));
+}
+
+/// Appends each event as a JSON output record, one per line.
+pub struct JsonlFile {
+    file: Mutex<std::fs::File>,
+}
+
+impl JsonlFile {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open events file: {}", path.display()))?;
+        Ok(Self { file: Mutex::new(file) })
+    }
+}
+
+impl EventSink for JsonlFile {
+    fn handle(&self, event: &RunEvent) {
+        let line = output::record(event.kind(), event.fields());
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = writeln!(file, "{line}") {
+            output::warn(&format!("Failed to write events file: {e}"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_keep_the_json_output_shape() {
+        let event = RunEvent::AgentOutputChunk { line: "hi".into(), stderr: true };
+        assert_eq!(event.kind(), "agent_output");
+        assert_eq!(event.fields(), json!({ "line": "hi", "stream": "stderr" }));
+        let event = RunEvent::FeatureStatusChanged { iteration: 2, feature: "a".into(), status: Status::Complete };
+        assert_eq!(event.fields(), json!({ "iteration": 2, "feature": "a", "status": "complete" }));
+    }
+
+    #[test]
+    fn jsonl_file_appends_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        let sink = JsonlFile::open(&path).unwrap();
+        sink.handle(&RunEvent::Completed { iterations: 3 });
+        sink.handle(&RunEvent::Stopped { iterations: 4 });
+        let lines: Vec<Value> =
+            std::fs::read_to_string(&path).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!((lines[0]["type"].as_str(), lines[0]["iterations"].as_u64()), (Some("complete"), Some(3)));
+        assert!(lines[1]["timestamp"].is_string());
+    }
+
+    #[test]
+    fn added_sinks_get_events() {
+        struct Count(Mutex<u32>);
+        impl EventSink for Count {
+            fn handle(&self, event: &RunEvent) {
+                if matches!(event, RunEvent::MilestoneReached { phase, .. } if phase == "sinks-test") {
+                    *self.0.lock().unwrap() += 1;
+                }
+            }
+        }
+        let count = Arc::new(Count(Mutex::new(0)));
+        add_sink(count.clone());
+        emit(&RunEvent::MilestoneReached { iteration: 1, phase: "sinks-test".into() });
+        let sink: Arc<dyn EventSink> = count.clone();
+        remove_sink(&sink);
+        emit(&RunEvent::MilestoneReached { iteration: 1, phase: "sinks-test".into() });
+        assert_eq!(*count.0.lock().unwrap(), 1);
+    }
+}
@@ -1,4 +1,6 @@
-use anyhow::{Context, Result};
+use crate::run_id;
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 #[derive(Debug, Clone)]
@@ -7,28 +9,34 @@ pub struct GitStatus {
     pub uncommitted_changes: usize,
 }
 
+fn git(dir: &Path) -> Command {
+    let mut cmd = Command::new("git");
+    cmd.current_dir(dir);
+    cmd
+}
+
 #[must_use]
-pub fn get_git_status() -> Option<GitStatus> {
-    if !is_git_repo() {
+pub fn get_git_status(dir: &Path) -> Option<GitStatus> {
+    if !is_git_repo(dir) {
         return None;
     }
     Some(GitStatus {
-        branch: current_branch().unwrap_or_else(|_| "unknown".into()),
-        uncommitted_changes: uncommitted_changes_count().unwrap_or(0),
+        branch: current_branch(dir).unwrap_or_else(|_| "unknown".into()),
+        uncommitted_changes: uncommitted_changes_count(dir).unwrap_or(0),
     })
 }
 
 #[must_use]
-pub fn is_git_repo() -> bool {
-    Command::new("git")
+pub fn is_git_repo(dir: &Path) -> bool {
+    git(dir)
         .args(["rev-parse", "--git-dir"])
         .output()
         .map(|o| o.status.success())
         .unwrap_or(false)
 }
 
-pub fn current_branch() -> Result<String> {
-    let output = Command::new("git")
+pub fn current_branch(dir: &Path) -> Result<String> {
+    let output = git(dir)
         .args(["branch", "--show-current"])
         .output()
         .context("Failed to get current branch")?;
@@ -40,8 +48,8 @@ pub(crate) fn parse_branch_output(output: &str) -> String {
     output.trim().to_string()
 }
 
-pub fn uncommitted_changes_count() -> Result<usize> {
-    let output = Command::new("git")
+pub fn uncommitted_changes_count(dir: &Path) -> Result<usize> {
+    let output = git(dir)
         .args(["status", "--porcelain"])
         .output()
         .context("Failed to get git status")?;
@@ -53,8 +61,8 @@ pub(crate) fn parse_porcelain_status(output: &str) -> usize {
     output.lines().filter(|l| !l.is_empty()).count()
 }
 
-pub fn recent_commits(count: usize) -> Result<Vec<String>> {
-    let output = Command::new("git")
+pub fn recent_commits(dir: &Path, count: usize) -> Result<Vec<String>> {
+    let output = git(dir)
         .args(["log", "--oneline", &format!("-{count}")])
         .output()
         .context("Failed to get git log")?;
@@ -66,8 +74,8 @@ pub(crate) fn parse_log_output(output: &str) -> Vec<String> {
     output.lines().map(String::from).collect()
 }
 
-pub fn diff_file_from_head(path: &str) -> Result<String> {
-    let output = Command::new("git")
+pub fn diff_file_from_head(dir: &Path, path: &str) -> Result<String> {
+    let output = git(dir)
         .args(["diff", "HEAD", "--", path])
         .output()
         .context("Failed to get git diff from HEAD")?;
@@ -75,6 +83,297 @@ pub fn diff_file_from_head(path: &str) -> Result<String> {
     Ok(String::from_utf8_lossy(&output.stdout).to_string())
 }
 
+/// A repository state the agent must not be started in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepoProblem {
+    Merge,
+    Rebase,
+    CherryPick,
+    Revert,
+    DetachedHead,
+}
+
+impl RepoProblem {
+    #[must_use]
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::Merge => "merge in progress",
+            Self::Rebase => "rebase in progress",
+            Self::CherryPick => "cherry-pick in progress",
+            Self::Revert => "revert in progress",
+            Self::DetachedHead => "detached HEAD",
+        }
+    }
+}
+
+/// Marker paths inside the git dir for each in-progress operation.
+const OPERATION_MARKERS: [(&str, RepoProblem); 5] = [
+    ("MERGE_HEAD", RepoProblem::Merge),
+    ("rebase-merge", RepoProblem::Rebase),
+    ("rebase-apply", RepoProblem::Rebase),
+    ("CHERRY_PICK_HEAD", RepoProblem::CherryPick),
+    ("REVERT_HEAD", RepoProblem::Revert),
+];
+
+/// In-progress operations first, then detached HEAD, so recovery can abort before checking out.
+pub fn repo_problems(dir: &Path) -> Result<Vec<RepoProblem>> {
+    let mut problems = Vec::new();
+    for (marker, problem) in OPERATION_MARKERS {
+        if git_path(dir, marker)?.exists() && !problems.contains(&problem) {
+            problems.push(problem);
+        }
+    }
+    // A rebase detaches HEAD by design; aborting it restores the branch
+    if !problems.contains(&RepoProblem::Rebase) && is_detached(dir)? {
+        problems.push(RepoProblem::DetachedHead);
+    }
+    Ok(problems)
+}
+
+fn git_path(dir: &Path, name: &str) -> Result<std::path::PathBuf> {
+    let output = git(dir)
+        .args(["rev-parse", "--git-path", name])
+        .output()
+        .context("Failed to resolve git path")?;
+    Ok(dir.join(String::from_utf8_lossy(&output.stdout).trim()))
+}
+
+fn is_detached(dir: &Path) -> Result<bool> {
+    let status = git(dir)
+        .args(["symbolic-ref", "-q", "HEAD"])
+        .output()
+        .context("Failed to read HEAD")?
+        .status;
+    Ok(!status.success())
+}
+
+/// Aborts the in-progress operation, or checks out `branch` for a detached HEAD.
+pub fn recover(dir: &Path, problem: RepoProblem, branch: Option<&str>) -> Result<()> {
+    let args: Vec<&str> = match problem {
+        RepoProblem::Merge => vec!["merge", "--abort"],
+        RepoProblem::Rebase => vec!["rebase", "--abort"],
+        RepoProblem::CherryPick => vec!["cherry-pick", "--abort"],
+        RepoProblem::Revert => vec!["revert", "--abort"],
+        RepoProblem::DetachedHead => match branch {
+            Some(branch) => vec!["checkout", branch],
+            None => bail!("Cannot recover from detached HEAD: the run did not start on a branch"),
+        },
+    };
+    run_checked(dir, &args).map(|_| ())
+}
+
+/// Contents of `path` (relative to `dir`) at HEAD, or None if it isn't tracked there.
+pub fn show_file_from_head(dir: &Path, path: &str) -> Result<Option<String>> {
+    let output = git(dir)
+        .args(["show", &format!("HEAD:./{path}")])
+        .output()
+        .context("Failed to read file from HEAD")?;
+
+    if !output.status.success() {
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8_lossy(&output.stdout).to_string()))
+}
+
+/// Runs git in `dir`, returning trimmed stdout or an error carrying stderr.
+fn run_checked(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = git(dir)
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to run git {}", args.join(" ")))?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// `git show --stat` of HEAD: its hash, subject, and changed files.
+/// The latest commit's subject, changed files, and patch.
+pub fn last_commit_diff(dir: &Path) -> Result<String> {
+    run_checked(dir, &["show", "--stat", "--patch", "--format=%h %s", "HEAD"])
+}
+
+pub fn head_sha(dir: &Path) -> Result<String> {
+    run_checked(dir, &["rev-parse", "HEAD"])
+}
+
+/// Full messages of the commits after `sha` on the current branch, newest first.
+pub fn commit_messages_since(dir: &Path, sha: &str) -> Result<Vec<String>> {
+    let log = run_checked(dir, &["log", "--format=%B%x00", &format!("{sha}..HEAD")])?;
+    Ok(log.split('\0').map(str::trim).filter(|m| !m.is_empty()).map(String::from).collect())
+}
+
+/// A commit object holding the uncommitted changes to tracked files, without touching
+/// the working tree or the stash list; `None` when there are none.
+pub fn stash_create(dir: &Path) -> Result<Option<String>> {
+    run_checked(dir, &["stash", "create"]).map(|sha| Some(sha).filter(|s| !s.is_empty()))
+}
+
+/// Reapplies changes captured by [`stash_create`].
+pub fn stash_apply(dir: &Path, sha: &str) -> Result<()> {
+    run_checked(dir, &["stash", "apply", "-q", sha]).map(|_| ())
+}
+
+/// Discards commits after `sha` and every change to tracked files. Untracked files stay.
+pub fn reset_hard(dir: &Path, sha: &str) -> Result<()> {
+    run_checked(dir, &["reset", "-q", "--hard", sha]).map(|_| ())
+}
+
+/// Changes since commit `sha`, committed or not, followed by the untracked files, leaving
+/// out the `exclude` paths (relative to `dir`).
+pub fn diff_since(dir: &Path, sha: &str, exclude: &[&str]) -> Result<String> {
+    let excludes: Vec<String> = exclude.iter().map(|path| format!(":(exclude){path}")).collect();
+    let mut diff = vec!["diff", sha, "--", "."];
+    diff.extend(excludes.iter().map(String::as_str));
+    let mut untracked = vec!["ls-files", "--others", "--exclude-standard", "--", "."];
+    untracked.extend(excludes.iter().map(String::as_str));
+    Ok(format!("{}\n{}", run_checked(dir, &diff)?, run_checked(dir, &untracked)?))
+}
+
+/// Files under `dir` (relative to it) that differ from commit `sha`, committed or not, plus
+/// the untracked ones. Without a commit, e.g. on an unborn branch, every file is listed.
+pub fn changed_files_since(dir: &Path, sha: Option<&str>) -> Result<Vec<String>> {
+    let listed = match sha {
+        Some(sha) => run_checked(dir, &["diff", "--name-only", "--no-renames", "--relative", "-z", sha])?,
+        None => run_checked(dir, &["ls-files", "-z"])?,
+    };
+    let untracked = run_checked(dir, &["ls-files", "-z", "--others", "--exclude-standard"])?;
+    let mut files: Vec<String> =
+        listed.split('\0').chain(untracked.split('\0')).filter(|f| !f.is_empty()).map(String::from).collect();
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// Unified diff of the working tree under `dir` against commit `sha`, with renames detected.
+pub fn diff_from(dir: &Path, sha: &str) -> Result<String> {
+    run_checked(dir, &["diff", "-M", "--relative", sha])
+}
+
+/// Root of the working tree containing `dir`.
+pub fn toplevel(dir: &Path) -> Result<PathBuf> {
+    run_checked(dir, &["rev-parse", "--show-toplevel"]).map(PathBuf::from)
+}
+
+/// Checks out a new `branch` from HEAD into a linked worktree at `path`.
+pub fn add_worktree(dir: &Path, path: &Path, branch: &str) -> Result<()> {
+    let path = path.to_string_lossy();
+    run_checked(dir, &["worktree", "add", "-q", "-b", branch, &path, "HEAD"]).map(|_| ())
+}
+
+/// Removes a linked worktree, discarding anything uncommitted in it.
+pub fn remove_worktree(dir: &Path, path: &Path) -> Result<()> {
+    let path = path.to_string_lossy();
+    run_checked(dir, &["worktree", "remove", "--force", &path]).map(|_| ())
+}
+
+/// Number of commits on `branch` that HEAD doesn't have.
+pub fn commits_ahead(dir: &Path, branch: &str) -> Result<usize> {
+    run_checked(dir, &["rev-list", "--count", &format!("HEAD..{branch}")])?
+        .parse()
+        .context("Unexpected git rev-list output")
+}
+
+/// Merges `branch` into the current branch. A conflicting merge is aborted and returns false.
+pub fn merge_branch(dir: &Path, branch: &str) -> Result<bool> {
+    let message = run_id::with_trailer(&format!("Merge {branch}"));
+    if run_checked(dir, &["merge", "--no-ff", "-q", "-m", &message, branch]).is_ok() {
+        return Ok(true);
+    }
+    if repo_problems(dir)?.contains(&RepoProblem::Merge) {
+        recover(dir, RepoProblem::Merge, None)?;
+        return Ok(false);
+    }
+    bail!("git merge {branch} failed")
+}
+
+/// Checks out `branch`, creating it at HEAD if it doesn't exist. Returns whether it was created.
+pub fn checkout_branch(dir: &Path, branch: &str) -> Result<bool> {
+    let exists = run_checked(dir, &["rev-parse", "--verify", "-q", &format!("refs/heads/{branch}")]).is_ok();
+    if exists {
+        run_checked(dir, &["checkout", "-q", branch])?;
+    } else {
+        run_checked(dir, &["checkout", "-q", "-b", branch])?;
+    }
+    Ok(!exists)
+}
+
+/// `name` with anything but ASCII letters, digits, `-`, `_` and `.` replaced by `-`, for branch names.
+#[must_use]
+pub fn branch_slug(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '-' }).collect()
+}
+
+pub fn delete_branch(dir: &Path, branch: &str) -> Result<()> {
+    run_checked(dir, &["branch", "-q", "-D", branch]).map(|_| ())
+}
+
+/// Commits the working-tree contents of `path` alone; anything else staged stays staged.
+/// Like merges, the commit carries the run id trailer.
+pub fn commit_file(dir: &Path, path: &str, message: &str) -> Result<()> {
+    let message = run_id::with_trailer(message);
+    run_checked(dir, &["commit", "-q", "--only", "-m", &message, "--", path]).map(|_| ())
+}
+
+/// Paths (relative to the repository root) under `dir` with uncommitted changes, untracked
+/// files included, leaving out the `exclude` paths (relative to `dir`).
+pub fn changed_paths(dir: &Path, exclude: &[&str]) -> Result<Vec<String>> {
+    let excludes: Vec<String> = exclude.iter().map(|path| format!(":(exclude){path}")).collect();
+    let mut args = vec!["status", "--porcelain", "-z", "--untracked-files=all", "--", "."];
+    args.extend(excludes.iter().map(String::as_str));
+    // Not `run_checked`: trimming would eat the status column of the first entry
+    let output = git(dir).args(&args).output().context("Failed to get git status")?;
+    if !output.status.success() {
+        bail!("git status failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(parse_porcelain_paths(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Paths in `git status --porcelain -z` output; a rename or copy gives both its paths.
+pub(crate) fn parse_porcelain_paths(output: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+    let mut entries = output.split('\0').filter(|e| !e.is_empty());
+    while let Some(entry) = entries.next() {
+        let Some(path) = entry.get(3..) else {
+            continue;
+        };
+        paths.push(path.to_string());
+        if entry.starts_with(['R', 'C']) {
+            paths.extend(entries.next().map(String::from));
+        }
+    }
+    paths
+}
+
+/// Stages and commits exactly `paths` (relative to the repository root), additions and
+/// deletions included; anything else staged stays staged. Carries the run id trailer.
+pub fn commit_paths(dir: &Path, paths: &[String], message: &str) -> Result<()> {
+    let root = toplevel(dir)?;
+    let message = run_id::with_trailer(message);
+    let mut add = vec!["add", "-A", "--"];
+    add.extend(paths.iter().map(String::as_str));
+    run_checked(&root, &add)?;
+    let mut commit = vec!["commit", "-q", "--only", "-m", &message, "--"];
+    commit.extend(paths.iter().map(String::as_str));
+    run_checked(&root, &commit).map(|_| ())
+}
+
+/// `paths` that lie under `dir`, relative to it with forward slashes, for pathspecs.
+#[must_use]
+pub fn relative_pathspecs(dir: &Path, paths: &[&Path]) -> Vec<String> {
+    let absolute = |path: &Path| std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let root = absolute(dir);
+    paths
+        .iter()
+        .filter_map(|path| absolute(path).strip_prefix(&root).ok().map(|p| p.to_string_lossy().replace('\\', "/")))
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -170,6 +469,16 @@ mod tests {
         assert_eq!(parse_porcelain_status(output), 1);
     }
 
+    #[test]
+    fn parse_porcelain_paths_lists_every_path() {
+        let output = " M src/main.rs\0?? new file.txt\0R  renamed.rs\0original.rs\0 D gone.txt\0";
+        assert_eq!(
+            parse_porcelain_paths(output),
+            ["src/main.rs", "new file.txt", "renamed.rs", "original.rs", "gone.txt"]
+        );
+        assert!(parse_porcelain_paths("").is_empty());
+    }
+
     #[test]
     fn parse_porcelain_status_with_spaces_in_filename() {
         let output = " M \"file with spaces.txt\"\n";
@@ -212,6 +521,103 @@ mod tests {
         assert_eq!(parse_log_output(output), vec!["abc1234 Commit message"]);
     }
 
+    mod repo_state_tests {
+        use super::*;
+
+        fn run(dir: &Path, args: &[&str]) {
+            let output = git(dir).args(args).output().unwrap();
+            assert!(output.status.success(), "git {args:?}: {}", String::from_utf8_lossy(&output.stderr));
+        }
+
+        fn repo() -> tempfile::TempDir {
+            let dir = tempfile::tempdir().unwrap();
+            run(dir.path(), &["init", "-q", "-b", "main"]);
+            run(dir.path(), &["config", "user.email", "t@example.com"]);
+            run(dir.path(), &["config", "user.name", "t"]);
+            std::fs::write(dir.path().join("f"), "base\n").unwrap();
+            run(dir.path(), &["add", "f"]);
+            run(dir.path(), &["commit", "-qm", "base"]);
+            dir
+        }
+
+        fn conflicting_branch(dir: &Path) {
+            run(dir, &["checkout", "-qb", "other"]);
+            std::fs::write(dir.join("f"), "other\n").unwrap();
+            run(dir, &["commit", "-qam", "other"]);
+            run(dir, &["checkout", "-q", "main"]);
+            std::fs::write(dir.join("f"), "main\n").unwrap();
+            run(dir, &["commit", "-qam", "main"]);
+        }
+
+        #[test]
+        fn clean_branch_has_no_problems() {
+            let dir = repo();
+            assert!(repo_problems(dir.path()).unwrap().is_empty());
+        }
+
+        #[test]
+        fn detects_and_recovers_detached_head() {
+            let dir = repo();
+            run(dir.path(), &["checkout", "-q", "--detach"]);
+            assert_eq!(repo_problems(dir.path()).unwrap(), vec![RepoProblem::DetachedHead]);
+
+            assert!(recover(dir.path(), RepoProblem::DetachedHead, None).is_err());
+            recover(dir.path(), RepoProblem::DetachedHead, Some("main")).unwrap();
+            assert!(repo_problems(dir.path()).unwrap().is_empty());
+            assert_eq!(current_branch(dir.path()).unwrap(), "main");
+        }
+
+        #[test]
+        fn detects_and_aborts_merge() {
+            let dir = repo();
+            conflicting_branch(dir.path());
+            let _ = git(dir.path()).args(["merge", "other"]).output().unwrap();
+            assert_eq!(repo_problems(dir.path()).unwrap(), vec![RepoProblem::Merge]);
+
+            recover(dir.path(), RepoProblem::Merge, Some("main")).unwrap();
+            assert!(repo_problems(dir.path()).unwrap().is_empty());
+        }
+
+        #[test]
+        fn rebase_is_reported_without_detached_head() {
+            let dir = repo();
+            conflicting_branch(dir.path());
+            let _ = git(dir.path()).args(["rebase", "other"]).output().unwrap();
+            assert_eq!(repo_problems(dir.path()).unwrap(), vec![RepoProblem::Rebase]);
+
+            recover(dir.path(), RepoProblem::Rebase, Some("main")).unwrap();
+            assert!(repo_problems(dir.path()).unwrap().is_empty());
+            assert_eq!(current_branch(dir.path()).unwrap(), "main");
+        }
+
+        #[test]
+        fn worktree_branch_merges_back() {
+            let dir = repo();
+            let wt = dir.path().join("wt");
+            add_worktree(dir.path(), &wt, "ralph/feat-1").unwrap();
+            std::fs::write(wt.join("g"), "new\n").unwrap();
+            run(&wt, &["add", "g"]);
+            run(&wt, &["commit", "-qm", "feat-1"]);
+
+            assert_eq!(commits_ahead(dir.path(), "ralph/feat-1").unwrap(), 1);
+            assert!(merge_branch(dir.path(), "ralph/feat-1").unwrap());
+            assert!(dir.path().join("g").exists());
+
+            remove_worktree(dir.path(), &wt).unwrap();
+            delete_branch(dir.path(), "ralph/feat-1").unwrap();
+            assert!(!wt.exists());
+        }
+
+        #[test]
+        fn conflicting_merge_is_aborted() {
+            let dir = repo();
+            conflicting_branch(dir.path());
+            assert!(!merge_branch(dir.path(), "other").unwrap());
+            assert!(repo_problems(dir.path()).unwrap().is_empty());
+            assert_eq!(std::fs::read_to_string(dir.path().join("f")).unwrap(), "main\n");
+        }
+    }
+
     #[test]
     fn parse_log_output_commit_with_special_chars() {
         let output = "abc1234 fix: handle edge case (JIRA-123)\n";
@@ -0,0 +1,215 @@
+//! GitHub Issues source (`--prd-source github:owner/repo?label=ralph`): open issues become
+//! features, and completed ones are labelled and commented on. `GITHUB_TOKEN` authenticates
+//! the requests; updates need it.
+
+use crate::source::{self, PrdSource, Ticket};
+use anyhow::{bail, Context, Result};
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use serde_json::json;
+use std::fmt;
+use std::str::FromStr;
+
+/// Label added to an issue whose feature is complete; labelled issues are read back as complete.
+pub const COMPLETE_LABEL: &str = "ralph-complete";
+const PER_PAGE: usize = 100;
+const DEFAULT_API: &str = "https://api.github.com";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GithubSource {
+    pub owner: String,
+    pub repo: String,
+    /// Only issues with this label (or comma-separated labels)
+    pub label: Option<String>,
+}
+
+/// `github:<owner>/<repo>`, optionally followed by `?label=<label>`.
+impl FromStr for GithubSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let expected = "expected github:<owner>/<repo>[?label=<label>]";
+        let (path, options) = source::parse_spec(s, "github", expected)?;
+        let Some((owner, repo)) = path.split_once('/').filter(|(o, r)| !o.is_empty() && !r.is_empty()) else {
+            bail!(expected);
+        };
+        let mut label = None;
+        for (key, value) in options {
+            match key {
+                "label" => label = Some(value.to_string()),
+                _ => bail!("unknown option '{key}': {expected}"),
+            }
+        }
+        Ok(Self {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            label,
+        })
+    }
+}
+
+impl fmt::Display for GithubSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "github:{}/{}", self.owner, self.repo)?;
+        if let Some(ref label) = self.label {
+            write!(f, "?label={label}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Issue {
+    number: u64,
+    title: String,
+    body: Option<String>,
+    html_url: String,
+    #[serde(default)]
+    labels: Vec<Label>,
+    /// Set when the "issue" is a pull request
+    pull_request: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Label {
+    name: String,
+}
+
+impl From<Issue> for Ticket {
+    fn from(issue: Issue) -> Self {
+        let labels: Vec<String> = issue.labels.into_iter().map(|l| l.name).collect();
+        Self {
+            key: issue.number.to_string(),
+            title: issue.title,
+            body: issue.body.unwrap_or_default(),
+            url: issue.html_url,
+            complete: labels.iter().any(|l| l == COMPLETE_LABEL),
+            labels,
+        }
+    }
+}
+
+impl PrdSource for GithubSource {
+    fn id_prefix(&self) -> &'static str {
+        "issue-"
+    }
+
+    fn project_name(&self) -> String {
+        self.repo.clone()
+    }
+
+    fn filter_labels(&self) -> Vec<&str> {
+        let mut labels: Vec<&str> = self.label.as_deref().map(|l| l.split(',').collect()).unwrap_or_default();
+        labels.push(COMPLETE_LABEL);
+        labels
+    }
+
+    /// The open issues (with the label), pull requests left out.
+    fn fetch(&self) -> BoxFuture<'_, Result<Vec<Ticket>>> {
+        Box::pin(async move {
+            let url = format!("{}/repos/{}/{}/issues", api_url(), self.owner, self.repo);
+            let client = reqwest::Client::new();
+            let mut issues = Vec::new();
+            for page in 1.. {
+                let mut query = vec![
+                    ("state", "open".to_string()),
+                    ("per_page", PER_PAGE.to_string()),
+                    ("page", page.to_string()),
+                ];
+                if let Some(ref label) = self.label {
+                    query.push(("labels", label.clone()));
+                }
+                let response = request(client.get(&url).query(&query))
+                    .send()
+                    .await
+                    .with_context(|| format!("Failed to list issues of {}/{}", self.owner, self.repo))?;
+                if !response.status().is_success() {
+                    bail!("Failed to list issues of {}/{}: GitHub returned {}", self.owner, self.repo, response.status());
+                }
+                let batch: Vec<Issue> = response.json().await.context("Failed to parse GitHub issues")?;
+                let last = batch.len() < PER_PAGE;
+                issues.extend(batch.into_iter().filter(|i| i.pull_request.is_none()));
+                if last {
+                    break;
+                }
+            }
+            issues.sort_by_key(|i| i.number);
+            Ok(issues.into_iter().map(Ticket::from).collect())
+        })
+    }
+
+    /// Adds [`COMPLETE_LABEL`] and the comment; the issue stays open.
+    fn complete<'a>(&'a self, key: &'a str, comment: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            if std::env::var_os("GITHUB_TOKEN").is_none() {
+                bail!("GITHUB_TOKEN is not set");
+            }
+            let issue_url = format!("{}/repos/{}/{}/issues/{key}", api_url(), self.owner, self.repo);
+            let client = reqwest::Client::new();
+            let requests = [
+                ("labels", json!({ "labels": [COMPLETE_LABEL] })),
+                ("comments", json!({ "body": comment })),
+            ];
+            for (path, body) in requests {
+                let response = request(client.post(format!("{issue_url}/{path}")).json(&body)).send().await?;
+                if !response.status().is_success() {
+                    bail!("GitHub returned {} for the {path} request", response.status());
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+/// `GITHUB_API_URL` (set on GitHub Enterprise runners), else api.github.com.
+fn api_url() -> String {
+    std::env::var("GITHUB_API_URL").unwrap_or_else(|_| DEFAULT_API.to_string()).trim_end_matches('/').to_string()
+}
+
+fn request(builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    let builder = builder
+        .header("User-Agent", concat!("ralph-rs/", env!("CARGO_PKG_VERSION")))
+        .header("Accept", "application/vnd.github+json");
+    match std::env::var("GITHUB_TOKEN") {
+        Ok(token) => builder.bearer_auth(token),
+        Err(_) => builder,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(label: Option<&str>) -> GithubSource {
+        GithubSource {
+            owner: "acme".into(),
+            repo: "shop".into(),
+            label: label.map(String::from),
+        }
+    }
+
+    #[test]
+    fn parses_source() {
+        assert_eq!("github:acme/shop?label=ralph".parse::<GithubSource>().unwrap(), source(Some("ralph")));
+        assert_eq!("github:acme/shop".parse::<GithubSource>().unwrap(), source(None));
+        assert_eq!(source(Some("ralph")).to_string(), "github:acme/shop?label=ralph");
+        for bad in ["gitlab:acme/shop", "github:acme", "github:/shop", "github:acme/shop?state=all"] {
+            assert!(bad.parse::<GithubSource>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn issues_become_tickets() {
+        let issue: Issue = serde_json::from_value(json!({
+            "number": 7,
+            "title": "Log in",
+            "body": null,
+            "html_url": "https://github.com/acme/shop/issues/7",
+            "labels": [{ "name": "ralph" }, { "name": COMPLETE_LABEL }],
+        }))
+        .unwrap();
+        let ticket = Ticket::from(issue);
+        assert_eq!((ticket.key.as_str(), ticket.body.as_str(), ticket.complete), ("7", "", true));
+        assert_eq!(source(Some("ralph")).filter_labels(), ["ralph", COMPLETE_LABEL]);
+    }
+}
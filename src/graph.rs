@@ -0,0 +1,248 @@
+//! `ralph graph`: renders features, their `dependsOn` edges, and `phase` groups as DOT or Mermaid.
+
+use crate::{
+    config::GraphFormat,
+    prd::{Feature, Prd, Status},
+};
+use std::collections::HashSet;
+use std::fmt::Write;
+
+#[must_use]
+pub fn render(prd: &Prd, format: GraphFormat) -> String {
+    match format {
+        GraphFormat::Dot => render_dot(prd),
+        GraphFormat::Mermaid => render_mermaid(prd),
+    }
+}
+
+fn status_color(status: Status) -> &'static str {
+    match status {
+        Status::Pending => "#e0e0e0",
+        Status::InProgress => "#fff3b0",
+        Status::Complete => "#b7e4c7",
+        Status::Blocked => "#f4a6a6",
+    }
+}
+
+/// Phases in first-appearance order, followed by features without a phase.
+fn phases(prd: &Prd) -> Vec<(Option<&str>, Vec<&Feature>)> {
+    let mut groups: Vec<(Option<&str>, Vec<&Feature>)> = Vec::new();
+    for feature in &prd.features {
+        let phase = feature.phase.as_deref();
+        match groups.iter_mut().find(|(p, _)| *p == phase) {
+            Some((_, features)) => features.push(feature),
+            None => groups.push((phase, vec![feature])),
+        }
+    }
+    groups.sort_by_key(|(p, _)| p.is_none());
+    groups
+}
+
+/// Dependencies naming no feature in the PRD, drawn as placeholder nodes.
+fn unknown_dependencies(prd: &Prd) -> Vec<&str> {
+    let ids: HashSet<&str> = prd.features.iter().map(|f| f.id.as_str()).collect();
+    let mut seen = HashSet::new();
+    prd.features
+        .iter()
+        .flat_map(|f| f.depends_on.iter().map(String::as_str))
+        .filter(|d| !ids.contains(d) && seen.insert(*d))
+        .collect()
+}
+
+/// True if `dep` isn't complete yet, i.e. the dependent feature is still waiting on it.
+fn is_waiting(prd: &Prd, dep: &str) -> bool {
+    prd.features
+        .iter()
+        .find(|f| f.id == dep)
+        .is_none_or(|f| f.status != Status::Complete)
+}
+
+fn render_dot(prd: &Prd) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "digraph {} {{", dot_quote(&prd.project.name));
+    let _ = writeln!(out, "  rankdir=LR;");
+    let _ = writeln!(out, "  node [shape=box, style=\"rounded,filled\"];");
+
+    for (i, (phase, features)) in phases(prd).iter().enumerate() {
+        let indent = if phase.is_some() { "    " } else { "  " };
+        if let Some(phase) = phase {
+            let _ = writeln!(out, "  subgraph cluster_{i} {{");
+            let _ = writeln!(out, "    label={};", dot_quote(phase));
+        }
+        for f in features {
+            let _ = writeln!(
+                out,
+                "{indent}{} [label={}, fillcolor=\"{}\"];",
+                dot_quote(&f.id),
+                dot_quote(&format!("{}\n{}", f.id, f.status.as_str())),
+                status_color(f.status)
+            );
+        }
+        if phase.is_some() {
+            let _ = writeln!(out, "  }}");
+        }
+    }
+
+    for dep in unknown_dependencies(prd) {
+        let _ = writeln!(
+            out,
+            "  {} [label={}, style=dashed];",
+            dot_quote(dep),
+            dot_quote(&format!("{dep}\nunknown"))
+        );
+    }
+
+    for f in &prd.features {
+        for dep in &f.depends_on {
+            let style = if is_waiting(prd, dep) { " [style=dashed]" } else { "" };
+            let _ = writeln!(out, "  {} -> {}{style};", dot_quote(dep), dot_quote(&f.id));
+        }
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+fn dot_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+fn render_mermaid(prd: &Prd) -> String {
+    let mut out = String::from("flowchart LR\n");
+
+    for (i, (phase, features)) in phases(prd).iter().enumerate() {
+        let indent = if phase.is_some() { "    " } else { "  " };
+        if let Some(phase) = phase {
+            let _ = writeln!(out, "  subgraph phase{i}[\"{}\"]", mermaid_text(phase));
+        }
+        for f in features {
+            let _ = writeln!(
+                out,
+                "{indent}{}[\"{}<br/>{}\"]:::{}",
+                mermaid_id(&f.id),
+                mermaid_text(&f.id),
+                f.status.as_str(),
+                mermaid_class(f.status)
+            );
+        }
+        if phase.is_some() {
+            let _ = writeln!(out, "  end");
+        }
+    }
+
+    for dep in unknown_dependencies(prd) {
+        let _ = writeln!(out, "  {}[\"{}<br/>unknown\"]:::unknown", mermaid_id(dep), mermaid_text(dep));
+    }
+
+    for f in &prd.features {
+        for dep in &f.depends_on {
+            let arrow = if is_waiting(prd, dep) { "-.->" } else { "-->" };
+            let _ = writeln!(out, "  {} {arrow} {}", mermaid_id(dep), mermaid_id(&f.id));
+        }
+    }
+
+    for status in [Status::Pending, Status::InProgress, Status::Complete, Status::Blocked] {
+        let _ = writeln!(out, "  classDef {} fill:{}", mermaid_class(status), status_color(status));
+    }
+    let _ = writeln!(out, "  classDef unknown stroke-dasharray:4");
+    out
+}
+
+/// Mermaid node ids must be plain identifiers; the real id is shown in the label.
+fn mermaid_id(id: &str) -> String {
+    let safe: String = id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect();
+    format!("f_{safe}")
+}
+
+fn mermaid_text(s: &str) -> String {
+    s.replace('"', "#quot;")
+}
+
+fn mermaid_class(status: Status) -> &'static str {
+    match status {
+        Status::Pending => "pending",
+        Status::InProgress => "inprogress",
+        Status::Complete => "complete",
+        Status::Blocked => "blocked",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FeatureBuilder, PrdBuilder};
+
+    fn sample() -> Prd {
+        PrdBuilder::new()
+            .name("demo")
+            .feature(FeatureBuilder::new("schema").status(Status::Complete).phase("Foundation"))
+            .feature(
+                FeatureBuilder::new("api")
+                    .status(Status::InProgress)
+                    .phase("Foundation")
+                    .depends_on("schema"),
+            )
+            .feature(FeatureBuilder::new("ui").depends_on("api").depends_on("design"))
+            .build()
+    }
+
+    mod dot_tests {
+        use super::*;
+
+        #[test]
+        fn renders_nodes_with_status_colors() {
+            let out = render(&sample(), GraphFormat::Dot);
+            assert!(out.starts_with("digraph \"demo\" {"));
+            assert!(out.contains("\"schema\" [label=\"schema\\ncomplete\", fillcolor=\"#b7e4c7\"];"));
+            assert!(out.contains("\"ui\" [label=\"ui\\npending\", fillcolor=\"#e0e0e0\"];"));
+        }
+
+        #[test]
+        fn groups_phases_into_clusters() {
+            let out = render(&sample(), GraphFormat::Dot);
+            assert!(out.contains("subgraph cluster_0 {\n    label=\"Foundation\";"));
+            assert_eq!(out.matches("subgraph").count(), 1);
+        }
+
+        #[test]
+        fn dashes_edges_from_incomplete_dependencies() {
+            let out = render(&sample(), GraphFormat::Dot);
+            assert!(out.contains("\"schema\" -> \"api\";"));
+            assert!(out.contains("\"api\" -> \"ui\" [style=dashed];"));
+        }
+
+        #[test]
+        fn draws_unknown_dependencies() {
+            let out = render(&sample(), GraphFormat::Dot);
+            assert!(out.contains("\"design\" [label=\"design\\nunknown\", style=dashed];"));
+        }
+    }
+
+    mod mermaid_tests {
+        use super::*;
+
+        #[test]
+        fn renders_flowchart_with_classes() {
+            let out = render(&sample(), GraphFormat::Mermaid);
+            assert!(out.starts_with("flowchart LR\n"));
+            assert!(out.contains("f_api[\"api<br/>in-progress\"]:::inprogress"));
+            assert!(out.contains("classDef complete fill:#b7e4c7"));
+        }
+
+        #[test]
+        fn renders_phase_subgraph_and_edges() {
+            let out = render(&sample(), GraphFormat::Mermaid);
+            assert!(out.contains("subgraph phase0[\"Foundation\"]"));
+            assert!(out.contains("f_schema --> f_api"));
+            assert!(out.contains("f_design -.-> f_ui"));
+        }
+
+        #[test]
+        fn sanitizes_ids() {
+            assert_eq!(mermaid_id("feat-1.a"), "f_feat_1_a");
+        }
+    }
+}
@@ -0,0 +1,279 @@
+//! Run index: one JSON line per run in `.ralph/history/runs.jsonl`, completed with the run's
+//! outcome when it ends, and `ralph history` to compare runs.
+
+use crate::{
+    atomic,
+    config::{Args, HistoryAction},
+    digest::ConfigDigest,
+    output, validation,
+};
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RunRecord {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    pub started_at: String,
+    pub digest: ConfigDigest,
+    /// Absent while the run is going, or when it was killed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<RunOutcome>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RunOutcome {
+    pub ended_at: String,
+    /// `complete` (the loop saw the completion marker), `incomplete` (a limit or an interrupt ended it),
+    /// `stopped`, or `failed`
+    pub result: String,
+    /// All of the run's iterations, including those before a `--resume`
+    pub iterations: u32,
+    /// Since this part of the run started
+    #[serde(default)]
+    pub features_completed: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+}
+
+#[must_use]
+pub fn index_path(ralph_dir: &Path) -> PathBuf {
+    ralph_dir.join("history").join("runs.jsonl")
+}
+
+pub fn append(ralph_dir: &Path, record: &RunRecord) -> Result<()> {
+    let path = index_path(ralph_dir);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context("Failed to create .ralph/history directory")?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open run index: {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+/// Records `outcome` on the latest record of run `run_id`.
+pub fn finish(ralph_dir: &Path, run_id: &str, outcome: &RunOutcome) -> Result<()> {
+    let path = index_path(ralph_dir);
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read run index: {}", path.display()))?;
+    let mut lines: Vec<String> = content.lines().map(String::from).collect();
+    let latest = lines.iter().enumerate().rev().find_map(|(at, line)| {
+        serde_json::from_str::<RunRecord>(line).ok().filter(|r| r.run_id.as_deref() == Some(run_id)).map(|r| (at, r))
+    });
+    let Some((at, mut record)) = latest else {
+        bail!("Run '{run_id}' is not in {}", path.display());
+    };
+    record.outcome = Some(outcome.clone());
+    lines[at] = serde_json::to_string(&record)?;
+    atomic::write(&path, lines.join("\n") + "\n").with_context(|| format!("Failed to update run index: {}", path.display()))
+}
+
+/// Loads all records, oldest first. Unparseable lines are skipped.
+pub fn load(ralph_dir: &Path) -> Result<Vec<RunRecord>> {
+    let path = index_path(ralph_dir);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read run index: {}", path.display()))?;
+    Ok(content
+        .lines()
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect())
+}
+
+/// `ralph history`: one line per run, or the details of one with `show`.
+pub fn run(prd: &Path, action: Option<&HistoryAction>) -> Result<()> {
+    let ralph_dir = validation::prd_dir(prd).join(".ralph");
+    let records = load(&ralph_dir)?;
+    if records.is_empty() {
+        bail!("No runs in {}", index_path(&ralph_dir).display());
+    }
+    match action {
+        None => {
+            for record in &records {
+                output::log(&list_line(record));
+            }
+        }
+        Some(HistoryAction::Show { run_id }) => {
+            let parts: Vec<&RunRecord> = records.iter().filter(|r| r.run_id.as_deref() == Some(run_id)).collect();
+            if parts.is_empty() {
+                bail!("No run '{run_id}' in {}", index_path(&ralph_dir).display());
+            }
+            for (at, record) in parts.iter().enumerate() {
+                if at > 0 {
+                    output::blank();
+                }
+                for line in details(record) {
+                    output::log(&line);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn model(record: &RunRecord) -> &str {
+    record.digest.flags.get("model").and_then(serde_json::Value::as_str).unwrap_or("default model")
+}
+
+fn list_line(record: &RunRecord) -> String {
+    let mut line = format!("{} {}", record.run_id.as_deref().unwrap_or("-"), record.started_at);
+    match &record.outcome {
+        Some(o) => {
+            line.push_str(&format!(" {} ({} iterations, {} completed", o.result, o.iterations, o.features_completed.len()));
+            if let Some(cost) = o.cost_usd {
+                line.push_str(&format!(", ${cost:.2}"));
+            }
+            line.push(')');
+        }
+        None => line.push_str(" unfinished"),
+    }
+    format!("{line} {}, config {}", model(record), record.digest.short_hash())
+}
+
+fn details(record: &RunRecord) -> Vec<String> {
+    let digest = &record.digest;
+    let mut lines = vec![
+        format!("Run: {}", record.run_id.as_deref().unwrap_or("-")),
+        format!("Started: {}", record.started_at),
+    ];
+    match &record.outcome {
+        Some(o) => {
+            lines.push(format!("Ended: {}", o.ended_at));
+            lines.push(format!("Result: {}", o.result));
+            lines.push(format!("Iterations: {}", o.iterations));
+            let completed = if o.features_completed.is_empty() { "none".into() } else { o.features_completed.join(", ") };
+            lines.push(format!("Features completed: {completed}"));
+            if let Some(cost) = o.cost_usd {
+                lines.push(format!("Cost: ${cost:.2}"));
+            }
+        }
+        None => lines.push("Result: unfinished".into()),
+    }
+    lines.push(format!("Model: {}", model(record)));
+    lines.push(format!("Config digest: {} (ralph {}, {})", digest.hash, digest.ralph_version, digest.agent_version));
+    lines.push(format!("PRD: {}", digest.prd_sha256));
+    lines.push(format!("Prompt: {}", digest.prompt_sha256));
+    let changed = changed_flags(&digest.flags);
+    if !changed.is_empty() {
+        lines.push("Flags changed from their defaults:".into());
+        lines.extend(changed.into_iter().map(|(name, value)| format!("  {name} = {value}")));
+    }
+    lines
+}
+
+/// Flags recorded in a digest that differ from ralph's current defaults.
+fn changed_flags(flags: &serde_json::Value) -> Vec<(String, serde_json::Value)> {
+    let defaults = Args::try_parse_from(["ralph"]).ok().and_then(|args| serde_json::to_value(args).ok());
+    let Some(flags) = flags.as_object() else {
+        return Vec::new();
+    };
+    flags
+        .iter()
+        .filter(|(name, value)| defaults.as_ref().and_then(|d| d.get(name.as_str())) != Some(*value))
+        .map(|(name, value)| (name.replace('_', "-"), value.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(started_at: &str) -> RunRecord {
+        let args = Args::try_parse_from(["ralph"]).unwrap();
+        RunRecord {
+            run_id: None,
+            started_at: started_at.into(),
+            digest: ConfigDigest::new(&args, "1.0.0", "prd", "prompt").unwrap(),
+            outcome: None,
+        }
+    }
+
+    fn outcome(result: &str) -> RunOutcome {
+        RunOutcome {
+            ended_at: "2026-01-01T11:00:00+00:00".into(),
+            result: result.into(),
+            iterations: 4,
+            features_completed: vec!["auth".into(), "api".into()],
+            cost_usd: Some(1.5),
+        }
+    }
+
+    #[test]
+    fn load_missing_index_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(load(dir.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn append_then_load_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        append(dir.path(), &record("a")).unwrap();
+        append(dir.path(), &record("b")).unwrap();
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(index_path(dir.path()))
+            .unwrap()
+            .write_all(b"garbage\n")
+            .unwrap();
+
+        let records = load(dir.path()).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].started_at, "a");
+        assert_eq!(records[1], record("b"));
+    }
+
+    #[test]
+    fn finish_completes_the_latest_record_of_the_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let run = |id: &str, started_at: &str| RunRecord { run_id: Some(id.into()), ..record(started_at) };
+        append(dir.path(), &run("r1", "a")).unwrap();
+        append(dir.path(), &run("r2", "b")).unwrap();
+        // A resumed run appends a second record under the same id
+        append(dir.path(), &run("r1", "c")).unwrap();
+
+        finish(dir.path(), "r1", &outcome("complete")).unwrap();
+        let records = load(dir.path()).unwrap();
+        assert_eq!(records.iter().map(|r| r.outcome.is_some()).collect::<Vec<_>>(), [false, false, true]);
+        assert_eq!(records[2].outcome, Some(outcome("complete")));
+        assert!(finish(dir.path(), "r3", &outcome("failed")).is_err());
+    }
+
+    #[test]
+    fn list_line_shows_outcome_model_and_config() {
+        let mut r = RunRecord { run_id: Some("r1".into()), ..record("2026-01-01T10:00:00+00:00") };
+        let hash = r.digest.short_hash().to_string();
+        assert_eq!(list_line(&r), format!("r1 2026-01-01T10:00:00+00:00 unfinished default model, config {hash}"));
+        r.outcome = Some(outcome("complete"));
+        assert_eq!(
+            list_line(&r),
+            format!("r1 2026-01-01T10:00:00+00:00 complete (4 iterations, 2 completed, $1.50) default model, config {hash}")
+        );
+    }
+
+    #[test]
+    fn details_list_completed_features_and_changed_flags() {
+        let args = Args::try_parse_from(["ralph", "--model", "opus", "-m", "3"]).unwrap();
+        let r = RunRecord {
+            digest: ConfigDigest::new(&args, "1.0.0", "prd", "prompt").unwrap(),
+            outcome: Some(outcome("incomplete")),
+            ..record("a")
+        };
+        let details = details(&r);
+        assert!(details.contains(&"Result: incomplete".to_string()));
+        assert!(details.contains(&"Features completed: auth, api".to_string()));
+        assert!(details.contains(&"Model: opus".to_string()));
+        let flags = details.iter().position(|l| l == "Flags changed from their defaults:").unwrap();
+        assert_eq!(details[flags + 1..], ["  max-iterations = 3", "  model = \"opus\""]);
+    }
+}
@@ -2,12 +2,17 @@ use crate::{git, output, prd::Prd};
 use anyhow::Result;
 use std::path::Path;
 
-pub fn run_init_phase(prd: &Prd, prd_path: &Path, progress_path: &Path) -> Result<()> {
+pub fn run_init_phase(
+    prd: &Prd,
+    prd_path: &Path,
+    progress_path: &Path,
+    project_dir: &Path,
+) -> Result<()> {
     output::section("Phase 1: Initialization");
 
     // Step 1: Verify git repository
     output::log("Step 1: Checking git status...");
-    match git::get_git_status() {
+    match git::get_git_status(project_dir) {
         Some(status) if status.uncommitted_changes > 0 => {
             output::warn(&format!(
                 "Branch: {} ({} uncommitted changes)",
@@ -42,21 +47,21 @@ pub fn run_init_phase(prd: &Prd, prd_path: &Path, progress_path: &Path) -> Resul
     output::log(&format!("Progress file: {}", progress_path.display()));
 
     // Step 4: Recent git history
-    if git::is_git_repo() {
+    if git::is_git_repo(project_dir) {
         output::log("Step 4: Recent git history...");
-        println!();
-        if let Ok(commits) = git::recent_commits(5) {
+        output::blank();
+        if let Ok(commits) = git::recent_commits(project_dir, 5) {
             for commit in commits {
-                println!("  {commit}");
+                output::dim(&format!("  {commit}"));
             }
         }
-        println!();
+        output::blank();
     }
 
     output::separator();
     output::success("Initialization complete - ready for Ralph iteration");
     output::separator();
-    println!();
+    output::blank();
 
     Ok(())
 }
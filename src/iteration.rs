@@ -1,22 +1,105 @@
 use crate::{
-    analysis::{analyze_iteration_output, IterationResult, OutputAnalysisContext},
+    agent::AgentBackend,
+    analysis::{
+        self, analyze_audit_output, analyze_iteration_output, AuditVerdict, IterationResult,
+        OutputAnalysisContext, Patterns,
+    },
+    checkpoint::Checkpoint,
     claude::{self, ClaudeArgs},
-    config::Args,
-    git, output, prd, prompt, validation,
+    config::{Args, PrematureCompletion},
+    context,
+    events::{self, RunEvent},
+    git,
+    metrics::CostTracker,
+    output,
+    prd::{self, Status},
+    progress, prompt, recent_context,
+    resources::ResourceUsage,
+    retry, review,
+    sandbox::Sandbox,
+    serve::{LiveEvent, LiveFeed},
+    session::Transcripts,
+    simulate::Simulator,
+    snapshot::Snapshot,
+    timing::{Phase, Timings},
+    validation, verification,
 };
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use chrono::Local;
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tokio::time::Duration;
 use tokio_util::sync::CancellationToken;
 
+/// Changed files listed after an iteration in `--snapshot` mode.
+const CHANGED_FILES_SHOWN: usize = 20;
+
 pub struct IterationContext<'a> {
     pub args: &'a Args,
     pub prd: &'a prd::Prd,
     pub progress_path: &'a Path,
-    pub logs_dir: &'a Path,
+    pub log_path: &'a Path,
     pub completion_marker: &'a str,
     pub project_dir: &'a Path,
     pub prompt_path: Option<&'a Path>,
+    /// PRD path as shown to the agent, resolvable from `project_dir`
+    pub prompt_prd_path: &'a Path,
+    pub permission_mode: &'a str,
+    pub live: Option<&'a LiveFeed>,
+    pub simulator: Option<&'a Simulator>,
+    pub backend: &'a dyn AgentBackend,
+    /// Feature a `--parallel` worker is restricted to
+    pub assigned_feature: Option<&'a str>,
+    pub costs: &'a CostTracker,
+    pub timings: &'a Timings,
+    pub transcripts: &'a Transcripts,
+    /// Summary substituted for `{verification_history}`
+    pub verification_history: &'a str,
+    /// Raw `{recent_context}`, summarized first with `--summarize-context`
+    pub recent_context: &'a str,
+    pub patterns: &'a Patterns,
+    /// Variables for the agent and verification commands, from `environment::resolve`
+    pub env: &'a BTreeMap<String, String>,
+    /// What the last `--review` asked the agent to fix
+    pub review_feedback: Option<&'a str>,
+}
+
+/// `<run id>-iteration-<n>.log`, so a run's files are predictable from its id.
+#[must_use]
+pub fn log_path(logs_dir: &Path, run_id: &str, iteration: u32) -> PathBuf {
+    logs_dir.join(format!("{run_id}-iteration-{iteration}.log"))
+}
+
+/// PRD and progress paths as shown to the agent; relative to `working_dir`, where it runs, with
+/// `--relative-paths`.
+fn prompt_paths(ctx: &IterationContext<'_>, working_dir: &Path) -> (PathBuf, PathBuf) {
+    let absolute = |path: &Path| std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let shown = |path: &Path| {
+        if ctx.args.relative_paths {
+            PathBuf::from(prompt::relative_posix_path(&absolute(path), &absolute(working_dir)))
+        } else {
+            path.to_path_buf()
+        }
+    };
+    (shown(ctx.prompt_prd_path), shown(ctx.progress_path))
+}
+
+/// The prompt file for features of `category`: `--category-prompt`, else the PRD's
+/// `categoryPrompts`. None leaves `--prompt` or the built-in one.
+#[must_use]
+pub fn category_prompt(args: &Args, prd: &prd::Prd, category: &str) -> Option<PathBuf> {
+    args.category_prompt
+        .iter()
+        .rev()
+        .find(|(c, _)| c == category)
+        .map(|(_, path)| path.clone())
+        .or_else(|| prd.category_prompt(&args.prd, category))
+}
+
+fn report_resources(resources: &ResourceUsage) {
+    let idle = if resources.is_idle() { " - idle, likely waiting on the network" } else { "" };
+    output::dim(&format!("Agent resources: {}{idle}", resources.summary()));
 }
 
 pub async fn run(
@@ -25,34 +108,189 @@ pub async fn run(
     cancel_token: &CancellationToken,
 ) -> Result<IterationResult> {
     let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
-    output::log("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    output::rule();
     output::log(&format!("Iteration {iteration} - {timestamp}"));
-    output::log("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-    println!();
-
-    let log_filename = format!(
-        "{}-iteration-{}.log",
-        Local::now().format("%Y%m%d-%H%M%S"),
-        iteration
-    );
-    let log_path = ctx.logs_dir.join(log_filename);
-
-    let system_prompt = prompt::get_system_prompt(
-        ctx.prompt_path,
-        ctx.prd,
-        &ctx.args.prd,
-        ctx.progress_path,
-    )?;
+    output::rule();
+    output::blank();
+    events::emit(&RunEvent::IterationStarted {
+        iteration,
+        feature: ctx.assigned_feature.map(String::from),
+        log_path: ctx.log_path.to_path_buf(),
+    });
+    if let Some(live) = ctx.live {
+        live.send(LiveEvent::Iteration(iteration));
+    }
+
+    let prompt_started = Instant::now();
+    let feature = match ctx.assigned_feature {
+        Some(id) => ctx.prd.features.iter().find(|f| f.id == id),
+        None => ctx.prd.next_feature(),
+    };
+    let working_dir = feature.map_or_else(|| ctx.project_dir.to_path_buf(), |f| f.working_dir(ctx.project_dir));
+    if let Some(feature) = feature.filter(|_| !working_dir.is_dir()) {
+        bail!("Working directory of '{}' not found: {}", feature.id, working_dir.display());
+    }
+    let (prd_path, progress_path) = prompt_paths(ctx, &working_dir);
+    let category_prompt = feature.and_then(|f| category_prompt(ctx.args, ctx.prd, &f.category));
+    if let Some(ref path) = category_prompt {
+        output::dim(&format!("Prompt for '{}' features: {}", feature.map_or("", |f| &f.category), path.display()));
+    }
+    let template = prompt::template_source(category_prompt.as_deref().or(ctx.prompt_path))?;
+    let git_branch = git::current_branch(ctx.project_dir).unwrap_or_default();
+    // Only worth a model call if the template uses it, as `{recent_context}` or `{{ recent_context }}`
+    let recent_context = if template.contains("recent_context")
+        && ctx.args.summarize_context
+        && ctx.recent_context != recent_context::NONE_YET
+    {
+        summarize_context(iteration, ctx, cancel_token).await
+    } else {
+        ctx.recent_context.to_string()
+    };
+    let vars = prompt::PromptVars {
+        prd: ctx.prd,
+        prd_path: &prd_path,
+        progress_path: &progress_path,
+        verification_history: ctx.verification_history,
+        recent_context: &recent_context,
+        session: prompt::Session { iteration, feature, git_branch: &git_branch },
+    };
+    let mut system_prompt = prompt::render(&template, &vars)?;
+
+    if ctx.args.allow_agent_notes {
+        system_prompt.push('\n');
+        system_prompt.push_str(&prompt::agent_notes_section(&prd_path));
+    }
+
+    if ctx.args.auto_complete {
+        system_prompt.push('\n');
+        system_prompt.push_str(&prompt::auto_complete_section(&prd_path));
+    }
+
+    if let Some(feedback) = ctx.review_feedback {
+        system_prompt.push('\n');
+        system_prompt.push_str(&review::feedback_section(feedback));
+    }
+
+    let protected_patterns = protected_patterns(ctx);
+    if let Some(section) = prompt::protected_paths_section(&protected_patterns) {
+        system_prompt.push('\n');
+        system_prompt.push_str(&section);
+    }
+
+    if let Some(feature_id) = ctx.assigned_feature {
+        system_prompt.push('\n');
+        system_prompt.push_str(&prompt::assigned_feature_section(feature_id));
+    }
+
+    if let Some(section) = feature.and_then(prompt::workdir_section) {
+        system_prompt.push('\n');
+        system_prompt.push_str(&section);
+    }
+
+    if let Some(section) = feature.and_then(prompt::acceptance_criteria_section) {
+        system_prompt.push('\n');
+        system_prompt.push_str(&section);
+    }
+
+    if let Some(section) = prompt::phase_markers_section(ctx.prd) {
+        system_prompt.push('\n');
+        system_prompt.push_str(&section);
+    }
+
+    if ctx.args.pack_context {
+        if let Some(feature) = feature {
+            if let Some(packed) = context::pack(feature, &working_dir, ctx.args.context_budget)? {
+                output::dim(&format!(
+                    "Packed {} files ({} of {} bytes) for '{}'",
+                    packed.included.len(),
+                    packed.bytes,
+                    packed.budget,
+                    feature.id
+                ));
+                system_prompt.push('\n');
+                system_prompt.push_str(&packed.section);
+            }
+        }
+    }
+
+    ctx.timings.add(iteration, Phase::Prompt, prompt_started.elapsed());
+    output::debug(&format!("Prompt: {} bytes", system_prompt.len()));
+    output::trace(&format!("Prompt:\n{system_prompt}"));
 
     let claude_args = ClaudeArgs {
-        permission_mode: ctx.args.permission_mode.clone(),
+        backend: ctx.backend,
+        permission_mode: ctx.permission_mode.to_string(),
         continue_session: ctx.args.continue_session,
         dangerously_skip_permissions: ctx.args.dangerously_skip_permissions,
-        timeout_secs: ctx.args.timeout,
-        project_dir: ctx.project_dir,
+        model: feature.and_then(|f| f.model.clone()).or_else(|| ctx.args.model.clone()),
+        allowed_tools: &ctx.args.allowed_tools,
+        disallowed_tools: &ctx.args.disallowed_tools,
+        timeout_secs: feature.and_then(|f| f.timeout_secs).unwrap_or(ctx.args.timeout),
+        stall_timeout: ctx.args.stall_timeout.map(Duration::from_secs),
+        project_dir: &working_dir,
+        output_buffer_bytes: ctx.args.output_buffer_kb.saturating_mul(1024),
+        live: ctx.live,
+        simulator: ctx.simulator,
+        env: ctx.env,
     };
 
-    let result = claude::run_claude(&system_prompt, &claude_args, &log_path, cancel_token).await?;
+    // Without git, hash the tree and keep the PRD so changes can still be checked afterwards
+    let snapshot = if ctx.args.snapshot && !git::is_git_repo(validation::prd_dir(&ctx.args.prd)) {
+        let prd_before = std::fs::read_to_string(&ctx.args.prd).context("Failed to read PRD file")?;
+        Some((Snapshot::take(ctx.project_dir)?, prd_before))
+    } else {
+        None
+    };
+
+    let checkpoint = if ctx.args.rollback_on_failure {
+        Some(Checkpoint::take(ctx.project_dir, &ctx.args.prd)?)
+    } else {
+        None
+    };
+
+    // An unborn HEAD has no commits to look past
+    let head_before = ctx.args.auto_complete.then(|| git::head_sha(ctx.project_dir).ok()).flatten();
+
+    // What was already uncommitted isn't the agent's to account for
+    let ralph_files = ralph_files(ctx);
+    let dirty_before = if git::is_git_repo(ctx.project_dir) {
+        Some(git::changed_paths(ctx.project_dir, &ralph_files.iter().map(String::as_str).collect::<Vec<_>>())?)
+    } else {
+        None
+    };
+
+    // Protected files that already differ from HEAD aren't the agent's doing
+    let protected = validation::ProtectedPaths::new(protected_patterns)?;
+    let protected_git = match protected {
+        Some(ref protected) if snapshot.is_none() && git::is_git_repo(ctx.project_dir) => {
+            let head = git::head_sha(ctx.project_dir).ok();
+            let before = protected.violations(git::changed_files_since(ctx.project_dir, head.as_deref())?);
+            Some((head, before))
+        }
+        _ => None,
+    };
+
+    // Tests already gone from the working tree were removed before this iteration
+    let test_guard = match git::head_sha(ctx.project_dir) {
+        Ok(head) if snapshot.is_none() => {
+            let before = validation::removed_tests(&git::diff_from(ctx.project_dir, &head)?);
+            let allowlist = validation::test_removal_allowlist(&ctx.args.allow_test_removal)?;
+            Some((head, before, allowlist))
+        }
+        _ => None,
+    };
+
+    let agent_started = Instant::now();
+    let result = claude::run_claude(&system_prompt, &claude_args, ctx.log_path, cancel_token).await?;
+    ctx.timings.add(iteration, Phase::Agent, agent_started.elapsed());
+    ctx.transcripts.record(iteration, &system_prompt, &result.output);
+    if let Some(ref usage) = result.usage {
+        ctx.costs.record(iteration, usage);
+    }
+    if let Some(ref resources) = result.resources {
+        ctx.costs.record_resources(iteration, resources);
+        report_resources(resources);
+    }
 
     if result.success {
         output::success(&format!("Iteration {iteration} completed"));
@@ -60,18 +298,579 @@ pub async fn run(
         output::warn(&format!("Iteration {iteration} exited with error"));
     }
 
-    if git::is_git_repo() {
-        if let Err(e) = validation::validate_prd_changes(&ctx.args.prd.to_string_lossy()) {
+    let validation_started = Instant::now();
+    let mut protected_changes = Vec::new();
+    let validated = if let Some((before, prd_before)) = snapshot {
+        let changes = before.diff(&Snapshot::take(ctx.project_dir)?);
+        if let Some(ref protected) = protected {
+            protected_changes = protected.violations(changes.paths());
+        }
+        if !changes.is_empty() {
+            output::log(&format!("Changed files ({}):", changes.len()));
+            for line in changes.report(CHANGED_FILES_SHOWN) {
+                output::dim(&format!("  {line}"));
+            }
+        }
+        Some(validation::validate_prd_against(&prd_before, &ctx.args.prd, ctx.args.allow_agent_notes))
+    } else if git::is_git_repo(validation::prd_dir(&ctx.args.prd)) {
+        Some(validation::validate_prd_changes(&ctx.args.prd, ctx.args.allow_agent_notes))
+    } else {
+        output::warn("Not a git repository - skipping PRD validation (use --snapshot)");
+        None
+    };
+    if let (Some(protected), Some((head, before))) = (&protected, &protected_git) {
+        protected_changes = protected
+            .violations(git::changed_files_since(ctx.project_dir, head.as_deref())?)
+            .into_iter()
+            .filter(|path| !before.contains(path))
+            .collect();
+    }
+    let removed_tests = match test_guard {
+        Some((ref head, ref before, ref allowlist)) => {
+            validation::removed_tests(&git::diff_from(ctx.project_dir, head)?)
+                .into_iter()
+                .filter(|test| !before.contains(test) && !test.allowed_by(allowlist))
+                .map(|test| test.to_string())
+                .collect()
+        }
+        None => Vec::new(),
+    };
+    ctx.timings.add(iteration, Phase::Validation, validation_started.elapsed());
+    if !protected_changes.is_empty() {
+        output::error(&format!("Protected paths changed: {}", protected_changes.join(", ")));
+        report_to_next_session(
+            iteration,
+            ctx,
+            "Protected paths changed",
+            "These files are protected but were changed:",
+            &protected_changes,
+            "Revert them; no iteration may change protected paths.",
+        )?;
+        roll_back(iteration, ctx, checkpoint.as_ref(), "protected paths")?;
+        return Ok(IterationResult::Failed);
+    }
+    if !removed_tests.is_empty() {
+        output::error(&format!("Tests removed: {}", removed_tests.join(", ")));
+        report_to_next_session(
+            iteration,
+            ctx,
+            "Tests removed",
+            "These tests were removed or disabled:",
+            &removed_tests,
+            "Restore them; tests may not be removed or weakened.",
+        )?;
+        roll_back(iteration, ctx, checkpoint.as_ref(), "test removal")?;
+        return Ok(IterationResult::Failed);
+    }
+    match validated {
+        Some(Ok(notes)) => {
+            for note in notes {
+                output::dim(&format!("Agent note on '{}': {}", note.feature_id, note.text));
+            }
+        }
+        Some(Err(e)) => {
             output::error(&format!("PRD validation failed: {e}"));
+            roll_back(iteration, ctx, checkpoint.as_ref(), "PRD validation")?;
             return Ok(IterationResult::Failed);
         }
-    } else {
-        output::warn("Not a git repository - skipping PRD validation");
+        None => {}
+    }
+
+    if let Some(ref before) = dirty_before {
+        if !account_for_uncommitted(iteration, ctx, before, &ralph_files, feature.map(|f| f.id.as_str()))? {
+            roll_back(iteration, ctx, checkpoint.as_ref(), "uncommitted changes")?;
+            return Ok(IterationResult::Failed);
+        }
+    }
+
+    let verification_started = Instant::now();
+    let failed_completions = verify_completions(iteration, ctx).await?;
+    ctx.timings.add(iteration, Phase::Verification, verification_started.elapsed());
+    if !failed_completions.is_empty() {
+        roll_back(iteration, ctx, checkpoint.as_ref(), "verification")?;
+        return Ok(IterationResult::Failed);
+    }
+
+    let verification_started = Instant::now();
+    let auto_completed = match head_before {
+        Some(ref head) => complete_committed(iteration, ctx, head).await?,
+        None => Vec::new(),
+    };
+    ctx.timings.add(iteration, Phase::Verification, verification_started.elapsed());
+
+    if result.stalled {
+        return Ok(IterationResult::Stalled);
     }
 
     let analysis_ctx = OutputAnalysisContext {
         success: result.success,
         completion_marker: ctx.completion_marker,
+        patterns: ctx.patterns,
     };
-    Ok(analyze_iteration_output(&result.output, &analysis_ctx))
+    let analyzed = analyze_iteration_output(&result.output, &analysis_ctx);
+    // The agent couldn't see its last feature completed, so it may not have printed the marker
+    if matches!(analyzed, IterationResult::Continue)
+        && !auto_completed.is_empty()
+        && prd::Prd::load(&ctx.args.prd)?.features.iter().all(|f| f.status == Status::Complete)
+    {
+        return Ok(IterationResult::Complete);
+    }
+    if analyzed == IterationResult::Complete {
+        let verification_started = Instant::now();
+        let rejections = completion_rejections(iteration, ctx).await?;
+        ctx.timings.add(iteration, Phase::Verification, verification_started.elapsed());
+        if !rejections.is_empty() {
+            reject_completion(iteration, ctx, &rejections)?;
+            return Ok(IterationResult::Failed);
+        }
+    }
+    Ok(analyzed)
+}
+
+/// The PRD's `protectedPaths` followed by `--protected-path`.
+fn protected_patterns<'a>(ctx: &IterationContext<'a>) -> Vec<&'a str> {
+    ctx.prd.project.protected_paths.iter().chain(&ctx.args.protected_path).map(String::as_str).collect()
+}
+
+/// Appends a `<title> (iteration N)` progress entry listing `items`, so the next session
+/// knows what to fix.
+fn report_to_next_session(
+    iteration: u32,
+    ctx: &IterationContext<'_>,
+    title: &str,
+    intro: &str,
+    items: &[String],
+    closing: &str,
+) -> Result<()> {
+    let body = std::iter::once(intro.to_string())
+        .chain(items.iter().map(|item| format!("- {item}")))
+        .chain(std::iter::once(closing.to_string()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    progress::append_entry(
+        ctx.progress_path,
+        &format!("{title} (iteration {iteration})"),
+        &body,
+        progress::EntryLimits {
+            max_lines: ctx.args.progress_entry_lines,
+            max_bytes: ctx.args.progress_entry_bytes,
+        },
+        None,
+    )
+}
+
+/// Ralph's own files under the project directory: the progress file, its records and `.ralph`.
+pub(crate) fn ralph_files(ctx: &IterationContext<'_>) -> Vec<String> {
+    let records = progress::records_path(ctx.progress_path);
+    let ralph_dir = validation::prd_dir(&ctx.args.prd).join(".ralph");
+    git::relative_pathspecs(ctx.project_dir, &[ctx.progress_path, &records, &ralph_dir])
+}
+
+/// Changes the agent left uncommitted, which the next `--print` session wouldn't know to keep:
+/// committed with `--auto-commit`, otherwise reported in the progress file. Paths that were
+/// already uncommitted before the agent ran are left alone. False fails the iteration.
+fn account_for_uncommitted(
+    iteration: u32,
+    ctx: &IterationContext<'_>,
+    before: &[String],
+    ralph_files: &[String],
+    feature: Option<&str>,
+) -> Result<bool> {
+    let exclude: Vec<&str> = ralph_files.iter().map(String::as_str).collect();
+    let left: Vec<String> =
+        git::changed_paths(ctx.project_dir, &exclude)?.into_iter().filter(|p| !before.contains(p)).collect();
+    if left.is_empty() {
+        return Ok(true);
+    }
+    if ctx.args.auto_commit {
+        // Naming the feature lets `--auto-complete` pick the commit up
+        let message = match feature {
+            Some(id) => format!("{id}: changes left uncommitted in iteration {iteration}"),
+            None => format!("Changes left uncommitted in iteration {iteration}"),
+        };
+        git::commit_paths(ctx.project_dir, &left, &message)?;
+        output::log(&format!("Committed {} file(s) the agent left uncommitted", left.len()));
+        return Ok(true);
+    }
+    output::error(&format!("The agent left {} file(s) uncommitted: {}", left.len(), left.join(", ")));
+    report_to_next_session(
+        iteration,
+        ctx,
+        "Uncommitted changes",
+        "These files were changed but not committed:",
+        &left,
+        "Commit or revert them; uncommitted work is lost between sessions.",
+    )?;
+    Ok(false)
+}
+
+/// Why the completion marker can't be accepted: a premature marker (see
+/// `--premature-completion`), blocked features under `allFeaturesComplete`, then verification
+/// commands that fail under `allVerificationsPassing`. Commands aren't run while features remain.
+async fn completion_rejections(iteration: u32, ctx: &IterationContext<'_>) -> Result<Vec<String>> {
+    let prd = prd::Prd::load(&ctx.args.prd)?;
+    let open = analysis::premature_completion(&prd);
+    if !open.is_empty() {
+        match ctx.args.premature_completion {
+            PrematureCompletion::Reject => return Ok(open),
+            PrematureCompletion::Warn => {
+                output::warn(&format!("Accepting a premature completion marker: {}", open.join(", ")));
+                events::action_required(
+                    &format!("Completion accepted in iteration {iteration} with features still open"),
+                    &format!("Check {} and reopen what isn't done", ctx.args.prd.display()),
+                );
+            }
+            PrematureCompletion::Accept => {}
+        }
+    }
+    if prd.completion.all_features_complete {
+        let blocked: Vec<String> = prd
+            .features
+            .iter()
+            .filter(|f| f.status == Status::Blocked)
+            .map(|f| format!("'{}' is blocked", f.id))
+            .collect();
+        if !blocked.is_empty() {
+            return Ok(blocked);
+        }
+    }
+    let commands = prd.all_verification();
+    if !prd.completion.all_verifications_passing || commands.is_empty() {
+        return Ok(Vec::new());
+    }
+    output::log("Verifying completion...");
+    let sandbox = Sandbox::from_args(ctx.args, ctx.env);
+    let mut results = Vec::new();
+    for (cmd, workdir) in commands {
+        let dir = workdir.map_or_else(|| ctx.project_dir.to_path_buf(), |dir| ctx.project_dir.join(dir));
+        results.extend(verification::run([cmd], &sandbox, &dir, Duration::from_secs(ctx.args.timeout)).await);
+    }
+    Ok(results.iter().filter(|r| !r.passed).map(|r| format!("verification '{}' fails", r.name)).collect())
+}
+
+/// Fails the iteration that printed the marker and tells the next one why, in the progress file.
+fn reject_completion(iteration: u32, ctx: &IterationContext<'_>, rejections: &[String]) -> Result<()> {
+    output::warn(&format!("Completion rejected: {}", rejections.join(", ")));
+    report_to_next_session(
+        iteration,
+        ctx,
+        "Completion rejected",
+        "The completion marker was printed, but:",
+        rejections,
+        "Resolve these before printing the marker again.",
+    )
+}
+
+/// `--auto-complete`: marks complete each open feature that a commit of this iteration
+/// mentions, once its verification commands pass, and commits the PRD if it's tracked.
+/// Returns the ids of the completed features.
+async fn complete_committed(iteration: u32, ctx: &IterationContext<'_>, head_before: &str) -> Result<Vec<String>> {
+    let messages = git::commit_messages_since(ctx.project_dir, head_before)?;
+    if messages.is_empty() {
+        return Ok(Vec::new());
+    }
+    let prd = prd::Prd::load(&ctx.args.prd)?;
+    let prd_dir = validation::prd_dir(&ctx.args.prd);
+    let name = ctx.args.prd.file_name().unwrap_or_default().to_string_lossy();
+    let tracked = git::show_file_from_head(prd_dir, &name).ok().flatten().is_some();
+
+    let mut completed = Vec::new();
+    for feature in prd.features.iter().filter(|f| matches!(f.status, Status::Pending | Status::InProgress)) {
+        if ctx.assigned_feature.is_some_and(|id| id != feature.id) || !messages.iter().any(|m| mentions(m, &feature.id)) {
+            continue;
+        }
+        let acceptance = feature.acceptance_check();
+        let mut commands = prd.verification_for(feature);
+        commands.extend(acceptance.as_ref());
+        if !commands.is_empty() {
+            output::log(&format!("Verifying '{}'...", feature.id));
+            let sandbox = Sandbox::from_args(ctx.args, ctx.env);
+            let dir = feature.working_dir(ctx.project_dir);
+            let results = verification::run(commands, &sandbox, &dir, Duration::from_secs(ctx.args.timeout)).await;
+            let failed: Vec<&str> = results.iter().filter(|r| !r.passed).map(|r| r.name.as_str()).collect();
+            if !failed.is_empty() {
+                output::error(&format!(
+                    "Verification failed for '{}' ({}); leaving it {}",
+                    feature.id,
+                    failed.join(", "),
+                    feature.status.as_str()
+                ));
+                progress::append_entry(
+                    ctx.progress_path,
+                    &format!("Verification failed (iteration {iteration})"),
+                    &format!(
+                        "'{}' was committed, but these verification commands failed: {}. It stays {} until they pass.",
+                        feature.id,
+                        failed.join(", "),
+                        feature.status.as_str()
+                    ),
+                    progress::EntryLimits {
+                        max_lines: ctx.args.progress_entry_lines,
+                        max_bytes: ctx.args.progress_entry_bytes,
+                    },
+                    None,
+                )?;
+                continue;
+            }
+        }
+
+        retry::update_feature_status(
+            &ctx.args.prd,
+            &feature.id,
+            &[Status::Pending, Status::InProgress],
+            Status::Complete,
+        )?;
+        if tracked {
+            git::commit_file(prd_dir, &name, &format!("Complete {}", feature.id))?;
+        }
+        output::success(&format!("Marked '{}' complete", feature.id));
+        completed.push(feature.id.clone());
+    }
+    Ok(completed)
+}
+
+/// Whether `message` mentions `id` on its own: "auth-login: add form", not "auth-login-2".
+fn mentions(message: &str, id: &str) -> bool {
+    let id_char = |c: char| c.is_alphanumeric() || matches!(c, '-' | '_');
+    message.match_indices(id).any(|(at, _)| {
+        !message[..at].chars().next_back().is_some_and(id_char)
+            && !message[at + id.len()..].chars().next().is_some_and(id_char)
+    })
+}
+
+/// Runs the verification commands of every feature this iteration marked complete, when
+/// the PRD sets `runAfterEachFeature`, and reopens the features whose commands fail. Then
+/// runs each one's `acceptanceCommand`, reopening it as in-progress if that fails.
+/// A completion the agent already committed is reverted with a commit of its own.
+/// Returns the ids of the reopened features.
+async fn verify_completions(iteration: u32, ctx: &IterationContext<'_>) -> Result<Vec<String>> {
+    let prd = prd::Prd::load(&ctx.args.prd)?;
+    let sandbox = Sandbox::from_args(ctx.args, ctx.env);
+    let timeout = Duration::from_secs(ctx.args.timeout);
+
+    let mut reopened = Vec::new();
+    for feature in prd.features.iter().filter(|f| f.status == Status::Complete) {
+        let Some(previous) = ctx
+            .prd
+            .features
+            .iter()
+            .find(|old| old.id == feature.id && old.status != Status::Complete)
+            .map(|old| old.status)
+        else {
+            continue;
+        };
+
+        let commands = prd.verification_for(feature);
+        let dir = feature.working_dir(ctx.project_dir);
+        if prd.verification.run_after_each_feature && !commands.is_empty() {
+            output::log(&format!("Verifying '{}'...", feature.id));
+            let results = verification::run(commands, &sandbox, &dir, timeout).await;
+            let failed: Vec<&str> = results.iter().filter(|r| !r.passed).map(|r| r.name.as_str()).collect();
+            if failed.is_empty() {
+                output::success(&format!("Verification passed for '{}'", feature.id));
+            } else {
+                output::error(&format!(
+                    "Verification failed for '{}' ({}); reopening it as {}",
+                    feature.id,
+                    failed.join(", "),
+                    previous.as_str()
+                ));
+                reopen(
+                    iteration,
+                    ctx,
+                    &feature.id,
+                    previous,
+                    &format!("verification failed ({})", failed.join(", ")),
+                    &format!(
+                        "'{}' was marked complete, but these verification commands failed: {}. Status reverted to {}.",
+                        feature.id,
+                        failed.join(", "),
+                        previous.as_str()
+                    ),
+                )?;
+                reopened.push(feature.id.clone());
+                continue;
+            }
+        }
+
+        let Some(acceptance) = feature.acceptance_check() else {
+            continue;
+        };
+        output::log(&format!("Checking acceptance of '{}'...", feature.id));
+        if verification::run(vec![&acceptance], &sandbox, &dir, timeout).await.iter().all(|r| r.passed) {
+            output::success(&format!("Acceptance check passed for '{}'", feature.id));
+            continue;
+        }
+        output::error(&format!("Acceptance check failed for '{}'; reopening it as in-progress", feature.id));
+        reopen(
+            iteration,
+            ctx,
+            &feature.id,
+            Status::InProgress,
+            "acceptance check failed",
+            &format!(
+                "'{}' was marked complete, but its acceptance command `{}` failed (output in .ralph/logs/verify-{}.log). \
+                 Status set back to in-progress; make the command pass before marking it complete again.",
+                feature.id, acceptance.command, acceptance.name
+            ),
+        )?;
+        reopened.push(feature.id.clone());
+    }
+    Ok(reopened)
+}
+
+/// Sets a feature the iteration completed back to `status`, reverting a committed completion
+/// with a `Reopen <id>: <reason>` commit, and tells the next session why in the progress file.
+fn reopen(
+    iteration: u32,
+    ctx: &IterationContext<'_>,
+    feature_id: &str,
+    status: Status,
+    reason: &str,
+    note: &str,
+) -> Result<()> {
+    retry::update_feature_status(&ctx.args.prd, feature_id, &[Status::Complete], status)?;
+    if completion_committed(&ctx.args.prd, feature_id) {
+        let name = ctx.args.prd.file_name().unwrap_or_default().to_string_lossy();
+        git::commit_file(validation::prd_dir(&ctx.args.prd), &name, &format!("Reopen {feature_id}: {reason}"))?;
+    }
+    progress::append_entry(
+        ctx.progress_path,
+        &format!("Verification failed (iteration {iteration})"),
+        note,
+        progress::EntryLimits {
+            max_lines: ctx.args.progress_entry_lines,
+            max_bytes: ctx.args.progress_entry_bytes,
+        },
+        None,
+    )
+}
+
+/// Undoes a failed iteration for `--rollback-on-failure`: its commits, its changes to
+/// tracked files, and its PRD edits. The progress files keep what was learned.
+fn roll_back(iteration: u32, ctx: &IterationContext<'_>, checkpoint: Option<&Checkpoint>, failed: &str) -> Result<()> {
+    let Some(checkpoint) = checkpoint else {
+        return Ok(());
+    };
+    let records = progress::records_path(ctx.progress_path);
+    checkpoint.restore(&[ctx.progress_path, &records]).context("Rollback failed")?;
+    let head = &checkpoint.head()[..checkpoint.head().len().min(12)];
+    output::warn(&format!("Rolled back iteration {iteration} to {head}"));
+    progress::append_entry(
+        ctx.progress_path,
+        &format!("Rolled back (iteration {iteration})"),
+        &format!(
+            "The iteration failed {failed}, so its commits, changes to tracked files, and PRD edits were \
+             discarded and the repository reset to {head}. Untracked files were kept."
+        ),
+        progress::EntryLimits {
+            max_lines: ctx.args.progress_entry_lines,
+            max_bytes: ctx.args.progress_entry_bytes,
+        },
+        None,
+    )
+}
+
+/// Whether the PRD at HEAD already has `feature_id` complete.
+fn completion_committed(prd_path: &Path, feature_id: &str) -> bool {
+    let dir = validation::prd_dir(prd_path);
+    let name = prd_path.file_name().unwrap_or_default().to_string_lossy();
+    if !git::is_git_repo(dir) {
+        return false;
+    }
+    git::show_file_from_head(dir, &name)
+        .ok()
+        .flatten()
+        .and_then(|content| json5::from_str::<prd::Prd>(&content).ok())
+        .is_some_and(|head| {
+            head.features
+                .iter()
+                .any(|f| f.id == feature_id && f.status == Status::Complete)
+        })
+}
+
+/// Condenses the raw recent context with `--summary-model`, falling back to the raw text
+/// if the call fails or says nothing.
+async fn summarize_context(iteration: u32, ctx: &IterationContext<'_>, cancel_token: &CancellationToken) -> String {
+    let stem = ctx.log_path.file_stem().unwrap_or_default().to_string_lossy();
+    let log_path = ctx.log_path.with_file_name(format!("{stem}-summary.log"));
+
+    // Plan mode keeps the summarizer read-only
+    let claude_args = ClaudeArgs {
+        backend: ctx.backend,
+        permission_mode: "plan".into(),
+        continue_session: false,
+        dangerously_skip_permissions: false,
+        model: ctx.args.summary_model.clone().or_else(|| ctx.args.model.clone()),
+        allowed_tools: &ctx.args.allowed_tools,
+        disallowed_tools: &ctx.args.disallowed_tools,
+        timeout_secs: ctx.args.timeout,
+        stall_timeout: ctx.args.stall_timeout.map(Duration::from_secs),
+        project_dir: ctx.project_dir,
+        output_buffer_bytes: ctx.args.output_buffer_kb.saturating_mul(1024),
+        live: None,
+        simulator: ctx.simulator,
+        env: ctx.env,
+    };
+
+    output::dim("Summarizing recent context...");
+    let prompt = recent_context::summary_prompt(ctx.recent_context);
+    match claude::run_claude(&prompt, &claude_args, &log_path, cancel_token).await {
+        Ok(result) => {
+            if let Some(ref usage) = result.usage {
+                ctx.costs.record(iteration, usage);
+            }
+            let summary = recent_context::clip_summary(&result.output);
+            if result.success && !summary.is_empty() {
+                return summary;
+            }
+            output::warn("Context summary failed; using the raw recent context");
+        }
+        Err(e) => output::warn(&format!("Context summary failed: {e:#}; using the raw recent context")),
+    }
+    ctx.recent_context.to_string()
+}
+
+/// Runs the read-only auditor pass requested by `--final-audit`.
+pub async fn run_audit(
+    iteration: u32,
+    ctx: &IterationContext<'_>,
+    cancel_token: &CancellationToken,
+) -> Result<AuditVerdict> {
+    output::log(&format!("Final audit after iteration {iteration}..."));
+    output::blank();
+
+    let stem = ctx.log_path.file_stem().unwrap_or_default().to_string_lossy();
+    let log_path = ctx.log_path.with_file_name(format!("{stem}-audit.log"));
+
+    let (prd_path, progress_path) = prompt_paths(ctx, ctx.project_dir);
+    let audit_prompt = prompt::build_audit_prompt(ctx.prd, &prd_path, &progress_path);
+
+    // Plan mode keeps the auditor read-only regardless of the run's permission flags
+    let claude_args = ClaudeArgs {
+        backend: ctx.backend,
+        permission_mode: "plan".into(),
+        continue_session: false,
+        dangerously_skip_permissions: false,
+        model: ctx.args.model.clone(),
+        allowed_tools: &ctx.args.allowed_tools,
+        disallowed_tools: &ctx.args.disallowed_tools,
+        timeout_secs: ctx.args.timeout,
+        stall_timeout: ctx.args.stall_timeout.map(Duration::from_secs),
+        project_dir: ctx.project_dir,
+        output_buffer_bytes: ctx.args.output_buffer_kb.saturating_mul(1024),
+        live: ctx.live,
+        simulator: ctx.simulator,
+        env: ctx.env,
+    };
+
+    let result = claude::run_claude(&audit_prompt, &claude_args, &log_path, cancel_token).await?;
+    if let Some(ref usage) = result.usage {
+        ctx.costs.record(iteration, usage);
+    }
+    if let Some(ref resources) = result.resources {
+        ctx.costs.record_resources(iteration, resources);
+        report_resources(resources);
+    }
+    Ok(analyze_audit_output(&result.output, ctx.completion_marker))
 }
@@ -0,0 +1,285 @@
+//! Jira source (`--prd-source jira:acme.atlassian.net?epic=SHOP-1`): the issues of an epic,
+//! a sprint, or a JQL query become features, and completed ones are transitioned (to
+//! `Done` unless `transition=` says otherwise) and commented on. `JIRA_EMAIL` and
+//! `JIRA_API_TOKEN` authenticate on Jira Cloud; `JIRA_API_TOKEN` alone is sent as a
+//! personal access token.
+
+use crate::source::{self, PrdSource, Ticket};
+use anyhow::{bail, Context, Result};
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use serde_json::json;
+use std::fmt;
+use std::str::FromStr;
+
+const PAGE_SIZE: u32 = 100;
+const DEFAULT_TRANSITION: &str = "Done";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scope {
+    /// Issues whose parent is this epic
+    Epic(String),
+    /// Issues in this sprint id
+    Sprint(u64),
+    Jql(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JiraSource {
+    /// As given: a host name, or a URL for self-hosted Jira
+    pub host: String,
+    pub scope: Scope,
+    /// Transition applied to completed issues
+    pub transition: Option<String>,
+}
+
+/// `jira:<host>?epic=<KEY>`, `?sprint=<id>`, or `?jql=<query>`, plus `&transition=<name>`.
+impl FromStr for JiraSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let expected = "expected jira:<host>?epic=<KEY>, ?sprint=<id>, or ?jql=<query>";
+        let (host, options) = source::parse_spec(s, "jira", expected)?;
+        let mut scope = None;
+        let mut transition = None;
+        for (key, value) in options {
+            let parsed = match key {
+                "epic" => Scope::Epic(value.to_string()),
+                "sprint" => Scope::Sprint(value.parse().with_context(|| format!("invalid sprint id '{value}'"))?),
+                "jql" => Scope::Jql(value.to_string()),
+                "transition" => {
+                    transition = Some(value.to_string());
+                    continue;
+                }
+                _ => bail!("unknown option '{key}': {expected}"),
+            };
+            if scope.replace(parsed).is_some() {
+                bail!("only one of epic, sprint, and jql may be given");
+            }
+        }
+        Ok(Self {
+            host: host.to_string(),
+            scope: scope.context(expected)?,
+            transition,
+        })
+    }
+}
+
+impl fmt::Display for JiraSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "jira:{}?", self.host)?;
+        match self.scope {
+            Scope::Epic(ref key) => write!(f, "epic={key}")?,
+            Scope::Sprint(id) => write!(f, "sprint={id}")?,
+            Scope::Jql(ref jql) => write!(f, "jql={jql}")?,
+        }
+        if let Some(ref transition) = self.transition {
+            write!(f, "&transition={transition}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchPage {
+    issues: Vec<Issue>,
+    #[serde(rename = "nextPageToken")]
+    next_page_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Issue {
+    key: String,
+    fields: Fields,
+}
+
+#[derive(Debug, Deserialize)]
+struct Fields {
+    summary: String,
+    description: Option<String>,
+    #[serde(default)]
+    labels: Vec<String>,
+    status: Option<IssueStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IssueStatus {
+    #[serde(rename = "statusCategory")]
+    category: Option<StatusCategory>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StatusCategory {
+    key: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Transitions {
+    transitions: Vec<Transition>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Transition {
+    id: String,
+    name: String,
+}
+
+impl JiraSource {
+    fn base_url(&self) -> String {
+        let host = self.host.trim_end_matches('/');
+        if host.starts_with("http://") || host.starts_with("https://") {
+            host.to_string()
+        } else {
+            format!("https://{host}")
+        }
+    }
+
+    fn jql(&self) -> String {
+        match self.scope {
+            Scope::Epic(ref key) => format!("parent = {key} ORDER BY created ASC"),
+            Scope::Sprint(id) => format!("sprint = {id} ORDER BY created ASC"),
+            Scope::Jql(ref jql) => jql.clone(),
+        }
+    }
+
+    fn ticket(&self, issue: Issue) -> Ticket {
+        let complete = issue.fields.status.and_then(|s| s.category).is_some_and(|c| c.key == "done");
+        Ticket {
+            url: format!("{}/browse/{}", self.base_url(), issue.key),
+            key: issue.key,
+            title: issue.fields.summary,
+            body: issue.fields.description.unwrap_or_default(),
+            labels: issue.fields.labels,
+            complete,
+        }
+    }
+}
+
+impl PrdSource for JiraSource {
+    fn id_prefix(&self) -> &'static str {
+        "jira-"
+    }
+
+    fn project_name(&self) -> String {
+        match self.scope {
+            Scope::Epic(ref key) => key.clone(),
+            Scope::Sprint(id) => format!("sprint-{id}"),
+            Scope::Jql(_) => self.host.clone(),
+        }
+    }
+
+    /// Every issue the query matches; done ones come back complete.
+    fn fetch(&self) -> BoxFuture<'_, Result<Vec<Ticket>>> {
+        Box::pin(async move {
+            let url = format!("{}/rest/api/2/search/jql", self.base_url());
+            let client = reqwest::Client::new();
+            let jql = self.jql();
+            let mut tickets = Vec::new();
+            let mut page_token = None;
+            loop {
+                let mut query = vec![
+                    ("jql", jql.clone()),
+                    ("fields", "summary,description,labels,status".to_string()),
+                    ("maxResults", PAGE_SIZE.to_string()),
+                ];
+                if let Some(token) = page_token.take() {
+                    query.push(("nextPageToken", token));
+                }
+                let response = request(client.get(&url).query(&query))
+                    .send()
+                    .await
+                    .with_context(|| format!("Failed to search {}", self.host))?;
+                if !response.status().is_success() {
+                    bail!("Failed to search {}: Jira returned {}", self.host, response.status());
+                }
+                let page: SearchPage = response.json().await.context("Failed to parse Jira search results")?;
+                tickets.extend(page.issues.into_iter().map(|issue| self.ticket(issue)));
+                match page.next_page_token {
+                    Some(token) => page_token = Some(token),
+                    None => break,
+                }
+            }
+            Ok(tickets)
+        })
+    }
+
+    fn complete<'a>(&'a self, key: &'a str, comment: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let issue_url = format!("{}/rest/api/2/issue/{key}", self.base_url());
+            let client = reqwest::Client::new();
+            let wanted = self.transition.as_deref().unwrap_or(DEFAULT_TRANSITION);
+            let response = request(client.get(format!("{issue_url}/transitions"))).send().await?;
+            if !response.status().is_success() {
+                bail!("Jira returned {} listing transitions", response.status());
+            }
+            let transitions: Transitions = response.json().await.context("Failed to parse Jira transitions")?;
+            let Some(transition) = transitions.transitions.iter().find(|t| t.name.eq_ignore_ascii_case(wanted)) else {
+                let names: Vec<&str> = transitions.transitions.iter().map(|t| t.name.as_str()).collect();
+                bail!("no '{wanted}' transition (available: {})", names.join(", "));
+            };
+            let requests = [
+                ("transitions", json!({ "transition": { "id": transition.id } })),
+                ("comment", json!({ "body": comment })),
+            ];
+            for (path, body) in requests {
+                let response = request(client.post(format!("{issue_url}/{path}")).json(&body)).send().await?;
+                if !response.status().is_success() {
+                    bail!("Jira returned {} for the {path} request", response.status());
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+fn request(builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    let builder = builder.header("Accept", "application/json");
+    match (std::env::var("JIRA_EMAIL"), std::env::var("JIRA_API_TOKEN")) {
+        (Ok(email), Ok(token)) => builder.basic_auth(email, Some(token)),
+        (Err(_), Ok(token)) => builder.bearer_auth(token),
+        _ => builder,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_scopes() {
+        let source: JiraSource = "jira:acme.atlassian.net?epic=SHOP-1&transition=Ready for QA".parse().unwrap();
+        assert_eq!(source.scope, Scope::Epic("SHOP-1".into()));
+        assert_eq!(source.jql(), "parent = SHOP-1 ORDER BY created ASC");
+        assert_eq!(source.transition.as_deref(), Some("Ready for QA"));
+        assert_eq!(source.to_string(), "jira:acme.atlassian.net?epic=SHOP-1&transition=Ready for QA");
+
+        let source: JiraSource = "jira:https://jira.acme.internal/?sprint=42".parse().unwrap();
+        assert_eq!(source.base_url(), "https://jira.acme.internal");
+        assert_eq!(source.jql(), "sprint = 42 ORDER BY created ASC");
+
+        for bad in ["jira:acme.atlassian.net", "jira:h?sprint=next", "jira:h?epic=A-1&sprint=2", "jira:h?board=3"] {
+            assert!(bad.parse::<JiraSource>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn search_results_become_tickets() {
+        let page: SearchPage = serde_json::from_value(json!({
+            "issues": [
+                { "key": "SHOP-2", "fields": {
+                    "summary": "Checkout", "description": "- [ ] Pay", "labels": ["payments"],
+                    "status": { "statusCategory": { "key": "indeterminate" } }
+                } },
+                { "key": "SHOP-3", "fields": { "summary": "Done one", "status": { "statusCategory": { "key": "done" } } } }
+            ]
+        }))
+        .unwrap();
+        assert_eq!(page.next_page_token, None);
+        let source: JiraSource = "jira:acme.atlassian.net?epic=SHOP-1".parse().unwrap();
+        let tickets: Vec<Ticket> = page.issues.into_iter().map(|i| source.ticket(i)).collect();
+        assert_eq!(tickets[0].url, "https://acme.atlassian.net/browse/SHOP-2");
+        assert_eq!((tickets[0].body.as_str(), tickets[0].complete), ("- [ ] Pay", false));
+        assert_eq!(tickets[0].labels, ["payments"]);
+        assert!(tickets[1].complete);
+    }
+}
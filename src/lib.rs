@@ -1,14 +1,73 @@
+pub mod api;
+pub mod approval;
+pub mod agent;
 pub mod analysis;
+pub mod artifact;
+pub mod atomic;
+pub mod backoff;
+pub mod bench;
+pub mod checkpoint;
 pub mod claude;
+pub mod clean;
 pub mod config;
+pub mod context;
+pub mod control;
+pub mod coverage;
+pub mod desktop;
+pub mod diagnostic;
+pub mod digest;
 pub mod dry_run;
+pub mod environment;
+pub mod estimate;
+pub mod events;
 pub mod git;
+pub mod github;
+pub mod graph;
+pub mod history;
 pub mod init;
 pub mod iteration;
+pub mod jira;
+pub mod linear;
+pub mod lint;
+pub mod log_sink;
+pub mod logging;
+pub mod logs;
+pub mod markdown;
+pub mod mcp;
+pub mod metrics;
+pub mod move_feature;
+pub mod multi_prd;
 pub mod output;
 pub mod prd;
+pub mod prd_edit;
+pub mod progress;
 pub mod prompt;
+pub mod recent_context;
+pub mod redact;
+pub mod report;
+pub mod resources;
 pub mod retry;
+pub mod review;
+pub mod run_id;
 pub mod runner;
+pub mod sandbox;
+pub mod serve;
+pub mod session;
+pub mod shell;
+pub mod simulate;
+pub mod snapshot;
+pub mod source;
+pub mod state;
+pub mod status;
+pub mod stop;
+pub mod stream;
+pub mod telemetry;
+pub mod testing;
+pub mod timing;
+pub mod todos;
 pub mod validation;
+pub mod verification;
+pub mod watch;
 pub mod webhook;
+
+pub use api::{Ralph, RunOptions, RunSummary};
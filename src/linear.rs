@@ -0,0 +1,293 @@
+//! Linear source (`--prd-source linear:ENG?cycle=current`): a team's issues in a cycle or
+//! project become features, and completed ones are moved to a completed state (the first,
+//! unless `state=` names one) and commented on. `LINEAR_API_KEY` authenticates.
+
+use crate::source::{self, PrdSource, Ticket};
+use anyhow::{bail, Context, Result};
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fmt;
+use std::str::FromStr;
+
+const API_URL: &str = "https://api.linear.app/graphql";
+
+const ISSUES_QUERY: &str = "query Issues($filter: IssueFilter, $after: String) {
+  issues(filter: $filter, first: 100, after: $after) {
+    nodes { identifier title description url createdAt labels { nodes { name } } state { type } }
+    pageInfo { hasNextPage endCursor }
+  }
+}";
+
+const STATES_QUERY: &str = "query Issue($id: String!) {
+  issue(id: $id) { id team { states { nodes { id name type } } } }
+}";
+
+const COMPLETE_MUTATION: &str = "mutation Complete($id: String!, $stateId: String!, $body: String!) {
+  issueUpdate(id: $id, input: { stateId: $stateId }) { success }
+  commentCreate(input: { issueId: $id, body: $body }) { success }
+}";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cycle {
+    Current,
+    Number(u64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinearSource {
+    /// Team key, e.g. `ENG`
+    pub team: String,
+    pub cycle: Option<Cycle>,
+    /// Project name
+    pub project: Option<String>,
+    /// Workflow state completed issues move to
+    pub state: Option<String>,
+}
+
+/// `linear:<TEAM>?cycle=current|<number>` and/or `?project=<name>`, plus `&state=<name>`.
+impl FromStr for LinearSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let expected = "expected linear:<team>?cycle=current|<number> or ?project=<name>";
+        let (team, options) = source::parse_spec(s, "linear", expected)?;
+        let mut source = Self {
+            team: team.to_string(),
+            cycle: None,
+            project: None,
+            state: None,
+        };
+        for (key, value) in options {
+            match key {
+                "cycle" if value == "current" => source.cycle = Some(Cycle::Current),
+                "cycle" => {
+                    source.cycle = Some(Cycle::Number(value.parse().with_context(|| format!("invalid cycle '{value}'"))?));
+                }
+                "project" => source.project = Some(value.to_string()),
+                "state" => source.state = Some(value.to_string()),
+                _ => bail!("unknown option '{key}': {expected}"),
+            }
+        }
+        if source.cycle.is_none() && source.project.is_none() {
+            bail!(expected);
+        }
+        Ok(source)
+    }
+}
+
+impl fmt::Display for LinearSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "linear:{}", self.team)?;
+        let mut options = Vec::new();
+        match self.cycle {
+            Some(Cycle::Current) => options.push("cycle=current".to_string()),
+            Some(Cycle::Number(n)) => options.push(format!("cycle={n}")),
+            None => {}
+        }
+        options.extend(self.project.as_ref().map(|p| format!("project={p}")));
+        options.extend(self.state.as_ref().map(|s| format!("state={s}")));
+        write!(f, "?{}", options.join("&"))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct IssuePage {
+    nodes: Vec<Issue>,
+    #[serde(rename = "pageInfo")]
+    page_info: PageInfo,
+}
+
+#[derive(Debug, Deserialize)]
+struct PageInfo {
+    #[serde(rename = "hasNextPage")]
+    has_next_page: bool,
+    #[serde(rename = "endCursor")]
+    end_cursor: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Issue {
+    identifier: String,
+    title: String,
+    description: Option<String>,
+    url: String,
+    #[serde(rename = "createdAt")]
+    created_at: String,
+    labels: Nodes<Named>,
+    state: State,
+}
+
+#[derive(Debug, Deserialize)]
+struct Nodes<T> {
+    nodes: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Named {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct State {
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+impl From<Issue> for Ticket {
+    fn from(issue: Issue) -> Self {
+        Self {
+            key: issue.identifier,
+            title: issue.title,
+            body: issue.description.unwrap_or_default(),
+            url: issue.url,
+            labels: issue.labels.nodes.into_iter().map(|l| l.name).collect(),
+            complete: issue.state.kind == "completed",
+        }
+    }
+}
+
+impl LinearSource {
+    /// The team's issues in the cycle and/or project, canceled ones left out.
+    fn filter(&self) -> Value {
+        let mut filter = json!({
+            "team": { "key": { "eq": self.team } },
+            "state": { "type": { "neq": "canceled" } },
+        });
+        match self.cycle {
+            Some(Cycle::Current) => filter["cycle"] = json!({ "isActive": { "eq": true } }),
+            Some(Cycle::Number(n)) => filter["cycle"] = json!({ "number": { "eq": n } }),
+            None => {}
+        }
+        if let Some(ref project) = self.project {
+            filter["project"] = json!({ "name": { "eq": project } });
+        }
+        filter
+    }
+
+    /// The state named by `state=`, else the team's first completed state.
+    fn done_state<'a>(&self, states: &'a [State]) -> Result<&'a State> {
+        match self.state {
+            Some(ref name) => states.iter().find(|s| s.name.eq_ignore_ascii_case(name)).with_context(|| {
+                let names: Vec<&str> = states.iter().map(|s| s.name.as_str()).collect();
+                format!("no '{name}' state (available: {})", names.join(", "))
+            }),
+            None => states.iter().find(|s| s.kind == "completed").context("the team has no completed state"),
+        }
+    }
+}
+
+impl PrdSource for LinearSource {
+    fn id_prefix(&self) -> &'static str {
+        "linear-"
+    }
+
+    fn project_name(&self) -> String {
+        self.project.clone().unwrap_or_else(|| self.team.clone())
+    }
+
+    fn fetch(&self) -> BoxFuture<'_, Result<Vec<Ticket>>> {
+        Box::pin(async move {
+            let mut issues = Vec::new();
+            let mut after: Option<String> = None;
+            loop {
+                let data = graphql(ISSUES_QUERY, json!({ "filter": self.filter(), "after": after })).await?;
+                let page: IssuePage =
+                    serde_json::from_value(data["issues"].clone()).context("Failed to parse Linear issues")?;
+                issues.extend(page.nodes);
+                match page.page_info.end_cursor {
+                    Some(cursor) if page.page_info.has_next_page => after = Some(cursor),
+                    _ => break,
+                }
+            }
+            issues.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+            Ok(issues.into_iter().map(Ticket::from).collect())
+        })
+    }
+
+    fn complete<'a>(&'a self, key: &'a str, comment: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let data = graphql(STATES_QUERY, json!({ "id": key })).await?;
+            let id = data["issue"]["id"].as_str().with_context(|| format!("issue {key} not found"))?;
+            let states: Vec<State> = serde_json::from_value(data["issue"]["team"]["states"]["nodes"].clone())
+                .context("Failed to parse Linear workflow states")?;
+            let state = self.done_state(&states)?;
+            graphql(COMPLETE_MUTATION, json!({ "id": id, "stateId": state.id, "body": comment })).await?;
+            Ok(())
+        })
+    }
+}
+
+/// Runs a GraphQL request, returning its `data`.
+async fn graphql(query: &str, variables: Value) -> Result<Value> {
+    let key = std::env::var("LINEAR_API_KEY").context("LINEAR_API_KEY is not set")?;
+    let response = reqwest::Client::new()
+        .post(API_URL)
+        .header("Authorization", key)
+        .json(&json!({ "query": query, "variables": variables }))
+        .send()
+        .await
+        .context("Failed to reach Linear")?;
+    if !response.status().is_success() {
+        bail!("Linear returned {}", response.status());
+    }
+    let mut body: Value = response.json().await.context("Failed to parse Linear response")?;
+    if let Some(error) = body["errors"].as_array().and_then(|errors| errors.first()) {
+        bail!("Linear: {}", error["message"].as_str().unwrap_or("unknown error"));
+    }
+    Ok(body["data"].take())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_source_and_builds_filter() {
+        let source: LinearSource = "linear:ENG?cycle=current&project=Checkout".parse().unwrap();
+        assert_eq!(source.to_string(), "linear:ENG?cycle=current&project=Checkout");
+        let filter = source.filter();
+        assert_eq!(filter["team"]["key"]["eq"], "ENG");
+        assert_eq!(filter["cycle"]["isActive"]["eq"], true);
+        assert_eq!(filter["project"]["name"]["eq"], "Checkout");
+        assert_eq!(source.project_name(), "Checkout");
+
+        let source: LinearSource = "linear:ENG?cycle=12".parse().unwrap();
+        assert_eq!(source.filter()["cycle"]["number"]["eq"], 12);
+
+        for bad in ["linear:ENG", "linear:ENG?cycle=next", "linear:?cycle=current", "linear:ENG?team=x"] {
+            assert!(bad.parse::<LinearSource>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn issues_become_tickets() {
+        let page: IssuePage = serde_json::from_value(json!({
+            "nodes": [{
+                "identifier": "ENG-7", "title": "Log in", "description": "- [ ] Form", "url": "https://linear.app/x/ENG-7",
+                "createdAt": "2026-01-01T00:00:00Z", "labels": { "nodes": [{ "name": "ui" }] }, "state": { "type": "completed" }
+            }],
+            "pageInfo": { "hasNextPage": false, "endCursor": null }
+        }))
+        .unwrap();
+        let ticket = Ticket::from(page.nodes.into_iter().next().unwrap());
+        assert_eq!((ticket.key.as_str(), ticket.complete), ("ENG-7", true));
+        assert_eq!(ticket.labels, ["ui"]);
+    }
+
+    #[test]
+    fn picks_the_done_state() {
+        let state = |name: &str, kind: &str| State { id: name.to_lowercase(), name: name.into(), kind: kind.into() };
+        let states = [state("Todo", "unstarted"), state("Done", "completed"), state("Shipped", "completed")];
+        let source: LinearSource = "linear:ENG?cycle=current".parse().unwrap();
+        assert_eq!(source.done_state(&states).unwrap().name, "Done");
+        let named = LinearSource { state: Some("shipped".into()), ..source.clone() };
+        assert_eq!(named.done_state(&states).unwrap().name, "Shipped");
+        let missing = LinearSource { state: Some("Released".into()), ..source };
+        assert!(missing.done_state(&states).unwrap_err().to_string().contains("available: Todo, Done, Shipped"));
+    }
+}
@@ -0,0 +1,362 @@
+//! `ralph validate`: checks a PRD for mistakes that parse fine, or that serde would only
+//! report one at a time, and points at each with its line and column.
+
+use crate::{
+    diagnostic::Diagnostic,
+    output,
+    prd::{Prd, Status},
+    prd_edit::{
+        self,
+        PathSegment::{self, Index, Key},
+    },
+};
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+const STATUSES: [Status; 4] = [Status::Pending, Status::InProgress, Status::Complete, Status::Blocked];
+
+/// Prints every problem in the PRD at `path`; errors if there are any.
+pub fn run(path: &Path) -> Result<()> {
+    let mut content = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    // Markdown is checked as the JSON `ralph convert` would write, so positions refer to that
+    if crate::markdown::is_markdown(path) {
+        let prd = crate::markdown::parse(&content).with_context(|| format!("Failed to parse {}", path.display()))?;
+        content = serde_json::to_string_pretty(&prd)?;
+    }
+    let problems = check(&content);
+    for problem in &problems {
+        output::error(&problem.to_string());
+        output::blank();
+    }
+    if !problems.is_empty() {
+        bail!("{} has {} problem(s)", path.display(), problems.len());
+    }
+    output::success(&format!("{} is valid", path.display()));
+    Ok(())
+}
+
+/// Every problem found, in document order where it can be located.
+#[must_use]
+pub fn check(content: &str) -> Vec<Diagnostic> {
+    let value: Value = match json5::from_str(content) {
+        Ok(value) => value,
+        Err(e) => return vec![Diagnostic::new(content, &e)],
+    };
+    let mut lint = Lint { content, problems: Vec::new() };
+    lint.verification_commands(&value["verification"]["commands"], &[Key("verification"), Key("commands")], "");
+    if let Some(features) = value["features"].as_array() {
+        lint.features(features);
+    }
+    lint.analysis_patterns(&value["analysis"]);
+    // Types and required fields; unknown statuses are already reported above
+    if let Err(e) = json5::from_str::<Prd>(content) {
+        let diagnostic = Diagnostic::new(content, &e);
+        if !diagnostic.message.starts_with("unknown variant") {
+            lint.problems.push(diagnostic);
+        }
+    }
+    lint.problems.sort_by_key(|d| d.location.unwrap_or((usize::MAX, 0)));
+    lint.problems
+}
+
+struct Lint<'a> {
+    content: &'a str,
+    problems: Vec<Diagnostic>,
+}
+
+impl Lint<'_> {
+    fn report(&mut self, path: &[PathSegment<'_>], message: String, hint: &str) {
+        let offset = prd_edit::locate(self.content, path);
+        self.problems.push(Diagnostic::at(self.content, offset, message, Some(hint.to_string())));
+    }
+
+    fn features(&mut self, features: &[Value]) {
+        let ids: Vec<&str> = features.iter().map(|f| f["id"].as_str().unwrap_or_default()).collect();
+        let mut first_index: HashMap<&str, usize> = HashMap::new();
+        for (index, (feature, id)) in features.iter().zip(&ids).enumerate() {
+            let at = |rest: &[PathSegment<'static>]| [&[Key("features"), Index(index)], rest].concat();
+            if let Some(&first) = first_index.get(id) {
+                self.report(
+                    &at(&[Key("id")]),
+                    format!("duplicate feature id '{id}'"),
+                    &format!("feature {} already uses it; ids must be unique", first + 1),
+                );
+            } else {
+                first_index.insert(id, index);
+            }
+
+            match feature["steps"].as_array() {
+                Some(steps) if steps.is_empty() => {
+                    let message = format!("feature '{id}' has no steps");
+                    self.report(&at(&[Key("steps")]), message, "list at least one step the agent can act on");
+                }
+                Some(steps) => {
+                    let blank = |step: &Value| step.as_str().is_some_and(|s| s.trim().is_empty());
+                    for n in (0..steps.len()).filter(|&n| blank(&steps[n])) {
+                        let message = format!("step {} of feature '{id}' is empty", n + 1);
+                        self.report(&at(&[Key("steps"), Index(n)]), message, "remove it or describe the step");
+                    }
+                }
+                None => {}
+            }
+
+            if let Some(status) = feature["status"].as_str() {
+                if !STATUSES.iter().any(|s| s.as_str() == status) {
+                    self.report(
+                        &at(&[Key("status")]),
+                        format!("feature '{id}' has unknown status '{status}'"),
+                        "status is one of pending, in-progress, complete, blocked",
+                    );
+                }
+            }
+
+            for (n, dependency) in feature["dependsOn"].as_array().into_iter().flatten().enumerate() {
+                let Some(dependency) = dependency.as_str() else { continue };
+                let path = at(&[Key("dependsOn"), Index(n)]);
+                if dependency == *id {
+                    let message = format!("feature '{id}' depends on itself");
+                    self.report(&path, message, "it can never become ready; remove the dependency");
+                } else if !ids.contains(&dependency) {
+                    self.report(
+                        &path,
+                        format!("feature '{id}' depends on unknown feature '{dependency}'"),
+                        "it can never become ready; fix the id or remove the dependency",
+                    );
+                }
+            }
+
+            for key in ["maxIterations", "timeoutSecs"] {
+                if feature[key].as_u64() == Some(0) {
+                    let message = format!("{key} of feature '{id}' is 0");
+                    self.report(&at(&[Key(key)]), message, "use at least 1, or remove it to use the run's setting");
+                }
+            }
+
+            let owner = format!(" of feature '{id}'");
+            self.verification_commands(&feature["verification"], &at(&[Key("verification")]), &owner);
+        }
+
+        for cycle in dependency_cycles(features, &ids) {
+            let path = [Key("features"), Index(cycle[0]), Key("dependsOn")];
+            let names: Vec<&str> = cycle.iter().chain(cycle.first()).map(|&i| ids[i]).collect();
+            self.report(
+                &path,
+                format!("dependency cycle: {}", names.join(" -> ")),
+                "none of these features can become ready; remove one of the dependencies",
+            );
+        }
+    }
+
+    fn analysis_patterns(&mut self, analysis: &Value) {
+        for key in ["loopPatterns", "rateLimitPatterns"] {
+            for (n, pattern) in analysis[key].as_array().into_iter().flatten().enumerate() {
+                let Some(Err(e)) = pattern.as_str().map(regex::Regex::new) else { continue };
+                let error = e.to_string();
+                self.report(
+                    &[Key("analysis"), Key(key), Index(n)],
+                    format!("analysis.{key} entry {} is not a valid regex", n + 1),
+                    error.lines().last().unwrap_or_default().trim_start_matches("error: "),
+                );
+            }
+        }
+    }
+
+    fn verification_commands(&mut self, commands: &Value, path: &[PathSegment<'_>], owner: &str) {
+        for (n, command) in commands.as_array().into_iter().flatten().enumerate() {
+            if command["command"].as_str().is_some_and(|c| c.trim().is_empty()) {
+                let name = command["name"].as_str().unwrap_or_default();
+                let mut path = path.to_vec();
+                path.extend([Index(n), Key("command")]);
+                self.report(
+                    &path,
+                    format!("verification command '{name}'{owner} is empty"),
+                    "an empty command always passes; remove it or fill it in",
+                );
+            }
+        }
+    }
+}
+
+/// Each dependency cycle once, as feature indexes starting from the earliest feature in it.
+fn dependency_cycles(features: &[Value], ids: &[&str]) -> Vec<Vec<usize>> {
+    let edges: Vec<Vec<usize>> = features
+        .iter()
+        .map(|f| {
+            f["dependsOn"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|d| ids.iter().position(|id| Some(*id) == d.as_str()))
+                .collect()
+        })
+        .collect();
+    let mut finished = vec![false; features.len()];
+    let mut cycles = Vec::new();
+    for start in 0..features.len() {
+        if !finished[start] {
+            find_cycles(&edges, &mut vec![start], &mut finished, &mut cycles);
+        }
+    }
+    cycles
+}
+
+/// Depth-first search from the top of `stack`; an edge back into the stack closes a cycle.
+fn find_cycles(edges: &[Vec<usize>], stack: &mut Vec<usize>, finished: &mut [bool], cycles: &mut Vec<Vec<usize>>) {
+    let current = stack[stack.len() - 1];
+    for &next in &edges[current] {
+        if finished[next] {
+            continue;
+        }
+        if let Some(pos) = stack.iter().position(|&i| i == next) {
+            // Self-dependencies are reported on their own
+            if stack.len() - pos > 1 {
+                let mut cycle = stack[pos..].to_vec();
+                let earliest = (0..cycle.len()).min_by_key(|&i| cycle[i]).unwrap_or(0);
+                cycle.rotate_left(earliest);
+                cycles.push(cycle);
+            }
+        } else {
+            stack.push(next);
+            find_cycles(edges, stack, finished, cycles);
+            stack.pop();
+        }
+    }
+    finished[current] = true;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prd(features: &str) -> String {
+        format!(
+            r#"{{
+  "project": {{ "name": "p", "description": "d" }},
+  "verification": {{
+    "commands": [{{ "name": "test", "command": "cargo test", "description": "t" }}],
+    "runAfterEachFeature": false
+  }},
+  "features": [
+{features}
+  ],
+  "completion": {{ "allFeaturesComplete": true, "allVerificationsPassing": true, "marker": "X" }}
+}}"#
+        )
+    }
+
+    fn feature(id: &str, extra: &str) -> String {
+        format!(r#"    {{ "id": "{id}", "category": "c", "description": "d", "steps": ["s"], "status": "pending"{extra} }}"#)
+    }
+
+    fn messages(content: &str) -> Vec<String> {
+        check(content).into_iter().map(|d| d.message).collect()
+    }
+
+    #[test]
+    fn valid_prd_has_no_problems() {
+        let content = prd(&[feature("a", ""), feature("b", r#", "dependsOn": ["a"]"#)].join(",\n"));
+        assert!(check(&content).is_empty(), "{:?}", messages(&content));
+    }
+
+    #[test]
+    fn duplicate_ids_point_at_the_second() {
+        let content = prd(&[feature("a", ""), feature("a", "")].join(",\n"));
+        let problems = check(&content);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].message, "duplicate feature id 'a'");
+        assert_eq!(problems[0].location, Some((9, 13)));
+        assert_eq!(problems[0].hint.as_deref(), Some("feature 1 already uses it; ids must be unique"));
+    }
+
+    #[test]
+    fn empty_steps() {
+        let content = prd(&feature("a", "").replace(r#"["s"]"#, "[]"));
+        assert_eq!(messages(&content), vec!["feature 'a' has no steps"]);
+        let content = prd(&feature("a", "").replace(r#"["s"]"#, r#"["s", " "]"#));
+        assert_eq!(messages(&content), vec!["step 2 of feature 'a' is empty"]);
+    }
+
+    #[test]
+    fn zero_budgets() {
+        let content = prd(&feature("a", r#", "maxIterations": 0, "timeoutSecs": 0"#));
+        assert_eq!(messages(&content), vec!["maxIterations of feature 'a' is 0", "timeoutSecs of feature 'a' is 0"]);
+        let content = prd(&feature("a", r#", "maxIterations": 3, "timeoutSecs": 600"#));
+        assert!(check(&content).is_empty());
+    }
+
+    #[test]
+    fn unknown_status_is_reported_once() {
+        let content = prd(&feature("a", "").replace("pending", "done"));
+        let problems = check(&content);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].message, "feature 'a' has unknown status 'done'");
+        assert!(problems[0].location.is_some());
+    }
+
+    #[test]
+    fn unknown_and_self_dependencies() {
+        let content = prd(&[feature("a", r#", "dependsOn": ["a"]"#), feature("b", r#", "dependsOn": ["x"]"#)].join(",\n"));
+        assert_eq!(
+            messages(&content),
+            vec!["feature 'a' depends on itself", "feature 'b' depends on unknown feature 'x'"]
+        );
+    }
+
+    #[test]
+    fn cycles_are_reported_once() {
+        let content = prd(
+            &[
+                feature("a", r#", "dependsOn": ["c"]"#),
+                feature("b", r#", "dependsOn": ["a"]"#),
+                feature("c", r#", "dependsOn": ["b"]"#),
+                feature("d", r#", "dependsOn": ["a"]"#),
+            ]
+            .join(",\n"),
+        );
+        assert_eq!(messages(&content), vec!["dependency cycle: a -> c -> b -> a"]);
+    }
+
+    #[test]
+    fn empty_verification_commands() {
+        let content = prd(&feature(
+            "a",
+            r#", "verification": [{ "name": "lint", "command": "", "description": "l" }]"#,
+        ))
+        .replace("cargo test", " ");
+        assert_eq!(
+            messages(&content),
+            vec!["verification command 'test' is empty", "verification command 'lint' of feature 'a' is empty"]
+        );
+    }
+
+    #[test]
+    fn invalid_analysis_patterns() {
+        let content = prd(&feature("a", "")).replace(
+            r#""features""#,
+            r#""analysis": { "loopPatterns": ["ok", "(unclosed"], "rateLimitPatterns": ["[z-a]"] },
+  "features""#,
+        );
+        let problems = check(&content);
+        assert_eq!(
+            problems.iter().map(|d| d.message.as_str()).collect::<Vec<_>>(),
+            vec!["analysis.loopPatterns entry 2 is not a valid regex", "analysis.rateLimitPatterns entry 1 is not a valid regex"]
+        );
+        assert_eq!(problems[0].location, Some((7, 40)));
+        assert_eq!(problems[0].hint.as_deref(), Some("unclosed group"));
+    }
+
+    #[test]
+    fn syntax_errors_stop_the_check() {
+        let problems = check("{ \"features\": [ }");
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].location.is_some());
+    }
+
+    #[test]
+    fn missing_fields_are_still_reported() {
+        let content = prd(&feature("a", "").replace(r#""category": "c", "#, ""));
+        assert_eq!(messages(&content), vec!["missing field `category`"]);
+    }
+}
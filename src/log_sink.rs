@@ -0,0 +1,214 @@
+//! Uploads iteration logs to cloud storage as they complete.
+//!
+//! Shells out to the `aws` and `gcloud` CLIs so credentials follow their usual
+//! resolution (env vars, instance metadata, profiles) without extra dependencies.
+
+use crate::output;
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio::process::Command;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogSink {
+    S3 { bucket: String, prefix: String },
+    Gcs { bucket: String, prefix: String },
+}
+
+impl LogSink {
+    pub fn parse(uri: &str) -> Result<Self> {
+        let (scheme, rest) = uri
+            .split_once("://")
+            .with_context(|| format!("Invalid log sink URI: {uri}"))?;
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            bail!("Log sink URI has no bucket: {uri}");
+        }
+        let bucket = bucket.to_string();
+        let prefix = prefix.trim_matches('/').to_string();
+        match scheme {
+            "s3" => Ok(Self::S3 { bucket, prefix }),
+            "gs" => Ok(Self::Gcs { bucket, prefix }),
+            _ => bail!("Unsupported log sink scheme '{scheme}' (expected s3:// or gs://)"),
+        }
+    }
+
+    #[must_use]
+    pub fn destination(&self, key: &str) -> String {
+        let (scheme, bucket, prefix) = match self {
+            Self::S3 { bucket, prefix } => ("s3", bucket, prefix),
+            Self::Gcs { bucket, prefix } => ("gs", bucket, prefix),
+        };
+        if prefix.is_empty() {
+            format!("{scheme}://{bucket}/{key}")
+        } else {
+            format!("{scheme}://{bucket}/{prefix}/{key}")
+        }
+    }
+
+    fn command(&self, local: &Path, dest: &str) -> Command {
+        let mut cmd = match self {
+            Self::S3 { .. } => {
+                let mut c = Command::new("aws");
+                c.args(["s3", "cp", "--only-show-errors"]);
+                c
+            }
+            Self::Gcs { .. } => {
+                let mut c = Command::new("gcloud");
+                c.args(["storage", "cp", "--quiet"]);
+                c
+            }
+        };
+        cmd.arg(local).arg(dest);
+        cmd
+    }
+
+    pub async fn upload(&self, local: &Path, key: &str) -> Result<()> {
+        let dest = self.destination(key);
+        let out = self
+            .command(local, &dest)
+            .output()
+            .await
+            .with_context(|| format!("Failed to run upload command for {dest}"))?;
+        if !out.status.success() {
+            bail!(
+                "Upload to {dest} failed: {}",
+                String::from_utf8_lossy(&out.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A file's length and modification time, to tell when it was rewritten.
+type Stamp = (u64, Option<SystemTime>);
+
+/// Tracks which version of each file has been shipped, so a log is uploaded again only
+/// once it changes, e.g. a verification log or the structured log.
+pub struct LogUploader {
+    sinks: Vec<LogSink>,
+    uploaded: HashMap<PathBuf, Stamp>,
+}
+
+impl LogUploader {
+    pub fn new(uris: &[String]) -> Result<Self> {
+        Ok(Self {
+            sinks: uris.iter().map(|u| LogSink::parse(u)).collect::<Result<_>>()?,
+            uploaded: HashMap::new(),
+        })
+    }
+
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        !self.sinks.is_empty()
+    }
+
+    /// Uploads files in `dir` not yet shipped or changed since, keyed as `<key_prefix>/<file name>`.
+    pub async fn sync_dir(&mut self, dir: &Path, key_prefix: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+        for (path, stamp) in self.pending(dir) {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let key = format!("{key_prefix}/{name}");
+            if self.upload_file(&path, &key).await {
+                self.uploaded.insert(path, stamp);
+            }
+        }
+    }
+
+    /// Files in `dir` that are new or differ from the version last uploaded, sorted.
+    fn pending(&self, dir: &Path) -> Vec<(PathBuf, Stamp)> {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let mut pending: Vec<(PathBuf, Stamp)> = entries
+            .filter_map(Result::ok)
+            .filter_map(|e| {
+                let meta = std::fs::metadata(e.path()).ok().filter(std::fs::Metadata::is_file)?;
+                Some((e.path(), (meta.len(), meta.modified().ok())))
+            })
+            .filter(|(path, stamp)| self.uploaded.get(path) != Some(stamp))
+            .collect();
+        pending.sort();
+        pending
+    }
+
+    /// Uploads a single file (re-uploading if it changed since last time).
+    pub async fn upload_file(&self, path: &Path, key: &str) -> bool {
+        let mut ok = true;
+        for sink in &self.sinks {
+            if let Err(e) = sink.upload(path, key).await {
+                output::warn(&format!("Log upload failed: {e:#}"));
+                ok = false;
+            }
+        }
+        ok
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_s3_uri_with_prefix() {
+        assert_eq!(
+            LogSink::parse("s3://my-bucket/ci/ralph/").unwrap(),
+            LogSink::S3 { bucket: "my-bucket".into(), prefix: "ci/ralph".into() }
+        );
+    }
+
+    #[test]
+    fn parses_gcs_uri_without_prefix() {
+        assert_eq!(
+            LogSink::parse("gs://logs").unwrap(),
+            LogSink::Gcs { bucket: "logs".into(), prefix: String::new() }
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_scheme() {
+        assert!(LogSink::parse("ftp://host/path").is_err());
+    }
+
+    #[test]
+    fn rejects_missing_scheme() {
+        assert!(LogSink::parse("my-bucket/path").is_err());
+    }
+
+    #[test]
+    fn rejects_missing_bucket() {
+        assert!(LogSink::parse("s3:///path").is_err());
+    }
+
+    #[test]
+    fn destination_joins_prefix_and_key() {
+        let sink = LogSink::parse("s3://b/runs").unwrap();
+        assert_eq!(sink.destination("logs/it-1.log"), "s3://b/runs/logs/it-1.log");
+        let sink = LogSink::parse("gs://b").unwrap();
+        assert_eq!(sink.destination("progress.txt"), "gs://b/progress.txt");
+    }
+
+    #[test]
+    fn uploader_without_sinks_is_disabled() {
+        assert!(!LogUploader::new(&[]).unwrap().is_enabled());
+    }
+
+    #[test]
+    fn rewritten_files_are_pending_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("verify-test.log");
+        std::fs::write(&log, "fail\n").unwrap();
+        let mut uploader = LogUploader::new(&["s3://bucket/logs".into()]).unwrap();
+
+        let pending = uploader.pending(dir.path());
+        assert_eq!(pending.iter().map(|(p, _)| p).collect::<Vec<_>>(), [&log]);
+        uploader.uploaded.extend(pending);
+        assert!(uploader.pending(dir.path()).is_empty());
+
+        std::fs::write(&log, "passed\n").unwrap();
+        assert_eq!(uploader.pending(dir.path()).len(), 1);
+    }
+}
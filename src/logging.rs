@@ -0,0 +1,221 @@
+//! ralph's messages as `tracing` events with the `ralph` target. One subscriber carries
+//! them: the console layer hands them to the [`output`] reporter (the colored format by
+//! default), filtered by `-q`, `-v`, and `-vv` and optionally timestamped;
+//! `--structured-log` writes every one of them as a JSON line to a file per run; and
+//! `--otel-endpoint` adds the OpenTelemetry layer.
+//!
+//! If another global subscriber was installed first (an embedding application's), ralph's
+//! messages go straight to the reporter instead.
+
+use crate::{config::Args, output};
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::fmt;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Mutex, OnceLock};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::layer::{Context as LayerContext, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, Layer, Registry};
+
+/// Target of ralph's message events.
+pub const TARGET: &str = "ralph";
+/// Messages kept for `--structured-log` before the run's file is opened.
+const BUFFERED_LINES: usize = 10_000;
+
+pub type OtelLayer = tracing_opentelemetry::OpenTelemetryLayer<Registry, opentelemetry_sdk::trace::Tracer>;
+
+/// Most verbose level shown on the console: 0 errors, 1 warnings, 2 info, 3 debug, 4 trace.
+static CONSOLE_LEVEL: AtomicU8 = AtomicU8::new(2);
+static TIMESTAMPS: AtomicBool = AtomicBool::new(false);
+static FILE: Mutex<LogFile> = Mutex::new(LogFile::Off);
+/// Set once ralph's subscriber is the global one.
+static OTEL: OnceLock<reload::Handle<Option<OtelLayer>, Registry>> = OnceLock::new();
+
+enum LogFile {
+    Off,
+    /// `--structured-log` before the run id, and so the file name, is known
+    Buffered(Vec<String>),
+    Open(BufWriter<std::fs::File>),
+}
+
+/// Applies `-q`, `-v`, `--timestamps`, and `--structured-log`, installing the subscriber
+/// on first use.
+pub fn configure(args: &Args) {
+    let level = match (args.quiet, args.verbose) {
+        (true, _) => 1,
+        (false, v) => 2 + v.min(2),
+    };
+    CONSOLE_LEVEL.store(level, Ordering::Relaxed);
+    TIMESTAMPS.store(args.timestamps, Ordering::Relaxed);
+    let mut file = FILE.lock().unwrap_or_else(|e| e.into_inner());
+    if args.structured_log && matches!(*file, LogFile::Off) {
+        *file = LogFile::Buffered(Vec::new());
+    }
+    drop(file);
+    install();
+}
+
+fn install() {
+    if OTEL.get().is_some() {
+        return;
+    }
+    let (otel, handle) = reload::Layer::new(None::<OtelLayer>);
+    // Spans pass, so the file can list the ones a message was logged in
+    let ours = filter_fn(|meta| meta.is_span() || meta.target() == TARGET);
+    let installed = tracing_subscriber::registry()
+        .with(otel)
+        .with(ConsoleLayer.with_filter(ours.clone()))
+        .with(FileLayer.with_filter(ours))
+        .try_init();
+    if installed.is_ok() {
+        let _ = OTEL.set(handle);
+    }
+}
+
+/// Whether ralph's subscriber receives its messages.
+#[must_use]
+pub fn installed() -> bool {
+    OTEL.get().is_some()
+}
+
+/// Adds the OpenTelemetry layer; only one per process.
+pub fn add_otel(layer: OtelLayer) -> Result<()> {
+    let Some(handle) = OTEL.get() else {
+        bail!("Another tracing subscriber is installed; ralph can't add its OpenTelemetry layer");
+    };
+    handle
+        .modify(|slot| {
+            slot.get_or_insert(layer);
+        })
+        .context("Failed to add the OpenTelemetry layer")
+}
+
+/// Starts writing the structured log to `path`, beginning with the messages buffered so far.
+pub fn open_file(path: &Path) -> Result<()> {
+    let mut file = FILE.lock().unwrap_or_else(|e| e.into_inner());
+    let LogFile::Buffered(ref lines) = *file else {
+        return Ok(());
+    };
+    let mut writer = BufWriter::new(
+        std::fs::File::create(path).with_context(|| format!("Failed to create {}", path.display()))?,
+    );
+    for line in lines {
+        writeln!(writer, "{line}")?;
+    }
+    *file = LogFile::Open(writer);
+    Ok(())
+}
+
+/// Flushes and closes the structured log at the end of a run.
+pub fn close_file() {
+    let mut file = FILE.lock().unwrap_or_else(|e| e.into_inner());
+    if let LogFile::Open(ref mut writer) = *file {
+        let _ = writer.flush();
+    }
+    *file = LogFile::Off;
+}
+
+/// Whether the console shows messages at `level`, for callers that build costly ones.
+#[must_use]
+pub fn console_enabled(level: Level) -> bool {
+    rank(level) <= CONSOLE_LEVEL.load(Ordering::Relaxed)
+}
+
+fn rank(level: Level) -> u8 {
+    match level {
+        Level::ERROR => 0,
+        Level::WARN => 1,
+        Level::INFO => 2,
+        Level::DEBUG => 3,
+        Level::TRACE => 4,
+    }
+}
+
+/// The `kind` (an [`output::Level`]) and `message` fields of a message event.
+#[derive(Default)]
+struct Message {
+    kind: String,
+    text: String,
+}
+
+impl Visit for Message {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "kind" => self.kind = value.to_string(),
+            "message" => self.text = value.to_string(),
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "kind" => self.kind = format!("{value:?}"),
+            "message" => self.text = format!("{value:?}"),
+            _ => {}
+        }
+    }
+}
+
+impl Message {
+    fn of(event: &Event<'_>) -> Self {
+        let mut message = Self::default();
+        event.record(&mut message);
+        message
+    }
+}
+
+struct ConsoleLayer;
+
+impl<S: Subscriber> Layer<S> for ConsoleLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: LayerContext<'_, S>) {
+        if !console_enabled(*event.metadata().level()) {
+            return;
+        }
+        let message = Message::of(event);
+        let level = output::Level::from_name(&message.kind);
+        if TIMESTAMPS.load(Ordering::Relaxed) && !output::is_json() {
+            let time = chrono::Local::now().format("%H:%M:%S");
+            output::report(level, &format!("{time} {}", message.text));
+        } else {
+            output::report(level, &message.text);
+        }
+    }
+}
+
+struct FileLayer;
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for FileLayer {
+    fn on_event(&self, event: &Event<'_>, ctx: LayerContext<'_, S>) {
+        let mut file = FILE.lock().unwrap_or_else(|e| e.into_inner());
+        if matches!(*file, LogFile::Off) {
+            return;
+        }
+        let message = Message::of(event);
+        let spans: Vec<Value> = ctx
+            .event_scope(event)
+            .map(|scope| scope.from_root().map(|span| Value::from(span.name())).collect())
+            .unwrap_or_default();
+        let line = output::record(
+            "log",
+            json!({
+                "level": event.metadata().level().as_str().to_ascii_lowercase(),
+                "kind": message.kind,
+                "message": message.text,
+                "spans": spans,
+            }),
+        );
+        match *file {
+            LogFile::Buffered(ref mut lines) if lines.len() < BUFFERED_LINES => lines.push(line),
+            LogFile::Open(ref mut writer) => {
+                let _ = writeln!(writer, "{line}");
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! `ralph logs`: prints an iteration log, or lists the iterations, from `.ralph/logs`.
+
+use crate::{artifact::IterationArtifact, config::LogsArgs, output, validation};
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+use std::time::Duration;
+
+/// Prints the log chosen by `opts`, or with `--list` one line per iteration.
+pub fn run(prd: &Path, opts: &LogsArgs) -> Result<()> {
+    let logs_dir = validation::prd_dir(prd).join(".ralph").join("logs");
+    let artifacts = IterationArtifact::load_all(&logs_dir);
+    if artifacts.is_empty() {
+        bail!("No iteration logs in {}", logs_dir.display());
+    }
+    let artifacts = in_run(artifacts, opts.run_id.as_deref())?;
+
+    if opts.list {
+        for artifact in artifacts.iter().filter(|a| opts.iteration.is_none_or(|n| a.iteration == n)) {
+            output::log(&list_line(artifact));
+        }
+        return Ok(());
+    }
+
+    let Some(artifact) = select(&artifacts, opts.iteration) else {
+        bail!("No log for iteration {}", opts.iteration.unwrap_or_default());
+    };
+    // Artifacts record the path as the run saw it, which may be relative to another directory
+    let path = artifact.log_path.file_name().map_or(artifact.log_path.clone(), |name| logs_dir.join(name));
+    let content = std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    print!("{content}");
+    Ok(())
+}
+
+/// The artifacts of run `id`, or all of them.
+fn in_run(artifacts: Vec<IterationArtifact>, id: Option<&str>) -> Result<Vec<IterationArtifact>> {
+    let Some(id) = id else {
+        return Ok(artifacts);
+    };
+    let artifacts: Vec<IterationArtifact> =
+        artifacts.into_iter().filter(|a| a.run_id.as_deref() == Some(id)).collect();
+    if artifacts.is_empty() {
+        bail!("No iteration logs for run '{id}'");
+    }
+    Ok(artifacts)
+}
+
+/// The most recent artifact, or the most recent for `iteration`.
+fn select(artifacts: &[IterationArtifact], iteration: Option<u32>) -> Option<&IterationArtifact> {
+    artifacts.iter().rev().find(|a| iteration.is_none_or(|n| a.iteration == n))
+}
+
+fn list_line(artifact: &IterationArtifact) -> String {
+    let mut line = format!(
+        "{} #{} {} {} ({})",
+        artifact.run_id.as_deref().unwrap_or("-"),
+        artifact.iteration,
+        artifact.started_at,
+        artifact.result,
+        output::format_duration(Duration::from_secs(artifact.duration_secs))
+    );
+    if let Some(feature) = &artifact.feature_id {
+        line.push_str(&format!(" {feature}"));
+    }
+    if let Some(cost) = artifact.cost_usd {
+        line.push_str(&format!(" ${cost:.2}"));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ArtifactBuilder;
+
+    fn artifact(run_id: &str, iteration: u32, started_at: &str) -> ArtifactBuilder {
+        ArtifactBuilder::new(iteration).run_id(run_id).started_at(started_at)
+    }
+
+    fn artifacts() -> Vec<IterationArtifact> {
+        vec![
+            artifact("a", 1, "2026-01-01T10:00:00+00:00").build(),
+            artifact("a", 2, "2026-01-01T10:10:00+00:00").build(),
+            artifact("b", 1, "2026-01-02T10:00:00+00:00").build(),
+        ]
+    }
+
+    #[test]
+    fn selects_the_latest_log() {
+        let artifacts = artifacts();
+        let latest = select(&artifacts, None).unwrap();
+        assert_eq!((latest.run_id.as_deref(), latest.iteration), (Some("b"), 1));
+        let second = select(&artifacts, Some(2)).unwrap();
+        assert_eq!((second.run_id.as_deref(), second.iteration), (Some("a"), 2));
+        assert!(select(&artifacts, Some(3)).is_none());
+    }
+
+    #[test]
+    fn filters_by_run() {
+        let run = in_run(artifacts(), Some("a")).unwrap();
+        assert_eq!(run.len(), 2);
+        assert_eq!(select(&run, Some(1)).unwrap().started_at, "2026-01-01T10:00:00+00:00");
+        assert!(in_run(artifacts(), Some("c")).is_err());
+    }
+
+    #[test]
+    fn list_line_shows_outcome_feature_and_cost() {
+        let a = artifact("a", 2, "2026-01-01T10:10:00+00:00").duration_secs(75).feature("feat-1").cost_usd(0.5);
+        assert_eq!(list_line(&a.build()), "a #2 2026-01-01T10:10:00+00:00 continue (1m 15s) feat-1 $0.50");
+    }
+
+    #[test]
+    fn prints_from_the_prd_logs_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let logs_dir = dir.path().join(".ralph/logs");
+        let a = artifact("a", 1, "2026-01-01T10:00:00+00:00").in_dir(&logs_dir).write().unwrap();
+        std::fs::write(&a.log_path, "agent output\n").unwrap();
+
+        let opts = LogsArgs { list: false, iteration: None, run_id: None };
+        run(&dir.path().join("prd.jsonc"), &opts).unwrap();
+        let opts = LogsArgs { list: false, iteration: Some(4), run_id: None };
+        assert!(run(&dir.path().join("prd.jsonc"), &opts).is_err());
+    }
+}
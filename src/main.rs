@@ -1,27 +1,116 @@
 use anyhow::Result;
-use clap::Parser;
-use ralph_rs::{config::Args, output, prd, prompt, runner};
+use ralph_rs::{
+    config::{Args, Command, ImportSource, InitTarget},
+    bench, clean, estimate, graph, history, lint, logging, logs, markdown, mcp, move_feature, multi_prd, output, prd, prompt, report, runner, session, status, stop, todos, watch, webhook,
+};
 use std::path::Path;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args = Args::parse();
+    let args = Args::load()?;
+    output::set_format(args.output_format);
+    output::set_color(args.color_choice());
+    logging::configure(&args);
 
-    // Handle --init flag
-    if args.init {
-        prd::generate_template(&args.prd)?;
-        output::success(&format!("Created template PRD at {}", args.prd.display()));
-        return Ok(());
+    if let Some(command) = args.command.as_ref().filter(|c| !matches!(c, Command::Run)) {
+        return match command {
+            Command::Run => unreachable!("handled below"),
+            Command::Init(opts) => match opts.target {
+                InitTarget::Prd => init_prd(&args.prd),
+                InitTarget::Prompt => init_prompt(),
+            },
+            Command::Logs(opts) => logs::run(&args.prd, opts),
+            Command::Clean(opts) => clean::run(&args, opts),
+            Command::History { action } => history::run(&args.prd, action.as_ref()),
+            Command::Replay(opts) => session::replay(&args.prd, opts),
+            Command::Status => status::run(&args),
+            Command::Report(opts) => report::run(&args, opts),
+            Command::Import {
+                source: ImportSource::Todos(opts),
+            } => {
+                let (comments, features) = todos::import(opts, &args.prd)?;
+                output::success(&format!(
+                    "Created {} with {features} features from {comments} comments",
+                    args.prd.display()
+                ));
+                Ok(())
+            }
+            Command::Estimate => estimate::run(&args).map(|_| ()),
+            Command::Bench(opts) => {
+                let result = bench::run(&args, opts).await;
+                webhook::flush().await;
+                result
+            }
+            Command::Graph(opts) => {
+                let prd = prd::Prd::load(&args.prd)?;
+                print!("{}", graph::render(&prd, opts.format));
+                Ok(())
+            }
+            Command::Schema => {
+                print!("{}", prd::schema()?);
+                Ok(())
+            }
+            Command::Validate => lint::run(&args.prd),
+            Command::Watch(opts) => match opts.remote {
+                Some(ref url) => watch::remote(url).await,
+                None => watch::local(&args).await,
+            },
+            Command::MoveFeature(opts) => {
+                let moved = move_feature::run(&args.prd, &opts.to, &opts.id, opts.new_id.as_deref())?;
+                output::success(&format!(
+                    "Moved '{}' to {} ({} iteration logs)",
+                    moved.id,
+                    opts.to.display(),
+                    moved.logs
+                ));
+                for id in &moved.dependents {
+                    output::warn(&format!(
+                        "'{id}' in {} depends on '{}', which is no longer in that PRD",
+                        args.prd.display(),
+                        opts.id
+                    ));
+                }
+                Ok(())
+            }
+            Command::Mcp => mcp::run(&args).await,
+            Command::Convert(opts) => {
+                let (path, features) = markdown::convert(&args.prd, opts)?;
+                output::success(&format!("Created {} with {features} features", path.display()));
+                Ok(())
+            }
+        };
     }
 
-    // Handle --init-prompt flag
+    // --init and --init-prompt are the older spellings of `ralph init`
+    if args.init {
+        return init_prd(&args.prd);
+    }
     if args.init_prompt {
-        let path = Path::new("prompt.md");
-        prompt::generate_prompt_template(path)?;
-        output::success(&format!("Created prompt template at {}", path.display()));
-        return Ok(());
+        return init_prompt();
+    }
+
+    // Run the main Ralph loop, once per PRD with --prd-dir or --prd-manifest
+    let result = if args.prd_dir.is_some() || args.prd_manifest.is_some() {
+        multi_prd::run(args).await
+    } else {
+        runner::run(args).await
+    };
+    webhook::flush().await;
+    match result {
+        Err(e) if e.is::<stop::OperatorStop>() => std::process::exit(stop::EXIT_CODE),
+        result => result,
     }
+}
+
+fn init_prd(path: &Path) -> Result<()> {
+    prd::generate_template(path)?;
+    output::success(&format!("Created template PRD at {}", path.display()));
+    Ok(())
+}
 
-    // Run the main Ralph loop
-    runner::run(args).await
+fn init_prompt() -> Result<()> {
+    let path = Path::new("prompt.md");
+    prompt::generate_prompt_template(path)?;
+    output::success(&format!("Created prompt template at {}", path.display()));
+    Ok(())
 }
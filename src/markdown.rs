@@ -0,0 +1,325 @@
+//! Markdown PRDs: a requirements file written for people, read into a [`Prd`].
+//!
+//! ````markdown
+//! # my-project
+//!
+//! What the project does.
+//!
+//! ## Verification
+//!
+//! ```sh
+//! check: cargo check
+//! cargo test
+//! ```
+//!
+//! ## auth-login: Users can log in
+//!
+//! Status: in-progress
+//! Priority: 1
+//! Depends on: db-schema
+//!
+//! - [ ] Add a /login route
+//! - [x] Hash passwords
+//!
+//! Anything else becomes the feature's notes.
+//! ````
+//!
+//! The `#` heading names the project and the text under it describes it. Every other `##`
+//! heading is a feature: `id: description`, or just a description the id is made from.
+//! Checklist items are its steps, and `Category`, `Status`, `Priority`, `Estimate`,
+//! `Phase`, and `Depends on` lines set those fields. Lines in the fenced block under
+//! `## Verification` are commands, `name: command` or just the command. Completion
+//! settings come from the `ralph init` template.
+
+use crate::atomic;
+use crate::config::ConvertArgs;
+use crate::prd::{self, Feature, Prd, Status, VerifyCommand};
+use crate::todos::slugify;
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+const VERIFICATION_HEADING: &str = "verification";
+
+/// True for `.md` and `.markdown` files.
+#[must_use]
+pub fn is_markdown(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("md") || e.eq_ignore_ascii_case("markdown"))
+}
+
+pub fn parse(content: &str) -> Result<Prd> {
+    let mut prd = prd::template()?;
+    prd.verification.commands.clear();
+    prd.features.clear();
+
+    let mut name = None;
+    let mut description = Vec::new();
+    let mut section: Option<Section> = None;
+    let mut in_fence = false;
+    for (index, line) in content.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            if let Some(Section::Feature(ref mut feature)) = section {
+                feature.notes.push(line.to_string());
+            }
+            continue;
+        }
+        if !in_fence {
+            if let Some(title) = trimmed.strip_prefix("# ") {
+                if name.is_some() {
+                    bail!("Line {}: only one `# Project name` heading is allowed", index + 1);
+                }
+                name = Some(title.trim().to_string());
+                continue;
+            }
+            if let Some(heading) = trimmed.strip_prefix("## ") {
+                finish(&mut prd, section.take())?;
+                section = Some(if heading.trim().eq_ignore_ascii_case(VERIFICATION_HEADING) {
+                    Section::Verification
+                } else {
+                    Section::Feature(Box::new(Draft::new(heading.trim())))
+                });
+                continue;
+            }
+        }
+        match section {
+            None => description.push(trimmed.to_string()),
+            Some(Section::Verification) if in_fence => {
+                if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                    prd.verification.commands.push(command(trimmed));
+                }
+            }
+            Some(Section::Verification) => {}
+            Some(Section::Feature(ref mut feature)) if in_fence => feature.notes.push(line.to_string()),
+            Some(Section::Feature(ref mut feature)) => {
+                feature.line(line).with_context(|| format!("Line {}", index + 1))?;
+            }
+        }
+    }
+    finish(&mut prd, section)?;
+
+    prd.project.name = name.context("Markdown PRD needs a `# Project name` heading")?;
+    prd.project.description = paragraph(&description);
+    Ok(prd)
+}
+
+/// `ralph convert`: writes the Markdown PRD at `prd_path` as canonical PRD JSON.
+/// Returns the path written and the number of features.
+pub fn convert(prd_path: &Path, args: &ConvertArgs) -> Result<(PathBuf, usize)> {
+    let output = args.output.clone().unwrap_or_else(|| prd_path.with_extension("jsonc"));
+    if output == prd_path {
+        bail!("{} would overwrite the Markdown PRD; pass --output", output.display());
+    }
+    if output.exists() && !args.force {
+        bail!("PRD file already exists: {} (use --force to overwrite)", output.display());
+    }
+    let content = std::fs::read_to_string(prd_path)
+        .with_context(|| format!("Failed to read PRD file: {}", prd_path.display()))?;
+    let prd = parse(&content).with_context(|| format!("Failed to parse Markdown PRD: {}", prd_path.display()))?;
+    let json = serde_json::to_string_pretty(&prd)?;
+    atomic::write(&output, json + "\n").with_context(|| format!("Failed to write PRD file: {}", output.display()))?;
+    Ok((output, prd.features.len()))
+}
+
+enum Section {
+    Verification,
+    Feature(Box<Draft>),
+}
+
+/// A feature being read, with the lines that aren't fields or steps.
+struct Draft {
+    feature: Feature,
+    notes: Vec<String>,
+}
+
+impl Draft {
+    fn new(heading: &str) -> Self {
+        let (id, description) = match heading.split_once(": ") {
+            Some((id, description)) if is_id(id) => (id.to_string(), description.trim().to_string()),
+            _ => (slugify(heading), heading.to_string()),
+        };
+        Self {
+            feature: Feature {
+                id,
+                category: "functional".into(),
+                description,
+                ..Default::default()
+            },
+            notes: Vec::new(),
+        }
+    }
+
+    fn line(&mut self, line: &str) -> Result<()> {
+        let trimmed = line.trim();
+        if let Some(step) = checklist_item(trimmed) {
+            self.feature.steps.push(step.to_string());
+            return Ok(());
+        }
+        let Some((key, value)) = trimmed.split_once(':').map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim())) else {
+            self.notes.push(line.to_string());
+            return Ok(());
+        };
+        let feature = &mut self.feature;
+        match key.as_str() {
+            "category" => feature.category = value.to_string(),
+            "status" => feature.status = status(value)?,
+            "priority" => feature.priority = Some(value.parse().with_context(|| format!("Invalid priority '{value}'"))?),
+            "estimate" => feature.estimate = Some(value.parse().with_context(|| format!("Invalid estimate '{value}'"))?),
+            "phase" => feature.phase = Some(value.to_string()),
+            "depends on" => {
+                feature.depends_on = value.split(',').map(str::trim).filter(|d| !d.is_empty()).map(String::from).collect();
+            }
+            _ => self.notes.push(line.to_string()),
+        }
+        Ok(())
+    }
+}
+
+fn finish(prd: &mut Prd, section: Option<Section>) -> Result<()> {
+    if let Some(Section::Feature(draft)) = section {
+        let Draft { mut feature, notes } = *draft;
+        if prd.features.iter().any(|f| f.id == feature.id) {
+            bail!("Duplicate feature id '{}'", feature.id);
+        }
+        let notes = paragraph(&notes);
+        feature.notes = (!notes.is_empty()).then_some(notes);
+        prd.features.push(feature);
+    }
+    Ok(())
+}
+
+/// The text of a `- [ ]` or `- [x]` task list item.
+pub(crate) fn checklist_item(line: &str) -> Option<&str> {
+    let trimmed = line.trim();
+    let item = trimmed.strip_prefix("- ").or_else(|| trimmed.strip_prefix("* "))?;
+    ["[ ] ", "[x] ", "[X] "].iter().find_map(|c| item.strip_prefix(c)).map(str::trim)
+}
+
+/// Lines joined with surrounding blank lines dropped.
+fn paragraph(lines: &[String]) -> String {
+    lines.join("\n").trim().to_string()
+}
+
+/// Feature ids are a single word such as `auth-login` or `feat_2`.
+fn is_id(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+fn status(value: &str) -> Result<Status> {
+    serde_json::from_value(serde_json::Value::String(value.to_ascii_lowercase()))
+        .with_context(|| format!("Invalid status '{value}': expected pending, in-progress, complete, or blocked"))
+}
+
+/// `name: command`, or a command named after its first two words.
+fn command(line: &str) -> VerifyCommand {
+    let (name, command) = match line.split_once(": ") {
+        Some((name, command)) if is_id(name) => (name.to_string(), command.trim()),
+        _ => (slugify(&line.split_whitespace().take(2).collect::<Vec<_>>().join(" ")), line),
+    };
+    VerifyCommand {
+        name,
+        command: command.to_string(),
+        description: String::new(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MARKDOWN: &str = "# shop
+
+An online shop.
+Built with Rust.
+
+## Verification
+
+```sh
+check: cargo check
+# comments are skipped
+cargo test --all
+```
+
+## auth-login: Users can log in
+
+Status: In-Progress
+Priority: 1
+Depends on: db-schema, sessions
+
+- [ ] Add a /login route
+- [x] Hash passwords
+
+Use argon2.
+
+```rust
+fn login() {}
+```
+
+## Show order history
+
+Category: ui
+";
+
+    #[test]
+    fn reads_project_verification_and_features() {
+        let prd = parse(MARKDOWN).unwrap();
+        assert_eq!(prd.project.name, "shop");
+        assert_eq!(prd.project.description, "An online shop.\nBuilt with Rust.");
+        let commands: Vec<_> = prd.verification.commands.iter().map(|c| (c.name.as_str(), c.command.as_str())).collect();
+        assert_eq!(commands, [("check", "cargo check"), ("cargo-test", "cargo test --all")]);
+
+        let login = &prd.features[0];
+        assert_eq!((login.id.as_str(), login.description.as_str()), ("auth-login", "Users can log in"));
+        assert_eq!(login.status, Status::InProgress);
+        assert_eq!(login.priority, Some(1));
+        assert_eq!(login.depends_on, ["db-schema", "sessions"]);
+        assert_eq!(login.steps, ["Add a /login route", "Hash passwords"]);
+        assert_eq!(login.notes.as_deref(), Some("Use argon2.\n\n```rust\nfn login() {}\n```"));
+
+        let history = &prd.features[1];
+        assert_eq!((history.id.as_str(), history.category.as_str()), ("show-order-history", "ui"));
+        assert_eq!(history.status, Status::Pending);
+        assert_eq!(history.notes, None);
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let prd = parse(MARKDOWN).unwrap();
+        let json = serde_json::to_string_pretty(&prd).unwrap();
+        let reparsed = Prd::parse(&json).unwrap();
+        assert_eq!(reparsed.features.len(), 2);
+        assert_eq!(reparsed.completion.marker, prd.completion.marker);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(parse("## a: no project").unwrap_err().to_string().contains("# Project name"));
+        let err = parse("# p\n\n## a: x\n\nStatus: done\n").unwrap_err();
+        assert!(format!("{err:#}").contains("Line 5: Invalid status 'done'"));
+        assert!(parse("# p\n## a: x\n## a: y\n").unwrap_err().to_string().contains("Duplicate feature id 'a'"));
+    }
+
+    #[test]
+    fn markdown_extensions() {
+        assert!(is_markdown(Path::new("docs/PRD.md")));
+        assert!(!is_markdown(Path::new("prd.jsonc")));
+    }
+
+    #[test]
+    fn convert_writes_a_loadable_prd() {
+        let dir = tempfile::tempdir().unwrap();
+        let md = dir.path().join("PRD.md");
+        std::fs::write(&md, MARKDOWN).unwrap();
+        let args = ConvertArgs { output: None, force: false };
+        let (written, features) = convert(&md, &args).unwrap();
+        assert_eq!((written, features), (dir.path().join("PRD.jsonc"), 2));
+        assert_eq!(Prd::load(&dir.path().join("PRD.jsonc")).unwrap().features[0].id, "auth-login");
+        // Markdown loads directly too
+        assert_eq!(Prd::load(&md).unwrap().features.len(), 2);
+
+        let err = convert(&md, &args).unwrap_err();
+        assert!(err.to_string().contains("--force"));
+        convert(&md, &ConvertArgs { output: None, force: true }).unwrap();
+    }
+}
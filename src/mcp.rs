@@ -0,0 +1,391 @@
+//! `ralph mcp`: a Model Context Protocol server on stdin/stdout, so other agents and IDEs
+//! can read the PRD's status and progress log and drive runs. `start` launches `ralph` with
+//! the options given before `mcp`; `pause`, `resume`, `skip_feature`, and
+//! `stop_after_iteration` go through `.ralph/control`, reaching whichever run is going.
+//!
+//! Messages are newline-delimited JSON-RPC 2.0. Only tools are offered.
+
+use crate::{
+    config::Args,
+    control::{self, Command},
+    prd::Prd,
+    progress, status, validation,
+};
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use serde_json::{json, Value};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+/// Newest first; an unknown version the client asks for gets the newest.
+const PROTOCOL_VERSIONS: [&str; 3] = ["2025-06-18", "2025-03-26", "2024-11-05"];
+/// Progress entries returned when the call doesn't say.
+const DEFAULT_PROGRESS_ENTRIES: usize = 5;
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// The control commands offered as tools, by tool name.
+const CONTROL_TOOLS: [(&str, Command, &str); 4] = [
+    ("pause", Command::Pause, "Hold the run before its next iteration"),
+    ("resume", Command::Resume, "Continue a paused run"),
+    ("skip_feature", Command::SkipFeature, "Mark the feature the next iteration would work on blocked"),
+    ("stop_after_iteration", Command::StopAfterIteration, "Let the current iteration finish, then end the run"),
+];
+
+pub struct Server {
+    args: Args,
+    ralph_dir: PathBuf,
+    /// What `start` runs: this executable...
+    exe: PathBuf,
+    /// ...with the options given before `mcp`
+    run_args: Vec<OsString>,
+    /// The run `start` launched, if any
+    run: Option<tokio::process::Child>,
+}
+
+impl Server {
+    #[must_use]
+    pub fn new(args: Args, exe: PathBuf, run_args: Vec<OsString>) -> Self {
+        let ralph_dir = validation::prd_dir(&args.prd).join(".ralph");
+        Self { args, ralph_dir, exe, run_args, run: None }
+    }
+
+    /// The response to one message; None for notifications.
+    pub fn handle(&mut self, message: &str) -> Option<Value> {
+        let request: Value = match serde_json::from_str(message) {
+            Ok(request) => request,
+            Err(e) => return Some(error(Value::Null, PARSE_ERROR, &format!("Invalid JSON: {e}"))),
+        };
+        let id = request.get("id").cloned()?;
+        let method = request["method"].as_str().unwrap_or_default();
+        let params = &request["params"];
+        Some(match method {
+            "initialize" => {
+                let requested = params["protocolVersion"].as_str().unwrap_or_default();
+                let version = PROTOCOL_VERSIONS.into_iter().find(|v| *v == requested).unwrap_or(PROTOCOL_VERSIONS[0]);
+                respond(
+                    id,
+                    json!({
+                        "protocolVersion": version,
+                        "capabilities": { "tools": {} },
+                        "serverInfo": { "name": "ralph", "version": env!("CARGO_PKG_VERSION") },
+                    }),
+                )
+            }
+            "ping" => respond(id, json!({})),
+            "tools/list" => respond(id, json!({ "tools": tools() })),
+            "tools/call" => {
+                let Some(name) = params["name"].as_str() else {
+                    return Some(error(id, INVALID_PARAMS, "tools/call needs a tool name"));
+                };
+                let result = match self.call(name, &params["arguments"]) {
+                    Ok(text) => json!({ "content": [{ "type": "text", "text": text }], "isError": false }),
+                    Err(e) => json!({ "content": [{ "type": "text", "text": format!("{e:#}") }], "isError": true }),
+                };
+                respond(id, result)
+            }
+            _ => error(id, METHOD_NOT_FOUND, &format!("Unknown method '{method}'")),
+        })
+    }
+
+    fn call(&mut self, name: &str, arguments: &Value) -> Result<String> {
+        if let Some((_, command, _)) = CONTROL_TOOLS.iter().find(|(tool, _, _)| *tool == name) {
+            control::send(&self.ralph_dir, *command)?;
+            return Ok(format!("Sent '{}'; the run applies it before its next iteration", command.as_str()));
+        }
+        match name {
+            "status" => Ok(serde_json::to_string_pretty(&self.status()?)?),
+            "progress" => {
+                let entries = match arguments.get("entries") {
+                    None | Some(Value::Null) => DEFAULT_PROGRESS_ENTRIES,
+                    Some(n) => n.as_u64().context("entries must be a non-negative integer")? as usize,
+                };
+                self.progress(entries)
+            }
+            "start" => {
+                let extra = match arguments.get("args") {
+                    None | Some(Value::Null) => Vec::new(),
+                    Some(Value::Array(items)) => items
+                        .iter()
+                        .map(|item| item.as_str().map(OsString::from).context("args must be strings"))
+                        .collect::<Result<_>>()?,
+                    Some(_) => bail!("args must be an array of strings"),
+                };
+                self.start(extra)
+            }
+            _ => bail!("Unknown tool '{name}'"),
+        }
+    }
+
+    /// Features with their status, counts, the last iteration, and the run `start` launched.
+    fn status(&mut self) -> Result<Value> {
+        let overview = status::collect(&self.args)?;
+        let prd = Prd::load(&self.args.prd)?;
+        let c = &overview.counts;
+        Ok(json!({
+            "project": overview.project,
+            "features": prd.features.iter().map(|f| json!({
+                "id": f.id,
+                "status": f.status,
+                "description": f.description,
+            })).collect::<Vec<_>>(),
+            "counts": {
+                "total": overview.total,
+                "pending": c.pending,
+                "inProgress": c.in_progress,
+                "complete": c.complete,
+                "blocked": c.blocked,
+            },
+            "lastIteration": overview.last_iteration,
+            "branch": overview.git.as_ref().map(|g| &g.branch),
+            "run": self.run_state(),
+        }))
+    }
+
+    /// The pid of the run `start` launched, and its exit code once it has finished.
+    fn run_state(&mut self) -> Value {
+        let Some(child) = self.run.as_mut() else {
+            return Value::Null;
+        };
+        match child.try_wait() {
+            Ok(None) => json!({ "pid": child.id(), "running": true }),
+            Ok(Some(status)) => json!({ "running": false, "exitCode": status.code() }),
+            Err(e) => json!({ "running": false, "error": e.to_string() }),
+        }
+    }
+
+    fn progress(&self, entries: usize) -> Result<String> {
+        let prd = Prd::load(&self.args.prd)?;
+        let path = prd.progress_path(&self.args.prd, self.args.progress_path.as_deref());
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok("(no progress yet)".to_string()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        Ok(progress::last_entries(&content, entries).to_string())
+    }
+
+    /// Launches a run in the background, its output going to `.ralph/mcp-run.log`.
+    fn start(&mut self, extra: Vec<OsString>) -> Result<String> {
+        if let Some(child) = self.run.as_mut() {
+            if child.try_wait()?.is_none() {
+                bail!("A run started from here is still going (pid {})", child.id().unwrap_or_default());
+            }
+        }
+        std::fs::create_dir_all(&self.ralph_dir)
+            .with_context(|| format!("Failed to create {}", self.ralph_dir.display()))?;
+        let log_path = log_path(&self.ralph_dir);
+        let log = std::fs::File::create(&log_path).with_context(|| format!("Failed to create {}", log_path.display()))?;
+        let child = tokio::process::Command::new(&self.exe)
+            .args(&self.run_args)
+            .args(&extra)
+            .stdin(Stdio::null())
+            .stdout(Stdio::from(log.try_clone()?))
+            .stderr(Stdio::from(log))
+            .spawn()
+            .with_context(|| format!("Failed to start {}", self.exe.display()))?;
+        let pid = child.id().unwrap_or_default();
+        self.run = Some(child);
+        Ok(format!("Started run (pid {pid}); output in {}", log_path.display()))
+    }
+}
+
+#[must_use]
+pub fn log_path(ralph_dir: &Path) -> PathBuf {
+    ralph_dir.join("mcp-run.log")
+}
+
+fn tools() -> Vec<Value> {
+    let no_arguments = json!({ "type": "object", "properties": {} });
+    let mut tools = vec![
+        json!({
+            "name": "status",
+            "description": "Features with their status, counts, the last iteration, and the run started here",
+            "inputSchema": no_arguments,
+        }),
+        json!({
+            "name": "progress",
+            "description": "The latest entries of the progress log",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "entries": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "description": format!("How many entries, 0 for all [default: {DEFAULT_PROGRESS_ENTRIES}]"),
+                    },
+                },
+            },
+        }),
+        json!({
+            "name": "start",
+            "description": "Start a run in the background with the options ralph mcp was given",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "args": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Extra ralph options, e.g. [\"--max-iterations\", \"3\"]",
+                    },
+                },
+            },
+        }),
+    ];
+    tools.extend(CONTROL_TOOLS.iter().map(|(name, _, description)| {
+        json!({ "name": name, "description": description, "inputSchema": no_arguments })
+    }));
+    tools
+}
+
+fn respond(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn error(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// The command line without the `mcp` subcommand: the options a started run gets.
+fn run_args(argv: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+    let mut argv: Vec<OsString> = argv.into_iter().collect();
+    // Options go on either side of the subcommand, and one may have the value `mcp`
+    let without = |at: usize| argv.iter().enumerate().filter(move |&(i, _)| i != at).map(|(_, arg)| arg.clone());
+    let subcommand = (1..argv.len())
+        .rev()
+        .filter(|&at| argv[at] == "mcp")
+        .find(|&at| Args::try_parse_from(without(at)).is_ok_and(|args| args.command.is_none()));
+    if let Some(at) = subcommand {
+        argv.remove(at);
+    }
+    argv.remove(0);
+    argv
+}
+
+/// `ralph mcp`: serves until stdin closes. Nothing else may write to stdout meanwhile.
+pub async fn run(args: &Args) -> Result<()> {
+    let exe = std::env::current_exe().context("Failed to find the ralph executable")?;
+    let mut server = Server::new(args.clone(), exe, run_args(std::env::args_os()));
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+    while let Some(line) = lines.next_line().await.context("Failed to read stdin")? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = server.handle(&line) {
+            stdout.write_all(format!("{response}\n").as_bytes()).await?;
+            stdout.flush().await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prd::Status;
+    use crate::testing::{FeatureBuilder, PrdBuilder};
+    use clap::Parser;
+
+    fn server(exe: &str) -> (tempfile::TempDir, Server) {
+        let dir = tempfile::tempdir().unwrap();
+        let prd_path = dir.path().join("prd.jsonc");
+        PrdBuilder::new()
+            .feature(FeatureBuilder::new("a").status(Status::Complete))
+            .feature(FeatureBuilder::new("b").status(Status::InProgress))
+            .write(&prd_path)
+            .unwrap();
+        let args = Args::try_parse_from(["ralph", "--prd", prd_path.to_str().unwrap(), "mcp"]).unwrap();
+        (dir, Server::new(args, exe.into(), vec!["-c".into(), "echo started \"$@\"".into(), "ralph".into()]))
+    }
+
+    fn call(server: &mut Server, name: &str, arguments: Value) -> (bool, String) {
+        let request = json!({
+            "jsonrpc": "2.0", "id": 7, "method": "tools/call", "params": { "name": name, "arguments": arguments },
+        });
+        let response = server.handle(&request.to_string()).unwrap();
+        let result = &response["result"];
+        (result["isError"].as_bool().unwrap(), result["content"][0]["text"].as_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn initializes_and_lists_tools() {
+        let (_dir, mut server) = server("sh");
+        let init =
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "protocolVersion": "2024-11-05" } });
+        let response = server.handle(&init.to_string()).unwrap();
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["protocolVersion"], "2024-11-05");
+        assert_eq!(response["result"]["serverInfo"]["name"], "ralph");
+        assert!(server.handle(r#"{ "jsonrpc": "2.0", "method": "notifications/initialized" }"#).is_none());
+
+        let response = server.handle(r#"{ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }"#).unwrap();
+        let names: Vec<&str> =
+            response["result"]["tools"].as_array().unwrap().iter().map(|t| t["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["status", "progress", "start", "pause", "resume", "skip_feature", "stop_after_iteration"]);
+
+        let unknown = server.handle(r#"{ "jsonrpc": "2.0", "id": 3, "method": "resources/list" }"#).unwrap();
+        assert_eq!(unknown["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(server.handle("{").unwrap()["error"]["code"], PARSE_ERROR);
+    }
+
+    #[test]
+    fn reports_status_and_progress() {
+        let (dir, mut server) = server("sh");
+        let (is_error, text) = call(&mut server, "status", json!({}));
+        assert!(!is_error, "{text}");
+        let status: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(status["counts"]["complete"], 1);
+        assert_eq!(status["features"][1], json!({ "id": "b", "status": "in-progress", "description": "Implement b" }));
+        assert_eq!(status["run"], Value::Null);
+
+        assert_eq!(call(&mut server, "progress", json!({})).1, "(no progress yet)");
+        let progress = "## Iteration 1\n\nDid a\n\n## Iteration 2\n\nStarted b\n";
+        std::fs::write(dir.path().join("progress.txt"), progress).unwrap();
+        assert_eq!(call(&mut server, "progress", json!({ "entries": 1 })).1, "## Iteration 2\n\nStarted b");
+        assert!(call(&mut server, "progress", json!({ "entries": "all" })).0);
+        assert!(call(&mut server, "bogus", json!({})).0);
+    }
+
+    #[test]
+    fn control_tools_write_the_control_file() {
+        let (dir, mut server) = server("sh");
+        assert!(!call(&mut server, "pause", json!({})).0);
+        assert!(!call(&mut server, "skip_feature", json!({})).0);
+        let ralph_dir = dir.path().join(".ralph");
+        assert_eq!(control::take(&ralph_dir).unwrap(), vec![Command::Pause, Command::SkipFeature]);
+    }
+
+    #[tokio::test]
+    async fn start_runs_in_the_background_once_at_a_time() {
+        let (dir, mut shell) = server("sh");
+        let (is_error, text) = call(&mut shell, "start", json!({ "args": ["-m", "1"] }));
+        assert!(!is_error, "{text}");
+        let exit = shell.run.as_mut().unwrap().wait().await.unwrap();
+        assert!(exit.success());
+        let log = std::fs::read_to_string(log_path(&dir.path().join(".ralph"))).unwrap();
+        assert_eq!(log, "started -m 1\n");
+        assert!(call(&mut shell, "status", json!({})).1.contains(r#""exitCode": 0"#));
+
+        let (_dir, mut slow) = server("sleep");
+        slow.run_args = vec!["5".into()];
+        assert!(!call(&mut slow, "start", json!({})).0);
+        let (is_error, text) = call(&mut slow, "start", json!({}));
+        assert!(is_error && text.contains("still going"), "{text}");
+        assert!(call(&mut slow, "start", json!({ "args": [1] })).0);
+        slow.run.as_mut().unwrap().kill().await.unwrap();
+    }
+
+    #[test]
+    fn run_args_drop_the_subcommand() {
+        let argv = ["ralph", "--run-id", "mcp", "-m", "3", "mcp"].map(OsString::from);
+        assert_eq!(run_args(argv), ["--run-id", "mcp", "-m", "3"].map(OsString::from));
+        let argv = ["ralph", "mcp", "-m", "3", "--run-id", "mcp"].map(OsString::from);
+        assert_eq!(run_args(argv), ["-m", "3", "--run-id", "mcp"].map(OsString::from));
+        assert_eq!(run_args(["ralph", "mcp"].map(OsString::from)), Vec::<OsString>::new());
+    }
+}
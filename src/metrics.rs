@@ -0,0 +1,281 @@
+//! Token and cost accounting from Claude's result event, plus the sampled resource usage of
+//! each iteration's agent.
+
+use crate::resources::ResourceUsage;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub struct Usage {
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    #[serde(default)]
+    pub cache_creation_input_tokens: u64,
+    #[serde(default)]
+    pub cache_read_input_tokens: u64,
+    /// Filled from the result event's cost, not the usage block.
+    #[serde(skip)]
+    pub cost_usd: f64,
+}
+
+impl Usage {
+    fn add(&mut self, other: &Usage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_creation_input_tokens += other.cache_creation_input_tokens;
+        self.cache_read_input_tokens += other.cache_read_input_tokens;
+        self.cost_usd += other.cost_usd;
+    }
+
+    /// All input tokens, cached or not.
+    #[must_use]
+    pub fn total_input_tokens(&self) -> u64 {
+        self.input_tokens + self.cache_creation_input_tokens + self.cache_read_input_tokens
+    }
+}
+
+/// The final `{"type": "result", ...}` object printed by `claude --print` in its JSON and
+/// stream-json output formats.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultEvent {
+    pub result: String,
+    pub is_error: bool,
+    pub usage: Usage,
+}
+
+#[derive(Deserialize)]
+struct RawResult {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    result: String,
+    #[serde(default)]
+    subtype: String,
+    #[serde(default)]
+    is_error: bool,
+    #[serde(default)]
+    usage: Usage,
+    // Older CLI versions report `cost_usd`
+    #[serde(alias = "cost_usd")]
+    total_cost_usd: Option<f64>,
+}
+
+/// Parses a line of agent output as a Claude result event, if it is one.
+#[must_use]
+pub fn parse_result_line(line: &str) -> Option<ResultEvent> {
+    let line = line.trim();
+    if !line.starts_with('{') {
+        return None;
+    }
+    let raw: RawResult = serde_json::from_str(line).ok()?;
+    if raw.kind != "result" {
+        return None;
+    }
+    let mut usage = raw.usage;
+    usage.cost_usd = raw.total_cost_usd.unwrap_or(0.0);
+    Some(ResultEvent {
+        result: raw.result,
+        // e.g. `error_max_turns`, which doesn't always set `is_error`
+        is_error: raw.is_error || raw.subtype.starts_with("error"),
+        usage,
+    })
+}
+
+/// Usage per iteration for the whole run. Shared by reference, so `--parallel` workers can record concurrently.
+#[derive(Debug, Default)]
+pub struct CostTracker {
+    prior_cost_usd: f64,
+    iterations: Mutex<BTreeMap<u32, Usage>>,
+    resources: Mutex<BTreeMap<u32, ResourceUsage>>,
+}
+
+impl CostTracker {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts from the cost of an earlier, resumed part of the run.
+    #[must_use]
+    pub fn with_prior_cost(cost_usd: f64) -> Self {
+        Self {
+            prior_cost_usd: cost_usd,
+            ..Self::default()
+        }
+    }
+
+    /// Adds usage to `iteration`; an iteration with an audit pass records twice.
+    pub fn record(&self, iteration: u32, usage: &Usage) {
+        let mut iterations = self.iterations.lock().unwrap_or_else(|e| e.into_inner());
+        iterations.entry(iteration).or_default().add(usage);
+    }
+
+    /// Adds a sampled agent run to `iteration`.
+    pub fn record_resources(&self, iteration: u32, usage: &ResourceUsage) {
+        let mut resources = self.resources.lock().unwrap_or_else(|e| e.into_inner());
+        resources.entry(iteration).or_default().merge(usage);
+    }
+
+    #[must_use]
+    pub fn resources(&self, iteration: u32) -> Option<ResourceUsage> {
+        let resources = self.resources.lock().unwrap_or_else(|e| e.into_inner());
+        resources.get(&iteration).copied()
+    }
+
+    /// Peaks across the run's iterations, or None if no agent run was sampled.
+    #[must_use]
+    pub fn peak_resources(&self) -> Option<ResourceUsage> {
+        let resources = self.resources.lock().unwrap_or_else(|e| e.into_inner());
+        resources.values().copied().reduce(|mut peak, r| {
+            peak.merge(&r);
+            peak
+        })
+    }
+
+    #[must_use]
+    pub fn iteration(&self, iteration: u32) -> Option<Usage> {
+        let iterations = self.iterations.lock().unwrap_or_else(|e| e.into_inner());
+        iterations.get(&iteration).copied()
+    }
+
+    /// Usage recorded in this process.
+    #[must_use]
+    pub fn total(&self) -> Usage {
+        let iterations = self.iterations.lock().unwrap_or_else(|e| e.into_inner());
+        iterations.values().fold(Usage::default(), |mut sum, u| {
+            sum.add(u);
+            sum
+        })
+    }
+
+    /// Cost of the whole run, including any resumed part.
+    #[must_use]
+    pub fn total_cost(&self) -> f64 {
+        self.prior_cost_usd + self.total().cost_usd
+    }
+
+    #[must_use]
+    pub fn over_budget(&self, max_cost: f64) -> bool {
+        self.total_cost() >= max_cost
+    }
+
+    /// One-line summary for the end of a run, or None if nothing was recorded.
+    #[must_use]
+    pub fn summary(&self) -> Option<String> {
+        let iterations = self.iterations.lock().unwrap_or_else(|e| e.into_inner()).len();
+        if iterations == 0 && self.prior_cost_usd == 0.0 {
+            return None;
+        }
+        let total = self.total();
+        Some(format!(
+            "${:.2} ({} input / {} output tokens over {iterations} iterations)",
+            self.total_cost(),
+            total.total_input_tokens(),
+            total.output_tokens,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(cost: f64) -> Usage {
+        Usage {
+            input_tokens: 10,
+            output_tokens: 5,
+            cache_read_input_tokens: 100,
+            cost_usd: cost,
+            ..Usage::default()
+        }
+    }
+
+    mod parse_result_line_tests {
+        use super::*;
+
+        #[test]
+        fn parses_result_event() {
+            let line = r#"{"type":"result","subtype":"success","is_error":false,"result":"Done <promise>COMPLETE</promise>","total_cost_usd":0.42,"usage":{"input_tokens":12,"output_tokens":340,"cache_read_input_tokens":5000}}"#;
+            let event = parse_result_line(line).unwrap();
+            assert_eq!(event.result, "Done <promise>COMPLETE</promise>");
+            assert!(!event.is_error);
+            assert_eq!(event.usage.output_tokens, 340);
+            assert_eq!(event.usage.total_input_tokens(), 5012);
+            assert_eq!(event.usage.cost_usd, 0.42);
+        }
+
+        #[test]
+        fn error_subtypes_are_errors() {
+            let event = parse_result_line(r#"{"type":"result","subtype":"error_max_turns","is_error":false}"#).unwrap();
+            assert!(event.is_error);
+        }
+
+        #[test]
+        fn accepts_legacy_cost_field() {
+            let event = parse_result_line(r#"{"type":"result","result":"x","cost_usd":0.1}"#).unwrap();
+            assert_eq!(event.usage.cost_usd, 0.1);
+        }
+
+        #[test]
+        fn ignores_other_lines() {
+            assert!(parse_result_line("plain text").is_none());
+            assert!(parse_result_line(r#"{"type":"assistant"}"#).is_none());
+            assert!(parse_result_line("{ not json").is_none());
+        }
+    }
+
+    mod cost_tracker_tests {
+        use super::*;
+
+        #[test]
+        fn sums_iterations() {
+            let tracker = CostTracker::new();
+            tracker.record(1, &usage(0.5));
+            tracker.record(2, &usage(0.25));
+            tracker.record(2, &usage(0.25));
+            assert_eq!(tracker.iteration(2).unwrap().cost_usd, 0.5);
+            assert_eq!(tracker.total().output_tokens, 15);
+            assert_eq!(tracker.total_cost(), 1.0);
+        }
+
+        #[test]
+        fn budget_includes_prior_cost() {
+            let tracker = CostTracker::with_prior_cost(4.0);
+            assert!(!tracker.over_budget(5.0));
+            tracker.record(1, &usage(1.0));
+            assert!(tracker.over_budget(5.0));
+        }
+
+        #[test]
+        fn peak_resources_across_iterations() {
+            let tracker = CostTracker::new();
+            assert!(tracker.peak_resources().is_none());
+            let sample = |cpu, memory| ResourceUsage {
+                peak_cpu_percent: cpu,
+                avg_cpu_percent: cpu / 2.0,
+                peak_memory_bytes: memory,
+                samples: 1,
+            };
+            tracker.record_resources(1, &sample(80.0, 100));
+            tracker.record_resources(2, &sample(20.0, 400));
+            assert_eq!(tracker.resources(2).unwrap().peak_memory_bytes, 400);
+            let peak = tracker.peak_resources().unwrap();
+            assert_eq!((peak.peak_cpu_percent, peak.peak_memory_bytes), (80.0, 400));
+            assert_eq!(peak.avg_cpu_percent, 25.0);
+        }
+
+        #[test]
+        fn summary_only_when_recorded() {
+            let tracker = CostTracker::new();
+            assert!(tracker.summary().is_none());
+            tracker.record(1, &usage(1.5));
+            assert_eq!(
+                tracker.summary().unwrap(),
+                "$1.50 (110 input / 5 output tokens over 1 iterations)"
+            );
+        }
+    }
+}
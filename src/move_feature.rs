@@ -0,0 +1,240 @@
+//! `ralph move-feature`: relocates a feature, and the iteration logs and artifacts
+//! recorded for it, from one PRD to another.
+
+use crate::{artifact::IterationArtifact, atomic, output, prd::Prd, prd_edit};
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Moved {
+    pub id: String,
+    /// Iteration logs moved along with the feature
+    pub logs: usize,
+    /// Features left behind in the source PRD that depend on the moved one
+    pub dependents: Vec<String>,
+}
+
+/// Moves feature `id` from `source` to the end of `target`'s features, renamed to
+/// `new_id` if given. Both PRDs must stay valid and the id must be unique in `target`.
+pub fn run(source: &Path, target: &Path, id: &str, new_id: Option<&str>) -> Result<Moved> {
+    if std::path::absolute(source)? == std::path::absolute(target)? {
+        bail!("Source and target PRD are the same file");
+    }
+    let source_content = read(source)?;
+    let target_content = read(target)?;
+    let source_prd = parse(&source_content, source)?;
+    let target_prd = parse(&target_content, target)?;
+
+    let dest_id = new_id.unwrap_or(id);
+    if target_prd.features.iter().any(|f| f.id == dest_id) {
+        bail!(
+            "Feature '{dest_id}' already exists in {} (use --as to rename it)",
+            target.display()
+        );
+    }
+
+    let (new_source, mut text) = prd_edit::remove_feature(&source_content, id)
+        .with_context(|| format!("Failed to remove feature from {}", source.display()))?;
+    if dest_id != id {
+        text = prd_edit::rename_feature(&text, dest_id)?;
+    }
+    let new_target = prd_edit::insert_feature(&target_content, &text)
+        .with_context(|| format!("Failed to add feature to {}", target.display()))?;
+    parse(&new_source, source)?;
+    parse(&new_target, target)?;
+
+    // Target first: a failure in between leaves the feature duplicated, never lost
+    atomic::write(target, &new_target)?;
+    atomic::write(source, &new_source)?;
+
+    let logs = move_logs(&logs_dir(source), &logs_dir(target), id, dest_id)?;
+    let dependents = source_prd
+        .features
+        .iter()
+        .filter(|f| f.id != id && f.depends_on.iter().any(|d| d == id))
+        .map(|f| f.id.clone())
+        .collect();
+
+    Ok(Moved {
+        id: dest_id.to_string(),
+        logs,
+        dependents,
+    })
+}
+
+fn read(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path).with_context(|| format!("Failed to read PRD file: {}", path.display()))
+}
+
+fn parse(content: &str, path: &Path) -> Result<Prd> {
+    Prd::parse(content).with_context(|| format!("Failed to parse PRD file: {}", path.display()))
+}
+
+fn logs_dir(prd: &Path) -> PathBuf {
+    prd.parent().unwrap_or(Path::new(".")).join(".ralph").join("logs")
+}
+
+/// Moves the logs and artifacts of iterations that worked on `id`. Files whose name is
+/// already taken in `to` stay where they are.
+fn move_logs(from: &Path, to: &Path, id: &str, new_id: &str) -> Result<usize> {
+    let artifacts: Vec<IterationArtifact> = IterationArtifact::load_all(from)
+        .into_iter()
+        .filter(|a| a.feature_id.as_deref() == Some(id))
+        .collect();
+    if artifacts.is_empty() {
+        return Ok(0);
+    }
+    std::fs::create_dir_all(to).with_context(|| format!("Failed to create {}", to.display()))?;
+
+    let mut moved = 0;
+    for mut artifact in artifacts {
+        let Some(name) = artifact.log_path.file_name() else {
+            continue;
+        };
+        let dest_log = to.join(name);
+        if IterationArtifact::path_for(&dest_log).exists() || dest_log.exists() {
+            output::warn(&format!(
+                "Not moving {}: a log with that name exists in {}",
+                artifact.log_path.display(),
+                to.display()
+            ));
+            continue;
+        }
+        let old_log = from.join(name);
+        if old_log.exists() {
+            std::fs::rename(&old_log, &dest_log)
+                .with_context(|| format!("Failed to move {}", old_log.display()))?;
+        }
+        std::fs::remove_file(IterationArtifact::path_for(&old_log))?;
+        artifact.feature_id = Some(new_id.to_string());
+        artifact.log_path = dest_log;
+        artifact.write()?;
+        moved += 1;
+    }
+    Ok(moved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn prd(features: &str) -> String {
+        format!(
+            r#"{{
+  "project": {{ "name": "p", "description": "d" }},
+  "verification": {{ "commands": [], "runAfterEachFeature": false }},
+  "features": [
+{features}
+  ],
+  "completion": {{ "allFeaturesComplete": true, "allVerificationsPassing": true, "marker": "X" }}
+}}"#
+        )
+    }
+
+    fn feature(id: &str, depends_on: &str) -> String {
+        format!(
+            r#"    {{ "id": "{id}", "category": "c", "description": "d", "steps": [], "status": "pending"{depends_on} }}"#
+        )
+    }
+
+    fn workspace() -> (TempDir, PathBuf, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("api")).unwrap();
+        std::fs::create_dir_all(dir.path().join("web")).unwrap();
+        let source = dir.path().join("api/prd.jsonc");
+        let target = dir.path().join("web/prd.jsonc");
+        let features = [
+            feature("login", ""),
+            feature("ui", r#", "dependsOn": ["login"]"#),
+        ]
+        .join(",\n");
+        std::fs::write(&source, prd(&features)).unwrap();
+        std::fs::write(&target, prd(&feature("home", ""))).unwrap();
+        (dir, source, target)
+    }
+
+    fn ids(path: &Path) -> Vec<String> {
+        Prd::load(path).unwrap().features.into_iter().map(|f| f.id).collect()
+    }
+
+    fn write_artifact(logs: &Path, iteration: u32, feature: &str) -> PathBuf {
+        std::fs::create_dir_all(logs).unwrap();
+        let log_path = logs.join(format!("20260101-000000-iteration-{iteration}.log"));
+        std::fs::write(&log_path, "log").unwrap();
+        IterationArtifact {
+            iteration,
+            run_id: None,
+            started_at: "2026-01-01T00:00:00+00:00".into(),
+            duration_secs: 1,
+            feature_id: Some(feature.into()),
+            permission_mode: "default".into(),
+            escalation: None,
+            result: "continue".into(),
+            coverage: None,
+            cost_usd: Some(0.5),
+            resources: None,
+            error: None,
+            log_path: log_path.clone(),
+        }
+        .write()
+        .unwrap();
+        log_path
+    }
+
+    #[test]
+    fn moves_feature_and_reports_dependents() {
+        let (_dir, source, target) = workspace();
+        let moved = run(&source, &target, "login", None).unwrap();
+        assert_eq!(moved.dependents, vec!["ui"]);
+        assert_eq!(ids(&source), vec!["ui"]);
+        assert_eq!(ids(&target), vec!["home", "login"]);
+    }
+
+    #[test]
+    fn duplicate_id_needs_rename() {
+        let (_dir, source, target) = workspace();
+        std::fs::write(&target, prd(&feature("login", ""))).unwrap();
+        let err = run(&source, &target, "login", None).unwrap_err();
+        assert!(err.to_string().contains("--as"));
+        assert_eq!(ids(&source), vec!["login", "ui"]);
+
+        let moved = run(&source, &target, "login", Some("web-login")).unwrap();
+        assert_eq!(moved.id, "web-login");
+        assert_eq!(ids(&target), vec!["login", "web-login"]);
+    }
+
+    #[test]
+    fn unknown_feature_changes_nothing() {
+        let (_dir, source, target) = workspace();
+        let before = std::fs::read_to_string(&target).unwrap();
+        assert!(run(&source, &target, "nope", None).is_err());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), before);
+    }
+
+    #[test]
+    fn same_file_is_rejected() {
+        let (_dir, source, _) = workspace();
+        assert!(run(&source, &source, "login", None).is_err());
+    }
+
+    #[test]
+    fn moves_iteration_logs_with_the_feature() {
+        let (_dir, source, target) = workspace();
+        let from = logs_dir(&source);
+        let login_log = write_artifact(&from, 1, "login");
+        write_artifact(&from, 2, "ui");
+
+        let moved = run(&source, &target, "login", Some("web-login")).unwrap();
+        assert_eq!(moved.logs, 1);
+        assert!(!login_log.exists());
+
+        let to = logs_dir(&target);
+        let artifacts = IterationArtifact::load_all(&to);
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].feature_id.as_deref(), Some("web-login"));
+        assert_eq!(artifacts[0].log_path, to.join(login_log.file_name().unwrap()));
+        assert!(artifacts[0].log_path.exists());
+        assert_eq!(IterationArtifact::load_all(&from).len(), 1);
+    }
+}
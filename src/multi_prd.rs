@@ -0,0 +1,290 @@
+//! Several PRDs in one invocation (`--prd-dir`, `--prd-manifest`), so a monorepo can keep
+//! requirements per package: each PRD gets its own run and progress file, one after another,
+//! and a combined summary at the end.
+
+use crate::{config::Args, history, output, prd::Prd, run_id, runner, stop, validation};
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// `--prd-manifest` file: PRD paths relative to it, run in order.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    prds: Vec<PathBuf>,
+}
+
+/// One PRD's part of the run.
+#[derive(Debug)]
+struct Part {
+    prd: PathBuf,
+    progress_path: Option<PathBuf>,
+    run_id: String,
+}
+
+/// How one PRD's run went, for the summary.
+#[derive(Debug, PartialEq)]
+struct PartSummary {
+    prd: String,
+    result: String,
+    iterations: u32,
+    complete: usize,
+    total: usize,
+    cost_usd: Option<f64>,
+}
+
+/// The PRDs named by `--prd-dir` (its `*.jsonc` files, by name) or `--prd-manifest`.
+pub fn prd_paths(args: &Args) -> Result<Vec<PathBuf>> {
+    let paths = match (&args.prd_dir, &args.prd_manifest) {
+        (Some(dir), _) => {
+            let entries = std::fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+            let mut paths: Vec<PathBuf> = entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "jsonc"))
+                .collect();
+            paths.sort();
+            paths
+        }
+        (None, Some(manifest)) => {
+            let content =
+                std::fs::read_to_string(manifest).with_context(|| format!("Failed to read {}", manifest.display()))?;
+            let parsed: Manifest =
+                toml::from_str(&content).with_context(|| format!("Failed to parse {}", manifest.display()))?;
+            let dir = manifest.parent().unwrap_or(Path::new(""));
+            parsed.prds.iter().map(|p| dir.join(p)).collect()
+        }
+        (None, None) => Vec::new(),
+    };
+    if paths.is_empty() {
+        bail!("No PRD files found for --prd-dir/--prd-manifest");
+    }
+    if let Some(path) = paths.iter().find(|p| !p.is_file()) {
+        bail!("PRD file not found: {}", path.display());
+    }
+    Ok(paths)
+}
+
+/// Progress file overrides keeping each PRD's progress separate: a PRD that would share
+/// `progress.txt` with another gets `<name>-progress.txt` beside it.
+fn progress_paths(prds: &[(PathBuf, Prd)]) -> Result<Vec<Option<PathBuf>>> {
+    let resolved: Vec<PathBuf> = prds.iter().map(|(path, prd)| prd.progress_path(path, None)).collect();
+    prds.iter()
+        .zip(&resolved)
+        .map(|((path, prd), progress)| {
+            if resolved.iter().filter(|p| *p == progress).count() == 1 {
+                return Ok(None);
+            }
+            if prd.project.progress_path.is_some() {
+                bail!("{} shares its progressPath with another PRD", path.display());
+            }
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            Ok(Some(validation::prd_dir(path).join(format!("{stem}-progress.txt"))))
+        })
+        .collect()
+}
+
+/// Runs each PRD in turn with `args`; a stop ends them all, a failure moves on to the next PRD.
+pub async fn run(args: Args) -> Result<()> {
+    if args.resume || args.run_id.is_some() {
+        bail!("--resume and --run-id apply to a single PRD; run it with -p instead");
+    }
+    if args.progress_path.is_some() {
+        bail!("--progress-path would be shared by every PRD; set progressPath in each PRD instead");
+    }
+    if args.prd_source.is_some() {
+        bail!("--prd-source builds a single PRD and can't be combined with --prd-dir or --prd-manifest");
+    }
+    let prds = prd_paths(&args)?
+        .into_iter()
+        .map(|path| Prd::load(&path).map(|prd| (path, prd)))
+        .collect::<Result<Vec<_>>>()?;
+    let parts: Vec<Part> = prds
+        .iter()
+        .zip(progress_paths(&prds)?)
+        .map(|((prd, _), progress_path)| Part { prd: prd.clone(), progress_path, run_id: run_id::generate() })
+        .collect();
+
+    output::log(&format!("Running {} PRDs: {}", parts.len(), display_list(&parts)));
+    let mut summaries = Vec::new();
+    for (at, part) in parts.iter().enumerate() {
+        output::section(&format!("PRD {}/{}: {}", at + 1, parts.len(), part.prd.display()));
+        let mut part_args = args.clone();
+        part_args.prd_dir = None;
+        part_args.prd_manifest = None;
+        part_args.prd.clone_from(&part.prd);
+        part_args.progress_path.clone_from(&part.progress_path);
+        part_args.run_id = Some(part.run_id.clone());
+        let result = Box::pin(runner::run(part_args)).await;
+        if let Err(ref e) = result {
+            if e.is::<stop::OperatorStop>() {
+                print_summary(&summaries);
+                return result;
+            }
+            output::error(&format!("{}: {e:#}", part.prd.display()));
+        }
+        summaries.push(summarize(part, result.is_ok()));
+    }
+
+    print_summary(&summaries);
+    let failed = summaries.iter().filter(|s| s.result == "failed").count();
+    if failed > 0 {
+        bail!("{failed} of {} PRDs failed", summaries.len());
+    }
+    Ok(())
+}
+
+fn display_list(parts: &[Part]) -> String {
+    parts.iter().map(|p| p.prd.display().to_string()).collect::<Vec<_>>().join(", ")
+}
+
+/// The part's outcome from its run index and the PRD's statuses now.
+fn summarize(part: &Part, ok: bool) -> PartSummary {
+    let outcome = history::load(&validation::prd_dir(&part.prd).join(".ralph"))
+        .ok()
+        .and_then(|records| records.into_iter().rev().find(|r| r.run_id.as_deref() == Some(part.run_id.as_str())))
+        .and_then(|r| r.outcome);
+    let counts = Prd::load(&part.prd).ok().map(|prd| (prd.status_counts().complete, prd.features.len()));
+    let (complete, total) = counts.unwrap_or_default();
+    PartSummary {
+        prd: part.prd.display().to_string(),
+        result: match (&outcome, ok) {
+            (Some(o), true) => o.result.clone(),
+            _ => if ok { "incomplete" } else { "failed" }.to_string(),
+        },
+        iterations: outcome.as_ref().map_or(0, |o| o.iterations),
+        complete,
+        total,
+        cost_usd: outcome.and_then(|o| o.cost_usd),
+    }
+}
+
+fn summary_line(s: &PartSummary) -> String {
+    let mut line =
+        format!("{}: {}, {}/{} features complete, {} iterations", s.prd, s.result, s.complete, s.total, s.iterations);
+    if let Some(cost) = s.cost_usd {
+        line.push_str(&format!(", ${cost:.2}"));
+    }
+    line
+}
+
+fn print_summary(summaries: &[PartSummary]) {
+    output::section("Summary");
+    for summary in summaries {
+        output::log(&summary_line(summary));
+    }
+    let complete: usize = summaries.iter().map(|s| s.complete).sum();
+    let total: usize = summaries.iter().map(|s| s.total).sum();
+    let iterations: u32 = summaries.iter().map(|s| s.iterations).sum();
+    let mut line = format!("Total: {complete}/{total} features complete over {iterations} iterations");
+    let costs: Vec<f64> = summaries.iter().filter_map(|s| s.cost_usd).collect();
+    if !costs.is_empty() {
+        line.push_str(&format!(", ${:.2}", costs.iter().sum::<f64>()));
+    }
+    output::log(&line);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FeatureBuilder, PrdBuilder};
+    use clap::Parser;
+
+    fn args(extra: &[&str]) -> Args {
+        Args::try_parse_from(std::iter::once("ralph").chain(extra.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn finds_prds_in_a_dir_or_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("reqs")).unwrap();
+        for name in ["web.jsonc", "api.jsonc", "notes.md"] {
+            std::fs::write(root.join("reqs").join(name), "").unwrap();
+        }
+        let reqs = root.join("reqs").display().to_string();
+        assert_eq!(
+            prd_paths(&args(&["--prd-dir", &reqs])).unwrap(),
+            [root.join("reqs/api.jsonc"), root.join("reqs/web.jsonc")]
+        );
+
+        std::fs::write(root.join("prds.toml"), "prds = [\"reqs/web.jsonc\", \"reqs/api.jsonc\"]\n").unwrap();
+        let manifest = root.join("prds.toml").display().to_string();
+        assert_eq!(
+            prd_paths(&args(&["--prd-manifest", &manifest])).unwrap(),
+            [root.join("reqs/web.jsonc"), root.join("reqs/api.jsonc")]
+        );
+
+        std::fs::write(root.join("prds.toml"), "prds = [\"reqs/missing.jsonc\"]\n").unwrap();
+        assert!(prd_paths(&args(&["--prd-manifest", &manifest])).unwrap_err().to_string().contains("not found"));
+        std::fs::create_dir_all(root.join("empty")).unwrap();
+        let empty = root.join("empty").display().to_string();
+        assert!(prd_paths(&args(&["--prd-dir", &empty])).is_err());
+    }
+
+    #[test]
+    fn prds_sharing_a_directory_get_their_own_progress_file() {
+        let prd = || PrdBuilder::new().build();
+        let mut own = prd();
+        own.project.progress_path = Some("notes/web.txt".into());
+        let prds = vec![
+            (PathBuf::from("reqs/api.jsonc"), prd()),
+            (PathBuf::from("reqs/jobs.jsonc"), prd()),
+            (PathBuf::from("reqs/web.jsonc"), own),
+            (PathBuf::from("packages/cli/prd.jsonc"), prd()),
+        ];
+        assert_eq!(
+            progress_paths(&prds).unwrap(),
+            [Some(PathBuf::from("reqs/api-progress.txt")), Some(PathBuf::from("reqs/jobs-progress.txt")), None, None]
+        );
+
+        let mut clash = prd();
+        clash.project.progress_path = Some("progress.txt".into());
+        let prds = vec![(PathBuf::from("reqs/api.jsonc"), prd()), (PathBuf::from("reqs/web.jsonc"), clash)];
+        assert!(progress_paths(&prds).is_err());
+    }
+
+    #[test]
+    fn summary_lines() {
+        let summary = PartSummary {
+            prd: "reqs/api.jsonc".into(),
+            result: "complete".into(),
+            iterations: 4,
+            complete: 3,
+            total: 3,
+            cost_usd: Some(1.25),
+        };
+        assert_eq!(summary_line(&summary), "reqs/api.jsonc: complete, 3/3 features complete, 4 iterations, $1.25");
+    }
+
+    #[tokio::test]
+    async fn runs_each_prd_with_its_own_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("reqs")).unwrap();
+        for name in ["api", "web"] {
+            PrdBuilder::new()
+                .marker("<promise>DONE</promise>")
+                .feature(FeatureBuilder::new(&format!("{name}-1")))
+                .write(&root.join(format!("reqs/{name}.jsonc")))
+                .unwrap();
+        }
+        std::fs::write(root.join("script.json"), r#"{ "steps": [ { "output": "<promise>DONE</promise>" } ] }"#)
+            .unwrap();
+        let reqs = root.join("reqs").display().to_string();
+        let script = root.join("script.json").display().to_string();
+        let base =
+            ["--prd-dir", &reqs, "--simulate", &script, "-d", "0", "--skip-init", "--premature-completion", "accept"];
+        run(args(&base)).await.unwrap();
+
+        for name in ["api", "web"] {
+            let progress = std::fs::read_to_string(root.join(format!("reqs/{name}-progress.txt"))).unwrap();
+            assert!(progress.contains("Ralph Progress Log"), "{progress}");
+        }
+        let runs = history::load(&root.join("reqs/.ralph")).unwrap();
+        assert_eq!(runs.len(), 2);
+        assert!(runs.iter().all(|r| r.outcome.as_ref().is_some_and(|o| o.result == "complete")));
+
+        assert!(run(args(&["--prd-dir", &reqs, "--resume"])).await.is_err());
+    }
+}
@@ -1,52 +1,350 @@
+//! Terminal output: `[ralph]` lines, colored on terminals, with warnings and errors on
+//! stderr; or with `--output-format json` one JSON record per line on stdout for CI and
+//! other tools.
+
+use crate::{
+    config::{ColorChoice, OutputFormat},
+    events::{EventSink, RunEvent},
+    logging,
+};
 use owo_colors::OwoColorize;
+use serde_json::{json, Value};
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
 const PREFIX: &str = "[ralph]";
+const RULE: &str = "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━";
+
+static JSON: AtomicBool = AtomicBool::new(false);
+static COLOR: RwLock<ColorChoice> = RwLock::new(ColorChoice::Auto);
+
+/// Where output goes instead of the terminal, e.g. for tools embedding the loop.
+static REPORTER: RwLock<Option<Arc<dyn Reporter>>> = RwLock::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Success,
+    Warn,
+    Error,
+    Debug,
+    /// A heading, e.g. a section title
+    Header,
+}
+
+impl Level {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Info | Self::Header => "info",
+            Self::Success => "success",
+            Self::Warn => "warn",
+            Self::Error => "error",
+            Self::Debug => "debug",
+        }
+    }
+
+    /// Unlike [`Level::as_str`], tells headers from info, so [`Level::from_name`] can restore them.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Header => "header",
+            level => level.as_str(),
+        }
+    }
+
+    #[must_use]
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "success" => Self::Success,
+            "warn" => Self::Warn,
+            "error" => Self::Error,
+            "debug" => Self::Debug,
+            "header" => Self::Header,
+            _ => Self::Info,
+        }
+    }
+}
+
+/// Receives everything ralph prints: messages, structured events (the `type` of a JSON
+/// record, e.g. `iteration_end`, and its fields), and the agent's output.
+pub trait Reporter: Send + Sync {
+    fn message(&self, level: Level, msg: &str);
+
+    fn event(&self, kind: &str, fields: &Value);
+
+    fn agent_line(&self, line: &str, stderr: bool);
+
+    /// A horizontal rule, bold for section separators; terminals only
+    fn rule(&self, _bold: bool) {}
+
+    /// An empty spacer line; terminals only
+    fn blank(&self) {}
+}
+
+/// The terminal: `[ralph]` lines, or JSON records with `--output-format json`.
+pub struct Console;
+
+impl Reporter for Console {
+    fn message(&self, level: Level, msg: &str) {
+        if is_json() {
+            println!("{}", record("log", json!({ "level": level.as_str(), "message": msg })));
+            return;
+        }
+        if matches!(level, Level::Warn | Level::Error) {
+            eprintln!("{}", line(level, msg, use_color(&std::io::stderr())));
+        } else {
+            println!("{}", line(level, msg, use_color(&std::io::stdout())));
+        }
+    }
+
+    /// Only printed in JSON mode, where it complements the text messages that describe
+    /// the same moment.
+    fn event(&self, kind: &str, fields: &Value) {
+        if is_json() {
+            println!("{}", record(kind, fields.clone()));
+        }
+    }
+
+    fn agent_line(&self, line: &str, stderr: bool) {
+        match (is_json(), stderr) {
+            (true, false) => println!("{}", record("agent_output", json!({ "line": line }))),
+            (true, true) => println!("{}", record("agent_output", json!({ "line": line, "stream": "stderr" }))),
+            (false, false) => println!("{line}"),
+            (false, true) => eprintln!("{line}"),
+        }
+    }
+
+    fn rule(&self, bold: bool) {
+        if !is_json() {
+            self.message(if bold { Level::Header } else { Level::Info }, RULE);
+        }
+    }
+
+    fn blank(&self) {
+        if !is_json() {
+            println!();
+        }
+    }
+}
+
+/// A message as a `[ralph]` line, colored by level if `color`.
+fn line(level: Level, msg: &str, color: bool) -> String {
+    if !color {
+        return format!("{PREFIX} {msg}");
+    }
+    match level {
+        Level::Info => format!("{} {}", PREFIX.blue(), msg),
+        Level::Success => format!("{} {}", PREFIX.green(), msg),
+        Level::Warn => format!("{} {}", PREFIX.yellow(), msg),
+        Level::Error => format!("{} {}", PREFIX.red(), msg),
+        Level::Debug => format!("{} {}", PREFIX.cyan(), msg.dimmed()),
+        Level::Header => format!("{} {}", PREFIX.blue().bold(), msg.bold()),
+    }
+}
+
+fn use_color(stream: &impl IsTerminal) -> bool {
+    let choice = *COLOR.read().unwrap_or_else(|e| e.into_inner());
+    color_enabled(choice, stream.is_terminal(), std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()))
+}
+
+/// `auto` colors terminals unless `NO_COLOR` is set to anything non-empty (no-color.org).
+fn color_enabled(choice: ColorChoice, terminal: bool, no_color: bool) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => terminal && !no_color,
+    }
+}
+
+/// Selects when to color for the rest of the process.
+pub fn set_color(choice: ColorChoice) {
+    *COLOR.write().unwrap_or_else(|e| e.into_inner()) = choice;
+}
+
+/// Selects the output format for the rest of the process.
+pub fn set_format(format: OutputFormat) {
+    JSON.store(format == OutputFormat::Json, Ordering::Relaxed);
+}
+
+#[must_use]
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Sends all output to `reporter` for the rest of the process, or back to the terminal.
+pub fn set_reporter(reporter: Option<Arc<dyn Reporter>>) {
+    *REPORTER.write().unwrap_or_else(|e| e.into_inner()) = reporter;
+}
+
+fn reporter() -> Arc<dyn Reporter> {
+    REPORTER.read().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_else(|| Arc::new(Console))
+}
+
+/// Hands a message to the reporter; the console layer of [`logging`] ends here.
+pub fn report(level: Level, msg: &str) {
+    reporter().message(level, msg);
+}
+
+/// Sends a message through `tracing`, or straight to the reporter when ralph's subscriber
+/// isn't installed.
+fn emit(level: Level, msg: &str) {
+    if !logging::installed() {
+        report(level, msg);
+        return;
+    }
+    let kind = level.name();
+    match level {
+        Level::Error => tracing::error!(target: logging::TARGET, kind, message = msg),
+        Level::Warn => tracing::warn!(target: logging::TARGET, kind, message = msg),
+        _ => tracing::info!(target: logging::TARGET, kind, message = msg),
+    }
+}
+
+/// Whether rules and spacers are shown, which `-q` turns off along with info messages.
+fn decorated() -> bool {
+    !logging::installed() || logging::console_enabled(tracing::Level::INFO)
+}
 
 #[must_use]
 pub fn format_duration(d: Duration) -> String {
     format!("{}m {}s", d.as_secs() / 60, d.as_secs() % 60)
 }
 
+/// A JSON output record: `type`, `timestamp`, and the `runId` once a run has started,
+/// plus `fields`, on one line.
+#[must_use]
+pub fn record(kind: &str, fields: Value) -> String {
+    let mut record = json!({ "type": kind, "timestamp": chrono::Local::now().to_rfc3339() });
+    if let Some(record) = record.as_object_mut() {
+        if let Some(id) = crate::run_id::current() {
+            record.insert("runId".into(), id.into());
+        }
+        if let Value::Object(fields) = fields {
+            record.extend(fields);
+        }
+    }
+    record.to_string()
+}
+
+/// A structured record outside a run, such as `status`; run events go through [`crate::events`].
+pub fn event(kind: &str, fields: Value) {
+    reporter().event(kind, &fields);
+}
+
 pub fn log(msg: &str) {
-    println!("{} {}", PREFIX.blue(), msg);
+    emit(Level::Info, msg);
 }
 
 pub fn success(msg: &str) {
-    println!("{} {}", PREFIX.green(), msg);
+    emit(Level::Success, msg);
 }
 
 pub fn warn(msg: &str) {
-    println!("{} {}", PREFIX.yellow(), msg);
+    emit(Level::Warn, msg);
 }
 
 pub fn error(msg: &str) {
-    println!("{} {}", PREFIX.red(), msg);
+    emit(Level::Error, msg);
 }
 
+/// Secondary detail, shown dimmed by default.
 pub fn dim(msg: &str) {
-    println!("{} {}", PREFIX.cyan(), msg.dimmed());
+    emit(Level::Debug, msg);
+}
+
+/// Shown with `-v`, and always written to `--structured-log`.
+pub fn debug(msg: &str) {
+    if logging::installed() {
+        tracing::debug!(target: logging::TARGET, kind = Level::Debug.name(), message = msg);
+    }
+}
+
+/// Shown with `-vv`, and always written to `--structured-log`.
+pub fn trace(msg: &str) {
+    if logging::installed() {
+        tracing::trace!(target: logging::TARGET, kind = Level::Debug.name(), message = msg);
+    }
 }
 
 pub fn header(msg: &str) {
-    println!("{} {}", PREFIX.blue().bold(), msg.bold());
+    emit(Level::Header, msg);
 }
 
 pub fn separator() {
-    header("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    if decorated() {
+        reporter().rule(true);
+    }
+}
+
+/// A plain horizontal rule, lighter than [`separator`].
+pub fn rule() {
+    if decorated() {
+        reporter().rule(false);
+    }
 }
 
 pub fn section(title: &str) {
     separator();
     header(title);
     separator();
-    println!();
+    blank();
+}
+
+/// An empty spacer line in text mode.
+pub fn blank() {
+    if decorated() {
+        reporter().blank();
+    }
+}
+
+/// Run events for the reporter: agent output is echoed, the rest are JSON records in JSON mode.
+pub struct ConsoleSink;
+
+impl EventSink for ConsoleSink {
+    fn handle(&self, event: &RunEvent) {
+        match event {
+            RunEvent::AgentOutputChunk { line, stderr } => reporter().agent_line(line, *stderr),
+            event => reporter().event(event.kind(), &event.fields()),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn record_merges_fields() {
+        let line = record("iteration_end", json!({ "iteration": 3, "result": "continue" }));
+        assert!(!line.contains('\n'));
+        let value: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["type"], "iteration_end");
+        assert!(value["timestamp"].is_string());
+        assert_eq!(value["iteration"], 3);
+        assert_eq!(value["result"], "continue");
+    }
+
+    #[test]
+    fn colors_only_when_wanted() {
+        assert!(color_enabled(ColorChoice::Auto, true, false));
+        assert!(!color_enabled(ColorChoice::Auto, true, true));
+        assert!(!color_enabled(ColorChoice::Auto, false, false));
+        assert!(color_enabled(ColorChoice::Always, false, true));
+        assert!(!color_enabled(ColorChoice::Never, true, false));
+        assert_eq!(line(Level::Warn, "careful", false), "[ralph] careful");
+        assert!(line(Level::Warn, "careful", true).contains("\u{1b}["));
+    }
+
+    #[test]
+    fn level_names_round_trip() {
+        for level in [Level::Info, Level::Success, Level::Warn, Level::Error, Level::Debug, Level::Header] {
+            assert_eq!(Level::from_name(level.name()), level);
+        }
+    }
+
     #[test]
     fn format_duration_zero() {
         let d = Duration::from_secs(0);
@@ -1,62 +1,232 @@
+use crate::{atomic, diagnostic::Diagnostic, shell::Shell};
 use anyhow::{Context, Result};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Directory relative paths in the PRD are resolved against.
+fn prd_dir(prd_path: &Path) -> &Path {
+    prd_path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."))
+}
+
+fn is_zero<T: Default + PartialEq>(n: &T) -> bool {
+    *n == T::default()
+}
+
+/// Product requirements document driving a ralph run.
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct Prd {
     pub project: Project,
     pub verification: Verification,
     pub features: Vec<Feature>,
     pub completion: Completion,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub analysis: Option<AnalysisConfig>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Regexes, matched case-insensitively, that replace the built-in output checks.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema)]
+pub struct AnalysisConfig {
+    /// Agent output that means it is stuck, looked for in the first 500 characters
+    #[serde(rename = "loopPatterns", default, skip_serializing_if = "Vec::is_empty")]
+    pub loop_patterns: Vec<String>,
+    /// Agent errors that mean a rate limit, looked for in the last 1000 characters
+    #[serde(rename = "rateLimitPatterns", default, skip_serializing_if = "Vec::is_empty")]
+    pub rate_limit_patterns: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct Project {
     pub name: String,
     pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub repository: Option<String>,
+    /// Progress file location, relative to the PRD's directory [default: progress.txt]
+    #[serde(rename = "progressPath", skip_serializing_if = "Option::is_none")]
+    pub progress_path: Option<PathBuf>,
+    /// Prompt template per feature category, relative to the PRD's directory [default: --prompt]
+    #[serde(rename = "categoryPrompts", default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub category_prompts: BTreeMap<String, PathBuf>,
+    /// Directory the agent and verification commands run in, relative to the PRD's directory [default: the PRD's
+    /// directory]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root: Option<PathBuf>,
+    /// Variables for the agent and verification commands; `${VAR}` expands from `envFile` and ralph's environment
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// `.env` file of variables for the agent and verification commands, relative to the PRD's directory
+    #[serde(rename = "envFile", skip_serializing_if = "Option::is_none")]
+    pub env_file: Option<PathBuf>,
+    /// Globs, relative to the project directory, of files an iteration may not change (e.g. `.github/**`)
+    #[serde(rename = "protectedPaths", default, skip_serializing_if = "Vec::is_empty")]
+    pub protected_paths: Vec<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct Verification {
     pub commands: Vec<VerifyCommand>,
     #[serde(rename = "runAfterEachFeature")]
     pub run_after_each_feature: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coverage: Option<CoverageConfig>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Coverage delta gate; `report` is an lcov tracefile or Cobertura XML relative to the project dir.
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct CoverageConfig {
+    pub command: String,
+    pub report: String,
+    /// Minimum allowed change in line coverage, in percentage points (e.g. -0.5 tolerates a small drop)
+    #[serde(rename = "minDelta", default)]
+    pub min_delta: f64,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema)]
 pub struct VerifyCommand {
     pub name: String,
     pub command: String,
     pub description: String,
+    /// Shell to run `command` with [default: sh, or powershell on Windows]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shell: Option<Shell>,
+    /// Directory to run in, relative to the project dir
+    #[serde(rename = "workingDir", skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<PathBuf>,
+    /// Host environment variables the command may see (plus PATH and HOME); unset passes all of them,
+    /// except into `--sandbox docker`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env: Option<Vec<String>>,
+    /// Timeout for this command, instead of `--timeout`
+    #[serde(rename = "timeoutSecs", skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+    /// Extra attempts before a failing command counts as failed, for flaky tests
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub retries: u32,
+    /// Wait between attempts
+    #[serde(rename = "retryDelaySecs", default, skip_serializing_if = "is_zero")]
+    pub retry_delay_secs: u64,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema)]
 pub struct Feature {
     pub id: String,
     pub category: String,
     pub description: String,
     pub steps: Vec<String>,
     pub status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub notes: Option<String>,
+    /// Conditions the finished feature must meet, listed in the prompt
+    #[serde(rename = "acceptanceCriteria", default, skip_serializing_if = "Vec::is_empty")]
+    pub acceptance_criteria: Vec<String>,
+    /// Shell command ralph runs once the feature is marked complete; failing reopens it as in-progress
+    #[serde(rename = "acceptanceCommand", skip_serializing_if = "Option::is_none")]
+    pub acceptance_command: Option<String>,
+    /// Expected number of iterations, used by `ralph estimate`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimate: Option<u32>,
+    /// Ids of features that must be complete before this one
+    #[serde(rename = "dependsOn", default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+    /// Optional grouping label, e.g. a milestone name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phase: Option<String>,
+    /// Pending features are picked lowest number first; features without one come after, in file order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u32>,
+    /// Iterations the feature may take before ralph blocks it
+    #[serde(rename = "maxIterations", skip_serializing_if = "Option::is_none")]
+    pub max_iterations: Option<u32>,
+    /// Agent timeout for iterations on this feature, instead of `--timeout`
+    #[serde(rename = "timeoutSecs", skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+    /// Agent model for iterations on this feature, instead of `--model`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Breadcrumbs appended by the agent with `--allow-agent-notes`
+    #[serde(rename = "agentNotes", default, skip_serializing_if = "Vec::is_empty")]
+    pub agent_notes: Vec<String>,
+    /// Globs of files to inline into the prompt with `--pack-context`, highest priority first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub context: Vec<String>,
+    #[serde(rename = "contextBudget", skip_serializing_if = "Option::is_none")]
+    pub context_budget: Option<ContextBudget>,
+    /// Extra verification commands for this feature; one named like a global command replaces it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub verification: Vec<VerifyCommand>,
+    /// Run only this feature's `verification` commands, not the global ones
+    #[serde(rename = "replaceVerification", default, skip_serializing_if = "std::ops::Not::not")]
+    pub replace_verification: bool,
+    /// Subdirectory of the project directory the agent and verification commands run in for this feature,
+    /// e.g. its package in a monorepo
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workdir: Option<PathBuf>,
+    /// Wait for the operator's approval before each iteration on this feature
+    #[serde(rename = "requiresApproval", default, skip_serializing_if = "std::ops::Not::not")]
+    pub requires_approval: bool,
+}
+
+impl Feature {
+    /// `acceptanceCommand` as a verification command named `<id>-acceptance`.
+    #[must_use]
+    pub fn acceptance_check(&self) -> Option<VerifyCommand> {
+        self.acceptance_command.as_ref().map(|command| VerifyCommand {
+            name: format!("{}-acceptance", self.id),
+            command: command.clone(),
+            description: format!("Acceptance check for {}", self.id),
+            ..Default::default()
+        })
+    }
+
+    /// Where iterations on the feature run: its `workdir` under `project_dir`, else `project_dir`.
+    #[must_use]
+    pub fn working_dir(&self, project_dir: &Path) -> PathBuf {
+        self.workdir.as_ref().map_or_else(|| project_dir.to_path_buf(), |dir| project_dir.join(dir))
+    }
+}
+
+/// Per-feature cap on packed context; when both are set the tighter one wins.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+pub struct ContextBudget {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<usize>,
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Hash)]
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, PartialEq, Eq, Clone, Copy, Hash)]
 #[serde(rename_all = "kebab-case")]
 pub enum Status {
+    #[default]
     Pending,
     InProgress,
     Complete,
     Blocked,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl Status {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::InProgress => "in-progress",
+            Self::Complete => "complete",
+            Self::Blocked => "blocked",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct Completion {
     #[serde(rename = "allFeaturesComplete")]
     pub all_features_complete: bool,
     #[serde(rename = "allVerificationsPassing")]
     pub all_verifications_passing: bool,
     pub marker: String,
+    /// Marker per feature `phase`, printed by the agent when that phase's features are all done.
+    #[serde(rename = "phaseMarkers", default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub phase_markers: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -72,10 +242,129 @@ impl Prd {
         let content = std::fs::read_to_string(path)
             .with_context(|| format!("Failed to read PRD file: {}", path.display()))?;
 
-        let prd: Prd = json5::from_str(&content)
-            .with_context(|| format!("Failed to parse PRD file: {}", path.display()))?;
+        if crate::markdown::is_markdown(path) {
+            return crate::markdown::parse(&content)
+                .with_context(|| format!("Failed to parse Markdown PRD: {}", path.display()));
+        }
+        Self::parse(&content).with_context(|| format!("Failed to parse PRD file: {}", path.display()))
+    }
 
-        Ok(prd)
+    /// Parses PRD text; errors carry the position and a snippet of the offending region.
+    pub fn parse(content: &str) -> Result<Self> {
+        json5::from_str(content).map_err(|e| Diagnostic::new(content, &e).into())
+    }
+
+    /// The feature the agent should work on next: the first in-progress one, else the first
+    /// pending one by priority.
+    #[must_use]
+    pub fn next_feature(&self) -> Option<&Feature> {
+        self.features
+            .iter()
+            .find(|f| f.status == Status::InProgress)
+            .or_else(|| self.by_priority().into_iter().find(|f| f.status == Status::Pending))
+    }
+
+    /// True if feature `id` is still pending or in progress.
+    #[must_use]
+    pub fn is_unfinished(&self, id: &str) -> bool {
+        self.features.iter().any(|f| f.id == id && matches!(f.status, Status::Pending | Status::InProgress))
+    }
+
+    /// Features by `priority`, lowest first, then unprioritized ones; ties keep file order.
+    #[must_use]
+    pub fn by_priority(&self) -> Vec<&Feature> {
+        let mut features: Vec<&Feature> = self.features.iter().collect();
+        features.sort_by_key(|f| f.priority.unwrap_or(u32::MAX));
+        features
+    }
+
+    /// Project directory: `override_dir` if given, else the PRD's `root` (relative to the
+    /// PRD's directory), else the PRD's directory.
+    #[must_use]
+    pub fn project_dir(&self, prd_path: &Path, override_dir: Option<&Path>) -> PathBuf {
+        match (override_dir, &self.project.root) {
+            (Some(dir), _) => dir.to_path_buf(),
+            (None, Some(root)) => prd_dir(prd_path).join(root),
+            (None, None) => prd_dir(prd_path).to_path_buf(),
+        }
+    }
+
+    /// Progress file path: `override_path` if given, else the PRD's `progressPath`
+    /// (relative to the PRD's directory), else `progress.txt` beside the PRD.
+    #[must_use]
+    pub fn progress_path(&self, prd_path: &Path, override_path: Option<&Path>) -> PathBuf {
+        if let Some(path) = override_path {
+            return path.to_path_buf();
+        }
+        prd_dir(prd_path).join(
+            self.project
+                .progress_path
+                .as_deref()
+                .unwrap_or(Path::new("progress.txt")),
+        )
+    }
+
+    /// The `categoryPrompts` template for `category`, resolved against the PRD's directory.
+    #[must_use]
+    pub fn category_prompt(&self, prd_path: &Path, category: &str) -> Option<PathBuf> {
+        self.project.category_prompts.get(category).map(|path| prd_dir(prd_path).join(path))
+    }
+
+    /// Pending or in-progress features whose `dependsOn` features are all complete.
+    /// Ids that match no feature don't hold anything back.
+    #[must_use]
+    pub fn ready_features(&self) -> Vec<&Feature> {
+        let waiting_on = |id: &String| {
+            self.features
+                .iter()
+                .any(|f| &f.id == id && f.status != Status::Complete)
+        };
+        self.by_priority()
+            .into_iter()
+            .filter(|f| matches!(f.status, Status::Pending | Status::InProgress))
+            .filter(|f| !f.depends_on.iter().any(waiting_on))
+            .collect()
+    }
+
+    /// Verification commands that apply to `feature`: the global commands merged with its own.
+    #[must_use]
+    pub fn verification_for<'a>(&'a self, feature: &'a Feature) -> Vec<&'a VerifyCommand> {
+        let mut commands: Vec<&VerifyCommand> = if feature.replace_verification {
+            Vec::new()
+        } else {
+            self.verification
+                .commands
+                .iter()
+                .filter(|c| !feature.verification.iter().any(|own| own.name == c.name))
+                .collect()
+        };
+        commands.extend(&feature.verification);
+        commands
+    }
+
+    /// The global verification commands and every feature's own, each distinct command once,
+    /// with the `workdir` of the feature it comes from.
+    #[must_use]
+    pub fn all_verification(&self) -> Vec<(&VerifyCommand, Option<&Path>)> {
+        let global = self.verification.commands.iter().map(|cmd| (cmd, None));
+        let own = self.features.iter().flat_map(|f| f.verification.iter().map(|cmd| (cmd, f.workdir.as_deref())));
+        let mut commands: Vec<(&VerifyCommand, Option<&Path>)> = Vec::new();
+        for (cmd, workdir) in global.chain(own) {
+            if !commands.iter().any(|(c, dir)| c.name == cmd.name && c.command == cmd.command && *dir == workdir) {
+                commands.push((cmd, workdir));
+            }
+        }
+        commands
+    }
+
+    /// Features in `phase` that are still pending or in progress.
+    #[must_use]
+    pub fn phase_remaining(&self, phase: &str) -> Vec<&Feature> {
+        self.features
+            .iter()
+            .filter(|f| f.phase.as_deref() == Some(phase))
+            .filter(|f| matches!(f.status, Status::Pending | Status::InProgress))
+            .collect()
     }
 
     #[must_use]
@@ -92,12 +381,23 @@ impl Prd {
     }
 }
 
+/// JSON Schema for the PRD format, for editor autocomplete and external generators.
+pub fn schema() -> Result<String> {
+    let schema = schemars::schema_for!(Prd);
+    Ok(serde_json::to_string_pretty(&schema)? + "\n")
+}
+
+/// Parses the built-in template, for callers that generate a PRD programmatically.
+pub fn template() -> Result<Prd> {
+    json5::from_str(DEFAULT_TEMPLATE).context("Failed to parse built-in PRD template")
+}
+
 pub fn generate_template(path: &Path) -> Result<()> {
     generate_template_content(path, DEFAULT_TEMPLATE)
 }
 
 fn generate_template_content(path: &Path, content: &str) -> Result<()> {
-    std::fs::write(path, content)
+    atomic::write(path, content)
         .with_context(|| format!("Failed to write PRD template to: {}", path.display()))?;
     Ok(())
 }
@@ -276,6 +576,18 @@ mod tests {
             let result = Prd::load(file.path());
             assert!(result.is_err());
         }
+
+        #[test]
+        fn parse_error_shows_position_and_snippet() {
+            let mut file = NamedTempFile::new().unwrap();
+            write!(file, "{}\n}}", minimal_prd_json5()).unwrap();
+
+            let err = format!("{:#}", Prd::load(file.path()).unwrap_err());
+            assert!(err.contains("Failed to parse PRD file"));
+            assert!(err.contains("unexpected text after the end of the document at line"));
+            assert!(err.contains("| }\n"));
+            assert!(err.contains("| ^"));
+        }
     }
 
     mod status_counts_tests {
@@ -331,9 +643,166 @@ mod tests {
         }
     }
 
+    mod next_feature_tests {
+        use super::*;
+        use crate::testing::{FeatureBuilder, PrdBuilder};
+
+        #[test]
+        fn prefers_in_progress_over_pending() {
+            let mut file = NamedTempFile::new().unwrap();
+            write!(file, "{}", full_prd_json5()).unwrap();
+
+            let prd = Prd::load(file.path()).unwrap();
+            assert_eq!(prd.next_feature().unwrap().id, "feat-2");
+        }
+
+        #[test]
+        fn falls_back_to_first_pending() {
+            let mut prd = template().unwrap();
+            prd.features.push(Feature { id: "second".into(), ..Default::default() });
+            assert_eq!(prd.next_feature().unwrap().id, "example-feature");
+        }
+
+        #[test]
+        fn pending_features_go_by_priority_then_file_order() {
+            let mut prd = PrdBuilder::new()
+                .feature(FeatureBuilder::new("a"))
+                .feature(FeatureBuilder::new("b").priority(2))
+                .feature(FeatureBuilder::new("c").priority(1))
+                .feature(FeatureBuilder::new("d").priority(2))
+                .build();
+            assert_eq!(prd.next_feature().unwrap().id, "c");
+            let ids: Vec<_> = prd.ready_features().iter().map(|f| f.id.as_str()).collect();
+            assert_eq!(ids, ["c", "b", "d", "a"]);
+
+            prd.features[0].status = Status::InProgress;
+            assert_eq!(prd.next_feature().unwrap().id, "a");
+        }
+
+        #[test]
+        fn none_when_all_done() {
+            let mut file = NamedTempFile::new().unwrap();
+            write!(file, "{}", minimal_prd_json5()).unwrap();
+
+            let prd = Prd::load(file.path()).unwrap();
+            assert!(prd.next_feature().is_none());
+        }
+    }
+
+    mod schema_tests {
+        use super::*;
+
+        #[test]
+        fn uses_serde_field_names() {
+            let schema: serde_json::Value = serde_json::from_str(&schema().unwrap()).unwrap();
+            let feature = &schema["$defs"]["Feature"]["properties"];
+            assert!(feature.get("dependsOn").is_some());
+            assert!(feature.get("depends_on").is_none());
+            assert_eq!(
+                schema["$defs"]["Status"]["enum"],
+                serde_json::json!(["pending", "in-progress", "complete", "blocked"])
+            );
+        }
+
+        #[test]
+        fn published_schema_is_up_to_date() {
+            let published = include_str!("../prd.schema.json");
+            assert_eq!(
+                published,
+                schema().unwrap(),
+                "prd.schema.json is stale; regenerate with `ralph schema > prd.schema.json`"
+            );
+        }
+    }
+
+    mod progress_path_tests {
+        use super::*;
+
+        #[test]
+        fn defaults_beside_prd() {
+            let prd = template().unwrap();
+            assert_eq!(
+                prd.progress_path(Path::new("plans/prd.jsonc"), None),
+                PathBuf::from("plans/progress.txt")
+            );
+            assert_eq!(prd.progress_path(Path::new("prd.jsonc"), None), PathBuf::from("./progress.txt"));
+        }
+
+        #[test]
+        fn prd_setting_is_relative_to_prd_dir() {
+            let mut prd = template().unwrap();
+            prd.project.progress_path = Some("docs/progress.md".into());
+            assert_eq!(
+                prd.progress_path(Path::new("plans/prd.jsonc"), None),
+                PathBuf::from("plans/docs/progress.md")
+            );
+        }
+
+        #[test]
+        fn category_prompts_are_relative_to_prd_dir() {
+            let mut prd = template().unwrap();
+            prd.project.category_prompts.insert("test".into(), "prompts/test.md".into());
+            assert_eq!(
+                prd.category_prompt(Path::new("plans/prd.jsonc"), "test"),
+                Some(PathBuf::from("plans/prompts/test.md"))
+            );
+            assert_eq!(prd.category_prompt(Path::new("plans/prd.jsonc"), "docs"), None);
+        }
+
+        #[test]
+        fn project_dir_is_the_root_or_the_prd_dir() {
+            let mut prd = template().unwrap();
+            assert_eq!(prd.project_dir(Path::new("plans/prd.jsonc"), None), PathBuf::from("plans"));
+            prd.project.root = Some("..".into());
+            assert_eq!(prd.project_dir(Path::new("plans/prd.jsonc"), None), PathBuf::from("plans/.."));
+            assert_eq!(prd.project_dir(Path::new("plans/prd.jsonc"), Some(Path::new("app"))), PathBuf::from("app"));
+        }
+
+        #[test]
+        fn override_wins() {
+            let mut prd = template().unwrap();
+            prd.project.progress_path = Some("docs/progress.md".into());
+            assert_eq!(
+                prd.progress_path(Path::new("plans/prd.jsonc"), Some(Path::new(".ralph/progress.txt"))),
+                PathBuf::from(".ralph/progress.txt")
+            );
+        }
+
+        #[test]
+        fn parses_progress_path() {
+            let json = r#"{
+                "project": { "name": "test", "description": "desc", "progressPath": ".ralph/progress.txt" },
+                "verification": { "commands": [], "runAfterEachFeature": true },
+                "features": [],
+                "completion": { "allFeaturesComplete": true, "allVerificationsPassing": true, "marker": "X" }
+            }"#;
+            let mut file = NamedTempFile::new().unwrap();
+            write!(file, "{}", json).unwrap();
+
+            let prd = Prd::load(file.path()).unwrap();
+            assert_eq!(prd.project.progress_path, Some(PathBuf::from(".ralph/progress.txt")));
+        }
+    }
+
     mod serde_roundtrip_tests {
         use super::*;
 
+        #[test]
+        fn builtin_template_parses() {
+            let prd = template().unwrap();
+            assert_eq!(prd.features.len(), 1);
+            assert_eq!(prd.verification.commands.len(), 3);
+        }
+
+        #[test]
+        fn omits_absent_optional_fields() {
+            let mut prd = template().unwrap();
+            prd.features[0].notes = None;
+            let json = serde_json::to_string(&prd).unwrap();
+            assert!(!json.contains("\"notes\""));
+            assert!(!json.contains("\"repository\""));
+        }
+
         #[test]
         fn status_serializes_to_kebab_case() {
             assert_eq!(serde_json::to_string(&Status::Pending).unwrap(), "\"pending\"");
@@ -359,6 +828,202 @@ mod tests {
             }
         }
 
+        #[test]
+        fn status_as_str_matches_serde() {
+            for status in [Status::Pending, Status::InProgress, Status::Complete, Status::Blocked] {
+                let json = serde_json::to_string(&status).unwrap();
+                assert_eq!(json, format!("\"{}\"", status.as_str()));
+            }
+        }
+
+        #[test]
+        fn parses_context_and_budget() {
+            let json = r#"{
+                "project": { "name": "test", "description": "desc" },
+                "verification": { "commands": [], "runAfterEachFeature": true },
+                "features": [
+                    { "id": "f1", "category": "functional", "description": "d", "steps": [], "status": "pending",
+                      "context": ["src/lib.rs", "src/**/*.rs"], "contextBudget": { "tokens": 2000 } }
+                ],
+                "completion": { "allFeaturesComplete": true, "allVerificationsPassing": true, "marker": "X" }
+            }"#;
+            let mut file = NamedTempFile::new().unwrap();
+            write!(file, "{}", json).unwrap();
+
+            let prd = Prd::load(file.path()).unwrap();
+            assert_eq!(prd.features[0].context, vec!["src/lib.rs", "src/**/*.rs"]);
+            assert_eq!(
+                prd.features[0].context_budget,
+                Some(ContextBudget { bytes: None, tokens: Some(2000) })
+            );
+        }
+
+        #[test]
+        fn parses_coverage_config() {
+            let json = r#"{
+                "project": { "name": "test", "description": "desc" },
+                "verification": {
+                    "commands": [],
+                    "runAfterEachFeature": true,
+                    "coverage": { "command": "cargo llvm-cov --lcov --output-path lcov.info", "report": "lcov.info", "minDelta": -0.5 }
+                },
+                "features": [],
+                "completion": { "allFeaturesComplete": true, "allVerificationsPassing": true, "marker": "X" }
+            }"#;
+            let mut file = NamedTempFile::new().unwrap();
+            write!(file, "{}", json).unwrap();
+
+            let prd = Prd::load(file.path()).unwrap();
+            let coverage = prd.verification.coverage.unwrap();
+            assert_eq!(coverage.report, "lcov.info");
+            assert_eq!(coverage.min_delta, -0.5);
+        }
+
+        #[test]
+        fn coverage_min_delta_defaults_to_zero() {
+            let json = r#"{ "command": "true", "report": "lcov.info" }"#;
+            let coverage: CoverageConfig = json5::from_str(json).unwrap();
+            assert_eq!(coverage.min_delta, 0.0);
+        }
+
+        #[test]
+        fn parses_dependencies_and_phase() {
+            let json = r#"{ "id": "f2", "category": "c", "description": "d", "steps": [], "status": "pending",
+                "dependsOn": ["f1"], "phase": "MVP" }"#;
+            let feature: Feature = json5::from_str(json).unwrap();
+            assert_eq!(feature.depends_on, vec!["f1"]);
+            assert_eq!(feature.phase.as_deref(), Some("MVP"));
+        }
+
+        #[test]
+        fn parses_phase_markers() {
+            let json = r#"{ "allFeaturesComplete": true, "allVerificationsPassing": true, "marker": "DONE",
+                "phaseMarkers": { "MVP": "<promise>MVP</promise>" } }"#;
+            let completion: Completion = json5::from_str(json).unwrap();
+            assert_eq!(completion.phase_markers["MVP"], "<promise>MVP</promise>");
+        }
+
+        #[test]
+        fn phase_remaining_ignores_done_and_other_phases() {
+            let json = r#"{
+                "project": { "name": "test", "description": "desc" },
+                "verification": { "commands": [], "runAfterEachFeature": true },
+                "features": [
+                    { "id": "f1", "category": "c", "description": "d", "steps": [], "status": "complete", "phase": "MVP" },
+                    { "id": "f2", "category": "c", "description": "d", "steps": [], "status": "blocked", "phase": "MVP" },
+                    { "id": "f3", "category": "c", "description": "d", "steps": [], "status": "pending", "phase": "MVP" },
+                    { "id": "f4", "category": "c", "description": "d", "steps": [], "status": "pending", "phase": "Later" }
+                ],
+                "completion": { "allFeaturesComplete": true, "allVerificationsPassing": true, "marker": "X" }
+            }"#;
+            let prd: Prd = json5::from_str(json).unwrap();
+            let ids: Vec<_> = prd.phase_remaining("MVP").iter().map(|f| f.id.as_str()).collect();
+            assert_eq!(ids, vec!["f3"]);
+            assert!(prd.phase_remaining("Unknown").is_empty());
+        }
+
+        #[test]
+        fn ready_features_wait_on_incomplete_dependencies() {
+            let json = r#"{
+                "project": { "name": "test", "description": "desc" },
+                "verification": { "commands": [], "runAfterEachFeature": true },
+                "features": [
+                    { "id": "f1", "category": "c", "description": "d", "steps": [], "status": "complete" },
+                    { "id": "f2", "category": "c", "description": "d", "steps": [], "status": "pending", "dependsOn": ["f1"] },
+                    { "id": "f3", "category": "c", "description": "d", "steps": [], "status": "pending", "dependsOn": ["f2"] },
+                    { "id": "f4", "category": "c", "description": "d", "steps": [], "status": "in-progress", "dependsOn": ["gone"] },
+                    { "id": "f5", "category": "c", "description": "d", "steps": [], "status": "blocked" }
+                ],
+                "completion": { "allFeaturesComplete": true, "allVerificationsPassing": true, "marker": "X" }
+            }"#;
+            let prd: Prd = json5::from_str(json).unwrap();
+            let ids: Vec<_> = prd.ready_features().iter().map(|f| f.id.as_str()).collect();
+            assert_eq!(ids, vec!["f2", "f4"]);
+        }
+
+        #[test]
+        fn feature_verification_merges_with_global() {
+            let json = r#"{
+                "project": { "name": "test", "description": "desc" },
+                "verification": { "commands": [
+                    { "name": "check", "command": "cargo check", "description": "" },
+                    { "name": "test", "command": "cargo test", "description": "" }
+                ], "runAfterEachFeature": true },
+                "features": [
+                    { "id": "f1", "category": "c", "description": "d", "steps": [], "status": "pending" },
+                    { "id": "f2", "category": "c", "description": "d", "steps": [], "status": "pending",
+                      "verification": [
+                        { "name": "test", "command": "cargo test -p api", "description": "" },
+                        { "name": "lint", "command": "cargo clippy -p api", "description": "" }
+                      ] },
+                    { "id": "f3", "category": "c", "description": "d", "steps": [], "status": "pending",
+                      "replaceVerification": true,
+                      "verification": [{ "name": "docs", "command": "mdbook build", "description": "" }] }
+                ],
+                "completion": { "allFeaturesComplete": true, "allVerificationsPassing": true, "marker": "X" }
+            }"#;
+            let prd: Prd = json5::from_str(json).unwrap();
+            let commands = |i: usize| -> Vec<&str> {
+                prd.verification_for(&prd.features[i]).iter().map(|c| c.command.as_str()).collect()
+            };
+            assert_eq!(commands(0), vec!["cargo check", "cargo test"]);
+            assert_eq!(commands(1), vec!["cargo check", "cargo test -p api", "cargo clippy -p api"]);
+            assert_eq!(commands(2), vec!["mdbook build"]);
+        }
+
+        #[test]
+        fn all_verification_lists_each_command_once() {
+            let prd = crate::testing::PrdBuilder::new()
+                .command("check", "cargo check")
+                .command("test", "cargo test")
+                .feature(crate::testing::FeatureBuilder::new("f1").command("test", "cargo test -p api"))
+                .feature(crate::testing::FeatureBuilder::new("f2").command("check", "cargo check"))
+                .build();
+            let commands: Vec<&str> = prd.all_verification().iter().map(|(c, _)| c.command.as_str()).collect();
+            assert_eq!(commands, vec!["cargo check", "cargo test", "cargo test -p api"]);
+        }
+
+        #[test]
+        fn all_verification_keeps_commands_of_other_workdirs() {
+            let mut prd = crate::testing::PrdBuilder::new()
+                .command("check", "cargo check")
+                .feature(crate::testing::FeatureBuilder::new("f1").command("check", "cargo check"))
+                .build();
+            prd.features[0].workdir = Some("packages/api".into());
+            let commands = prd.all_verification();
+            assert_eq!(commands.len(), 2);
+            assert_eq!(commands[1].1, Some(Path::new("packages/api")));
+        }
+
+        #[test]
+        fn feature_working_dir_is_under_the_project_dir() {
+            let mut feature = crate::testing::FeatureBuilder::new("f1").build();
+            assert_eq!(feature.working_dir(Path::new("repo")), PathBuf::from("repo"));
+            feature.workdir = Some("packages/api".into());
+            assert_eq!(feature.working_dir(Path::new("repo")), PathBuf::from("repo/packages/api"));
+        }
+
+        #[test]
+        fn parses_acceptance_criteria_and_command() {
+            let json = r#"{
+                "project": { "name": "test", "description": "desc" },
+                "verification": { "commands": [], "runAfterEachFeature": true },
+                "features": [
+                    { "id": "f1", "category": "c", "description": "d", "steps": [], "status": "pending",
+                      "acceptanceCriteria": ["Wrong passwords are rejected"],
+                      "acceptanceCommand": "cargo test --test login" },
+                    { "id": "f2", "category": "c", "description": "d", "steps": [], "status": "pending" }
+                ],
+                "completion": { "allFeaturesComplete": true, "allVerificationsPassing": true, "marker": "X" }
+            }"#;
+            let prd: Prd = json5::from_str(json).unwrap();
+            assert_eq!(prd.features[0].acceptance_criteria, ["Wrong passwords are rejected"]);
+            let check = prd.features[0].acceptance_check().unwrap();
+            assert_eq!((check.name.as_str(), check.command.as_str()), ("f1-acceptance", "cargo test --test login"));
+            assert!(prd.features[1].acceptance_check().is_none());
+            assert!(!serde_json::to_string(&prd.features[1]).unwrap().contains("acceptance"));
+        }
+
         #[test]
         fn invalid_status_fails() {
             assert!(serde_json::from_str::<Status>("\"unknown\"").is_err());
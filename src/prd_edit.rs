@@ -0,0 +1,605 @@
+//! Text-level PRD edits that keep comments and formatting: locate, remove, and insert
+//! feature objects, or set a feature's status, in a JSON5 document without re-serializing it.
+
+use crate::prd::{Feature, Status};
+use anyhow::{bail, Context, Result};
+use std::ops::Range;
+
+/// A feature object in the top-level `features` array.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeatureSpan {
+    pub id: String,
+    /// Byte range of the object, `{` through `}`.
+    pub range: Range<usize>,
+}
+
+/// Byte positions of the `features` array and its elements.
+#[derive(Debug)]
+struct FeaturesArray {
+    close: usize,
+    elements: Vec<FeatureSpan>,
+}
+
+/// Finds every feature object, in document order.
+pub fn feature_spans(content: &str) -> Result<Vec<FeatureSpan>> {
+    Ok(features_array(content)?.elements)
+}
+
+/// Removes the feature `id`, returning the new document and the feature's original text.
+pub fn remove_feature(content: &str, id: &str) -> Result<(String, String)> {
+    let array = features_array(content)?;
+    let index = array
+        .elements
+        .iter()
+        .position(|f| f.id == id)
+        .with_context(|| format!("Feature '{id}' not found in PRD"))?;
+    let range = array.elements[index].range.clone();
+    let text = content[range.clone()].to_string();
+    let bytes = content.as_bytes();
+
+    // Take the element's own lines when it sits alone on them
+    let line_start = content[..range.start].rfind('\n').map_or(0, |i| i + 1);
+    let mut start = if content[line_start..range.start].trim().is_empty() {
+        line_start
+    } else {
+        range.start
+    };
+    let mut end = skip_inline_space(bytes, range.end);
+    let had_comma = bytes.get(end) == Some(&b',');
+    if had_comma {
+        end = skip_inline_space(bytes, end + 1);
+    }
+    if bytes.get(end) == Some(&b'\n') {
+        end += 1;
+    } else if start == line_start && !had_comma {
+        // Last element followed by `]` on the same line: keep the line break before it
+        start = range.start;
+    }
+
+    // Without a trailing comma, the previous element's separator must go too
+    if !had_comma && index > 0 {
+        let prev_end = array.elements[index - 1].range.end;
+        let comma = skip_inline_space(bytes, prev_end);
+        if bytes.get(comma) == Some(&b',') {
+            let mut out = String::with_capacity(content.len());
+            out.push_str(&content[..comma]);
+            out.push_str(&content[comma + 1..start]);
+            out.push_str(&content[end..]);
+            return Ok((out, text));
+        }
+    }
+
+    Ok((format!("{}{}", &content[..start], &content[end..]), text))
+}
+
+/// Appends a feature object (as text, e.g. from [`remove_feature`]) to the end of the
+/// `features` array, re-indented to match its new neighbours.
+pub fn insert_feature(content: &str, feature_text: &str) -> Result<String> {
+    let array = features_array(content)?;
+    let bytes = content.as_bytes();
+    let text = reindent(feature_text);
+
+    let Some(last) = array.elements.last() else {
+        let close_indent = line_indent(content, array.close);
+        let indent = format!("{close_indent}  ");
+        let open = content[..array.close].rfind('[').context("Malformed features array")?;
+        return Ok(format!(
+            "{}[\n{}\n{close_indent}]{}",
+            &content[..open],
+            indent_lines(&text, &indent),
+            &content[array.close + 1..]
+        ));
+    };
+
+    let indent = line_indent(content, last.range.start);
+    let after = skip_inline_space(bytes, last.range.end);
+    let entry = indent_lines(&text, &indent);
+    Ok(if bytes.get(after) == Some(&b',') {
+        // Keep the trailing-comma style
+        format!("{}\n{entry},{}", &content[..=after], &content[after + 1..])
+    } else {
+        format!("{},\n{entry}{}", &content[..last.range.end], &content[last.range.end..])
+    })
+}
+
+/// Replaces the value of a feature object's `id` key.
+pub fn rename_feature(feature_text: &str, new_id: &str) -> Result<String> {
+    let mut scanner = Scanner::new(feature_text);
+    scanner.skip_trivia()?;
+    if !scanner.find_key("id")? {
+        bail!("Feature has no id");
+    }
+    let start = scanner.pos;
+    scanner.skip_value()?;
+    Ok(format!(
+        "{}{}{}",
+        &feature_text[..start],
+        serde_json::to_string(new_id)?,
+        &feature_text[scanner.pos..]
+    ))
+}
+
+/// Sets the status of feature `id` to `to` if it's one of `from`, replacing only the status
+/// value. None when the feature has another status.
+pub fn set_status(content: &str, id: &str, from: &[Status], to: Status) -> Result<Option<String>> {
+    let array = features_array(content)?;
+    let span = array
+        .elements
+        .iter()
+        .find(|f| f.id == id)
+        .with_context(|| format!("Feature {id} not found in PRD"))?;
+    let text = &content[span.range.clone()];
+    let mut feature: Feature = json5::from_str(text).with_context(|| format!("Failed to parse feature {id}"))?;
+    if !from.contains(&feature.status) {
+        return Ok(None);
+    }
+
+    let mut scanner = Scanner::new(text);
+    if !scanner.find_key("status")? {
+        bail!("Feature {id} has no status");
+    }
+    let start = scanner.pos;
+    scanner.skip_value()?;
+    let edited_text = format!("{}{}{}", &text[..start], serde_json::to_string(&to)?, &text[scanner.pos..]);
+
+    // Nothing but the status may change, or the PRD would be corrupted on write
+    let edited: Feature = json5::from_str(&edited_text).with_context(|| format!("Failed to update feature {id}"))?;
+    feature.status = to;
+    if serde_json::to_value(&edited)? != serde_json::to_value(&feature)? {
+        bail!("Failed to update feature {id}: the edit changed more than its status");
+    }
+    Ok(Some(format!(
+        "{}{edited_text}{}",
+        &content[..span.range.start],
+        &content[span.range.end..]
+    )))
+}
+
+/// A step in a path to a value: an object key or an array index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathSegment<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+/// Byte offset of the value at `path`, e.g. `features[2].steps`; None if it isn't there.
+#[must_use]
+pub fn locate(content: &str, path: &[PathSegment<'_>]) -> Option<usize> {
+    let mut scanner = Scanner::new(content);
+    scanner.skip_trivia().ok()?;
+    for segment in path {
+        let found = match *segment {
+            PathSegment::Key(key) => scanner.find_key(key),
+            PathSegment::Index(index) => scanner.find_index(index),
+        };
+        if !found.ok()? {
+            return None;
+        }
+    }
+    Some(scanner.pos)
+}
+
+fn features_array(content: &str) -> Result<FeaturesArray> {
+    let mut scanner = Scanner::new(content);
+    scanner.skip_trivia()?;
+    if !scanner.find_key("features")? {
+        bail!("PRD has no features array");
+    }
+    scanner.read_features(content)
+}
+
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(content: &'a str) -> Self {
+        Self {
+            bytes: content.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, b: u8) -> Result<()> {
+        if self.peek() != Some(b) {
+            bail!("Expected '{}' at byte {} of PRD", b as char, self.pos);
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// Moves from the object at the cursor to the value of `wanted`; false if it has no
+    /// such key.
+    fn find_key(&mut self, wanted: &str) -> Result<bool> {
+        self.expect(b'{')?;
+        loop {
+            self.skip_trivia()?;
+            if self.peek() == Some(b'}') {
+                return Ok(false);
+            }
+            let key = self.read_key()?;
+            self.skip_trivia()?;
+            self.expect(b':')?;
+            self.skip_trivia()?;
+            if key == wanted {
+                return Ok(true);
+            }
+            self.skip_value()?;
+            self.skip_trivia()?;
+            if self.peek() == Some(b',') {
+                self.pos += 1;
+            }
+        }
+    }
+
+    /// Moves from the array at the cursor to its element `index`; false if it's shorter.
+    fn find_index(&mut self, index: usize) -> Result<bool> {
+        self.expect(b'[')?;
+        for _ in 0..index {
+            self.skip_trivia()?;
+            if self.peek() == Some(b']') {
+                return Ok(false);
+            }
+            self.skip_value()?;
+            self.skip_trivia()?;
+            if self.peek() == Some(b',') {
+                self.pos += 1;
+            }
+        }
+        self.skip_trivia()?;
+        Ok(self.peek().is_some_and(|b| b != b']'))
+    }
+
+    /// Skips whitespace and `//` / `/* */` comments.
+    fn skip_trivia(&mut self) -> Result<()> {
+        loop {
+            match (self.peek(), self.bytes.get(self.pos + 1)) {
+                (Some(b), _) if b.is_ascii_whitespace() => self.pos += 1,
+                (Some(b'/'), Some(b'/')) => {
+                    while self.peek().is_some_and(|b| b != b'\n') {
+                        self.pos += 1;
+                    }
+                }
+                (Some(b'/'), Some(b'*')) => {
+                    let rest = &self.bytes[self.pos + 2..];
+                    let end = rest
+                        .windows(2)
+                        .position(|w| w == b"*/")
+                        .context("Unterminated comment in PRD")?;
+                    self.pos += end + 4;
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn skip_string(&mut self) -> Result<()> {
+        let quote = self.peek().context("Unexpected end of PRD")?;
+        self.pos += 1;
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+            if b == b'\\' {
+                self.pos += 1;
+            } else if b == quote {
+                return Ok(());
+            }
+        }
+        bail!("Unterminated string in PRD")
+    }
+
+    /// A quoted or bare (identifier) object key.
+    fn read_key(&mut self) -> Result<String> {
+        let start = self.pos;
+        match self.peek() {
+            Some(b'"' | b'\'') => {
+                self.skip_string()?;
+                let raw = std::str::from_utf8(&self.bytes[start..self.pos])?;
+                json5::from_str(raw).context("Invalid key in PRD")
+            }
+            _ => {
+                while self
+                    .peek()
+                    .is_some_and(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'$')
+                {
+                    self.pos += 1;
+                }
+                if self.pos == start {
+                    bail!("Expected a key at byte {start} of PRD");
+                }
+                Ok(String::from_utf8_lossy(&self.bytes[start..self.pos]).into_owned())
+            }
+        }
+    }
+
+    /// Skips a string, balanced object/array, or bare literal.
+    fn skip_value(&mut self) -> Result<()> {
+        match self.peek() {
+            Some(b'"' | b'\'') => self.skip_string(),
+            Some(b'{' | b'[') => {
+                let mut depth = 0usize;
+                loop {
+                    self.skip_trivia()?;
+                    match self.peek() {
+                        Some(b'"' | b'\'') => self.skip_string()?,
+                        Some(b'{' | b'[') => {
+                            depth += 1;
+                            self.pos += 1;
+                        }
+                        Some(b'}' | b']') => {
+                            depth -= 1;
+                            self.pos += 1;
+                            if depth == 0 {
+                                return Ok(());
+                            }
+                        }
+                        Some(_) => self.pos += 1,
+                        None => bail!("Unbalanced brackets in PRD"),
+                    }
+                }
+            }
+            Some(_) => {
+                while self
+                    .peek()
+                    .is_some_and(|b| !matches!(b, b',' | b'}' | b']') && !b.is_ascii_whitespace())
+                {
+                    self.pos += 1;
+                }
+                Ok(())
+            }
+            None => bail!("Unexpected end of PRD"),
+        }
+    }
+
+    fn read_features(&mut self, content: &str) -> Result<FeaturesArray> {
+        self.expect(b'[')?;
+        let mut elements = Vec::new();
+        loop {
+            self.skip_trivia()?;
+            if self.peek() == Some(b']') {
+                return Ok(FeaturesArray {
+                    close: self.pos,
+                    elements,
+                });
+            }
+            let start = self.pos;
+            self.skip_value()?;
+            let feature: Feature = json5::from_str(&content[start..self.pos])
+                .with_context(|| format!("Failed to parse feature at byte {start} of PRD"))?;
+            elements.push(FeatureSpan {
+                id: feature.id,
+                range: start..self.pos,
+            });
+            self.skip_trivia()?;
+            if self.peek() == Some(b',') {
+                self.pos += 1;
+            }
+        }
+    }
+}
+
+fn skip_inline_space(bytes: &[u8], mut pos: usize) -> usize {
+    while bytes.get(pos).is_some_and(|&b| b == b' ' || b == b'\t' || b == b'\r') {
+        pos += 1;
+    }
+    pos
+}
+
+/// Leading whitespace of the line containing `pos`.
+fn line_indent(content: &str, pos: usize) -> String {
+    let line_start = content[..pos].rfind('\n').map_or(0, |i| i + 1);
+    content[line_start..]
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .collect()
+}
+
+/// Strips the common indentation of all lines but the first (which starts at `{`).
+fn reindent(text: &str) -> String {
+    let mut lines = text.lines();
+    let first = lines.next().unwrap_or_default();
+    let rest: Vec<&str> = lines.collect();
+    // The closing `}` line carries the object's own indentation
+    let common = rest
+        .iter()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let base = rest
+        .last()
+        .map_or(common, |l| (l.len() - l.trim_start().len()).min(common));
+    std::iter::once(first.to_string())
+        .chain(rest.iter().map(|l| l.get(base..).unwrap_or(l.trim_start()).to_string()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn indent_lines(text: &str, indent: &str) -> String {
+    text.lines()
+        .map(|l| if l.is_empty() { String::new() } else { format!("{indent}{l}") })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRD: &str = r#"{
+  // Project
+  "project": { "name": "p", "description": "d" },
+  "verification": { "commands": [], "runAfterEachFeature": false },
+  "features": [
+    {
+      "id": "f1", // first
+      "category": "c", "description": "one", "steps": [], "status": "pending"
+    },
+    // second feature
+    {
+      "id": "f2",
+      "category": "c", "description": "two, with ] and }", "steps": ["a"], "status": "pending"
+    }
+  ],
+  "completion": { "allFeaturesComplete": true, "allVerificationsPassing": true, "marker": "X" }
+}"#;
+
+    fn ids(content: &str) -> Vec<String> {
+        feature_spans(content).unwrap().into_iter().map(|f| f.id).collect()
+    }
+
+    mod feature_spans_tests {
+        use super::*;
+
+        #[test]
+        fn finds_features_past_comments_and_strings() {
+            let spans = feature_spans(PRD).unwrap();
+            assert_eq!(ids(PRD), vec!["f1", "f2"]);
+            assert!(PRD[spans[1].range.clone()].ends_with("\"status\": \"pending\"\n    }"));
+        }
+
+        #[test]
+        fn accepts_unquoted_keys() {
+            let content = r#"{ features: [ { id: 'a', category: 'c', description: 'd', steps: [], status: 'pending' } ] }"#;
+            assert_eq!(ids(content), vec!["a"]);
+        }
+
+        #[test]
+        fn missing_array_is_an_error() {
+            assert!(feature_spans(r#"{ "project": {} }"#).is_err());
+        }
+    }
+
+    mod remove_feature_tests {
+        use super::*;
+
+        #[test]
+        fn removes_first_and_keeps_comments() {
+            let (out, text) = remove_feature(PRD, "f1").unwrap();
+            assert!(text.starts_with("{\n      \"id\": \"f1\""));
+            assert_eq!(ids(&out), vec!["f2"]);
+            assert!(out.contains("// second feature"));
+            assert!(out.contains("// Project"));
+            json5::from_str::<crate::prd::Prd>(&out).unwrap();
+        }
+
+        #[test]
+        fn removes_last_and_its_separator() {
+            let (out, _) = remove_feature(PRD, "f2").unwrap();
+            assert_eq!(ids(&out), vec!["f1"]);
+            assert!(out.contains("\"status\": \"pending\"\n    }\n    // second feature\n  ],"));
+        }
+
+        #[test]
+        fn removes_only_feature_on_one_line() {
+            let content = r#"{ "features": [ { "id": "a", "category": "c", "description": "d", "steps": [], "status": "pending" } ] }"#;
+            let (out, _) = remove_feature(content, "a").unwrap();
+            assert_eq!(out, r#"{ "features": [ ] }"#);
+        }
+
+        #[test]
+        fn unknown_feature_is_an_error() {
+            assert!(remove_feature(PRD, "nope").is_err());
+        }
+    }
+
+    mod insert_feature_tests {
+        use super::*;
+
+        #[test]
+        fn appends_with_matching_indent() {
+            let (_, text) = remove_feature(PRD, "f1").unwrap();
+            let (target, _) = remove_feature(PRD, "f1").unwrap();
+            let out = insert_feature(&target, &text).unwrap();
+            assert_eq!(ids(&out), vec!["f2", "f1"]);
+            assert!(out.contains("    },\n    {\n      \"id\": \"f1\", // first\n"));
+            json5::from_str::<crate::prd::Prd>(&out).unwrap();
+        }
+
+        #[test]
+        fn keeps_trailing_comma_style() {
+            let content = "{\n  \"features\": [\n    { \"id\": \"a\", \"category\": \"c\", \"description\": \"d\", \"steps\": [], \"status\": \"pending\" },\n  ]\n}";
+            let out = insert_feature(content, r#"{ "id": "b", "category": "c", "description": "d", "steps": [], "status": "pending" }"#).unwrap();
+            assert_eq!(ids(&out), vec!["a", "b"]);
+            assert!(out.contains("},\n    { \"id\": \"b\""));
+            assert!(out.contains("\"pending\" },\n  ]"));
+        }
+
+        #[test]
+        fn fills_empty_array() {
+            let content = "{\n  \"features\": []\n}";
+            let out = insert_feature(content, "{\n  \"id\": \"a\", \"category\": \"c\", \"description\": \"d\", \"steps\": [], \"status\": \"pending\"\n}").unwrap();
+            assert_eq!(
+                out,
+                "{\n  \"features\": [\n    {\n      \"id\": \"a\", \"category\": \"c\", \"description\": \"d\", \"steps\": [], \"status\": \"pending\"\n    }\n  ]\n}"
+            );
+        }
+    }
+
+    mod rename_feature_tests {
+        use super::*;
+
+        #[test]
+        fn replaces_only_the_id_value() {
+            let text = r#"{ "description": "id: f1", "id": "f1", "status": "pending" }"#;
+            assert_eq!(
+                rename_feature(text, "f1-web").unwrap(),
+                r#"{ "description": "id: f1", "id": "f1-web", "status": "pending" }"#
+            );
+        }
+    }
+
+    mod set_status_tests {
+        use super::*;
+
+        #[test]
+        fn replaces_only_the_status_value() {
+            let out = set_status(PRD, "f2", &[Status::Pending], Status::Blocked).unwrap().unwrap();
+            assert_eq!(out, PRD.replacen(r#"["a"], "status": "pending""#, r#"["a"], "status": "blocked""#, 1));
+            let prd: crate::prd::Prd = json5::from_str(&out).unwrap();
+            assert_eq!((prd.features[0].status, prd.features[1].status), (Status::Pending, Status::Blocked));
+        }
+
+        #[test]
+        fn keeps_quote_style_and_comments_around_the_value() {
+            let content = "{ features: [{ id: 'a', category: 'c', description: 'd', steps: [],\n  status: 'complete' /* done */ }] }";
+            let out = set_status(content, "a", &[Status::Complete], Status::Pending).unwrap().unwrap();
+            assert_eq!(out, content.replace("'complete'", r#""pending""#));
+        }
+
+        #[test]
+        fn none_when_status_not_in_from() {
+            assert_eq!(set_status(PRD, "f1", &[Status::Complete], Status::Pending).unwrap(), None);
+        }
+
+        #[test]
+        fn errors_for_unknown_feature() {
+            let err = set_status(PRD, "nope", &[Status::Pending], Status::Blocked).unwrap_err();
+            assert!(err.to_string().contains("Feature nope not found"));
+        }
+    }
+
+    mod locate_tests {
+        use super::*;
+        use PathSegment::{Index, Key};
+
+        #[test]
+        fn finds_nested_values_past_comments() {
+            let at = locate(PRD, &[Key("features"), Index(1), Key("steps"), Index(0)]).unwrap();
+            assert!(PRD[at..].starts_with(r#""a"], "status""#));
+            let at = locate(PRD, &[Key("completion"), Key("marker")]).unwrap();
+            assert!(PRD[at..].starts_with(r#""X""#));
+        }
+
+        #[test]
+        fn none_for_missing_keys_and_indexes() {
+            assert_eq!(locate(PRD, &[Key("features"), Index(2)]), None);
+            assert_eq!(locate(PRD, &[Key("features"), Index(0), Key("dependsOn")]), None);
+            assert_eq!(locate(PRD, &[Key("features"), Index(0), Key("steps"), Index(0)]), None);
+        }
+    }
+}
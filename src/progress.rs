@@ -0,0 +1,427 @@
+//! Structured entries ralph itself appends to the progress file.
+//!
+//! The agent rereads the progress file every iteration, so entries are capped and point
+//! to the iteration artifact for the full details. Beside it, `progress.jsonl` gets one
+//! record per iteration for tools that chart progress over time. Once the file outgrows
+//! `--progress-max-bytes` it is archived as `progress-YYYYMMDD.txt` and restarted with a
+//! summary of what came before.
+
+use crate::artifact::IterationArtifact;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// One line of `progress.jsonl`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Record {
+    /// When the iteration started
+    pub timestamp: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    pub iteration: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feature_id: Option<String>,
+    pub result: String,
+    pub duration_secs: u64,
+    pub log_path: PathBuf,
+    /// HEAD once the iteration finished
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+}
+
+impl Record {
+    #[must_use]
+    pub fn new(artifact: &IterationArtifact, commit: Option<String>) -> Self {
+        Self {
+            timestamp: artifact.started_at.clone(),
+            run_id: artifact.run_id.clone(),
+            iteration: artifact.iteration,
+            feature_id: artifact.feature_id.clone(),
+            result: artifact.result.clone(),
+            duration_secs: artifact.duration_secs,
+            log_path: artifact.log_path.clone(),
+            commit,
+        }
+    }
+}
+
+/// `progress.jsonl` for `progress.txt`: the progress file with a `jsonl` extension.
+#[must_use]
+pub fn records_path(progress_path: &Path) -> PathBuf {
+    progress_path.with_extension("jsonl")
+}
+
+pub fn append_record(progress_path: &Path, record: &Record) -> Result<()> {
+    let path = records_path(progress_path);
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+/// The records beside `progress_path`, oldest first. Unparseable lines are skipped.
+#[must_use]
+pub fn load_records(progress_path: &Path) -> Vec<Record> {
+    std::fs::read_to_string(records_path(progress_path))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryLimits {
+    pub max_lines: usize,
+    pub max_bytes: usize,
+}
+
+/// Appends a `## {title}` entry with `body` truncated to `limits`.
+pub fn append_entry(
+    progress_path: &Path,
+    title: &str,
+    body: &str,
+    limits: EntryLimits,
+    artifact: Option<&Path>,
+) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(progress_path)
+        .context("Failed to open progress file")?;
+    write!(file, "{}", format_entry(title, body, limits, artifact))?;
+    Ok(())
+}
+
+pub(crate) fn format_entry(title: &str, body: &str, limits: EntryLimits, artifact: Option<&Path>) -> String {
+    let lines: Vec<&str> = body.lines().collect();
+    let mut kept = String::new();
+    let mut kept_lines = 0;
+    let mut shortened = false;
+
+    for line in lines.iter().take(limits.max_lines) {
+        if kept.len() + line.len() + 1 > limits.max_bytes {
+            let room = limits.max_bytes.saturating_sub(kept.len());
+            if room > 0 && kept_lines == 0 {
+                // A single oversized first line is cut rather than dropped entirely
+                let mut end = room.min(line.len());
+                while !line.is_char_boundary(end) {
+                    end -= 1;
+                }
+                kept.push_str(&line[..end]);
+                kept.push('\n');
+                kept_lines = 1;
+                shortened = true;
+            }
+            break;
+        }
+        kept.push_str(line);
+        kept.push('\n');
+        kept_lines += 1;
+    }
+
+    let mut entry = format!("\n## {title}\n\n{kept}");
+    let omitted = lines.len() - kept_lines;
+    if omitted > 0 || shortened {
+        entry.push_str(&truncation_marker(omitted, artifact));
+        entry.push('\n');
+    }
+    entry.push('\n');
+    entry
+}
+
+fn truncation_marker(omitted_lines: usize, artifact: Option<&Path>) -> String {
+    let what = if omitted_lines > 0 {
+        format!("{omitted_lines} more line{}", if omitted_lines == 1 { "" } else { "s" })
+    } else {
+        "line shortened".to_string()
+    };
+    match artifact {
+        Some(path) => format!("[ralph: truncated, {what}; full details in {}]", path.display()),
+        None => format!("[ralph: truncated, {what}]"),
+    }
+}
+
+/// Heading of the section [`rotate`] puts at the top of a fresh progress file.
+pub const SUMMARY_HEADING: &str = "Summary so far";
+/// Entry titles listed in the summary; older ones are only counted.
+const SUMMARY_TITLES: usize = 40;
+/// Lines of the last entry carried over into the summary.
+const SUMMARY_LAST_ENTRY_LINES: usize = 40;
+
+/// Archives the progress file as `<stem>-<date>.<ext>` beside it once it is larger than
+/// `max_bytes` (0 never rotates), and starts a new one with the old file's preamble and a
+/// summary: the archives so far, the entry titles, and the last entry. Returns the archive.
+pub fn rotate(progress_path: &Path, max_bytes: u64, date: &str) -> Result<Option<PathBuf>> {
+    let size = match std::fs::metadata(progress_path) {
+        Ok(meta) => meta.len(),
+        Err(_) => return Ok(None),
+    };
+    if max_bytes == 0 || size <= max_bytes {
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(progress_path).context("Failed to read progress file")?;
+    let archive = archive_path(progress_path, date);
+    let name = archive.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let summary = summarize(&content, &name);
+    std::fs::rename(progress_path, &archive)
+        .with_context(|| format!("Failed to archive progress file to {}", archive.display()))?;
+    crate::atomic::write(progress_path, &summary)?;
+    Ok(Some(archive))
+}
+
+/// `progress-20260101.txt` for `progress.txt`, with `-2`, `-3`, ... once that is taken.
+fn archive_path(progress_path: &Path, date: &str) -> PathBuf {
+    let stem = progress_path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = progress_path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    (1..)
+        .map(|n| match n {
+            1 => progress_path.with_file_name(format!("{stem}-{date}{ext}")),
+            n => progress_path.with_file_name(format!("{stem}-{date}-{n}{ext}")),
+        })
+        .find(|path| !path.exists())
+        .unwrap_or_else(|| progress_path.with_extension("old"))
+}
+
+/// The new progress file for `content` archived as `archive`. A summary already in
+/// `content` is folded in, so the list covers every archive.
+fn summarize(content: &str, archive: &str) -> String {
+    let (preamble, sections) = split_sections(content);
+    let mut archives: Vec<String> = Vec::new();
+    let mut titles: Vec<String> = Vec::new();
+    let mut earlier = 0;
+    for (title, body) in &sections {
+        if *title != SUMMARY_HEADING {
+            titles.push(title.to_string());
+            continue;
+        }
+        for line in body.lines() {
+            if let Some(list) = line.strip_prefix("Archived: ") {
+                archives.extend(list.split(", ").map(String::from));
+            } else if let Some(n) = line.strip_prefix("- (").and_then(|l| l.split(' ').next()?.parse::<usize>().ok()) {
+                earlier += n;
+            } else if let Some(title) = line.strip_prefix("- ") {
+                titles.push(title.to_string());
+            }
+        }
+    }
+    archives.push(archive.to_string());
+    let dropped = titles.len().saturating_sub(SUMMARY_TITLES);
+    earlier += dropped;
+
+    let mut summary = preamble.trim_end().to_string();
+    if !summary.is_empty() {
+        summary.push_str("\n\n");
+    }
+    summary.push_str(&format!("## {SUMMARY_HEADING}\n\nArchived: {}\n\n", archives.join(", ")));
+    if !titles.is_empty() || earlier > 0 {
+        summary.push_str("Entries so far:\n");
+        if earlier > 0 {
+            summary.push_str(&format!("- ({earlier} earlier entries, see the archives)\n"));
+        }
+        for title in &titles[dropped..] {
+            summary.push_str(&format!("- {title}\n"));
+        }
+        summary.push('\n');
+    }
+    if let Some((title, body)) = sections.iter().rev().find(|(title, _)| *title != SUMMARY_HEADING) {
+        // Demoted so it stays inside the summary section
+        summary.push_str(&format!("### Last entry: {title}\n\n"));
+        let lines: Vec<&str> = body.trim().lines().collect();
+        for line in lines.iter().take(SUMMARY_LAST_ENTRY_LINES) {
+            summary.push_str(line);
+            summary.push('\n');
+        }
+        if lines.len() > SUMMARY_LAST_ENTRY_LINES {
+            summary.push_str(&format!("[ralph: truncated; full entry in {archive}]\n"));
+        }
+        summary.push('\n');
+    }
+    summary.push_str("---\n\n");
+    summary
+}
+
+/// The text before the first `## ` heading, and each `## ` section as (title, body).
+fn split_sections(content: &str) -> (&str, Vec<(&str, &str)>) {
+    let mut starts: Vec<usize> = content.match_indices("\n## ").map(|(i, _)| i + 1).collect();
+    if content.starts_with("## ") {
+        starts.insert(0, 0);
+    }
+    let preamble = &content[..starts.first().copied().unwrap_or(content.len())];
+    let sections = starts
+        .iter()
+        .enumerate()
+        .map(|(n, &start)| {
+            let end = starts.get(n + 1).copied().unwrap_or(content.len());
+            let section = &content[start + 3..end];
+            let (title, body) = section.split_once('\n').unwrap_or((section, ""));
+            (title.trim(), body)
+        })
+        .collect();
+    (preamble, sections)
+}
+
+/// The last entry in the progress file: from its last `## ` heading on, or the last
+/// paragraph if it has no headings.
+#[must_use]
+pub fn last_entry(content: &str) -> Option<&str> {
+    let content = content.trim_end();
+    let start = match content.rfind("\n## ") {
+        Some(i) => i + 1,
+        None if content.starts_with("## ") => 0,
+        None => content.rfind("\n\n").map_or(0, |i| i + 2),
+    };
+    Some(content[start..].trim()).filter(|entry| !entry.is_empty())
+}
+
+/// The last `n` entries in the progress file, from the `n`th-to-last `## ` heading on; all of
+/// it when it has fewer.
+#[must_use]
+pub fn last_entries(content: &str, n: usize) -> &str {
+    let mut starts: Vec<usize> = content.match_indices("\n## ").map(|(i, _)| i + 1).collect();
+    if content.starts_with("## ") {
+        starts.insert(0, 0);
+    }
+    let start = starts.len().checked_sub(n).and_then(|i| starts.get(i)).copied().unwrap_or(0);
+    content[start..].trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOMY: EntryLimits = EntryLimits {
+        max_lines: 100,
+        max_bytes: 10_000,
+    };
+
+    #[test]
+    fn short_body_is_unchanged() {
+        assert_eq!(
+            format_entry("Note", "one\ntwo", ROOMY, None),
+            "\n## Note\n\none\ntwo\n\n"
+        );
+    }
+
+    #[test]
+    fn truncates_by_line_count_with_artifact_pointer() {
+        let limits = EntryLimits { max_lines: 2, max_bytes: 10_000 };
+        let entry = format_entry("Note", "a\nb\nc\nd", limits, Some(Path::new(".ralph/logs/x.json")));
+        assert_eq!(
+            entry,
+            "\n## Note\n\na\nb\n[ralph: truncated, 2 more lines; full details in .ralph/logs/x.json]\n\n"
+        );
+    }
+
+    #[test]
+    fn truncates_by_bytes_at_line_boundary() {
+        let limits = EntryLimits { max_lines: 100, max_bytes: 8 };
+        let entry = format_entry("Note", "abc\ndef\nghi", limits, None);
+        assert_eq!(entry, "\n## Note\n\nabc\ndef\n[ralph: truncated, 1 more line]\n\n");
+    }
+
+    #[test]
+    fn cuts_oversized_first_line_on_char_boundary() {
+        let limits = EntryLimits { max_lines: 100, max_bytes: 2 };
+        let entry = format_entry("Note", "héllo", limits, None);
+        assert_eq!(entry, "\n## Note\n\nh\n[ralph: truncated, line shortened]\n\n");
+    }
+
+    #[test]
+    fn appends_to_existing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("progress.txt");
+        std::fs::write(&path, "# Log\n").unwrap();
+        append_entry(&path, "Note", "body", ROOMY, None).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "# Log\n\n## Note\n\nbody\n\n");
+    }
+
+    #[test]
+    fn last_entry_starts_at_the_last_heading() {
+        let content = "# Progress\n\n## Iteration 1\n\nDid a\n\n## Iteration 2\n\nDid b\n\nand c\n";
+        assert_eq!(last_entry(content), Some("## Iteration 2\n\nDid b\n\nand c"));
+        assert_eq!(last_entry("## Only\nbody\n"), Some("## Only\nbody"));
+    }
+
+    #[test]
+    fn last_entry_without_headings_is_the_last_paragraph() {
+        assert_eq!(last_entry("first\n\nsecond\nline\n\n"), Some("second\nline"));
+        assert_eq!(last_entry("\n \n"), None);
+    }
+
+    #[test]
+    fn last_entries_counts_back_from_the_end() {
+        let content = "# Progress\n\n## Iteration 1\n\nDid a\n\n## Iteration 2\n\nDid b\n";
+        assert_eq!(last_entries(content, 1), "## Iteration 2\n\nDid b");
+        assert_eq!(last_entries(content, 2), "## Iteration 1\n\nDid a\n\n## Iteration 2\n\nDid b");
+        assert_eq!(last_entries(content, 5), content.trim());
+    }
+
+    #[test]
+    fn rotate_leaves_small_files_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("progress.txt");
+        assert_eq!(rotate(&path, 10, "20260101").unwrap(), None);
+        std::fs::write(&path, "# Log\n\n## Iteration 1\n\nDid a\n").unwrap();
+        assert_eq!(rotate(&path, 1000, "20260101").unwrap(), None);
+        assert_eq!(rotate(&path, 0, "20260101").unwrap(), None);
+    }
+
+    #[test]
+    fn rotate_archives_and_summarizes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("progress.txt");
+        let old = "# Log\n\n---\n\n## Iteration 1\n\nDid a\n\n## Iteration 2\n\nDid b\nand c\n";
+        std::fs::write(&path, old).unwrap();
+
+        let archive = rotate(&path, 10, "20260101").unwrap().unwrap();
+        assert_eq!(archive, dir.path().join("progress-20260101.txt"));
+        assert_eq!(std::fs::read_to_string(&archive).unwrap(), old);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "# Log\n\n---\n\n## Summary so far\n\nArchived: progress-20260101.txt\n\nEntries so far:\n\
+             - Iteration 1\n- Iteration 2\n\n### Last entry: Iteration 2\n\nDid b\nand c\n\n---\n\n"
+        );
+    }
+
+    #[test]
+    fn rotate_folds_in_the_previous_summary() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("progress.txt");
+        let entries: String = (1..=SUMMARY_TITLES).map(|n| format!("## Iteration {n}\n\nDid {n}\n\n")).collect();
+        std::fs::write(&path, &entries).unwrap();
+        rotate(&path, 10, "20260101").unwrap();
+        append_entry(&path, "Iteration 41", "Did 41", ROOMY, None).unwrap();
+        append_entry(&path, "Iteration 42", "Did 42", ROOMY, None).unwrap();
+
+        let archive = rotate(&path, 10, "20260101").unwrap().unwrap();
+        assert_eq!(archive, dir.path().join("progress-20260101-2.txt"));
+        let summary = std::fs::read_to_string(&path).unwrap();
+        assert!(summary.starts_with("## Summary so far\n\nArchived: progress-20260101.txt, progress-20260101-2.txt\n"));
+        assert!(summary.contains("- (2 earlier entries, see the archives)\n- Iteration 3\n"));
+        assert!(summary.contains("- Iteration 42\n\n### Last entry: Iteration 42\n\nDid 42\n"));
+        assert!(!summary.contains("- Iteration 1\n"));
+    }
+
+    #[test]
+    fn records_are_appended_beside_the_progress_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let progress = dir.path().join("notes.md");
+        let artifact = crate::testing::ArtifactBuilder::new(2).run_id("r").feature("api").build();
+        append_record(&progress, &Record::new(&artifact, Some("abc123".into()))).unwrap();
+        append_record(&progress, &Record::new(&artifact, None)).unwrap();
+
+        let content = std::fs::read_to_string(dir.path().join("notes.jsonl")).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(
+            lines[0],
+            r#"{"timestamp":"2026-01-01T00:00:00+00:00","runId":"r","iteration":2,"featureId":"api","result":"continue","durationSecs":60,"logPath":"r-iteration-2.log","commit":"abc123"}"#
+        );
+        let second: Record = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(second.commit, None);
+        assert_eq!(load_records(&progress), vec![Record::new(&artifact, Some("abc123".into())), second]);
+    }
+}
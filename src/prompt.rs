@@ -1,12 +1,21 @@
-use crate::prd::Prd;
+use crate::{
+    atomic,
+    prd::{Feature, Prd, Status, VerifyCommand},
+};
 use anyhow::{Context, Result};
-use std::fs;
 use std::path::Path;
 
 pub const PLACEHOLDER_PRD_PATH: &str = "{prd_path}";
 pub const PLACEHOLDER_PROGRESS_PATH: &str = "{progress_path}";
 pub const PLACEHOLDER_VERIFICATION_COMMANDS: &str = "{verification_commands}";
 pub const PLACEHOLDER_COMPLETION_MARKER: &str = "{completion_marker}";
+pub const PLACEHOLDER_VERIFICATION_HISTORY: &str = "{verification_history}";
+pub const PLACEHOLDER_RECENT_CONTEXT: &str = "{recent_context}";
+pub const PLACEHOLDER_CURRENT_FEATURE: &str = "{current_feature}";
+pub const PLACEHOLDER_PENDING_COUNT: &str = "{pending_count}";
+pub const PLACEHOLDER_FEATURE_LIST: &str = "{feature_list}";
+pub const PLACEHOLDER_GIT_BRANCH: &str = "{git_branch}";
+pub const PLACEHOLDER_ITERATION_NUMBER: &str = "{iteration_number}";
 
 const PROMPT_TEMPLATE: &str = r#"You are an autonomous coding agent working through features defined in a PRD.
 
@@ -28,10 +37,22 @@ const PROMPT_TEMPLATE: &str = r#"You are an autonomous coding agent working thro
 Run these commands to verify your changes:
 {verification_commands}
 
+## Verification History
+
+Results of the verification commands from recent sessions, oldest first:
+{verification_history}
+
+If a check keeps failing, fix it before starting new work.
+
+## Recent Context
+
+Where the previous session left off:
+{recent_context}
+
 ## Workflow
 
 1. Read {prd_path} and {progress_path} for context
-2. Find the first feature with status "pending" or "in-progress"
+2. Pick the feature with status "in-progress"; if there is none, the highest-priority "pending" feature (lowest `priority` number, features without one last, ties in file order)
 3. If "pending", update status to "in-progress"
 4. Implement the feature following the defined steps
 5. Run verification commands
@@ -53,8 +74,53 @@ When ALL features have status "complete" or "blocked" and all verifications pass
 3. Output: {completion_marker}
 "#;
 
+const AUDIT_PROMPT_TEMPLATE: &str = r#"You are auditing the work of an autonomous coding agent. Do NOT modify any files.
+
+## Important Paths
+
+- **PRD file**: {prd_path}
+- **Progress file**: {progress_path}
+
+## Task
+
+The agent claims every feature in {prd_path} is finished. Verify that claim against the code:
+
+1. Read {prd_path} and {progress_path} for context
+2. For each feature with status "complete", inspect the code and confirm its description and steps are implemented
+3. Where useful, run the verification commands:
+{verification_commands}
+
+## Verdict
+
+For every feature that is NOT actually complete, output one line:
+REOPEN <feature-id>: <short reason>
+
+If every feature marked "complete" is implemented, output: {completion_marker}
+"#;
+
+const AGENT_NOTES_INSTRUCTIONS: &str = r#"## Agent Notes
+
+Besides "status", you may append short strings to a feature's "agentNotes" array in {prd_path}
+(create the array if missing) to leave breadcrumbs for later iterations. Never edit or remove
+existing notes; append-only changes are verified after each iteration.
+"#;
+
+const AUTO_COMPLETE_INSTRUCTIONS: &str = r#"## Feature Status
+
+Ralph updates feature statuses in {prd_path} for you: do not change them. When a feature is
+done, commit the work with the feature's id in the commit message (e.g. "auth-login: add the
+login form"). Ralph then runs the feature's verification commands and marks it complete once
+they pass.
+"#;
+
+const PROTECTED_PATHS_INSTRUCTIONS: &str = r#"## Protected Paths
+
+Do not create, modify, or delete files matching these patterns. An iteration that changes
+one fails, even if the change is committed:
+"#;
+
 pub fn generate_prompt_template(path: &Path) -> Result<()> {
-    fs::write(path, PROMPT_TEMPLATE)
+    atomic::write(path, PROMPT_TEMPLATE)
         .with_context(|| format!("Failed to write prompt template to {}", path.display()))
 }
 
@@ -63,6 +129,14 @@ pub fn load_custom_prompt(path: &Path) -> Result<String> {
         .with_context(|| format!("Failed to read custom prompt file: {}", path.display()))
 }
 
+/// The raw template used for the system prompt: the custom file if given, else the built-in one.
+pub fn template_source(prompt_path: Option<&Path>) -> Result<String> {
+    match prompt_path {
+        Some(path) => load_custom_prompt(path),
+        None => Ok(PROMPT_TEMPLATE.to_string()),
+    }
+}
+
 pub fn substitute_placeholders(
     template: &str,
     prd: &Prd,
@@ -72,12 +146,166 @@ pub fn substitute_placeholders(
     let verification_commands = format_verification_commands(prd);
 
     template
-        .replace(PLACEHOLDER_PRD_PATH, &prd_path.display().to_string())
-        .replace(PLACEHOLDER_PROGRESS_PATH, &progress_path.display().to_string())
+        .replace(PLACEHOLDER_PRD_PATH, &posix_path(prd_path))
+        .replace(PLACEHOLDER_PROGRESS_PATH, &posix_path(progress_path))
         .replace(PLACEHOLDER_VERIFICATION_COMMANDS, &verification_commands)
         .replace(PLACEHOLDER_COMPLETION_MARKER, &prd.completion.marker)
 }
 
+/// `path` as shown to the agent: `/`-separated, without the `\\?\` verbatim prefix and
+/// with an upper-case drive letter. Backslashes only count as separators in Windows-style
+/// paths, since they are legal in Unix file names.
+#[must_use]
+pub fn posix_path(path: &Path) -> String {
+    let text = path.display().to_string();
+    let text = match text.strip_prefix(r"\\?\") {
+        Some(rest) => rest.strip_prefix(r"UNC\").map_or_else(|| rest.to_string(), |unc| format!(r"\\{unc}")),
+        None => text,
+    };
+    if !(cfg!(windows) || is_windows_style(&text)) {
+        return text;
+    }
+    let mut posix = text.replace('\\', "/");
+    if has_drive_letter(&posix) {
+        posix[..1].make_ascii_uppercase();
+    }
+    posix
+}
+
+/// `path` relative to `base` in `/`-separated form, or just normalized when it lies
+/// outside `base`.
+#[must_use]
+pub fn relative_posix_path(path: &Path, base: &Path) -> String {
+    let path = posix_path(path);
+    let base = posix_path(base);
+    let base = base.trim_end_matches('/');
+    match path.strip_prefix(base).and_then(|rest| rest.strip_prefix('/')) {
+        Some(rest) if !base.is_empty() && !rest.is_empty() => rest.to_string(),
+        _ => path,
+    }
+}
+
+fn has_drive_letter(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+fn is_windows_style(path: &str) -> bool {
+    has_drive_letter(path) || path.starts_with(r"\\")
+}
+
+/// Fills `{verification_history}`, which depends on run state rather than the PRD.
+#[must_use]
+pub fn substitute_verification_history(prompt: &str, history: &str) -> String {
+    prompt.replace(PLACEHOLDER_VERIFICATION_HISTORY, history)
+}
+
+/// Fills `{recent_context}`, gathered (or summarized) before each iteration.
+#[must_use]
+pub fn substitute_recent_context(prompt: &str, context: &str) -> String {
+    prompt.replace(PLACEHOLDER_RECENT_CONTEXT, context)
+}
+
+/// Per-iteration values for the session placeholders.
+#[derive(Debug, Clone, Copy)]
+pub struct Session<'a> {
+    pub iteration: u32,
+    /// The feature this iteration should work on, if any is left
+    pub feature: Option<&'a Feature>,
+    /// Empty outside git or on a detached HEAD
+    pub git_branch: &'a str,
+}
+
+/// Fills `{current_feature}`, `{pending_count}`, `{feature_list}`, `{git_branch}`, and
+/// `{iteration_number}`.
+#[must_use]
+pub fn substitute_session(prompt: &str, prd: &Prd, session: &Session<'_>) -> String {
+    let current_feature = session
+        .feature
+        .map_or_else(|| "none".to_string(), |f| format!("`{}` - {}", f.id, f.description));
+    let feature_list = prd
+        .features
+        .iter()
+        .map(|f| format!("- `{}` [{}] {}", f.id, f.status.as_str(), f.description))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let git_branch = if session.git_branch.is_empty() { "none" } else { session.git_branch };
+
+    prompt
+        .replace(PLACEHOLDER_CURRENT_FEATURE, &current_feature)
+        .replace(PLACEHOLDER_PENDING_COUNT, &prd.status_counts().pending.to_string())
+        .replace(PLACEHOLDER_FEATURE_LIST, &feature_list)
+        .replace(PLACEHOLDER_GIT_BRANCH, git_branch)
+        .replace(PLACEHOLDER_ITERATION_NUMBER, &session.iteration.to_string())
+}
+
+/// Everything a prompt template can refer to.
+#[derive(Debug, Clone, Copy)]
+pub struct PromptVars<'a> {
+    pub prd: &'a Prd,
+    pub prd_path: &'a Path,
+    pub progress_path: &'a Path,
+    pub verification_history: &'a str,
+    pub recent_context: &'a str,
+    pub session: Session<'a>,
+}
+
+/// Renders `template` with Tera, then fills the `{placeholder}`s, which keep working
+/// beside (or instead of) Tera's `{{ }}` and `{% %}`.
+pub fn render(template: &str, vars: &PromptVars<'_>) -> Result<String> {
+    let prd = vars.prd;
+    let counts = prd.status_counts();
+    let mut context = tera::Context::new();
+    context.insert("prd_path", &posix_path(vars.prd_path));
+    context.insert("progress_path", &posix_path(vars.progress_path));
+    context.insert("verification_commands", &format_verification_commands(prd));
+    context.insert("completion_marker", &prd.completion.marker);
+    context.insert("verification_history", vars.verification_history);
+    context.insert("recent_context", vars.recent_context);
+    context.insert("iteration_number", &vars.session.iteration);
+    context.insert("git_branch", vars.session.git_branch);
+    context.insert("pending_count", &counts.pending);
+    context.insert("project", &prd.project);
+    context.insert("features", &prd.features);
+    context.insert("feature", &vars.session.feature);
+    context.insert(
+        "counts",
+        &serde_json::json!({
+            "pending": counts.pending,
+            "in_progress": counts.in_progress,
+            "complete": counts.complete,
+            "blocked": counts.blocked,
+        }),
+    );
+    let rendered = tera::Tera::one_off(template, &context, false)
+        .map_err(|e| anyhow::anyhow!(tera_error(&e)))
+        .context("Failed to render prompt template (wrap literal {{ or {% in {% raw %}...{% endraw %})")?;
+
+    let prompt = substitute_placeholders(&rendered, prd, vars.prd_path, vars.progress_path);
+    let prompt = substitute_verification_history(&prompt, vars.verification_history);
+    let prompt = substitute_recent_context(&prompt, vars.recent_context);
+    Ok(substitute_session(&prompt, prd, &vars.session))
+}
+
+/// Parses `template` without rendering it, so syntax errors surface before the first iteration.
+pub fn check_template(template: &str) -> Result<()> {
+    tera::Tera::default()
+        .add_raw_template("prompt", template)
+        .map_err(|e| anyhow::anyhow!(tera_error(&e)))
+        .context("Invalid prompt template (wrap literal {{ or {% in {% raw %}...{% endraw %})")
+}
+
+/// Tera's error with its causes, which hold the line and column.
+fn tera_error(error: &tera::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        message.push_str(&format!(": {cause}"));
+        source = cause.source();
+    }
+    message
+}
+
 pub fn get_system_prompt(
     prompt_path: Option<&Path>,
     prd: &Prd,
@@ -93,10 +321,24 @@ pub fn get_system_prompt(
     }
 }
 
+/// The global commands, followed by the merged list for each unfinished feature with its own.
 fn format_verification_commands(prd: &Prd) -> String {
-    prd.verification
-        .commands
-        .iter()
+    let mut sections = vec![format_command_list(prd.verification.commands.iter())];
+    for feature in &prd.features {
+        if feature.verification.is_empty() || matches!(feature.status, Status::Complete | Status::Blocked) {
+            continue;
+        }
+        sections.push(format!(
+            "When working on `{}`, run these instead:\n{}",
+            feature.id,
+            format_command_list(prd.verification_for(feature).into_iter())
+        ));
+    }
+    sections.join("\n\n")
+}
+
+fn format_command_list<'a>(commands: impl Iterator<Item = &'a VerifyCommand>) -> String {
+    commands
         .map(|cmd| format!("- `{}` - {}", cmd.command, cmd.description))
         .collect::<Vec<_>>()
         .join("\n")
@@ -107,10 +349,99 @@ pub fn build_system_prompt(prd: &Prd, prd_path: &Path, progress_path: &Path) ->
     substitute_placeholders(PROMPT_TEMPLATE, prd, prd_path, progress_path)
 }
 
+/// Extra instructions appended to the system prompt with `--allow-agent-notes`.
+#[must_use]
+pub fn agent_notes_section(prd_path: &Path) -> String {
+    AGENT_NOTES_INSTRUCTIONS.replace(PLACEHOLDER_PRD_PATH, &posix_path(prd_path))
+}
+
+/// Replaces the agent's status updates with commit messages, for `--auto-complete`.
+#[must_use]
+pub fn auto_complete_section(prd_path: &Path) -> String {
+    AUTO_COMPLETE_INSTRUCTIONS.replace(PLACEHOLDER_PRD_PATH, &posix_path(prd_path))
+}
+
+/// Lists the globs the agent may not touch, if there are any.
+#[must_use]
+pub fn protected_paths_section(patterns: &[&str]) -> Option<String> {
+    if patterns.is_empty() {
+        return None;
+    }
+    let mut section = String::from(PROTECTED_PATHS_INSTRUCTIONS);
+    for pattern in patterns {
+        section.push_str(&format!("- `{pattern}`\n"));
+    }
+    Some(section)
+}
+
+/// Where the session runs, if `feature` has a `workdir`.
+#[must_use]
+pub fn workdir_section(feature: &Feature) -> Option<String> {
+    let workdir = feature.workdir.as_deref()?;
+    Some(format!(
+        "## Working Directory\n\nThis session runs in `{}`, where `{}` lives. Keep your changes there unless the \
+         feature needs them elsewhere, and run commands from there.\n",
+        posix_path(workdir),
+        feature.id
+    ))
+}
+
+/// What `feature` must meet to be done, if its PRD entry says.
+#[must_use]
+pub fn acceptance_criteria_section(feature: &Feature) -> Option<String> {
+    if feature.acceptance_criteria.is_empty() && feature.acceptance_command.is_none() {
+        return None;
+    }
+    let mut section = format!("## Acceptance Criteria\n\n`{}` is only done when:\n", feature.id);
+    for criterion in &feature.acceptance_criteria {
+        section.push_str(&format!("- {criterion}\n"));
+    }
+    if let Some(ref command) = feature.acceptance_command {
+        section.push_str(&format!(
+            "\nOnce you mark it complete, ralph runs `{command}`; if that fails, the feature goes back to in-progress.\n"
+        ));
+    }
+    Some(section)
+}
+
+const ASSIGNED_FEATURE_INSTRUCTIONS: &str = r#"## Assigned Feature
+
+Other agents are working on other features in parallel. In this session, work ONLY on the
+feature with id "{feature_id}", regardless of its position in the PRD. Leave every other
+feature's status untouched.
+"#;
+
+/// Pins a `--parallel` worker to its feature.
+#[must_use]
+pub fn assigned_feature_section(feature_id: &str) -> String {
+    ASSIGNED_FEATURE_INSTRUCTIONS.replace("{feature_id}", feature_id)
+}
+
+/// Milestone instructions for the PRD's `phaseMarkers`, if it has any.
+#[must_use]
+pub fn phase_markers_section(prd: &Prd) -> Option<String> {
+    if prd.completion.phase_markers.is_empty() {
+        return None;
+    }
+    let mut section = String::from(
+        "## Milestones\n\nWhen the last feature of a phase becomes \"complete\" or \"blocked\", output that phase's marker:\n",
+    );
+    for (phase, marker) in &prd.completion.phase_markers {
+        section.push_str(&format!("- {phase}: {marker}\n"));
+    }
+    Some(section)
+}
+
+/// Read-only prompt for the `--final-audit` pass that double-checks a completion claim.
+#[must_use]
+pub fn build_audit_prompt(prd: &Prd, prd_path: &Path, progress_path: &Path) -> String {
+    substitute_placeholders(AUDIT_PROMPT_TEMPLATE, prd, prd_path, progress_path)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::prd::{Completion, Feature, Project, Status, Verification, VerifyCommand};
+    use crate::prd::{Completion, Feature, Project, Verification};
     use std::io::Write;
     use tempfile::NamedTempFile;
 
@@ -120,10 +451,17 @@ mod tests {
                 name: "test-project".into(),
                 description: "A test project".into(),
                 repository: None,
+                progress_path: None,
+                category_prompts: Default::default(),
+                root: None,
+                env: Default::default(),
+                env_file: None,
+                protected_paths: Vec::new(),
             },
             verification: Verification {
                 commands,
                 run_after_each_feature: true,
+                coverage: None,
             },
             features: vec![Feature {
                 id: "feat-1".into(),
@@ -132,12 +470,15 @@ mod tests {
                 steps: vec!["Step 1".into()],
                 status: Status::Pending,
                 notes: None,
+                ..Default::default()
             }],
             completion: Completion {
                 all_features_complete: true,
                 all_verifications_passing: true,
                 marker: marker.into(),
+                phase_markers: Default::default(),
             },
+            analysis: None,
         }
     }
 
@@ -182,7 +523,7 @@ mod tests {
             let result = build_system_prompt(&prd, prd_file.path(), Path::new("progress.txt"));
 
             assert!(result.contains("## Workflow"));
-            assert!(result.contains("Find the first feature"));
+            assert!(result.contains("highest-priority \"pending\" feature"));
             assert!(result.contains("Run verification commands"));
             assert!(result.contains("Commit your changes"));
             assert!(result.contains("**STOP**"));
@@ -260,6 +601,7 @@ mod tests {
                     name: "test".into(),
                     command: "cargo test".into(),
                     description: "Run tests".into(),
+                    ..Default::default()
                 }],
                 "DONE",
             );
@@ -278,6 +620,7 @@ mod tests {
                     name: "check".into(),
                     command: "cargo check".into(),
                     description: "Type checking".into(),
+                    ..Default::default()
                 }],
                 "DONE",
             );
@@ -289,6 +632,31 @@ mod tests {
             assert!(result.contains("- `cargo check` - Type checking"));
         }
 
+        #[test]
+        fn lists_merged_commands_for_features_with_overrides() {
+            let command = |name: &str, command: &str| VerifyCommand {
+                name: name.into(),
+                command: command.into(),
+                description: "d".into(),
+                ..Default::default()
+            };
+            let mut prd = make_test_prd(vec![command("check", "cargo check"), command("test", "cargo test")], "DONE");
+            prd.features[0].verification = vec![command("test", "cargo test -p api")];
+            prd.features.push(Feature {
+                id: "feat-2".into(),
+                status: Status::Complete,
+                verification: vec![command("docs", "mdbook build")],
+                ..Default::default()
+            });
+
+            let result = build_system_prompt(&prd, Path::new("prd.jsonc"), Path::new("progress.txt"));
+
+            assert!(result.contains(
+                "- `cargo check` - d\n- `cargo test` - d\n\nWhen working on `feat-1`, run these instead:\n- `cargo check` - d\n- `cargo test -p api` - d"
+            ));
+            assert!(!result.contains("feat-2"));
+        }
+
         #[test]
         fn formats_multiple_commands_correctly() {
             let prd = make_test_prd(
@@ -297,16 +665,19 @@ mod tests {
                         name: "check".into(),
                         command: "cargo check".into(),
                         description: "Type checking".into(),
+                        ..Default::default()
                     },
                     VerifyCommand {
                         name: "test".into(),
                         command: "cargo test".into(),
                         description: "Run tests".into(),
+                        ..Default::default()
                     },
                     VerifyCommand {
                         name: "lint".into(),
                         command: "cargo clippy".into(),
                         description: "Lint code".into(),
+                        ..Default::default()
                     },
                 ],
                 "DONE",
@@ -340,6 +711,7 @@ mod tests {
                     name: "clippy".into(),
                     command: "cargo clippy -- -D warnings".into(),
                     description: "Lint with warnings as errors".into(),
+                    ..Default::default()
                 }],
                 "DONE",
             );
@@ -358,6 +730,7 @@ mod tests {
                     name: "count".into(),
                     command: "wc -l src/*.rs | tail -1".into(),
                     description: "Count lines".into(),
+                    ..Default::default()
                 }],
                 "DONE",
             );
@@ -461,6 +834,7 @@ mod tests {
                     name: "test".into(),
                     command: "cargo test".into(),
                     description: "Run tests".into(),
+                    ..Default::default()
                 }],
                 "COMPLETE",
             );
@@ -548,11 +922,13 @@ mod tests {
                         name: "check".into(),
                         command: "cargo check".into(),
                         description: "Type check".into(),
+                        ..Default::default()
                     },
                     VerifyCommand {
                         name: "test".into(),
                         command: "cargo test".into(),
                         description: "Run tests".into(),
+                        ..Default::default()
                     },
                 ],
                 "DONE",
@@ -579,6 +955,7 @@ mod tests {
                     name: "test".into(),
                     command: "cargo test".into(),
                     description: "Run tests".into(),
+                    ..Default::default()
                 }],
                 "COMPLETE",
             );
@@ -622,6 +999,7 @@ mod tests {
                     name: "check".into(),
                     command: "cargo check".into(),
                     description: "Type check".into(),
+                    ..Default::default()
                 }],
                 "MARKER",
             );
@@ -687,6 +1065,294 @@ mod tests {
         }
     }
 
+    mod audit_prompt_tests {
+        use super::*;
+
+        #[test]
+        fn substitutes_placeholders() {
+            let prd = make_test_prd(vec![], "AUDIT_OK");
+            let result = build_audit_prompt(&prd, Path::new("prd.jsonc"), Path::new("progress.txt"));
+
+            assert!(result.contains("prd.jsonc"));
+            assert!(result.contains("progress.txt"));
+            assert!(result.contains("output: AUDIT_OK"));
+            assert!(!result.contains("{prd_path}"));
+        }
+
+        #[test]
+        fn instructs_read_only_and_reopen_format() {
+            let prd = make_test_prd(vec![], "DONE");
+            let result = build_audit_prompt(&prd, Path::new("prd.jsonc"), Path::new("progress.txt"));
+
+            assert!(result.contains("Do NOT modify any files"));
+            assert!(result.contains("REOPEN <feature-id>: <short reason>"));
+        }
+    }
+
+    mod posix_path_tests {
+        use super::*;
+
+        #[test]
+        fn windows_separators_become_slashes() {
+            assert_eq!(posix_path(Path::new(r"c:\work\app\prd.jsonc")), "C:/work/app/prd.jsonc");
+        }
+
+        #[test]
+        fn strips_verbatim_prefix() {
+            assert_eq!(posix_path(Path::new(r"\\?\C:\work\prd.jsonc")), "C:/work/prd.jsonc");
+            assert_eq!(posix_path(Path::new(r"\\?\UNC\server\share\prd.jsonc")), "//server/share/prd.jsonc");
+        }
+
+        #[test]
+        fn mixed_separators_are_unified() {
+            assert_eq!(posix_path(Path::new(r"C:\work/app\progress.txt")), "C:/work/app/progress.txt");
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn unix_backslash_in_name_is_kept() {
+            assert_eq!(posix_path(Path::new(r"/work/odd\name.txt")), r"/work/odd\name.txt");
+        }
+
+        #[test]
+        fn relative_to_project_dir() {
+            assert_eq!(
+                relative_posix_path(Path::new(r"C:\work\app\docs\prd.jsonc"), Path::new(r"c:\work\app\")),
+                "docs/prd.jsonc"
+            );
+            assert_eq!(relative_posix_path(Path::new("/work/app/progress.txt"), Path::new("/work/app")), "progress.txt");
+        }
+
+        #[test]
+        fn outside_project_dir_stays_absolute() {
+            assert_eq!(
+                relative_posix_path(Path::new(r"D:\notes\progress.txt"), Path::new(r"C:\work\app")),
+                "D:/notes/progress.txt"
+            );
+            assert_eq!(relative_posix_path(Path::new("/work/application/prd.jsonc"), Path::new("/work/app")), "/work/application/prd.jsonc");
+        }
+
+        #[test]
+        fn prompt_shows_windows_paths_with_slashes() {
+            let prd = make_test_prd(vec![], "DONE");
+            let result = substitute_placeholders(
+                "{prd_path} {progress_path}",
+                &prd,
+                Path::new(r"C:\work\prd.jsonc"),
+                Path::new(r"C:\work\progress.txt"),
+            );
+            assert_eq!(result, "C:/work/prd.jsonc C:/work/progress.txt");
+        }
+    }
+
+    mod agent_notes_section_tests {
+        use super::*;
+
+        #[test]
+        fn substitutes_prd_path() {
+            let result = agent_notes_section(Path::new("/work/prd.jsonc"));
+            assert!(result.contains("\"agentNotes\" array in /work/prd.jsonc"));
+            assert!(!result.contains("{prd_path}"));
+        }
+    }
+
+    mod verification_history_tests {
+        use super::*;
+
+        #[test]
+        fn builtin_template_has_placeholder() {
+            let prompt = build_system_prompt(&make_test_prd(vec![], "DONE"), Path::new("prd.jsonc"), Path::new("progress.txt"));
+            let filled = substitute_verification_history(&prompt, "- test: fail (failing since the last run)");
+            assert!(filled.contains("## Verification History"));
+            assert!(filled.contains("- test: fail (failing since the last run)"));
+            assert!(!filled.contains(PLACEHOLDER_VERIFICATION_HISTORY));
+        }
+    }
+
+    mod recent_context_tests {
+        use super::*;
+
+        #[test]
+        fn builtin_template_has_placeholder() {
+            let prompt = build_system_prompt(&make_test_prd(vec![], "DONE"), Path::new("prd.jsonc"), Path::new("progress.txt"));
+            let filled = substitute_recent_context(&prompt, "Last progress entry:\n## Iteration 1");
+            assert!(filled.contains("## Recent Context\n\nWhere the previous session left off:\nLast progress entry:"));
+            assert!(!filled.contains(PLACEHOLDER_RECENT_CONTEXT));
+        }
+    }
+
+    mod session_tests {
+        use super::*;
+
+        fn feature(id: &str, status: Status) -> Feature {
+            crate::testing::FeatureBuilder::new(id).description(&format!("{id} work")).status(status).build()
+        }
+
+        #[test]
+        fn fills_session_placeholders() {
+            let mut prd = make_test_prd(vec![], "DONE");
+            prd.features = vec![
+                feature("a", Status::Complete),
+                feature("b", Status::InProgress),
+                feature("c", Status::Pending),
+                feature("d", Status::Pending),
+            ];
+            let template = "{iteration_number} on {git_branch}: {current_feature}, {pending_count} pending\n{feature_list}";
+            let session = Session { iteration: 3, feature: prd.next_feature(), git_branch: "ralph/run" };
+            assert_eq!(
+                substitute_session(template, &prd, &session),
+                "3 on ralph/run: `b` - b work, 2 pending\n\
+                 - `a` [complete] a work\n- `b` [in-progress] b work\n- `c` [pending] c work\n- `d` [pending] d work"
+            );
+        }
+
+        #[test]
+        fn missing_feature_and_branch_are_none() {
+            let prd = make_test_prd(vec![], "DONE");
+            let session = Session { iteration: 1, feature: None, git_branch: "" };
+            assert_eq!(substitute_session("{current_feature} {git_branch}", &prd, &session), "none none");
+        }
+    }
+
+    mod render_tests {
+        use super::*;
+
+        fn render_with(template: &str, prd: &Prd) -> Result<String> {
+            let vars = PromptVars {
+                prd,
+                prd_path: Path::new("prd.jsonc"),
+                progress_path: Path::new("progress.txt"),
+                verification_history: "- test: pass",
+                recent_context: "did a",
+                session: Session { iteration: 2, feature: prd.next_feature(), git_branch: "main" },
+            };
+            render(template, &vars)
+        }
+
+        fn prd() -> Prd {
+            let mut prd = make_test_prd(vec![], "DONE");
+            prd.features = vec![
+                crate::testing::FeatureBuilder::new("a").category("docs").status(Status::Complete).build(),
+                crate::testing::FeatureBuilder::new("b").category("functional").build(),
+                crate::testing::FeatureBuilder::new("c").category("docs").build(),
+            ];
+            prd
+        }
+
+        #[test]
+        fn supports_loops_and_conditionals() {
+            let template = "{% for f in features | filter(attribute=\"status\", value=\"pending\") %}{{ f.id }} {% endfor %}\
+                            {% if feature %}next {{ feature.id }}{% endif %} of {{ counts.pending }} on {{ git_branch }}";
+            assert_eq!(render_with(template, &prd()).unwrap(), "b c next b of 2 on main");
+        }
+
+        #[test]
+        fn groups_by_category() {
+            let template = "{% for category, fs in features | group_by(attribute=\"category\") %}\
+                            [{{ category }}: {% for f in fs %}{{ f.id }}{% endfor %}]{% endfor %}";
+            let rendered = render_with(template, &prd()).unwrap();
+            assert!(rendered.contains("[docs: ac]"));
+            assert!(rendered.contains("[functional: b]"));
+        }
+
+        #[test]
+        fn legacy_placeholders_still_work() {
+            let template = "{prd_path} {{ progress_path }} {iteration_number} {recent_context} {{ verification_history }}";
+            assert_eq!(render_with(template, &prd()).unwrap(), "prd.jsonc progress.txt 2 did a - test: pass");
+            let builtin = render_with(PROMPT_TEMPLATE, &prd()).unwrap();
+            assert_eq!(
+                builtin,
+                substitute_recent_context(
+                    &substitute_verification_history(
+                        &build_system_prompt(&prd(), Path::new("prd.jsonc"), Path::new("progress.txt")),
+                        "- test: pass"
+                    ),
+                    "did a"
+                )
+            );
+        }
+
+        #[test]
+        fn syntax_errors_say_where() {
+            let err = render_with("uses: ${{ secrets.TOKEN }", &prd()).unwrap_err();
+            assert!(format!("{err:#}").contains("{% raw %}"));
+        }
+    }
+
+    mod assigned_feature_section_tests {
+        use super::*;
+
+        #[test]
+        fn names_the_feature() {
+            let section = assigned_feature_section("feat-2");
+            assert!(section.contains(r#"feature with id "feat-2""#));
+        }
+    }
+
+    mod protected_paths_section_tests {
+        use super::*;
+
+        #[test]
+        fn lists_each_pattern() {
+            assert!(protected_paths_section(&[]).is_none());
+            let section = protected_paths_section(&["Cargo.lock", ".github/**"]).unwrap();
+            assert!(section.starts_with("## Protected Paths"));
+            assert!(section.ends_with("- `Cargo.lock`\n- `.github/**`\n"));
+        }
+    }
+
+    mod workdir_section_tests {
+        use super::*;
+        use crate::testing::FeatureBuilder;
+
+        #[test]
+        fn names_the_workdir() {
+            let mut feature = FeatureBuilder::new("api-auth").build();
+            assert!(workdir_section(&feature).is_none());
+            feature.workdir = Some("packages/api".into());
+            let section = workdir_section(&feature).unwrap();
+            assert!(section.starts_with("## Working Directory\n\nThis session runs in `packages/api`, where `api-auth`"));
+        }
+    }
+
+    mod acceptance_criteria_section_tests {
+        use super::*;
+        use crate::testing::FeatureBuilder;
+
+        #[test]
+        fn absent_without_criteria_or_command() {
+            assert!(acceptance_criteria_section(&FeatureBuilder::new("f1").build()).is_none());
+        }
+
+        #[test]
+        fn lists_criteria_and_command() {
+            let mut feature = FeatureBuilder::new("login").build();
+            feature.acceptance_criteria = vec!["Wrong passwords are rejected".into(), "Sessions expire".into()];
+            feature.acceptance_command = Some("cargo test --test login".into());
+            let section = acceptance_criteria_section(&feature).unwrap();
+            assert!(section.contains("`login` is only done when:\n- Wrong passwords are rejected\n- Sessions expire\n"));
+            assert!(section.contains("ralph runs `cargo test --test login`"));
+        }
+    }
+
+    mod phase_markers_section_tests {
+        use super::*;
+
+        #[test]
+        fn absent_without_phase_markers() {
+            assert!(phase_markers_section(&make_test_prd(vec![], "DONE")).is_none());
+        }
+
+        #[test]
+        fn lists_each_phase_marker() {
+            let mut prd = make_test_prd(vec![], "DONE");
+            prd.completion.phase_markers.insert("MVP".into(), "<promise>MVP</promise>".into());
+            prd.completion.phase_markers.insert("Beta".into(), "<promise>BETA</promise>".into());
+            let section = phase_markers_section(&prd).unwrap();
+            assert!(section.contains("- Beta: <promise>BETA</promise>\n- MVP: <promise>MVP</promise>"));
+        }
+    }
+
     mod generate_prompt_template_tests {
         use super::*;
         use tempfile::TempDir;
@@ -0,0 +1,107 @@
+//! `{recent_context}`: where the previous session left off, for agents that start every
+//! session without memory of the last one (`--print` mode). It is the last progress entry
+//! and the end of the previous iteration's output; with `--summarize-context` a model
+//! condenses those first.
+
+use crate::progress;
+use std::path::Path;
+
+/// Substituted when there is nothing to go on yet.
+pub const NONE_YET: &str = "No earlier session in this run.";
+/// Lines kept from the last progress entry and from the end of the previous log.
+const ENTRY_LINES: usize = 30;
+const LOG_LINES: usize = 30;
+/// Longest log line kept; tool output can be one huge line.
+const LOG_LINE_CHARS: usize = 300;
+/// Cap on a model-written summary.
+pub const SUMMARY_BYTES: usize = 2048;
+
+const SUMMARY_PROMPT: &str = r#"Below are the latest progress-file entry and the end of the output of an autonomous coding agent's last session. Summarize in at most 10 short bullet points what the next session needs to know: what was being worked on, what was finished, what failed or is blocked, and what should happen next. Do not modify any files. Output only the bullet points.
+
+{recent_context}
+"#;
+
+/// The raw `{recent_context}`: the last progress entry and the tail of `last_log`.
+#[must_use]
+pub fn gather(progress_path: &Path, last_log: Option<&Path>) -> String {
+    let mut sections = Vec::new();
+    let progress = std::fs::read_to_string(progress_path).unwrap_or_default();
+    // A fresh progress file's last "entry" is the header's `---`
+    let entry = progress::last_entry(&progress).filter(|e| e.chars().any(char::is_alphanumeric));
+    if let Some(entry) = entry {
+        let lines: Vec<&str> = entry.lines().take(ENTRY_LINES).collect();
+        sections.push(format!("Last progress entry:\n{}", lines.join("\n")));
+    }
+    let log = last_log.and_then(|path| std::fs::read_to_string(path).ok()).unwrap_or_default();
+    let lines: Vec<&str> = log.lines().filter(|l| !l.trim().is_empty()).collect();
+    if !lines.is_empty() {
+        let tail: Vec<String> = lines[lines.len().saturating_sub(LOG_LINES)..]
+            .iter()
+            .map(|line| match line.char_indices().nth(LOG_LINE_CHARS) {
+                Some((end, _)) => format!("{}...", &line[..end]),
+                None => line.to_string(),
+            })
+            .collect();
+        sections.push(format!("End of the previous session's output:\n{}", tail.join("\n")));
+    }
+    if sections.is_empty() {
+        return NONE_YET.to_string();
+    }
+    sections.join("\n\n")
+}
+
+/// Prompt asking the summary model to condense `raw`.
+#[must_use]
+pub fn summary_prompt(raw: &str) -> String {
+    SUMMARY_PROMPT.replace(crate::prompt::PLACEHOLDER_RECENT_CONTEXT, raw)
+}
+
+/// The model's answer, trimmed and cut at a line boundary within [`SUMMARY_BYTES`].
+#[must_use]
+pub fn clip_summary(output: &str) -> String {
+    let mut summary = String::new();
+    for line in output.trim().lines() {
+        if summary.len() + line.len() + 1 > SUMMARY_BYTES {
+            break;
+        }
+        summary.push_str(line);
+        summary.push('\n');
+    }
+    summary.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gathers_last_entry_and_log_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let progress = dir.path().join("progress.txt");
+        std::fs::write(&progress, "# Log\n\n## Iteration 1\n\nDid a\n\n## Iteration 2\n\nDid b\n").unwrap();
+        let log = dir.path().join("run-iteration-2.log");
+        let lines: Vec<String> = (1..=40).map(|n| format!("line {n}")).collect();
+        std::fs::write(&log, format!("{}\n\n{}\n", lines.join("\n"), "x".repeat(400))).unwrap();
+
+        let context = gather(&progress, Some(&log));
+        assert!(context.starts_with("Last progress entry:\n## Iteration 2\n\nDid b\n\nEnd of the previous session's output:\n"));
+        assert!(!context.contains("line 10\n"));
+        assert!(context.contains("line 12\n"));
+        assert!(context.ends_with(&format!("{}...", "x".repeat(LOG_LINE_CHARS))));
+    }
+
+    #[test]
+    fn nothing_to_gather() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.log");
+        std::fs::write(dir.path().join("progress.txt"), "# Ralph Progress Log\n\nAppend-only log.\n\n---\n\n").unwrap();
+        assert_eq!(gather(&dir.path().join("progress.txt"), Some(&missing)), NONE_YET);
+    }
+
+    #[test]
+    fn summary_is_clipped_at_a_line() {
+        let output = format!("\n- a\n{}\n- c\n", "b".repeat(SUMMARY_BYTES));
+        assert_eq!(clip_summary(&output), "- a");
+        assert!(summary_prompt("- raw").ends_with("\n- raw\n"));
+    }
+}
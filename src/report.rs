@@ -0,0 +1,399 @@
+//! `ralph report`: the PRD, iteration artifacts, `progress.jsonl`, and verification history
+//! combined into one Markdown or HTML document for sharing a run's results.
+
+use crate::{
+    artifact::IterationArtifact,
+    config::{Args, ReportArgs, ReportFormat},
+    output,
+    prd::{Prd, Status, StatusCounts},
+    progress,
+    verification::{self, CommandResult},
+    validation,
+};
+use anyhow::{Context, Result};
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Results that count as a retry: the iteration ended without progress and had to be redone.
+const RETRY_RESULTS: &[&str] = &["failed", "stalled", "rate_limit", "loop_detected", "error"];
+
+#[derive(Debug)]
+pub struct Report {
+    pub project: String,
+    pub generated_at: String,
+    pub counts: StatusCounts,
+    /// PRD features in file order, then any that iterations name but the PRD no longer has,
+    /// then iterations without a feature
+    pub features: Vec<FeatureReport>,
+}
+
+#[derive(Debug)]
+pub struct FeatureReport {
+    /// `None` for iterations that weren't working on a feature
+    pub id: Option<String>,
+    pub description: String,
+    /// `None` if the feature isn't in the PRD
+    pub status: Option<Status>,
+    pub iterations: Vec<IterationRow>,
+}
+
+#[derive(Debug)]
+pub struct IterationRow {
+    pub run_id: Option<String>,
+    pub iteration: u32,
+    pub started_at: String,
+    pub result: String,
+    pub duration_secs: u64,
+    pub cost_usd: Option<f64>,
+    /// HEAD after the iteration, if it moved since the previous iteration
+    pub commit: Option<String>,
+    pub verification: Vec<CommandResult>,
+}
+
+impl FeatureReport {
+    fn retries(&self) -> usize {
+        self.iterations.iter().filter(|i| RETRY_RESULTS.contains(&i.result.as_str())).count()
+    }
+
+    fn duration_secs(&self) -> u64 {
+        self.iterations.iter().map(|i| i.duration_secs).sum()
+    }
+
+    fn cost(&self) -> Option<f64> {
+        sum_costs(self.iterations.iter().map(|i| i.cost_usd))
+    }
+
+    fn commits(&self) -> usize {
+        self.iterations.iter().filter(|i| i.commit.is_some()).count()
+    }
+
+    fn title(&self) -> String {
+        match (&self.id, self.status) {
+            (Some(id), Some(status)) => format!("{id}: {} ({})", self.description, status.as_str()),
+            (Some(id), None) => format!("{id} (not in the PRD)"),
+            (None, _) => "No feature".to_string(),
+        }
+    }
+}
+
+fn sum_costs(costs: impl Iterator<Item = Option<f64>>) -> Option<f64> {
+    costs.flatten().fold(None, |total, cost| Some(total.unwrap_or(0.0) + cost))
+}
+
+/// Gathers the report for the PRD at `args.prd`.
+pub fn collect(args: &Args) -> Result<Report> {
+    let prd = Prd::load(&args.prd)?;
+    let prd_dir = validation::prd_dir(&args.prd);
+    let ralph_dir = prd_dir.join(".ralph");
+    let artifacts = IterationArtifact::load_all(&ralph_dir.join("logs"));
+    let records = progress::load_records(&prd.progress_path(&args.prd, args.progress_path.as_deref()));
+    let verifications = verification::load(&ralph_dir)?;
+
+    let mut features: Vec<FeatureReport> = prd
+        .features
+        .iter()
+        .map(|f| FeatureReport {
+            id: Some(f.id.clone()),
+            description: f.description.clone(),
+            status: Some(f.status),
+            iterations: Vec::new(),
+        })
+        .collect();
+    let mut last_commit: Option<&str> = None;
+    for artifact in &artifacts {
+        let same_iteration = |run_id: &Option<String>, n: u32| *run_id == artifact.run_id && n == artifact.iteration;
+        let head = records
+            .iter()
+            .rev()
+            .find(|r| same_iteration(&r.run_id, r.iteration))
+            .and_then(|r| r.commit.as_deref());
+        let commit = head.filter(|&sha| last_commit != Some(sha)).map(str::to_string);
+        last_commit = head.or(last_commit);
+        let row = IterationRow {
+            run_id: artifact.run_id.clone(),
+            iteration: artifact.iteration,
+            started_at: artifact.started_at.clone(),
+            result: artifact.result.clone(),
+            duration_secs: artifact.duration_secs,
+            cost_usd: artifact.cost_usd,
+            commit,
+            verification: verifications
+                .iter()
+                .rev()
+                .find(|v| same_iteration(&v.run_id, v.iteration))
+                .map(|v| v.results.clone())
+                .unwrap_or_default(),
+        };
+        match features.iter_mut().find(|f| f.id == artifact.feature_id) {
+            Some(feature) => feature.iterations.push(row),
+            None => features.push(FeatureReport {
+                id: artifact.feature_id.clone(),
+                description: String::new(),
+                status: None,
+                iterations: vec![row],
+            }),
+        }
+    }
+    // Iterations without a feature go last
+    features.sort_by_key(|f| f.id.is_none());
+
+    Ok(Report {
+        project: prd.project.name.clone(),
+        generated_at: chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+        counts: prd.status_counts(),
+        features,
+    })
+}
+
+pub fn run(args: &Args, opts: &ReportArgs) -> Result<()> {
+    let report = render(&collect(args)?, opts.format);
+    match &opts.output {
+        Some(path) => {
+            std::fs::write(path, report).with_context(|| format!("Failed to write {}", path.display()))?;
+            output::success(&format!("Wrote report to {}", path.display()));
+        }
+        None => print!("{report}"),
+    }
+    Ok(())
+}
+
+#[must_use]
+pub fn render(report: &Report, format: ReportFormat) -> String {
+    match format {
+        ReportFormat::Markdown => render_markdown(report),
+        ReportFormat::Html => render_html(report),
+    }
+}
+
+fn summary(report: &Report) -> String {
+    let c = &report.counts;
+    let iterations: Vec<&IterationRow> = report.features.iter().flat_map(|f| &f.iterations).collect();
+    let mut summary = format!(
+        "Generated {}. {} features: {} complete, {} in-progress, {} pending, {} blocked. {} iterations over {}",
+        report.generated_at,
+        c.complete + c.in_progress + c.pending + c.blocked,
+        c.complete,
+        c.in_progress,
+        c.pending,
+        c.blocked,
+        iterations.len(),
+        output::format_duration(Duration::from_secs(iterations.iter().map(|i| i.duration_secs).sum()))
+    );
+    if let Some(cost) = sum_costs(iterations.iter().map(|i| i.cost_usd)) {
+        let _ = write!(summary, ", ${cost:.2}");
+    }
+    summary.push('.');
+    summary
+}
+
+fn cost_cell(cost: Option<f64>) -> String {
+    cost.map_or_else(|| "-".to_string(), |c| format!("${c:.2}"))
+}
+
+const FEATURE_HEADER: &[&str] = &["Feature", "Status", "Iterations", "Retries", "Duration", "Cost", "Commits"];
+
+fn feature_rows(report: &Report) -> Vec<Vec<String>> {
+    report
+        .features
+        .iter()
+        .map(|f| {
+            vec![
+                f.id.clone().unwrap_or_else(|| "-".to_string()),
+                f.status.map_or("-", Status::as_str).to_string(),
+                f.iterations.len().to_string(),
+                f.retries().to_string(),
+                output::format_duration(Duration::from_secs(f.duration_secs())),
+                cost_cell(f.cost()),
+                f.commits().to_string(),
+            ]
+        })
+        .collect()
+}
+
+const ITERATION_HEADER: &[&str] =
+    &["Run", "Iteration", "Started", "Result", "Duration", "Cost", "Commit", "Verification"];
+
+fn iteration_rows(feature: &FeatureReport) -> Vec<Vec<String>> {
+    feature
+        .iterations
+        .iter()
+        .map(|i| {
+            vec![
+                i.run_id.clone().unwrap_or_else(|| "-".to_string()),
+                i.iteration.to_string(),
+                i.started_at.clone(),
+                i.result.clone(),
+                output::format_duration(Duration::from_secs(i.duration_secs)),
+                cost_cell(i.cost_usd),
+                i.commit.as_deref().map_or("-", |sha| &sha[..sha.len().min(7)]).to_string(),
+                verification_cell(&i.verification),
+            ]
+        })
+        .collect()
+}
+
+fn verification_cell(results: &[CommandResult]) -> String {
+    if results.is_empty() {
+        return "-".to_string();
+    }
+    results
+        .iter()
+        .map(CommandResult::summary)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub(crate) fn markdown_table(out: &mut String, header: &[&str], rows: &[Vec<String>]) {
+    let _ = writeln!(out, "| {} |", header.join(" | "));
+    let _ = writeln!(out, "|{}", "---|".repeat(header.len()));
+    for row in rows {
+        let cells: Vec<String> = row.iter().map(|c| c.replace('|', "\\|")).collect();
+        let _ = writeln!(out, "| {} |", cells.join(" | "));
+    }
+}
+
+fn render_markdown(report: &Report) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# {}: run report\n", report.project);
+    let _ = writeln!(out, "{}\n", summary(report));
+    let _ = writeln!(out, "## Features\n");
+    markdown_table(&mut out, FEATURE_HEADER, &feature_rows(report));
+    let _ = writeln!(out, "\n## Timeline");
+    for feature in report.features.iter().filter(|f| !f.iterations.is_empty()) {
+        let _ = writeln!(out, "\n### {}\n", feature.title());
+        markdown_table(&mut out, ITERATION_HEADER, &iteration_rows(feature));
+    }
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn html_table(out: &mut String, header: &[&str], rows: &[Vec<String>]) {
+    out.push_str("<table>\n<tr>");
+    for cell in header {
+        let _ = write!(out, "<th>{}</th>", escape(cell));
+    }
+    out.push_str("</tr>\n");
+    for row in rows {
+        out.push_str("<tr>");
+        for cell in row {
+            let _ = write!(out, "<td>{}</td>", escape(cell));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</table>\n");
+}
+
+fn render_html(report: &Report) -> String {
+    let title = escape(&format!("{}: run report", report.project));
+    let mut out = String::new();
+    let _ = writeln!(out, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>");
+    out.push_str(
+        "<style>body{font-family:sans-serif;margin:2em}table{border-collapse:collapse;margin-bottom:1em}\
+         th,td{border:1px solid #ccc;padding:4px 8px;text-align:left}th{background:#f0f0f0}</style>\n",
+    );
+    let _ = writeln!(out, "</head>\n<body>\n<h1>{title}</h1>\n<p>{}</p>", escape(&summary(report)));
+    out.push_str("<h2>Features</h2>\n");
+    html_table(&mut out, FEATURE_HEADER, &feature_rows(report));
+    out.push_str("<h2>Timeline</h2>\n");
+    for feature in report.features.iter().filter(|f| !f.iterations.is_empty()) {
+        let _ = writeln!(out, "<h3>{}</h3>", escape(&feature.title()));
+        html_table(&mut out, ITERATION_HEADER, &iteration_rows(feature));
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ArtifactBuilder, FeatureBuilder, PrdBuilder};
+    use clap::Parser;
+
+    /// A project with two iterations on `a` (the first failed verification), one on a feature
+    /// since removed from the PRD, and `b` untouched.
+    fn project() -> (tempfile::TempDir, Args) {
+        let dir = tempfile::tempdir().unwrap();
+        let prd_path = dir.path().join("prd.jsonc");
+        PrdBuilder::new()
+            .feature(FeatureBuilder::new("a").status(Status::Complete))
+            .feature(FeatureBuilder::new("b"))
+            .write(&prd_path)
+            .unwrap();
+        let logs_dir = dir.path().join(".ralph/logs");
+        let progress_path = dir.path().join("progress.txt");
+        let iterations = [
+            (1, "a", "failed", Some(0.25), "1111111aaaa"),
+            (2, "a", "complete", Some(0.5), "2222222bbbb"),
+            (3, "gone", "continue", None, "2222222bbbb"),
+        ];
+        for (n, feature, result, cost, commit) in iterations {
+            let mut builder = ArtifactBuilder::new(n)
+                .run_id("r1")
+                .started_at(&format!("2026-01-01T10:0{n}:00+00:00"))
+                .feature(feature)
+                .result(result)
+                .duration_secs(90);
+            if let Some(cost) = cost {
+                builder = builder.cost_usd(cost);
+            }
+            let artifact = builder.in_dir(&logs_dir).write().unwrap();
+            progress::append_record(&progress_path, &progress::Record::new(&artifact, Some(commit.into()))).unwrap();
+        }
+        for (n, passed) in [(1, false), (2, true)] {
+            let run = verification::VerificationRun {
+                run_id: Some("r1".into()),
+                iteration: n,
+                finished_at: String::new(),
+                results: vec![CommandResult { name: "test".into(), passed, attempts: 1 }],
+            };
+            verification::append(&dir.path().join(".ralph"), &run).unwrap();
+        }
+        let args = Args::try_parse_from(["ralph", "--prd", prd_path.to_str().unwrap(), "report"]).unwrap();
+        (dir, args)
+    }
+
+    #[test]
+    fn groups_iterations_by_feature() {
+        let (_dir, args) = project();
+        let report = collect(&args).unwrap();
+        let ids: Vec<Option<&str>> = report.features.iter().map(|f| f.id.as_deref()).collect();
+        assert_eq!(ids, [Some("a"), Some("b"), Some("gone")]);
+
+        let a = &report.features[0];
+        assert_eq!((a.iterations.len(), a.retries(), a.commits(), a.duration_secs()), (2, 1, 2, 180));
+        assert_eq!(a.cost(), Some(0.75));
+        assert!(!a.iterations[0].verification[0].passed);
+        assert!(report.features[1].iterations.is_empty());
+        // The commit didn't move in iteration 3
+        assert_eq!((report.features[2].commits(), report.features[2].cost()), (0, None));
+    }
+
+    #[test]
+    fn renders_markdown() {
+        let (_dir, args) = project();
+        let markdown = render(&collect(&args).unwrap(), ReportFormat::Markdown);
+        assert!(markdown.starts_with("# test: run report\n"), "{markdown}");
+        assert!(markdown.contains("3 iterations over 4m 30s, $0.75."), "{markdown}");
+        assert!(markdown.contains("| a | complete | 2 | 1 | 3m 0s | $0.75 | 2 |"), "{markdown}");
+        assert!(markdown.contains("| b | pending | 0 | 0 | 0m 0s | - | 0 |"), "{markdown}");
+        assert!(markdown.contains("### a: Implement a (complete)"), "{markdown}");
+        let first = "| r1 | 1 | 2026-01-01T10:01:00+00:00 | failed | 1m 30s | $0.25 | 1111111 | test FAIL |";
+        assert!(markdown.contains(first), "{markdown}");
+        assert!(markdown.contains("### gone (not in the PRD)"), "{markdown}");
+        assert!(!markdown.contains("### b"), "{markdown}");
+    }
+
+    #[test]
+    fn renders_escaped_html() {
+        let (dir, args) = project();
+        let prd_path = dir.path().join("prd.jsonc");
+        PrdBuilder::new().name("<R&D>").feature(FeatureBuilder::new("a")).write(&prd_path).unwrap();
+        let html = render(&collect(&args).unwrap(), ReportFormat::Html);
+        assert!(html.contains("<title>&lt;R&amp;D&gt;: run report</title>"), "{html}");
+        assert!(html.contains("<td>a</td><td>pending</td><td>2</td>"), "{html}");
+        assert!(html.ends_with("</html>\n"));
+    }
+}
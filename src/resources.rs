@@ -0,0 +1,259 @@
+//! CPU and memory sampling of the agent process tree, to tell an iteration that hangs
+//! burning CPU from one that sits idle waiting on the network.
+
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use sysinfo::{Pid, Process, ProcessRefreshKind, ProcessesToUpdate, Signal, System};
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Average CPU below which an agent is reported as idle.
+const IDLE_CPU_PERCENT: f32 = 2.0;
+
+/// Peak and average usage of the agent and its child processes. CPU is in percent of one
+/// core, so a busy multi-threaded agent can exceed 100.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceUsage {
+    pub peak_cpu_percent: f32,
+    pub avg_cpu_percent: f32,
+    pub peak_memory_bytes: u64,
+    pub samples: u32,
+}
+
+impl ResourceUsage {
+    fn add_sample(&mut self, cpu_percent: f32, memory_bytes: u64) {
+        let total = self.avg_cpu_percent * self.samples as f32 + cpu_percent;
+        self.samples += 1;
+        self.avg_cpu_percent = total / self.samples as f32;
+        self.peak_cpu_percent = self.peak_cpu_percent.max(cpu_percent);
+        self.peak_memory_bytes = self.peak_memory_bytes.max(memory_bytes);
+    }
+
+    /// Combines two runs in the same iteration, e.g. the agent and a `--final-audit` pass.
+    pub fn merge(&mut self, other: &ResourceUsage) {
+        let samples = self.samples + other.samples;
+        if samples > 0 {
+            self.avg_cpu_percent = (self.avg_cpu_percent * self.samples as f32
+                + other.avg_cpu_percent * other.samples as f32)
+                / samples as f32;
+        }
+        self.samples = samples;
+        self.peak_cpu_percent = self.peak_cpu_percent.max(other.peak_cpu_percent);
+        self.peak_memory_bytes = self.peak_memory_bytes.max(other.peak_memory_bytes);
+    }
+
+    #[must_use]
+    pub fn is_idle(&self) -> bool {
+        self.samples > 0 && self.avg_cpu_percent < IDLE_CPU_PERCENT
+    }
+
+    /// e.g. `peak 135% CPU, 412 MB; avg 12% CPU`
+    #[must_use]
+    pub fn summary(&self) -> String {
+        format!(
+            "peak {:.0}% CPU, {} MB; avg {:.0}% CPU",
+            self.peak_cpu_percent,
+            self.peak_memory_bytes / (1024 * 1024),
+            self.avg_cpu_percent
+        )
+    }
+}
+
+/// Samples a process tree in the background until [`Sampler::finish`].
+pub struct Sampler {
+    usage: Arc<Mutex<ResourceUsage>>,
+    handle: JoinHandle<()>,
+}
+
+impl Sampler {
+    #[must_use]
+    pub fn start(pid: u32) -> Self {
+        let usage = Arc::new(Mutex::new(ResourceUsage::default()));
+        let shared = Arc::clone(&usage);
+        let handle = tokio::spawn(async move {
+            let root = Pid::from_u32(pid);
+            let mut system = System::new();
+            // CPU usage is measured between two refreshes, so the first one only primes it
+            refresh(&mut system);
+            loop {
+                tokio::time::sleep(SAMPLE_INTERVAL).await;
+                refresh(&mut system);
+                let Some((cpu, memory)) = tree_usage(&system, root) else {
+                    break;
+                };
+                shared.lock().unwrap_or_else(|e| e.into_inner()).add_sample(cpu, memory);
+            }
+        });
+        Self { usage, handle }
+    }
+
+    /// Stops sampling; None if the process exited before the first sample.
+    #[must_use]
+    pub fn finish(self) -> Option<ResourceUsage> {
+        self.handle.abort();
+        let usage = *self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        (usage.samples > 0).then_some(usage)
+    }
+}
+
+fn refresh(system: &mut System) {
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::nothing().with_cpu().with_memory(),
+    );
+}
+
+/// `root` and its descendants, root first; empty once `root` is gone.
+fn process_tree(system: &System, root: Pid) -> Vec<&Process> {
+    let in_tree = |mut pid: Pid| loop {
+        if pid == root {
+            return true;
+        }
+        match system.process(pid).and_then(Process::parent) {
+            Some(parent) if parent != pid => pid = parent,
+            _ => return false,
+        }
+    };
+    let Some(root_process) = system.process(root) else {
+        return Vec::new();
+    };
+    let mut tree: Vec<&Process> = system
+        .processes()
+        .iter()
+        .filter(|(pid, _)| **pid != root && in_tree(**pid))
+        .map(|(_, p)| p)
+        .collect();
+    tree.sort_by_key(|p| p.pid());
+    tree.insert(0, root_process);
+    tree
+}
+
+/// Summed CPU and memory of `root` and its descendants, or None once `root` is gone.
+fn tree_usage(system: &System, root: Pid) -> Option<(f32, u64)> {
+    let tree = process_tree(system, root);
+    (!tree.is_empty()).then(|| tree.iter().fold((0.0, 0), |(cpu, memory), p| (cpu + p.cpu_usage(), memory + p.memory())))
+}
+
+/// One line per process in the tree rooted at `pid`, e.g.
+/// `4242 claude (Sleeping) 212 MB, up 305s`, for diagnosing a stalled agent.
+#[must_use]
+pub fn process_snapshot(pid: u32) -> Vec<String> {
+    let mut system = System::new();
+    system.refresh_processes_specifics(ProcessesToUpdate::All, true, ProcessRefreshKind::nothing().with_memory());
+    process_tree(&system, Pid::from_u32(pid))
+        .iter()
+        .map(|p| {
+            format!(
+                "{} {} ({}) {} MB, up {}s",
+                p.pid(),
+                p.name().to_string_lossy(),
+                p.status(),
+                p.memory() / (1024 * 1024),
+                p.run_time()
+            )
+        })
+        .collect()
+}
+
+/// Asks the process to exit (SIGTERM where supported); false if it couldn't be signalled.
+pub fn terminate(pid: u32) -> bool {
+    let mut system = System::new();
+    let pid = Pid::from_u32(pid);
+    system.refresh_processes_specifics(ProcessesToUpdate::Some(&[pid]), true, ProcessRefreshKind::nothing());
+    system.process(pid).and_then(|p| p.kill_with(Signal::Term)).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_peaks_and_average() {
+        let mut usage = ResourceUsage::default();
+        usage.add_sample(10.0, 100);
+        usage.add_sample(50.0, 300);
+        usage.add_sample(0.0, 200);
+        assert_eq!(usage.samples, 3);
+        assert_eq!(usage.peak_cpu_percent, 50.0);
+        assert_eq!(usage.avg_cpu_percent, 20.0);
+        assert_eq!(usage.peak_memory_bytes, 300);
+    }
+
+    #[test]
+    fn merge_weights_averages_by_samples() {
+        let mut agent = ResourceUsage {
+            peak_cpu_percent: 90.0,
+            avg_cpu_percent: 30.0,
+            peak_memory_bytes: 500,
+            samples: 3,
+        };
+        agent.merge(&ResourceUsage {
+            peak_cpu_percent: 20.0,
+            avg_cpu_percent: 10.0,
+            peak_memory_bytes: 800,
+            samples: 1,
+        });
+        assert_eq!(agent.avg_cpu_percent, 25.0);
+        assert_eq!(agent.peak_cpu_percent, 90.0);
+        assert_eq!(agent.peak_memory_bytes, 800);
+        assert_eq!(agent.samples, 4);
+    }
+
+    #[test]
+    fn idle_needs_samples_and_low_cpu() {
+        assert!(!ResourceUsage::default().is_idle());
+        let mut usage = ResourceUsage::default();
+        usage.add_sample(0.5, 1024);
+        assert!(usage.is_idle());
+        usage.add_sample(80.0, 1024);
+        assert!(!usage.is_idle());
+    }
+
+    #[test]
+    fn summary_in_megabytes() {
+        let usage = ResourceUsage {
+            peak_cpu_percent: 135.4,
+            avg_cpu_percent: 12.2,
+            peak_memory_bytes: 412 * 1024 * 1024,
+            samples: 5,
+        };
+        assert_eq!(usage.summary(), "peak 135% CPU, 412 MB; avg 12% CPU");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn samples_a_running_child() {
+        let mut child = tokio::process::Command::new("sh")
+            .args(["-c", "sleep 3"])
+            .spawn()
+            .unwrap();
+        let sampler = Sampler::start(child.id().unwrap());
+        tokio::time::sleep(SAMPLE_INTERVAL + Duration::from_millis(500)).await;
+        let usage = sampler.finish().unwrap();
+        child.kill().await.unwrap();
+        assert!(usage.samples >= 1);
+        assert!(usage.peak_memory_bytes > 0);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn snapshots_the_tree_and_terminates() {
+        let mut child = tokio::process::Command::new("sh")
+            .args(["-c", "sleep 30 & wait"])
+            .spawn()
+            .unwrap();
+        let pid = child.id().unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let snapshot = process_snapshot(pid);
+        assert!(snapshot[0].starts_with(&format!("{pid} sh ")), "{snapshot:?}");
+        assert!(snapshot.iter().any(|line| line.contains("sleep")), "{snapshot:?}");
+
+        assert!(terminate(pid));
+        let status = tokio::time::timeout(Duration::from_secs(5), child.wait()).await.unwrap().unwrap();
+        assert!(!status.success());
+    }
+}
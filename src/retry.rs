@@ -1,4 +1,4 @@
-use crate::{output, prd};
+use crate::{atomic, output, prd, prd_edit};
 use anyhow::{Context, Result};
 use std::collections::HashMap;
 use std::path::Path;
@@ -17,6 +17,17 @@ impl IterationErrorTracker {
         }
     }
 
+    /// Restores counts saved by a previous run (see `--resume`).
+    #[must_use]
+    pub fn with_counts(max_errors: u32, counts: HashMap<String, u32>) -> Self {
+        Self { counts, max_errors }
+    }
+
+    #[must_use]
+    pub fn counts(&self) -> &HashMap<String, u32> {
+        &self.counts
+    }
+
     pub fn record_error(&mut self, feature_id: &str) -> u32 {
         let count = self.counts.entry(feature_id.to_string()).or_insert(0);
         *count += 1;
@@ -48,6 +59,32 @@ impl IterationErrorTracker {
     }
 }
 
+/// Iterations spent on each feature, for its `maxIterations`.
+#[derive(Debug, Default)]
+pub struct IterationBudget {
+    spent: HashMap<String, u32>,
+}
+
+impl IterationBudget {
+    /// Restores counts saved by a previous run (see `--resume`).
+    #[must_use]
+    pub fn with_counts(spent: HashMap<String, u32>) -> Self {
+        Self { spent }
+    }
+
+    #[must_use]
+    pub fn counts(&self) -> &HashMap<String, u32> {
+        &self.spent
+    }
+
+    /// Counts an iteration on `feature`; true once it has used up its `maxIterations`.
+    pub fn record(&mut self, feature: &prd::Feature) -> bool {
+        let spent = self.spent.entry(feature.id.clone()).or_insert(0);
+        *spent += 1;
+        feature.max_iterations.is_some_and(|max| *spent >= max)
+    }
+}
+
 pub fn get_current_feature_id(prd: &prd::Prd) -> Option<String> {
     prd.features
         .iter()
@@ -56,19 +93,16 @@ pub fn get_current_feature_id(prd: &prd::Prd) -> Option<String> {
 }
 
 pub fn update_feature_status_to_blocked(prd_path: &Path, feature_id: &str) -> Result<()> {
-    let content = std::fs::read_to_string(prd_path)
-        .with_context(|| format!("Failed to read PRD file: {}", prd_path.display()))?;
-
-    let pattern = format!(r#""id": "{}""#, feature_id);
-    if !content.contains(&pattern) {
-        anyhow::bail!("Feature {} not found in PRD", feature_id);
+    let blocked = update_feature_status(
+        prd_path,
+        feature_id,
+        &[prd::Status::InProgress, prd::Status::Pending],
+        prd::Status::Blocked,
+    )?;
+    if !blocked {
+        return Ok(());
     }
 
-    let updated = update_status_in_content(&content, feature_id);
-
-    std::fs::write(prd_path, updated)
-        .with_context(|| format!("Failed to write PRD file: {}", prd_path.display()))?;
-
     output::warn(&format!(
         "Feature '{}' auto-blocked after max retries",
         feature_id
@@ -77,43 +111,58 @@ pub fn update_feature_status_to_blocked(prd_path: &Path, feature_id: &str) -> Re
     Ok(())
 }
 
-fn update_status_in_content(content: &str, feature_id: &str) -> String {
-    let mut result = String::new();
-    let mut in_target_feature = false;
-    let mut status_updated = false;
-    let id_pattern = format!(r#""id": "{}""#, feature_id);
-
-    for line in content.lines() {
-        if line.contains(&id_pattern) {
-            in_target_feature = true;
-        }
+/// Rewrites the status of `feature_id` to `to` if it currently has one of the `from` statuses.
+/// Only the status value changes, so comments and formatting survive, and the file is
+/// replaced atomically. False when the feature had another status and nothing changed.
+pub fn update_feature_status(
+    prd_path: &Path,
+    feature_id: &str,
+    from: &[prd::Status],
+    to: prd::Status,
+) -> Result<bool> {
+    let content = std::fs::read_to_string(prd_path)
+        .with_context(|| format!("Failed to read PRD file: {}", prd_path.display()))?;
 
-        if in_target_feature && !status_updated && line.contains(r#""status":"#) {
-            let updated_line = line
-                .replace(r#""status": "in-progress""#, r#""status": "blocked""#)
-                .replace(r#""status": "pending""#, r#""status": "blocked""#)
-                .replace(r#""status":"in-progress""#, r#""status": "blocked""#)
-                .replace(r#""status":"pending""#, r#""status": "blocked""#);
-            result.push_str(&updated_line);
-            status_updated = true;
-            in_target_feature = false;
-        } else {
-            result.push_str(line);
-        }
-        result.push('\n');
-    }
+    let Some(edited) = prd_edit::set_status(&content, feature_id, from, to)
+        .with_context(|| format!("Failed to update PRD file: {}", prd_path.display()))?
+    else {
+        return Ok(false);
+    };
 
-    if result.ends_with('\n') && !content.ends_with('\n') {
-        result.pop();
-    }
+    atomic::write(prd_path, edited)
+        .with_context(|| format!("Failed to write PRD file: {}", prd_path.display()))?;
 
-    result
+    Ok(true)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    mod iteration_budget_tests {
+        use super::*;
+        use crate::testing::FeatureBuilder;
+
+        #[test]
+        fn exhausted_at_max_iterations() {
+            let limited = FeatureBuilder::new("a").max_iterations(2).build();
+            let unlimited = FeatureBuilder::new("b").build();
+            let mut budget = IterationBudget::default();
+            assert!(!budget.record(&limited));
+            assert!(!budget.record(&unlimited));
+            assert!(budget.record(&limited));
+            assert_eq!(budget.counts().get("a"), Some(&2));
+            assert!(!budget.record(&unlimited));
+        }
+
+        #[test]
+        fn with_counts_restores_spent_iterations() {
+            let limited = FeatureBuilder::new("a").max_iterations(3).build();
+            let mut budget = IterationBudget::with_counts(HashMap::from([("a".to_string(), 2)]));
+            assert!(budget.record(&limited));
+        }
+    }
+
     mod iteration_error_tracker_tests {
         use super::*;
 
@@ -186,78 +235,119 @@ mod tests {
             let tracker = IterationErrorTracker::new(0);
             assert!(!tracker.is_enabled());
         }
+
+        #[test]
+        fn with_counts_restores_saved_counts() {
+            let mut tracker = IterationErrorTracker::with_counts(3, HashMap::from([("feat-1".to_string(), 2)]));
+            assert_eq!(tracker.get_count("feat-1"), 2);
+            tracker.record_error("feat-1");
+            assert!(tracker.should_block("feat-1"));
+            assert_eq!(tracker.counts().get("feat-1"), Some(&3));
+        }
     }
 
     mod update_status_tests {
         use super::*;
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        fn feature(id: &str, status: &str) -> String {
+            format!(r#"{{ "id": "{id}", "category": "c", "description": "d", "steps": [], "status": "{status}" }}"#)
+        }
+
+        fn prd_file(features: &[String]) -> NamedTempFile {
+            let mut file = NamedTempFile::new().unwrap();
+            write!(
+                file,
+                r#"{{
+  // Keep me
+  "project": {{ "name": "test", "description": "d" }},
+  "verification": {{ "commands": [], "runAfterEachFeature": true }},
+  "features": [
+    {}
+  ],
+  "completion": {{ "allFeaturesComplete": true, "allVerificationsPassing": true, "marker": "X" }}
+}}"#,
+                features.join(",\n    ")
+            )
+            .unwrap();
+            file
+        }
+
+        fn statuses(file: &NamedTempFile) -> Vec<prd::Status> {
+            prd::Prd::load(file.path()).unwrap().features.iter().map(|f| f.status).collect()
+        }
 
         #[test]
         fn updates_in_progress_to_blocked() {
-            let content = r#"{
-  "features": [
-    {
-      "id": "feat-1",
-      "status": "in-progress"
-    }
-  ]
-}"#;
-            let result = update_status_in_content(content, "feat-1");
-            assert!(result.contains(r#""status": "blocked""#));
-            assert!(!result.contains(r#""in-progress""#));
+            let file = prd_file(&[feature("feat-1", "in-progress")]);
+            update_feature_status_to_blocked(file.path(), "feat-1").unwrap();
+            assert_eq!(statuses(&file), [prd::Status::Blocked]);
         }
 
         #[test]
         fn updates_pending_to_blocked() {
-            let content = r#"{
-  "features": [
-    {
-      "id": "feat-1",
-      "status": "pending"
-    }
-  ]
-}"#;
-            let result = update_status_in_content(content, "feat-1");
-            assert!(result.contains(r#""status": "blocked""#));
-            assert!(!result.contains(r#""pending""#));
+            let file = prd_file(&[feature("feat-1", "pending")]);
+            update_feature_status_to_blocked(file.path(), "feat-1").unwrap();
+            assert_eq!(statuses(&file), [prd::Status::Blocked]);
         }
 
         #[test]
         fn only_updates_target_feature() {
-            let content = r#"{
-  "features": [
-    {
-      "id": "feat-1",
-      "status": "in-progress"
-    },
-    {
-      "id": "feat-2",
-      "status": "pending"
-    }
-  ]
-}"#;
-            let result = update_status_in_content(content, "feat-1");
-            assert!(result.contains(r#""status": "blocked""#));
-            assert!(result.contains(r#""status": "pending""#));
+            let file = prd_file(&[feature("feat-1", "in-progress"), feature("feat-2", "pending")]);
+            update_feature_status_to_blocked(file.path(), "feat-1").unwrap();
+            assert_eq!(statuses(&file), [prd::Status::Blocked, prd::Status::Pending]);
         }
 
         #[test]
         fn handles_no_space_format() {
-            let content = r#"{"id": "feat-1","status":"in-progress"}"#;
-            let result = update_status_in_content(content, "feat-1");
-            assert!(result.contains(r#""status": "blocked""#));
+            let compact = r#"{"id":"feat-1","category":"c","description":"d","steps":[],"status":"in-progress"}"#;
+            let file = prd_file(&[compact.to_string()]);
+            update_feature_status_to_blocked(file.path(), "feat-1").unwrap();
+            assert_eq!(statuses(&file), [prd::Status::Blocked]);
+            assert!(std::fs::read_to_string(file.path()).unwrap().contains(r#""status":"blocked"}"#));
+        }
+
+        #[test]
+        fn reopens_complete_feature() {
+            let file = prd_file(&[feature("feat-1", "complete")]);
+            let before = std::fs::read_to_string(file.path()).unwrap();
+            assert!(update_feature_status(file.path(), "feat-1", &[prd::Status::Complete], prd::Status::Pending).unwrap());
+            let after = std::fs::read_to_string(file.path()).unwrap();
+            assert_eq!(after, before.replace(r#""status": "complete""#, r#""status": "pending""#));
+        }
+
+        #[test]
+        fn ignores_statuses_not_in_from() {
+            let file = prd_file(&[feature("feat-1", "blocked")]);
+            let before = std::fs::read_to_string(file.path()).unwrap();
+            assert!(!update_feature_status(file.path(), "feat-1", &[prd::Status::Complete], prd::Status::Pending).unwrap());
+            assert_eq!(std::fs::read_to_string(file.path()).unwrap(), before);
         }
 
         #[test]
         fn leaves_other_features_unchanged() {
-            let content = r#"{
-  "features": [
-    { "id": "feat-1", "status": "complete" },
-    { "id": "feat-2", "status": "in-progress" }
-  ]
-}"#;
-            let result = update_status_in_content(content, "feat-2");
-            assert!(result.contains(r#""status": "complete""#));
-            assert!(result.contains(r#""status": "blocked""#));
+            let file = prd_file(&[feature("feat-1", "complete"), feature("feat-2", "in-progress")]);
+            update_feature_status_to_blocked(file.path(), "feat-2").unwrap();
+            assert_eq!(statuses(&file), [prd::Status::Complete, prd::Status::Blocked]);
+        }
+
+        #[test]
+        fn keeps_comments_and_ignores_lookalike_text() {
+            // The old line-based editor matched `"id": "feat-1"` anywhere, including inside strings
+            let decoy = r#"{ "id": "feat-2", "category": "c", "description": "see \"id\": \"feat-1\"", "steps": [], "status": "pending" }"#;
+            let file = prd_file(&[decoy.to_string(), feature("feat-1", "pending")]);
+            update_feature_status_to_blocked(file.path(), "feat-1").unwrap();
+            assert_eq!(statuses(&file), [prd::Status::Pending, prd::Status::Blocked]);
+            assert!(std::fs::read_to_string(file.path()).unwrap().contains("// Keep me"));
+        }
+
+        #[test]
+        fn missing_feature_is_an_error_and_leaves_the_file() {
+            let file = prd_file(&[feature("feat-1", "pending")]);
+            let before = std::fs::read_to_string(file.path()).unwrap();
+            assert!(update_feature_status_to_blocked(file.path(), "nope").is_err());
+            assert_eq!(std::fs::read_to_string(file.path()).unwrap(), before);
         }
     }
 
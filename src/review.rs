@@ -0,0 +1,215 @@
+//! `--review`: after an iteration that changed something, a second, read-only agent reviews
+//! the diff against the feature it worked on. It approves, or its feedback is put in front of
+//! the next iteration.
+
+use crate::{
+    claude::{self, ClaudeArgs},
+    git,
+    iteration::{self, IterationContext},
+    output,
+    prd::Feature,
+};
+use anyhow::Result;
+use tokio::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+pub const APPROVED: &str = "APPROVED";
+pub const CHANGES_REQUESTED: &str = "CHANGES REQUESTED";
+/// Diff shown to the reviewer; the rest is cut.
+const DIFF_BYTES: usize = 60_000;
+/// Feedback carried into the next prompt.
+const FEEDBACK_BYTES: usize = 4096;
+
+const REVIEW_PROMPT: &str = r#"You are reviewing one session of an autonomous coding agent. Do NOT modify any files.
+
+## Feature
+
+{feature}
+
+## Changes
+
+```diff
+{diff}
+```
+
+## Instructions
+
+Check the changes against the feature, its steps, and any acceptance criteria: is the work
+correct, complete for what it attempts, tested, and free of unrelated edits? You may read
+files in the project for context.
+
+End your answer with exactly one verdict line:
+- {approved} if the changes are acceptable as they are
+- {changes_requested}, followed by short bullet points of what the next session must fix
+"#;
+
+const FEEDBACK_SECTION: &str = r#"## Review Feedback
+
+A reviewer looked at the previous session's changes and asked for the following. Address it
+before moving on:
+
+{feedback}
+"#;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Approved,
+    ChangesRequested(String),
+    Inconclusive,
+}
+
+/// The reviewer's prompt for `feature` and the iteration's `diff`.
+#[must_use]
+pub fn build_prompt(feature: Option<&Feature>, diff: &str) -> String {
+    let feature = match feature {
+        Some(f) => {
+            let steps: String = f.steps.iter().map(|s| format!("\n- {s}")).collect();
+            let criteria: String = f.acceptance_criteria.iter().map(|c| format!("\n- {c}")).collect();
+            let criteria = if criteria.is_empty() { criteria } else { format!("\n\nAcceptance criteria:{criteria}") };
+            format!("{} ({}): {}{steps}{criteria}", f.id, f.category, f.description)
+        }
+        None => "No feature was in progress; judge the changes on their own.".to_string(),
+    };
+    let diff = match diff.char_indices().nth(DIFF_BYTES) {
+        Some((end, _)) => format!("{}\n... diff truncated", &diff[..end]),
+        None => diff.to_string(),
+    };
+    REVIEW_PROMPT
+        .replace("{feature}", &feature)
+        .replace("{approved}", APPROVED)
+        .replace("{changes_requested}", CHANGES_REQUESTED)
+        .replace("{diff}", &diff)
+}
+
+/// The last verdict line of the reviewer's output; feedback is what follows it.
+#[must_use]
+pub fn parse_verdict(output: &str) -> Verdict {
+    let lines: Vec<&str> = output.lines().collect();
+    let verdict_line = |line: &str| line.trim().trim_matches(['*', '#', '`', ' ', ':']).to_uppercase();
+    for (at, line) in lines.iter().enumerate().rev() {
+        let line = verdict_line(line);
+        if line == APPROVED {
+            return Verdict::Approved;
+        }
+        if line.starts_with(CHANGES_REQUESTED) {
+            let inline = lines[at].split_once(':').map(|(_, rest)| rest.trim()).unwrap_or_default();
+            let feedback = std::iter::once(inline).chain(lines[at + 1..].iter().copied()).collect::<Vec<_>>();
+            return Verdict::ChangesRequested(clip(feedback.join("\n").trim()));
+        }
+    }
+    Verdict::Inconclusive
+}
+
+fn clip(feedback: &str) -> String {
+    if feedback.is_empty() {
+        return "The reviewer gave no details; re-check the last session's changes.".to_string();
+    }
+    let mut end = feedback.len().min(FEEDBACK_BYTES);
+    while !feedback.is_char_boundary(end) {
+        end -= 1;
+    }
+    feedback[..end].to_string()
+}
+
+/// Prompt section carrying the last review's feedback.
+#[must_use]
+pub fn feedback_section(feedback: &str) -> String {
+    FEEDBACK_SECTION.replace("{feedback}", feedback)
+}
+
+/// Reviews the changes since `head`, leaving out ralph's own files. None when there are none.
+pub async fn run(
+    iteration: u32,
+    ctx: &IterationContext<'_>,
+    feature: Option<&Feature>,
+    head: &str,
+    cancel_token: &CancellationToken,
+) -> Result<Option<Verdict>> {
+    let exclude = iteration::ralph_files(ctx);
+    let diff = git::diff_since(ctx.project_dir, head, &exclude.iter().map(String::as_str).collect::<Vec<_>>())?;
+    if diff.trim().is_empty() {
+        return Ok(None);
+    }
+    output::log(&format!("Reviewing iteration {iteration}..."));
+
+    let stem = ctx.log_path.file_stem().unwrap_or_default().to_string_lossy();
+    let log_path = ctx.log_path.with_file_name(format!("{stem}-review.log"));
+
+    // Plan mode keeps the reviewer read-only regardless of the run's permission flags
+    let claude_args = ClaudeArgs {
+        backend: ctx.backend,
+        permission_mode: "plan".into(),
+        continue_session: false,
+        dangerously_skip_permissions: false,
+        model: ctx.args.review_model.clone().or_else(|| ctx.args.model.clone()),
+        allowed_tools: &ctx.args.allowed_tools,
+        disallowed_tools: &ctx.args.disallowed_tools,
+        timeout_secs: ctx.args.timeout,
+        stall_timeout: ctx.args.stall_timeout.map(Duration::from_secs),
+        project_dir: ctx.project_dir,
+        output_buffer_bytes: ctx.args.output_buffer_kb.saturating_mul(1024),
+        live: ctx.live,
+        simulator: ctx.simulator,
+        env: ctx.env,
+    };
+
+    let result = claude::run_claude(&build_prompt(feature, &diff), &claude_args, &log_path, cancel_token).await?;
+    if let Some(ref usage) = result.usage {
+        ctx.costs.record(iteration, usage);
+    }
+    Ok(Some(parse_verdict(&result.output)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FeatureBuilder;
+
+    #[test]
+    fn prompt_has_the_feature_and_diff() {
+        let mut feature = FeatureBuilder::new("auth-login").description("Users can log in").step("Add the form").build();
+        feature.acceptance_criteria = vec!["Wrong passwords are rejected".into()];
+        let prompt = build_prompt(Some(&feature), "+fn login() {}");
+        assert!(prompt.contains(
+            "auth-login (functional): Users can log in\n- Add the form\n\nAcceptance criteria:\n- Wrong passwords are rejected"
+        ));
+        assert!(prompt.contains("```diff\n+fn login() {}\n```"));
+        assert!(prompt.contains("- APPROVED if"));
+        assert!(build_prompt(None, "").contains("No feature was in progress"));
+    }
+
+    #[test]
+    fn long_diffs_are_truncated() {
+        let prompt = build_prompt(None, &"x".repeat(DIFF_BYTES + 10));
+        assert!(prompt.contains("... diff truncated"));
+        assert!(!prompt.contains(&"x".repeat(DIFF_BYTES + 1)));
+    }
+
+    #[test]
+    fn parses_approval() {
+        assert_eq!(parse_verdict("Looks fine.\n**APPROVED**"), Verdict::Approved);
+        assert_eq!(parse_verdict("Looked around"), Verdict::Inconclusive);
+    }
+
+    #[test]
+    fn parses_requested_changes() {
+        let output = "The form has no test.\nCHANGES REQUESTED\n- add a test for the form\n- drop debug.log";
+        assert_eq!(
+            parse_verdict(output),
+            Verdict::ChangesRequested("- add a test for the form\n- drop debug.log".into())
+        );
+        assert_eq!(
+            parse_verdict("CHANGES REQUESTED: handle the empty password"),
+            Verdict::ChangesRequested("handle the empty password".into())
+        );
+        let Verdict::ChangesRequested(feedback) = parse_verdict("CHANGES REQUESTED") else {
+            panic!("expected requested changes");
+        };
+        assert!(feedback.contains("no details"));
+    }
+
+    #[test]
+    fn the_last_verdict_wins() {
+        assert_eq!(parse_verdict("CHANGES REQUESTED\n- a\nOn second thought:\nAPPROVED"), Verdict::Approved);
+    }
+}
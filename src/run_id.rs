@@ -0,0 +1,89 @@
+//! Run ids: one per run (kept across `--resume`), embedded in log and artifact names,
+//! JSON events, webhook payloads, and ralph's commits so CI and notification channels
+//! can correlate them. `--run-id` lets an external system choose it.
+
+use anyhow::{bail, Result};
+use sha2::{Digest, Sha256};
+use std::sync::RwLock;
+
+/// Trailer added to commits ralph makes.
+pub const TRAILER: &str = "Ralph-Run-Id";
+
+/// Environment variable carrying the id to the agent and verification commands.
+pub const ENV_VAR: &str = "RALPH_RUN_ID";
+
+const MAX_LEN: usize = 64;
+
+static CURRENT: RwLock<Option<String>> = RwLock::new(None);
+
+/// A new id: start time plus random suffix, e.g. `20260115-103000-4f2a`, so ids sort by start.
+#[must_use]
+pub fn generate() -> String {
+    format!("{}-{:04x}", chrono::Local::now().format("%Y%m%d-%H%M%S"), fastrand::u16(..))
+}
+
+/// Ids end up in file names, branch names, and commit trailers.
+pub fn validate(id: &str) -> Result<()> {
+    if id.is_empty() || id.len() > MAX_LEN {
+        bail!("--run-id must be 1 to {MAX_LEN} characters");
+    }
+    if !id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')) || id.starts_with('.') {
+        bail!("--run-id may only contain letters, digits, '-', '_' and '.', and can't start with '.', got '{id}'");
+    }
+    Ok(())
+}
+
+/// Seed for the run's random choices (backoff jitter), so a run id replays the same delays.
+#[must_use]
+pub fn seed(id: &str) -> u64 {
+    let hash = Sha256::digest(id.as_bytes());
+    u64::from_le_bytes(hash[..8].try_into().unwrap_or_default())
+}
+
+/// Sets the id of the run in progress for the rest of the process.
+pub fn set(id: &str) {
+    *CURRENT.write().unwrap_or_else(|e| e.into_inner()) = Some(id.to_string());
+}
+
+#[must_use]
+pub fn current() -> Option<String> {
+    CURRENT.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// `message` with the current run's trailer appended, if a run is in progress.
+#[must_use]
+pub fn with_trailer(message: &str) -> String {
+    match current() {
+        Some(id) => format!("{}\n\n{TRAILER}: {id}", message.trim_end()),
+        None => message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_ids_are_valid_and_distinct() {
+        let a = generate();
+        validate(&a).unwrap();
+        assert_eq!(a.len(), "20260115-103000-4f2a".len());
+        assert!((0..20).map(|_| generate()).any(|b| b != a));
+    }
+
+    #[test]
+    fn validate_rejects_unsafe_ids() {
+        for id in ["ci-1234", "build_7.2", "A"] {
+            assert!(validate(id).is_ok(), "{id}");
+        }
+        for id in ["", "a/b", "has space", "..", ".hidden", &"x".repeat(65)] {
+            assert!(validate(id).is_err(), "{id}");
+        }
+    }
+
+    #[test]
+    fn seed_is_stable_per_id() {
+        assert_eq!(seed("ci-1"), seed("ci-1"));
+        assert_ne!(seed("ci-1"), seed("ci-2"));
+    }
+}
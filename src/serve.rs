@@ -0,0 +1,315 @@
+//! `--serve` mode: a minimal HTTP server streaming live iteration output as server-sent events.
+//!
+//! `GET /events` streams `iteration` events (data: iteration number) and `line` events
+//! (data: one output line). `GET /health` returns 200. `POST /approve` and `POST /reject`
+//! answer a waiting approval gate, or return 409 when none is waiting. They need an
+//! `Authorization: Bearer <token>` header, which a cross-site form can't send, and return
+//! 401 without it.
+
+use anyhow::{Context, Result};
+use std::net::SocketAddr;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+
+const CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LiveEvent {
+    Iteration(u32),
+    Line(String),
+}
+
+impl LiveEvent {
+    #[must_use]
+    pub fn to_sse(&self) -> String {
+        match self {
+            Self::Iteration(n) => format!("event: iteration\ndata: {n}\n\n"),
+            // Lines never contain \n, but a stray \r would end the SSE field early
+            Self::Line(line) => format!("event: line\ndata: {}\n\n", line.replace('\r', "")),
+        }
+    }
+}
+
+/// Fan-out of live output to any connected SSE clients. Sends never block and are
+/// dropped when nobody is listening.
+#[derive(Clone)]
+pub struct LiveFeed {
+    tx: broadcast::Sender<LiveEvent>,
+}
+
+impl Default for LiveFeed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LiveFeed {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            tx: broadcast::channel(CHANNEL_CAPACITY).0,
+        }
+    }
+
+    pub fn send(&self, event: LiveEvent) {
+        let _ = self.tx.send(event);
+    }
+
+    #[must_use]
+    pub fn subscribe(&self) -> broadcast::Receiver<LiveEvent> {
+        self.tx.subscribe()
+    }
+}
+
+/// Where a running `--serve` server listens, and the token approvals must carry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    pub addr: SocketAddr,
+    pub token: String,
+}
+
+/// `--serve-token`, else a random one for this run.
+#[must_use]
+pub fn token(configured: Option<&str>) -> String {
+    configured.map_or_else(|| std::iter::repeat_with(fastrand::alphanumeric).take(32).collect(), str::to_string)
+}
+
+/// Binds `addr` and serves `feed` in the background, approvals taking `token`.
+pub async fn start(addr: SocketAddr, feed: LiveFeed, token: String) -> Result<Endpoint> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind --serve address {addr}"))?;
+    let local = listener.local_addr()?;
+
+    let endpoint = Endpoint { addr: local, token };
+    let token = endpoint.token.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let (feed, token) = (feed.clone(), token.clone());
+            tokio::spawn(async move {
+                let _ = handle(stream, &feed, &token).await;
+            });
+        }
+    });
+
+    Ok(endpoint)
+}
+
+/// Whether `headers` carry `Authorization: Bearer <token>`.
+fn authorized(headers: &[String], token: &str) -> bool {
+    headers.iter().any(|header| {
+        let Some((name, value)) = header.split_once(':') else {
+            return false;
+        };
+        let value = value.trim();
+        name.trim().eq_ignore_ascii_case("authorization")
+            && value.get(..7).is_some_and(|scheme| scheme.eq_ignore_ascii_case("bearer "))
+            && value[7..].trim() == token
+    })
+}
+
+async fn handle(stream: TcpStream, feed: &LiveFeed, token: &str) -> Result<()> {
+    let (read, mut write) = stream.into_split();
+    let mut reader = BufReader::new(read);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    let mut headers = Vec::new();
+    let mut header = String::new();
+    while reader.read_line(&mut header).await? > 2 {
+        headers.push(std::mem::take(&mut header));
+    }
+
+    match route(&request_line) {
+        Route::Events => {
+            let mut rx = feed.subscribe();
+            write
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+                      Cache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n",
+                )
+                .await?;
+            loop {
+                let chunk = match rx.recv().await {
+                    Ok(event) => event.to_sse(),
+                    Err(broadcast::error::RecvError::Lagged(n)) => format!(": skipped {n} events\n\n"),
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                write.write_all(chunk.as_bytes()).await?;
+            }
+        }
+        Route::Health => {
+            write
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")
+                .await?;
+        }
+        Route::Approve | Route::Reject if !authorized(&headers, token) => {
+            let response: &[u8] = b"HTTP/1.1 401 Unauthorized\r\nContent-Length: 16\r\nConnection: close\r\n\r\nbad or no token\n";
+            write.write_all(response).await?;
+        }
+        answer @ (Route::Approve | Route::Reject) => {
+            let response: &[u8] = if crate::approval::decide(answer == Route::Approve) {
+                b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok"
+            } else {
+                b"HTTP/1.1 409 Conflict\r\nContent-Length: 19\r\nConnection: close\r\n\r\nnothing to approve\n"
+            };
+            write.write_all(response).await?;
+        }
+        Route::NotFound => {
+            write
+                .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Route {
+    Events,
+    Health,
+    Approve,
+    Reject,
+    NotFound,
+}
+
+fn route(request_line: &str) -> Route {
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Route::NotFound;
+    };
+    match (method, target.split('?').next()) {
+        ("GET", Some("/events")) => Route::Events,
+        ("GET", Some("/health")) => Route::Health,
+        ("POST", Some("/approve")) => Route::Approve,
+        ("POST", Some("/reject")) => Route::Reject,
+        _ => Route::NotFound,
+    }
+}
+
+/// Parses one SSE event block (without the trailing blank line) into a `LiveEvent`.
+pub(crate) fn parse_sse_event(block: &str) -> Option<LiveEvent> {
+    let mut event = "message";
+    let mut data = Vec::new();
+    for line in block.lines() {
+        if let Some(v) = line.strip_prefix("event:") {
+            event = v.trim();
+        } else if let Some(v) = line.strip_prefix("data:") {
+            data.push(v.strip_prefix(' ').unwrap_or(v));
+        }
+    }
+    let data = data.join("\n");
+    match event {
+        "iteration" => data.parse().ok().map(LiveEvent::Iteration),
+        "line" => Some(LiveEvent::Line(data)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod sse_tests {
+        use super::*;
+
+        #[test]
+        fn formats_events() {
+            assert_eq!(LiveEvent::Iteration(3).to_sse(), "event: iteration\ndata: 3\n\n");
+            assert_eq!(
+                LiveEvent::Line("hello\r".into()).to_sse(),
+                "event: line\ndata: hello\n\n"
+            );
+        }
+
+        #[test]
+        fn roundtrips_through_parser() {
+            for event in [LiveEvent::Iteration(7), LiveEvent::Line(" indented: yes".into())] {
+                let sse = event.to_sse();
+                assert_eq!(parse_sse_event(sse.trim_end_matches('\n')), Some(event));
+            }
+        }
+
+        #[test]
+        fn ignores_comments_and_unknown_events() {
+            assert_eq!(parse_sse_event(": skipped 3 events"), None);
+            assert_eq!(parse_sse_event("event: other\ndata: x"), None);
+        }
+    }
+
+    mod route_tests {
+        use super::*;
+
+        #[test]
+        fn routes_known_paths() {
+            assert_eq!(route("GET /events HTTP/1.1\r\n"), Route::Events);
+            assert_eq!(route("GET /events?x=1 HTTP/1.1\r\n"), Route::Events);
+            assert_eq!(route("GET /health HTTP/1.1\r\n"), Route::Health);
+            assert_eq!(route("POST /approve HTTP/1.1\r\n"), Route::Approve);
+            assert_eq!(route("POST /reject HTTP/1.1\r\n"), Route::Reject);
+        }
+
+        #[test]
+        fn rejects_other_requests() {
+            assert_eq!(route("POST /events HTTP/1.1\r\n"), Route::NotFound);
+            assert_eq!(route("GET / HTTP/1.1\r\n"), Route::NotFound);
+            assert_eq!(route("GET /approve HTTP/1.1\r\n"), Route::NotFound);
+            assert_eq!(route(""), Route::NotFound);
+        }
+    }
+
+    mod server_tests {
+        use super::*;
+        use tokio::io::AsyncReadExt;
+
+        #[test]
+        fn approvals_need_the_bearer_token() {
+            let headers = |lines: &[&str]| lines.iter().map(|l| format!("{l}\r\n")).collect::<Vec<_>>();
+            assert!(authorized(&headers(&["Host: x", "Authorization: Bearer s3cret"]), "s3cret"));
+            assert!(authorized(&headers(&["authorization: bearer s3cret"]), "s3cret"));
+            assert!(!authorized(&headers(&["Authorization: Bearer wrong"]), "s3cret"));
+            assert!(!authorized(&headers(&["Authorization: s3cret"]), "s3cret"));
+            assert!(!authorized(&headers(&["Host: x"]), "s3cret"));
+            assert_eq!(token(Some("mine")), "mine");
+            assert_eq!(token(None).len(), 32);
+            assert_ne!(token(None), token(None));
+        }
+
+        #[tokio::test]
+        async fn rejects_approvals_without_the_token() {
+            let endpoint = start("127.0.0.1:0".parse().unwrap(), LiveFeed::new(), "s3cret".into()).await.unwrap();
+            let mut stream = TcpStream::connect(endpoint.addr).await.unwrap();
+            stream.write_all(b"POST /approve HTTP/1.1\r\nHost: x\r\n\r\n").await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            assert!(response.starts_with("HTTP/1.1 401"), "{response}");
+        }
+
+        #[tokio::test]
+        async fn streams_events_to_client() {
+            let feed = LiveFeed::new();
+            let addr = start("127.0.0.1:0".parse().unwrap(), feed.clone(), token(None)).await.unwrap().addr;
+
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream.write_all(b"GET /events HTTP/1.1\r\nHost: x\r\n\r\n").await.unwrap();
+
+            // Wait until the server has subscribed before sending
+            while feed.tx.receiver_count() == 0 {
+                tokio::task::yield_now().await;
+            }
+            feed.send(LiveEvent::Iteration(1));
+            feed.send(LiveEvent::Line("working".into()));
+
+            let mut received = String::new();
+            let mut buf = [0u8; 1024];
+            while !received.contains("data: working") {
+                let n = stream.read(&mut buf).await.unwrap();
+                assert!(n > 0, "server closed early: {received}");
+                received.push_str(&String::from_utf8_lossy(&buf[..n]));
+            }
+            assert!(received.starts_with("HTTP/1.1 200 OK"));
+            assert!(received.contains("event: iteration\ndata: 1\n\n"));
+        }
+    }
+}
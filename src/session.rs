@@ -0,0 +1,233 @@
+//! Iteration transcripts in `.ralph/sessions/<run id>-<n>.json`: the prompt, the agent's
+//! output, the diff, and the verification results, kept for `ralph replay`.
+
+use crate::{artifact::IterationArtifact, config::ReplayArgs, redact, validation, verification::CommandResult};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    pub run_id: String,
+    pub iteration: u32,
+    pub started_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feature_id: Option<String>,
+    pub result: String,
+    pub prompt: String,
+    pub output: String,
+    /// Changes made during the iteration, without ralph's own files; git projects only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+    /// Absent when no verification ran after the iteration
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification: Option<Vec<CommandResult>>,
+}
+
+#[must_use]
+pub fn sessions_dir(ralph_dir: &Path) -> PathBuf {
+    ralph_dir.join("sessions")
+}
+
+/// `<run id>-<n>.json`, named like the iteration's log.
+#[must_use]
+pub fn path(ralph_dir: &Path, run_id: &str, iteration: u32) -> PathBuf {
+    sessions_dir(ralph_dir).join(format!("{run_id}-{iteration}.json"))
+}
+
+impl Session {
+    pub fn write(&self, ralph_dir: &Path) -> Result<PathBuf> {
+        let path = path(ralph_dir, &self.run_id, self.iteration);
+        std::fs::create_dir_all(sessions_dir(ralph_dir))
+            .with_context(|| format!("Failed to create {}", sessions_dir(ralph_dir).display()))?;
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(&path, json).with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+
+    /// Every readable session, oldest first.
+    #[must_use]
+    pub fn load_all(ralph_dir: &Path) -> Vec<Session> {
+        let Ok(entries) = std::fs::read_dir(sessions_dir(ralph_dir)) else {
+            return Vec::new();
+        };
+        let mut sessions: Vec<Session> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|p| serde_json::from_str(&std::fs::read_to_string(p).ok()?).ok())
+            .collect();
+        sessions.sort_by(|a, b| (&a.started_at, a.iteration).cmp(&(&b.started_at, b.iteration)));
+        sessions
+    }
+}
+
+/// Prompt and output of each iteration's agent session, until the runner archives them.
+#[derive(Debug, Default)]
+pub struct Transcripts {
+    iterations: Mutex<BTreeMap<u32, (String, String)>>,
+}
+
+impl Transcripts {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, iteration: u32, prompt: &str, output: &str) {
+        let mut iterations = self.iterations.lock().unwrap_or_else(|e| e.into_inner());
+        iterations.insert(iteration, (prompt.to_string(), output.to_string()));
+    }
+
+    /// Writes the session of `artifact`'s iteration; None when the agent never ran in it.
+    pub fn archive(
+        &self,
+        ralph_dir: &Path,
+        artifact: &IterationArtifact,
+        diff: Option<String>,
+        verification: Option<Vec<CommandResult>>,
+    ) -> Result<Option<PathBuf>> {
+        let recorded = self.iterations.lock().unwrap_or_else(|e| e.into_inner()).remove(&artifact.iteration);
+        let Some((prompt, output)) = recorded else {
+            return Ok(None);
+        };
+        let session = Session {
+            run_id: artifact.run_id.clone().unwrap_or_default(),
+            iteration: artifact.iteration,
+            started_at: artifact.started_at.clone(),
+            feature_id: artifact.feature_id.clone(),
+            result: artifact.result.clone(),
+            // Output is masked as it streams; the prompt and diff can quote files holding secrets
+            prompt: redact::text(&prompt).into_owned(),
+            output,
+            diff: diff.filter(|d| !d.trim().is_empty()).map(|d| redact::text(&d).into_owned()),
+            verification,
+        };
+        session.write(ralph_dir).map(Some)
+    }
+}
+
+/// `ralph replay`: prints the session chosen by `opts`.
+pub fn replay(prd: &Path, opts: &ReplayArgs) -> Result<()> {
+    let ralph_dir = validation::prd_dir(prd).join(".ralph");
+    let sessions = Session::load_all(&ralph_dir);
+    if sessions.is_empty() {
+        bail!("No sessions in {}", sessions_dir(&ralph_dir).display());
+    }
+    let Some(session) = select(&sessions, opts.iteration, opts.run_id.as_deref()) else {
+        match &opts.run_id {
+            Some(id) => bail!("No session for iteration {} of run '{id}'", opts.iteration),
+            None => bail!("No session for iteration {}", opts.iteration),
+        }
+    };
+    print!("{}", render(session));
+    Ok(())
+}
+
+/// The latest session of `iteration`, in run `run_id` if given.
+fn select<'a>(sessions: &'a [Session], iteration: u32, run_id: Option<&str>) -> Option<&'a Session> {
+    sessions.iter().rev().find(|s| s.iteration == iteration && run_id.is_none_or(|id| s.run_id == id))
+}
+
+#[must_use]
+pub fn render(session: &Session) -> String {
+    let feature = session.feature_id.as_deref().map(|f| format!(" on '{f}'")).unwrap_or_default();
+    let mut out = format!(
+        "# Run {}, iteration {}{feature}\n\nStarted {}, result: {}\n",
+        session.run_id, session.iteration, session.started_at, session.result
+    );
+    let mut section = |title: &str, body: &str| {
+        out.push_str(&format!("\n## {title}\n\n{}\n", body.trim_end()));
+    };
+    section("Prompt", &session.prompt);
+    section("Output", if session.output.trim().is_empty() { "(no output)" } else { &session.output });
+    match &session.diff {
+        Some(diff) => section("Diff", diff),
+        None => section("Diff", "(no changes, or not a git project)"),
+    }
+    match &session.verification {
+        Some(results) => {
+            let lines: Vec<String> = results.iter().map(|r| format!("- {}", r.summary())).collect();
+            section("Verification", &lines.join("\n"));
+        }
+        None => section("Verification", "(not run)"),
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ArtifactBuilder;
+
+    fn session(run_id: &str, iteration: u32, started_at: &str) -> Session {
+        Session {
+            run_id: run_id.into(),
+            iteration,
+            started_at: started_at.into(),
+            feature_id: Some("auth-login".into()),
+            result: "continue".into(),
+            prompt: "Implement the next feature".into(),
+            output: "Added the login form".into(),
+            diff: Some("+fn login() {}\n".into()),
+            verification: Some(vec![CommandResult { name: "test".into(), passed: true, attempts: 1 }]),
+        }
+    }
+
+    #[test]
+    fn archives_recorded_transcripts() {
+        let dir = tempfile::tempdir().unwrap();
+        let transcripts = Transcripts::new();
+        transcripts.record(2, "the prompt", "the output");
+        let artifact = ArtifactBuilder::new(2).run_id("a").build();
+        let path = transcripts.archive(dir.path(), &artifact, Some(" \n".into()), None).unwrap().unwrap();
+        assert_eq!(path, dir.path().join("sessions/a-2.json"));
+
+        let sessions = Session::load_all(dir.path());
+        assert_eq!(sessions.len(), 1);
+        assert_eq!((sessions[0].prompt.as_str(), sessions[0].output.as_str()), ("the prompt", "the output"));
+        assert_eq!(sessions[0].diff, None);
+        // Archiving takes the transcript, and iterations without one write nothing
+        assert!(transcripts.archive(dir.path(), &artifact, None, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn selects_the_latest_session_of_an_iteration() {
+        let sessions = vec![
+            session("a", 1, "2026-01-01T10:00:00+00:00"),
+            session("a", 2, "2026-01-01T10:10:00+00:00"),
+            session("b", 1, "2026-01-02T10:00:00+00:00"),
+        ];
+        assert_eq!(select(&sessions, 1, None).unwrap().run_id, "b");
+        assert_eq!(select(&sessions, 1, Some("a")).unwrap().started_at, "2026-01-01T10:00:00+00:00");
+        assert!(select(&sessions, 2, Some("b")).is_none());
+        assert!(select(&sessions, 3, None).is_none());
+    }
+
+    #[test]
+    fn renders_every_part() {
+        let rendered = render(&session("a", 2, "2026-01-01T10:10:00+00:00"));
+        assert!(rendered.starts_with("# Run a, iteration 2 on 'auth-login'\n\nStarted 2026-01-01T10:10:00+00:00"));
+        assert!(rendered.contains("## Prompt\n\nImplement the next feature\n"));
+        assert!(rendered.contains("## Output\n\nAdded the login form\n"));
+        assert!(rendered.contains("## Diff\n\n+fn login() {}\n"));
+        assert!(rendered.contains("## Verification\n\n- test pass\n"));
+
+        let bare = Session { diff: None, verification: None, ..session("a", 1, "") };
+        assert!(render(&bare).contains("## Verification\n\n(not run)"));
+    }
+
+    #[test]
+    fn replays_from_the_prd_sessions_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        session("a", 1, "2026-01-01T10:00:00+00:00").write(&dir.path().join(".ralph")).unwrap();
+        let prd = dir.path().join("prd.jsonc");
+        replay(&prd, &ReplayArgs { iteration: 1, run_id: None }).unwrap();
+        assert!(replay(&prd, &ReplayArgs { iteration: 1, run_id: Some("b".into()) }).is_err());
+        assert!(replay(&tempfile::tempdir().unwrap().path().join("prd.jsonc"), &ReplayArgs { iteration: 1, run_id: None })
+            .is_err());
+    }
+}
@@ -0,0 +1,92 @@
+//! The shell ralph runs command strings with: verification and coverage commands, simulation
+//! steps, and `--agent-command`. `sh -c` on Unix and PowerShell on Windows, unless a
+//! verification command picks its own `shell`.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Shell {
+    Sh,
+    Bash,
+    /// Windows `cmd.exe`
+    Cmd,
+    /// Windows PowerShell
+    Powershell,
+    /// PowerShell 7+
+    Pwsh,
+}
+
+impl Default for Shell {
+    /// The platform's shell
+    fn default() -> Self {
+        if cfg!(windows) {
+            Self::Powershell
+        } else {
+            Self::Sh
+        }
+    }
+}
+
+impl Shell {
+    /// Program and arguments that run `script`.
+    #[must_use]
+    pub fn argv(self, script: &str) -> Vec<&str> {
+        match self {
+            Self::Sh => vec!["sh", "-c", script],
+            Self::Bash => vec!["bash", "-c", script],
+            Self::Cmd => vec!["cmd", "/C", script],
+            Self::Powershell => vec!["powershell", "-NoProfile", "-NonInteractive", "-Command", script],
+            Self::Pwsh => vec!["pwsh", "-NoProfile", "-NonInteractive", "-Command", script],
+        }
+    }
+
+    /// A process running `script`, for the caller to configure and spawn.
+    #[must_use]
+    pub fn command(self, script: &str) -> std::process::Command {
+        let argv = self.argv(script);
+        let mut command = std::process::Command::new(argv[0]);
+        // cmd.exe doesn't unquote its arguments the way other Windows programs do
+        #[cfg(windows)]
+        if self == Self::Cmd {
+            use std::os::windows::process::CommandExt;
+            command.arg("/C").raw_arg(script);
+            return command;
+        }
+        command.args(&argv[1..]);
+        command
+    }
+}
+
+/// `script` run by the platform's shell.
+#[must_use]
+pub fn command(script: &str) -> tokio::process::Command {
+    Shell::default().command(script).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn platform_default() {
+        let expected = if cfg!(windows) { Shell::Powershell } else { Shell::Sh };
+        assert_eq!(Shell::default(), expected);
+    }
+
+    #[test]
+    fn argv_per_shell() {
+        assert_eq!(Shell::Sh.argv("make"), ["sh", "-c", "make"]);
+        assert_eq!(Shell::Cmd.argv("dir"), ["cmd", "/C", "dir"]);
+        assert_eq!(Shell::Pwsh.argv("ls")[..2], ["pwsh", "-NoProfile"]);
+        assert_eq!(serde_json::from_str::<Shell>(r#""powershell""#).unwrap(), Shell::Powershell);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn runs_scripts() {
+        let status = Shell::default().command("test 2 -gt 1 && exit 3").status().unwrap();
+        assert_eq!(status.code(), Some(3));
+    }
+}
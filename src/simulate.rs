@@ -0,0 +1,254 @@
+//! `--simulate <script.json>`: replaces the Claude CLI with scripted outputs so runner
+//! policies (retries, blocking, failure limits, audits) can be exercised end to end.
+//!
+//! ```json
+//! { "steps": [
+//!     { "output": "Working on feat-1", "run": "touch done.txt" },
+//!     { "output": "Error: tool failed", "exitCode": 1 },
+//!     { "output": "<promise>COMPLETE</promise>", "delayMs": 500 }
+//! ] }
+//! ```
+//!
+//! Each Claude invocation (iterations, audits, and reviews alike) consumes the next step; running
+//! past the last step is an iteration error.
+
+use crate::{
+    claude::ClaudeResult,
+    events::{self, RunEvent},
+    metrics, output, redact,
+    serve::{LiveEvent, LiveFeed},
+};
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Deserialize)]
+pub struct Script {
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Step {
+    /// Printed as the agent's output, one line per `\n`
+    #[serde(default)]
+    pub output: String,
+    #[serde(default)]
+    pub exit_code: i32,
+    /// Simulated run time before any output, cancellable like a real agent and stopped
+    /// by `--stall-timeout` when longer
+    #[serde(default)]
+    pub delay_ms: u64,
+    /// Shell command run in the project dir before output, to fake the agent's edits
+    pub run: Option<String>,
+}
+
+pub struct Simulator {
+    steps: Vec<Step>,
+    next: AtomicUsize,
+}
+
+impl Simulator {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read simulation script: {}", path.display()))?;
+        let script: Script = json5::from_str(&content)
+            .with_context(|| format!("Failed to parse simulation script: {}", path.display()))?;
+        Ok(Self::new(script.steps))
+    }
+
+    #[must_use]
+    pub fn new(steps: Vec<Step>) -> Self {
+        Self {
+            steps,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub async fn run(
+        &self,
+        project_dir: &Path,
+        log_path: &Path,
+        live: Option<&LiveFeed>,
+        stall: Option<Duration>,
+        env: &BTreeMap<String, String>,
+        cancel_token: &CancellationToken,
+    ) -> Result<ClaudeResult> {
+        let index = self.next.fetch_add(1, Ordering::SeqCst);
+        let Some(step) = self.steps.get(index) else {
+            bail!("Simulation script exhausted after {} steps", self.steps.len());
+        };
+
+        if step.delay_ms > 0 {
+            let delay = Duration::from_millis(step.delay_ms);
+            let stalled_after = stall.filter(|stall| *stall < delay);
+            tokio::select! {
+                _ = tokio::time::sleep(stalled_after.unwrap_or(delay)) => {}
+                _ = cancel_token.cancelled() => {
+                    return Ok(ClaudeResult {
+                        output: "Cancelled".to_string(),
+                        success: false,
+                        usage: None,
+                        resources: None,
+                        stalled: false,
+                    });
+                }
+            }
+            if let Some(stall) = stalled_after {
+                let message = format!("Stalled: no agent output for {}s", stall.as_secs());
+                output::warn(&message);
+                std::fs::write(log_path, format!("[ralph: {message}]\n")).context("Failed to create log file")?;
+                return Ok(ClaudeResult {
+                    output: message,
+                    success: false,
+                    usage: None,
+                    resources: None,
+                    stalled: true,
+                });
+            }
+        }
+
+        if let Some(ref command) = step.run {
+            let status = crate::shell::command(command)
+                .current_dir(project_dir)
+                .envs(env)
+                .status()
+                .await
+                .with_context(|| format!("Failed to run simulation step command: {command}"))?;
+            if !status.success() {
+                bail!("Simulation step {} command failed: {command}", index + 1);
+            }
+        }
+
+        // Masked like the real agent's output
+        let step_output = redact::text(&step.output);
+        let mut log_file = std::fs::File::create(log_path).context("Failed to create log file")?;
+        for line in step_output.lines() {
+            events::emit(&RunEvent::AgentOutputChunk { line: line.to_string(), stderr: false });
+            writeln!(log_file, "{line}")?;
+            if let Some(live) = live {
+                live.send(LiveEvent::Line(line.to_string()));
+            }
+        }
+
+        Ok(ClaudeResult {
+            output: step_output.into_owned(),
+            success: step.exit_code == 0,
+            usage: step.output.lines().find_map(metrics::parse_result_line).map(|e| e.usage),
+            resources: None,
+            stalled: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(output: &str, exit_code: i32) -> Step {
+        Step {
+            output: output.into(),
+            exit_code,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parses_script() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("script.json");
+        std::fs::write(
+            &path,
+            r#"{ steps: [ { output: "hi" }, { output: "bad", exitCode: 2, delayMs: 5, run: "true" } ] }"#,
+        )
+        .unwrap();
+
+        let sim = Simulator::load(&path).unwrap();
+        assert_eq!(sim.len(), 2);
+        assert_eq!(sim.steps[1].exit_code, 2);
+        assert_eq!(sim.steps[1].run.as_deref(), Some("true"));
+    }
+
+    #[test]
+    fn rejects_unknown_step_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("script.json");
+        std::fs::write(&path, r#"{ "steps": [ { "exit": 1 } ] }"#).unwrap();
+        assert!(Simulator::load(&path).is_err());
+    }
+
+    #[tokio::test]
+    async fn replays_steps_in_order_then_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("it.log");
+        let sim = Simulator::new(vec![step("one\ntwo", 0), step("fail", 1)]);
+        let token = CancellationToken::new();
+
+        let first = sim.run(dir.path(), &log, None, None, &BTreeMap::new(), &token).await.unwrap();
+        assert!(first.success);
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "one\ntwo\n");
+
+        let second = sim.run(dir.path(), &log, None, None, &BTreeMap::new(), &token).await.unwrap();
+        assert!(!second.success);
+        assert_eq!(second.output, "fail");
+
+        assert!(sim.run(dir.path(), &log, None, None, &BTreeMap::new(), &token).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn runs_step_command_in_project_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let sim = Simulator::new(vec![Step {
+            run: Some("echo x > touched".into()),
+            ..Default::default()
+        }]);
+        sim.run(dir.path(), &dir.path().join("it.log"), None, None, &BTreeMap::new(), &CancellationToken::new())
+            .await
+            .unwrap();
+        assert!(dir.path().join("touched").exists());
+    }
+
+    #[tokio::test]
+    async fn delay_is_cancellable() {
+        let dir = tempfile::tempdir().unwrap();
+        let sim = Simulator::new(vec![Step {
+            delay_ms: 60_000,
+            ..Default::default()
+        }]);
+        let token = CancellationToken::new();
+        token.cancel();
+        let result = sim.run(dir.path(), &dir.path().join("it.log"), None, None, &BTreeMap::new(), &token).await.unwrap();
+        assert!(!result.success);
+    }
+
+    #[tokio::test]
+    async fn delay_past_stall_timeout_stalls() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("it.log");
+        let sim = Simulator::new(vec![Step {
+            output: "never".into(),
+            delay_ms: 60_000,
+            ..Default::default()
+        }]);
+        let stall = Some(Duration::from_millis(10));
+        let result = sim.run(dir.path(), &log, None, stall, &BTreeMap::new(), &CancellationToken::new()).await.unwrap();
+        assert!(result.stalled);
+        assert!(!result.output.contains("never"));
+        assert!(std::fs::read_to_string(&log).unwrap().contains("Stalled"));
+    }
+}
@@ -0,0 +1,170 @@
+//! `--snapshot`: content hashes of the project tree, the change tracker for projects
+//! without a git repository.
+
+use anyhow::{Context, Result};
+use ignore::WalkBuilder;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Directories never tracked: ralph's own state and VCS metadata.
+const SKIPPED_DIRS: [&str; 2] = [".ralph", ".git"];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    files: BTreeMap<PathBuf, [u8; 32]>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Changes {
+    pub added: Vec<PathBuf>,
+    pub modified: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+}
+
+impl Snapshot {
+    /// Hashes every file under `root`, honouring `.gitignore`/`.ignore` files even outside a repository.
+    pub fn take(root: &Path) -> Result<Self> {
+        let mut files = BTreeMap::new();
+        let walker = WalkBuilder::new(root)
+            .hidden(false)
+            .require_git(false)
+            .filter_entry(|e| !SKIPPED_DIRS.iter().any(|d| e.file_name() == *d))
+            .build();
+        for entry in walker {
+            let entry = entry.context("Failed to walk project tree")?;
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            let content = std::fs::read(entry.path())
+                .with_context(|| format!("Failed to read {}", entry.path().display()))?;
+            let rel = entry.path().strip_prefix(root).unwrap_or(entry.path());
+            files.insert(rel.to_path_buf(), Sha256::digest(&content).into());
+        }
+        Ok(Self { files })
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// What changed between `self` (before) and `after`, each list in path order.
+    #[must_use]
+    pub fn diff(&self, after: &Snapshot) -> Changes {
+        let mut changes = Changes::default();
+        for (path, hash) in &after.files {
+            match self.files.get(path) {
+                None => changes.added.push(path.clone()),
+                Some(old) if old != hash => changes.modified.push(path.clone()),
+                Some(_) => {}
+            }
+        }
+        changes.removed = self
+            .files
+            .keys()
+            .filter(|p| !after.files.contains_key(*p))
+            .cloned()
+            .collect();
+        changes
+    }
+}
+
+impl Changes {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.modified.is_empty() && self.removed.is_empty()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.added.len() + self.modified.len() + self.removed.len()
+    }
+
+    /// Every added, modified, and removed path.
+    pub fn paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.added.iter().chain(&self.modified).chain(&self.removed)
+    }
+
+    /// `A`/`M`/`D`-prefixed lines like `git status --short`, at most `limit` of them.
+    #[must_use]
+    pub fn report(&self, limit: usize) -> Vec<String> {
+        let tagged = self
+            .added
+            .iter()
+            .map(|p| ('A', p))
+            .chain(self.modified.iter().map(|p| ('M', p)))
+            .chain(self.removed.iter().map(|p| ('D', p)));
+        let mut lines: Vec<String> = tagged
+            .take(limit)
+            .map(|(tag, p)| format!("{tag} {}", p.display()))
+            .collect();
+        if self.len() > limit {
+            lines.push(format!("... and {} more", self.len() - limit));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn tree() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+        std::fs::write(dir.path().join("README.md"), "readme").unwrap();
+        dir
+    }
+
+    #[test]
+    fn unchanged_tree_has_no_changes() {
+        let dir = tree();
+        let before = Snapshot::take(dir.path()).unwrap();
+        assert_eq!(before.len(), 2);
+        assert!(before.diff(&Snapshot::take(dir.path()).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn detects_added_modified_and_removed() {
+        let dir = tree();
+        let before = Snapshot::take(dir.path()).unwrap();
+        std::fs::write(dir.path().join("src/main.rs"), "fn main() { todo!() }").unwrap();
+        std::fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+        std::fs::remove_file(dir.path().join("README.md")).unwrap();
+
+        let changes = before.diff(&Snapshot::take(dir.path()).unwrap());
+        assert_eq!(changes.added, vec![PathBuf::from("src/lib.rs")]);
+        assert_eq!(changes.modified, vec![PathBuf::from("src/main.rs")]);
+        assert_eq!(changes.removed, vec![PathBuf::from("README.md")]);
+        assert_eq!(changes.report(10), vec!["A src/lib.rs", "M src/main.rs", "D README.md"]);
+    }
+
+    #[test]
+    fn skips_ralph_dir_and_ignored_files() {
+        let dir = tree();
+        std::fs::write(dir.path().join(".gitignore"), "target/\n").unwrap();
+        let before = Snapshot::take(dir.path()).unwrap();
+        std::fs::create_dir_all(dir.path().join(".ralph/logs")).unwrap();
+        std::fs::write(dir.path().join(".ralph/logs/1.log"), "log").unwrap();
+        std::fs::create_dir(dir.path().join("target")).unwrap();
+        std::fs::write(dir.path().join("target/out"), "bin").unwrap();
+        assert!(before.diff(&Snapshot::take(dir.path()).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn report_is_capped() {
+        let changes = Changes {
+            added: vec!["a".into(), "b".into(), "c".into()],
+            ..Changes::default()
+        };
+        assert_eq!(changes.report(2), vec!["A a", "A b", "... and 1 more"]);
+    }
+}
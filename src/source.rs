@@ -0,0 +1,333 @@
+//! Issue trackers as PRD sources (`--prd-source`): at the start of a run the tracker's
+//! tickets become features, and completed features are reported back. Adapters live in
+//! [`crate::github`], [`crate::jira`], and [`crate::linear`].
+
+use crate::{
+    atomic,
+    events::{EventSink, RunEvent},
+    git,
+    github::GithubSource,
+    jira::JiraSource,
+    linear::LinearSource,
+    markdown, output,
+    prd::{self, Feature, Prd, Status},
+    validation, webhook,
+};
+use anyhow::{bail, Context, Result};
+use futures::future::BoxFuture;
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+
+/// The tracker completed features are reported to, set by [`configure`].
+static SOURCE: RwLock<Option<Arc<dyn PrdSource>>> = RwLock::new(None);
+
+/// A tracker ralph reads features from and reports completions to.
+pub trait PrdSource: fmt::Display + Send + Sync {
+    /// Starts the ids of features made from this tracker's tickets, e.g. `issue-`; the
+    /// rest of the id is the ticket's key.
+    fn id_prefix(&self) -> &'static str;
+
+    /// Name for a PRD created from the tracker.
+    fn project_name(&self) -> String;
+
+    /// Labels that select or mark tickets, so they aren't used as categories.
+    fn filter_labels(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
+    /// The tickets to work on, oldest first.
+    fn fetch(&self) -> BoxFuture<'_, Result<Vec<Ticket>>>;
+
+    /// Marks the ticket `key` done on the tracker, with `comment`.
+    fn complete<'a>(&'a self, key: &'a str, comment: &'a str) -> BoxFuture<'a, Result<()>>;
+}
+
+/// A tracker's issue, story, or task.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Ticket {
+    /// e.g. `12` on GitHub, `SHOP-12` on Jira
+    pub key: String,
+    pub title: String,
+    /// Markdown; task list items become steps
+    pub body: String,
+    pub url: String,
+    pub labels: Vec<String>,
+    /// The tracker says it's done
+    pub complete: bool,
+}
+
+/// `--prd-source` values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceSpec {
+    Github(GithubSource),
+    Jira(JiraSource),
+    Linear(LinearSource),
+}
+
+impl SourceSpec {
+    #[must_use]
+    pub fn adapter(&self) -> Arc<dyn PrdSource> {
+        match self {
+            Self::Github(source) => Arc::new(source.clone()),
+            Self::Jira(source) => Arc::new(source.clone()),
+            Self::Linear(source) => Arc::new(source.clone()),
+        }
+    }
+}
+
+impl FromStr for SourceSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once(':').map(|(kind, _)| kind) {
+            Some("github") => s.parse().map(Self::Github),
+            Some("jira") => s.parse().map(Self::Jira),
+            Some("linear") => s.parse().map(Self::Linear),
+            _ => bail!("expected github:<owner>/<repo>, jira:<host>?..., or linear:<team>?..."),
+        }
+    }
+}
+
+impl fmt::Display for SourceSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Github(source) => source.fmt(f),
+            Self::Jira(source) => source.fmt(f),
+            Self::Linear(source) => source.fmt(f),
+        }
+    }
+}
+
+impl Serialize for SourceSpec {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+/// `<kind>:<target>?<key>=<value>&...`, as the target and its options.
+pub(crate) fn parse_spec<'a>(s: &'a str, kind: &str, expected: &str) -> Result<(&'a str, Vec<(&'a str, &'a str)>)> {
+    let rest = s.strip_prefix(kind).and_then(|r| r.strip_prefix(':')).context(expected.to_string())?;
+    let (target, query) = rest.split_once('?').unwrap_or((rest, ""));
+    if target.is_empty() {
+        bail!("{expected}");
+    }
+    let mut options = Vec::new();
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        match pair.split_once('=') {
+            Some((key, value)) if !value.is_empty() => options.push((key, value)),
+            _ => bail!("invalid option '{pair}': {expected}"),
+        }
+    }
+    Ok((target, options))
+}
+
+/// Rewrites the PRD at `prd_path` from the tracker, creating it from the template if needed.
+/// Features that aren't from the tracker are kept, and so are the statuses of tickets
+/// already in the PRD, unless the tracker says they're done. A tracked PRD that changed is
+/// committed, since PRD validation only allows status changes. Returns the number of tickets.
+pub async fn sync(source: &dyn PrdSource, prd_path: &Path) -> Result<usize> {
+    let tickets = source.fetch().await?;
+    let existing = if prd_path.exists() { Some(Prd::load(prd_path)?) } else { None };
+    let prd = to_prd(source, existing, &tickets)?;
+    let json = serde_json::to_string_pretty(&prd)? + "\n";
+    if std::fs::read_to_string(prd_path).ok().as_deref() == Some(json.as_str()) {
+        return Ok(tickets.len());
+    }
+    atomic::write(prd_path, &json).with_context(|| format!("Failed to write PRD file: {}", prd_path.display()))?;
+
+    let dir = validation::prd_dir(prd_path);
+    let name = prd_path.file_name().unwrap_or_default().to_string_lossy();
+    if git::is_git_repo(dir) && git::show_file_from_head(dir, &name).ok().flatten().is_some() {
+        git::commit_file(dir, &name, &format!("Sync PRD from {source}"))?;
+    }
+    Ok(tickets.len())
+}
+
+fn to_prd(source: &dyn PrdSource, existing: Option<Prd>, tickets: &[Ticket]) -> Result<Prd> {
+    let mut prd = match existing {
+        Some(prd) => prd,
+        None => {
+            let mut prd = prd::template()?;
+            prd.project.name = source.project_name();
+            prd.project.description = format!("Work items from {source}");
+            prd.features.clear();
+            prd
+        }
+    };
+    let old = std::mem::take(&mut prd.features);
+    let (from_tracker, mut features): (Vec<Feature>, Vec<Feature>) =
+        old.into_iter().partition(|f| f.id.starts_with(source.id_prefix()));
+    for ticket in tickets {
+        let mut feature = feature(source, ticket);
+        if let Some(known) = from_tracker.iter().find(|f| f.id == feature.id) {
+            if !ticket.complete {
+                feature.status = known.status;
+            }
+        }
+        features.push(feature);
+    }
+    prd.features = features;
+    Ok(prd)
+}
+
+/// Title → description, task list → steps, first other label → category, the link and the
+/// rest of the body → notes.
+fn feature(source: &dyn PrdSource, ticket: &Ticket) -> Feature {
+    let mut steps = Vec::new();
+    let mut notes = Vec::new();
+    for line in ticket.body.lines() {
+        match markdown::checklist_item(line) {
+            Some(step) => steps.push(step.to_string()),
+            None => notes.push(line.trim_end()),
+        }
+    }
+    let notes = notes.join("\n");
+    let notes = notes.trim();
+    let filter_labels = source.filter_labels();
+    let category = ticket
+        .labels
+        .iter()
+        .map(String::as_str)
+        .find(|name| !filter_labels.contains(name))
+        .unwrap_or("functional");
+    Feature {
+        id: format!("{}{}", source.id_prefix(), ticket.key),
+        category: category.to_string(),
+        description: ticket.title.trim().to_string(),
+        steps,
+        status: if ticket.complete { Status::Complete } else { Status::Pending },
+        notes: Some(if notes.is_empty() { ticket.url.clone() } else { format!("{}\n\n{notes}", ticket.url) }),
+        ..Default::default()
+    }
+}
+
+/// Sets the tracker completed features are reported to, or none.
+pub fn configure(source: Option<Arc<dyn PrdSource>>) {
+    *SOURCE.write().unwrap_or_else(|e| e.into_inner()) = source;
+}
+
+/// Reports features that become complete to their tickets, in the background.
+pub struct SourceSink;
+
+impl EventSink for SourceSink {
+    fn handle(&self, event: &RunEvent) {
+        let RunEvent::FeatureStatusChanged { iteration, feature, status: Status::Complete } = event else {
+            return;
+        };
+        let Some(source) = SOURCE.read().unwrap_or_else(|e| e.into_inner()).clone() else {
+            return;
+        };
+        let Some(key) = feature.strip_prefix(source.id_prefix()).map(String::from) else {
+            return;
+        };
+        let mut comment = format!("Completed by ralph in iteration {iteration}");
+        if let Some(run_id) = crate::run_id::current() {
+            comment.push_str(&format!(" of run `{run_id}`"));
+        }
+        comment.push('.');
+        webhook::track(tokio::spawn(async move {
+            match source.complete(&key, &comment).await {
+                Ok(()) => output::dim(&format!("Marked {key} complete on {source}")),
+                Err(e) => output::warn(&format!("Failed to update {key} on {source}: {e:#}")),
+            }
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fake;
+
+    impl fmt::Display for Fake {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("fake:board")
+        }
+    }
+
+    impl PrdSource for Fake {
+        fn id_prefix(&self) -> &'static str {
+            "fake-"
+        }
+
+        fn project_name(&self) -> String {
+            "board".into()
+        }
+
+        fn filter_labels(&self) -> Vec<&str> {
+            vec!["ralph"]
+        }
+
+        fn fetch(&self) -> BoxFuture<'_, Result<Vec<Ticket>>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+
+        fn complete<'a>(&'a self, _key: &'a str, _comment: &'a str) -> BoxFuture<'a, Result<()>> {
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    fn ticket(key: &str, labels: &[&str], complete: bool) -> Ticket {
+        Ticket {
+            key: key.into(),
+            title: format!("Ticket {key}"),
+            url: format!("https://tracker/{key}"),
+            labels: labels.iter().map(|l| (*l).to_string()).collect(),
+            complete,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn tickets_become_features() {
+        let body = "Let people log in.\n\n- [ ] Add a form\n- [x] Hash passwords\n";
+        let tickets = [Ticket { body: body.into(), ..ticket("7", &["ralph", "ui"], false) }];
+        let prd = to_prd(&Fake, None, &tickets).unwrap();
+        assert_eq!((prd.project.name.as_str(), prd.project.description.as_str()), ("board", "Work items from fake:board"));
+        let feature = &prd.features[0];
+        assert_eq!((feature.id.as_str(), feature.description.as_str()), ("fake-7", "Ticket 7"));
+        assert_eq!(feature.category, "ui");
+        assert_eq!(feature.steps, ["Add a form", "Hash passwords"]);
+        assert_eq!(feature.notes.as_deref(), Some("https://tracker/7\n\nLet people log in."));
+        assert_eq!(feature.status, Status::Pending);
+    }
+
+    #[test]
+    fn keeps_local_features_and_statuses() {
+        let mut existing = to_prd(&Fake, None, &[ticket("1", &[], false), ticket("2", &[], false)]).unwrap();
+        existing.features[0].status = Status::InProgress;
+        existing.features[1].status = Status::Blocked;
+        existing.features.push(Feature { id: "local".into(), ..Default::default() });
+
+        let tickets = [ticket("1", &[], false), ticket("2", &[], true), ticket("3", &[], false)];
+        let prd = to_prd(&Fake, Some(existing), &tickets).unwrap();
+        let features: Vec<_> = prd.features.iter().map(|f| (f.id.as_str(), f.status)).collect();
+        assert_eq!(
+            features,
+            [
+                ("local", Status::Pending),
+                ("fake-1", Status::InProgress),
+                ("fake-2", Status::Complete),
+                ("fake-3", Status::Pending),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_specs() {
+        for spec in ["github:acme/shop?label=ralph", "jira:acme.atlassian.net?epic=SHOP-1", "linear:ENG?cycle=current"] {
+            assert_eq!(spec.parse::<SourceSpec>().unwrap().to_string(), spec);
+        }
+        assert!("gitlab:acme/shop".parse::<SourceSpec>().is_err());
+        assert_eq!(
+            parse_spec("jira:host?a=1&b=2", "jira", "x").unwrap(),
+            ("host", vec![("a", "1"), ("b", "2")])
+        );
+        assert!(parse_spec("jira:host?a", "jira", "x").is_err());
+        assert!(parse_spec("jira:?a=1", "jira", "x").is_err());
+    }
+}
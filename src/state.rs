@@ -0,0 +1,134 @@
+//! Loop state persisted to `.ralph/state.json` after each iteration, restored by `--resume`.
+
+use crate::atomic;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LoopState {
+    /// Id of the run, reused by `--resume` unless `--run-id` is given.
+    #[serde(default)]
+    pub run_id: Option<String>,
+    /// Last iteration that finished.
+    pub iteration: u32,
+    pub consecutive_failures: u32,
+    /// Iteration errors per feature id, for `--max-iteration-errors`.
+    #[serde(default)]
+    pub feature_errors: HashMap<String, u32>,
+    /// Iterations spent per feature id, for the PRD's `maxIterations`.
+    #[serde(default)]
+    pub feature_iterations: HashMap<String, u32>,
+    /// RFC 3339 start of the original run.
+    pub started_at: String,
+    /// Phases whose milestone was already reported.
+    #[serde(default)]
+    pub milestones: Vec<String>,
+    /// Agent cost recorded so far, for `--max-cost`.
+    #[serde(default)]
+    pub cost_usd: f64,
+}
+
+impl LoopState {
+    /// Time since the original run started; zero if `started_at` is unparseable or in the future.
+    #[must_use]
+    pub fn elapsed(&self) -> std::time::Duration {
+        chrono::DateTime::parse_from_rfc3339(&self.started_at)
+            .ok()
+            .and_then(|t| (chrono::Local::now().fixed_offset() - t).to_std().ok())
+            .unwrap_or_default()
+    }
+}
+
+#[must_use]
+pub fn path(ralph_dir: &Path) -> PathBuf {
+    ralph_dir.join("state.json")
+}
+
+pub fn save(ralph_dir: &Path, state: &LoopState) -> Result<()> {
+    let path = path(ralph_dir);
+    atomic::write(&path, serde_json::to_string_pretty(state)? + "\n")
+        .with_context(|| format!("Failed to write loop state: {}", path.display()))
+}
+
+/// Loads the saved state, or `None` if no run has saved one.
+pub fn load(ralph_dir: &Path) -> Result<Option<LoopState>> {
+    let path = path(ralph_dir);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read loop state: {}", path.display()))?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .with_context(|| format!("Failed to parse loop state: {}", path.display()))
+}
+
+/// Removes the saved state once a run has finished.
+pub fn clear(ralph_dir: &Path) -> Result<()> {
+    match std::fs::remove_file(path(ralph_dir)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(e).context("Failed to remove loop state")
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn state() -> LoopState {
+        LoopState {
+            run_id: Some("20260101-100000-4f2a".into()),
+            iteration: 4,
+            consecutive_failures: 2,
+            feature_errors: HashMap::from([("feat-1".to_string(), 2)]),
+            feature_iterations: HashMap::from([("feat-1".to_string(), 5)]),
+            started_at: "2026-01-01T10:00:00+00:00".into(),
+            milestones: vec!["MVP".into()],
+            cost_usd: 1.25,
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let dir = TempDir::new().unwrap();
+        save(dir.path(), &state()).unwrap();
+        assert_eq!(load(dir.path()).unwrap(), Some(state()));
+    }
+
+    #[test]
+    fn missing_state_is_none() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(load(dir.path()).unwrap(), None);
+    }
+
+    #[test]
+    fn corrupt_state_is_an_error() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(path(dir.path()), "{ not json").unwrap();
+        assert!(load(dir.path()).is_err());
+    }
+
+    #[test]
+    fn clear_removes_state_and_tolerates_missing() {
+        let dir = TempDir::new().unwrap();
+        save(dir.path(), &state()).unwrap();
+        clear(dir.path()).unwrap();
+        assert!(!path(dir.path()).exists());
+        clear(dir.path()).unwrap();
+    }
+
+    #[test]
+    fn elapsed_since_original_start() {
+        let mut s = state();
+        s.started_at = (chrono::Local::now() - chrono::Duration::seconds(90)).to_rfc3339();
+        assert!(s.elapsed().as_secs() >= 90);
+        s.started_at = "garbage".into();
+        assert_eq!(s.elapsed(), std::time::Duration::ZERO);
+    }
+}
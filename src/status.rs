@@ -0,0 +1,147 @@
+//! `ralph status`: a read-only overview of where the project stands, like the dry-run
+//! summary without running verification commands.
+
+use crate::{
+    artifact::IterationArtifact,
+    config::Args,
+    git::{self, GitStatus},
+    output,
+    prd::{Prd, Status, StatusCounts},
+    progress, validation,
+};
+use anyhow::Result;
+use serde_json::json;
+
+#[derive(Debug)]
+pub struct Overview {
+    pub project: String,
+    pub total: usize,
+    pub counts: StatusCounts,
+    /// Ids and descriptions of the features being worked on
+    pub in_progress: Vec<(String, String)>,
+    pub last_progress: Option<String>,
+    pub last_iteration: Option<IterationArtifact>,
+    pub git: Option<GitStatus>,
+}
+
+/// Gathers the overview for the PRD at `args.prd`.
+pub fn collect(args: &Args) -> Result<Overview> {
+    let prd = Prd::load(&args.prd)?;
+    let prd_dir = validation::prd_dir(&args.prd);
+    let project_dir = prd.project_dir(&args.prd, args.project_dir.as_deref());
+    let progress = std::fs::read_to_string(prd.progress_path(&args.prd, args.progress_path.as_deref()))
+        .unwrap_or_default();
+    Ok(Overview {
+        project: prd.project.name.clone(),
+        total: prd.features.len(),
+        counts: prd.status_counts(),
+        in_progress: prd
+            .features
+            .iter()
+            .filter(|f| f.status == Status::InProgress)
+            .map(|f| (f.id.clone(), f.description.clone()))
+            .collect(),
+        last_progress: progress::last_entry(&progress).map(str::to_string),
+        last_iteration: IterationArtifact::load_all(&prd_dir.join(".ralph").join("logs")).pop(),
+        git: git::get_git_status(&project_dir),
+    })
+}
+
+pub fn run(args: &Args) -> Result<()> {
+    let overview = collect(args)?;
+    let c = &overview.counts;
+    output::event(
+        "status",
+        json!({
+            "project": overview.project,
+            "features": overview.total,
+            "pending": c.pending,
+            "inProgress": overview.in_progress.iter().map(|(id, _)| id).collect::<Vec<_>>(),
+            "complete": c.complete,
+            "blocked": c.blocked,
+            "lastIteration": overview.last_iteration.as_ref().map(|a| &a.started_at),
+            "branch": overview.git.as_ref().map(|g| &g.branch),
+        }),
+    );
+
+    output::header(&format!("Status: {}", overview.project));
+    output::log(&format!(
+        "Features: {} ({} complete, {} in-progress, {} pending, {} blocked)",
+        overview.total, c.complete, c.in_progress, c.pending, c.blocked
+    ));
+    if overview.in_progress.is_empty() {
+        output::log("In progress: none");
+    }
+    for (id, description) in &overview.in_progress {
+        output::log(&format!("In progress: {id} - {description}"));
+    }
+    match &overview.last_iteration {
+        Some(a) => output::log(&format!(
+            "Last iteration: {} (run {}, iteration {}, {})",
+            a.started_at,
+            a.run_id.as_deref().unwrap_or("-"),
+            a.iteration,
+            a.result
+        )),
+        None => output::log("Last iteration: none"),
+    }
+    match &overview.git {
+        Some(g) => output::log(&format!("Git branch: {} ({} uncommitted changes)", g.branch, g.uncommitted_changes)),
+        None => output::log("Git branch: not a git repository"),
+    }
+    match &overview.last_progress {
+        Some(entry) => {
+            output::log("Last progress entry:");
+            for line in entry.lines() {
+                output::dim(&format!("  {line}"));
+            }
+        }
+        None => output::log("Last progress entry: none"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ArtifactBuilder, FeatureBuilder, PrdBuilder};
+    use clap::Parser;
+
+    #[test]
+    fn collects_counts_progress_and_last_iteration() {
+        let dir = tempfile::tempdir().unwrap();
+        let prd_path = dir.path().join("prd.jsonc");
+        PrdBuilder::new()
+            .feature(FeatureBuilder::new("a").status(Status::Complete))
+            .feature(FeatureBuilder::new("b").status(Status::InProgress))
+            .feature(FeatureBuilder::new("c"))
+            .write(&prd_path)
+            .unwrap();
+        std::fs::write(dir.path().join("progress.txt"), "## Iteration 1\n\nDid a\n\n## Iteration 2\n\nStarted b\n")
+            .unwrap();
+        let logs_dir = dir.path().join(".ralph/logs");
+        ArtifactBuilder::new(1).started_at("2026-01-01T10:00:00+00:00").in_dir(&logs_dir).write().unwrap();
+        ArtifactBuilder::new(2).started_at("2026-01-01T10:30:00+00:00").in_dir(&logs_dir).write().unwrap();
+
+        let args = Args::try_parse_from(["ralph", "--prd", prd_path.to_str().unwrap(), "status"]).unwrap();
+        let overview = collect(&args).unwrap();
+        assert_eq!((overview.total, overview.counts.complete, overview.counts.pending), (3, 1, 1));
+        assert_eq!(overview.in_progress, vec![("b".to_string(), "Implement b".to_string())]);
+        assert_eq!(overview.last_progress.as_deref(), Some("## Iteration 2\n\nStarted b"));
+        assert_eq!(overview.last_iteration.unwrap().iteration, 2);
+        run(&args).unwrap();
+    }
+
+    #[test]
+    fn fresh_project_has_no_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let prd_path = dir.path().join("prd.jsonc");
+        PrdBuilder::new().feature(FeatureBuilder::new("a")).write(&prd_path).unwrap();
+
+        let args = Args::try_parse_from(["ralph", "--prd", prd_path.to_str().unwrap()]).unwrap();
+        let overview = collect(&args).unwrap();
+        assert!(overview.in_progress.is_empty());
+        assert!(overview.last_progress.is_none());
+        assert!(overview.last_iteration.is_none());
+    }
+}
@@ -0,0 +1,84 @@
+//! Operator kill switch: creating `.ralph/STOP` ends the run, cancelling the agent if an
+//! iteration is in progress. For when Ctrl-C can't reach the terminal ralph runs in.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tokio::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Process exit code for a run ended by the stop file.
+pub const EXIT_CODE: i32 = 3;
+
+/// Returned by the runner when the stop file ended the run.
+#[derive(Debug)]
+pub struct OperatorStop;
+
+impl std::fmt::Display for OperatorStop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Stopped by operator (.ralph/STOP)")
+    }
+}
+
+impl std::error::Error for OperatorStop {}
+
+#[must_use]
+pub fn path(ralph_dir: &Path) -> PathBuf {
+    ralph_dir.join("STOP")
+}
+
+#[must_use]
+pub fn requested(ralph_dir: &Path) -> bool {
+    path(ralph_dir).exists()
+}
+
+/// Removes the stop file so the next run isn't stopped straight away.
+pub fn clear(ralph_dir: &Path) -> Result<()> {
+    match std::fs::remove_file(path(ralph_dir)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e).context("Failed to remove stop file"),
+        _ => Ok(()),
+    }
+}
+
+/// Resolves once the stop file exists.
+pub async fn wait(ralph_dir: &Path) {
+    while !requested(ralph_dir) {
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Cancels `token` once the stop file exists. Spawn it alongside an iteration.
+pub async fn cancel_on_stop(ralph_dir: PathBuf, token: CancellationToken) {
+    tokio::select! {
+        () = wait(&ralph_dir) => token.cancel(),
+        () = token.cancelled() => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clear_removes_file_and_tolerates_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(path(dir.path()), "").unwrap();
+        assert!(requested(dir.path()));
+        clear(dir.path()).unwrap();
+        assert!(!requested(dir.path()));
+        clear(dir.path()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn cancels_token_when_file_appears() {
+        let dir = tempfile::tempdir().unwrap();
+        let token = CancellationToken::new();
+        let watcher = tokio::spawn(cancel_on_stop(dir.path().to_path_buf(), token.clone()));
+        tokio::time::sleep(POLL_INTERVAL * 2).await;
+        assert!(!token.is_cancelled());
+        std::fs::write(path(dir.path()), "").unwrap();
+        tokio::time::timeout(Duration::from_secs(5), watcher).await.unwrap().unwrap();
+        assert!(token.is_cancelled());
+    }
+}
@@ -0,0 +1,213 @@
+//! Events from `claude --print --output-format stream-json`: one JSON object per line for
+//! each assistant message, tool result, and the final result, turned into readable log lines.
+
+use crate::metrics::{self, ResultEvent};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Longest tool input shown after the tool name in logs.
+const MAX_SUMMARY_CHARS: usize = 120;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamEvent {
+    /// Session start
+    Init { model: Option<String> },
+    /// An assistant message: its text and the tools it called
+    Assistant { text: Vec<String>, tool_uses: Vec<ToolUse> },
+    /// Results of tool calls, sent back to the model
+    ToolResults(Vec<ToolResult>),
+    Result(ResultEvent),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolUse {
+    pub name: String,
+    /// The most telling input field, e.g. the command or file path
+    pub summary: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolResult {
+    pub is_error: bool,
+    /// First line of the tool's output
+    pub first_line: String,
+}
+
+#[derive(Deserialize)]
+struct RawEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    message: Option<RawMessage>,
+}
+
+#[derive(Deserialize)]
+struct RawMessage {
+    #[serde(default)]
+    content: Vec<RawBlock>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum RawBlock {
+    Text {
+        text: String,
+    },
+    ToolUse {
+        name: String,
+        #[serde(default)]
+        input: Value,
+    },
+    ToolResult {
+        #[serde(default)]
+        content: Value,
+        #[serde(default)]
+        is_error: bool,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// Parses a line of stream-json output; None for lines that aren't events (plain text, or
+/// event types ralph doesn't use).
+#[must_use]
+pub fn parse_line(line: &str) -> Option<StreamEvent> {
+    if let Some(result) = metrics::parse_result_line(line) {
+        return Some(StreamEvent::Result(result));
+    }
+    let line = line.trim();
+    if !line.starts_with('{') {
+        return None;
+    }
+    let raw: RawEvent = serde_json::from_str(line).ok()?;
+    let blocks = raw.message.map(|m| m.content).unwrap_or_default();
+    match raw.kind.as_str() {
+        "system" => Some(StreamEvent::Init { model: raw.model }),
+        "assistant" => {
+            let mut text = Vec::new();
+            let mut tool_uses = Vec::new();
+            for block in blocks {
+                match block {
+                    RawBlock::Text { text: t } => text.push(t),
+                    RawBlock::ToolUse { name, input } => tool_uses.push(ToolUse { summary: summarize(&input), name }),
+                    RawBlock::ToolResult { .. } | RawBlock::Other => {}
+                }
+            }
+            Some(StreamEvent::Assistant { text, tool_uses })
+        }
+        "user" => Some(StreamEvent::ToolResults(
+            blocks
+                .into_iter()
+                .filter_map(|block| match block {
+                    RawBlock::ToolResult { content, is_error } => {
+                        Some(ToolResult { is_error, first_line: first_line(&content) })
+                    }
+                    _ => None,
+                })
+                .collect(),
+        )),
+        _ => None,
+    }
+}
+
+/// The input field that says what a tool call does, cut to [`MAX_SUMMARY_CHARS`].
+fn summarize(input: &Value) -> String {
+    let field = ["command", "file_path", "path", "pattern", "url", "description"]
+        .iter()
+        .find_map(|key| input.get(key).and_then(Value::as_str));
+    let summary = field.unwrap_or_default().lines().next().unwrap_or_default();
+    match summary.char_indices().nth(MAX_SUMMARY_CHARS) {
+        Some((cut, _)) => format!("{}...", &summary[..cut]),
+        None => summary.to_string(),
+    }
+}
+
+/// Tool results are a string or a list of text blocks.
+fn first_line(content: &Value) -> String {
+    let text = match content {
+        Value::String(s) => s.as_str(),
+        Value::Array(blocks) => blocks.iter().find_map(|b| b.get("text").and_then(Value::as_str)).unwrap_or_default(),
+        _ => "",
+    };
+    text.lines().next().unwrap_or_default().to_string()
+}
+
+impl StreamEvent {
+    /// Lines for the terminal and log. Result text repeats the last assistant message, so
+    /// it is only shown when `seen_text` is false.
+    #[must_use]
+    pub fn log_lines(&self, seen_text: bool) -> Vec<String> {
+        match self {
+            Self::Init { model } => model.iter().map(|m| format!("[session] model {m}")).collect(),
+            Self::Assistant { text, tool_uses } => text
+                .iter()
+                .flat_map(|t| t.lines().map(String::from))
+                .chain(tool_uses.iter().map(|t| match t.summary.as_str() {
+                    "" => format!("[tool] {}", t.name),
+                    summary => format!("[tool] {}: {summary}", t.name),
+                }))
+                .collect(),
+            Self::ToolResults(results) => results
+                .iter()
+                .filter(|r| r.is_error)
+                .map(|r| format!("[tool error] {}", r.first_line))
+                .collect(),
+            Self::Result(result) if !seen_text => result.result.lines().map(String::from).collect(),
+            Self::Result(_) => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_assistant_text_and_tool_calls() {
+        let line = r#"{"type":"assistant","message":{"content":[{"type":"text","text":"Running tests\nnow"},{"type":"tool_use","id":"t1","name":"Bash","input":{"command":"cargo test","description":"Run tests"}}]}}"#;
+        let event = parse_line(line).unwrap();
+        assert_eq!(
+            event,
+            StreamEvent::Assistant {
+                text: vec!["Running tests\nnow".into()],
+                tool_uses: vec![ToolUse { name: "Bash".into(), summary: "cargo test".into() }],
+            }
+        );
+        assert_eq!(event.log_lines(false), ["Running tests", "now", "[tool] Bash: cargo test"]);
+    }
+
+    #[test]
+    fn tool_errors_are_logged() {
+        let line = r#"{"type":"user","message":{"content":[{"type":"tool_result","tool_use_id":"t1","content":"error: no such file\nmore","is_error":true},{"type":"tool_result","content":[{"type":"text","text":"ok"}]}]}}"#;
+        let event = parse_line(line).unwrap();
+        assert_eq!(event.log_lines(false), ["[tool error] error: no such file"]);
+        let StreamEvent::ToolResults(results) = event else { panic!("not tool results") };
+        assert_eq!(results[1], ToolResult { is_error: false, first_line: "ok".into() });
+    }
+
+    #[test]
+    fn result_text_only_when_nothing_was_shown() {
+        let line = r#"{"type":"result","subtype":"success","is_error":false,"result":"Done","total_cost_usd":0.2}"#;
+        let event = parse_line(line).unwrap();
+        assert!(matches!(&event, StreamEvent::Result(r) if r.usage.cost_usd == 0.2));
+        assert_eq!(event.log_lines(false), ["Done"]);
+        assert!(event.log_lines(true).is_empty());
+    }
+
+    #[test]
+    fn init_and_unknown_lines() {
+        let init = parse_line(r#"{"type":"system","subtype":"init","model":"claude-sonnet","tools":[]}"#).unwrap();
+        assert_eq!(init.log_lines(false), ["[session] model claude-sonnet"]);
+        assert!(parse_line(r#"{"type":"stream_event"}"#).is_none());
+        assert!(parse_line("plain text").is_none());
+    }
+
+    #[test]
+    fn long_summaries_are_cut() {
+        let input = serde_json::json!({ "command": "x".repeat(200) });
+        assert_eq!(summarize(&input).chars().count(), MAX_SUMMARY_CHARS + 3);
+        assert_eq!(summarize(&serde_json::json!({})), "");
+    }
+}
@@ -0,0 +1,95 @@
+//! OpenTelemetry export (`--otel-endpoint`): iterations, agent calls, and verification
+//! commands are `tracing` spans, sent over OTLP/HTTP to a collector when an endpoint is set.
+//! Without one no subscriber is installed and the spans cost next to nothing.
+
+use crate::{artifact::IterationArtifact, logging, output};
+use anyhow::{Context, Result};
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use std::sync::OnceLock;
+use tracing::{field, Span};
+
+const SERVICE_NAME: &str = "ralph";
+const TRACES_PATH: &str = "/v1/traces";
+
+/// Installed by the first run in the process that sets an endpoint.
+static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+/// `http://host:4318` becomes `http://host:4318/v1/traces`; a full traces URL is kept.
+#[must_use]
+pub fn traces_url(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if endpoint.ends_with(TRACES_PATH) {
+        endpoint.to_string()
+    } else {
+        format!("{endpoint}{TRACES_PATH}")
+    }
+}
+
+/// Starts exporting spans to `endpoint`. Only one exporter can be added to the subscriber,
+/// so later runs in the same process keep the first endpoint.
+pub fn configure(endpoint: Option<&str>) -> Result<()> {
+    let Some(endpoint) = endpoint else {
+        return Ok(());
+    };
+    if PROVIDER.get().is_some() {
+        return Ok(());
+    }
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(traces_url(endpoint))
+        .build()
+        .context("Failed to create the OTLP exporter")?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build())
+        .build();
+    logging::add_otel(tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME)))?;
+    let _ = PROVIDER.set(provider);
+    output::dim(&format!("Exporting traces to {}", traces_url(endpoint)));
+    Ok(())
+}
+
+/// Sends the spans still buffered; called when a run ends.
+pub fn flush() {
+    if let Some(provider) = PROVIDER.get() {
+        if let Err(e) = provider.force_flush() {
+            output::warn(&format!("Failed to export traces: {e}"));
+        }
+    }
+}
+
+/// Span for one iteration; [`record_iteration`] adds the outcome once it is known.
+#[must_use]
+pub fn iteration_span(iteration: u32, feature_id: Option<&str>) -> Span {
+    tracing::info_span!(
+        "iteration",
+        iteration,
+        feature.id = feature_id,
+        result = field::Empty,
+        duration_secs = field::Empty,
+        cost_usd = field::Empty,
+    )
+}
+
+pub fn record_iteration(span: &Span, artifact: &IterationArtifact) {
+    span.record("result", artifact.result.as_str());
+    span.record("duration_secs", artifact.duration_secs);
+    if let Some(cost) = artifact.cost_usd {
+        span.record("cost_usd", cost);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn appends_the_traces_path() {
+        assert_eq!(traces_url("http://localhost:4318"), "http://localhost:4318/v1/traces");
+        assert_eq!(traces_url("http://localhost:4318/"), "http://localhost:4318/v1/traces");
+        assert_eq!(traces_url("https://otel.example/v1/traces"), "https://otel.example/v1/traces");
+    }
+}
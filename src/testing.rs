@@ -0,0 +1,401 @@
+//! Fixture builders for tests against ralph's types: PRDs, features, and iteration
+//! artifacts with sensible defaults, so a test only states the fields it cares about.
+//!
+//! ```
+//! use ralph_rs::prd::Status;
+//! use ralph_rs::testing::{FeatureBuilder, PrdBuilder};
+//!
+//! let prd = PrdBuilder::new()
+//!     .command("test", "cargo test")
+//!     .feature(FeatureBuilder::new("api").status(Status::Complete))
+//!     .feature(FeatureBuilder::new("ui").depends_on("api"))
+//!     .build();
+//! assert_eq!(prd.ready_features()[0].id, "ui");
+//! ```
+
+use crate::{
+    artifact::IterationArtifact,
+    atomic,
+    prd::{Completion, Feature, Prd, Project, Status, Verification, VerifyCommand},
+    resources::ResourceUsage,
+};
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_MARKER: &str = "<promise>COMPLETE</promise>";
+
+/// Builds a [`Prd`]: project "test", no verification commands, no features, and
+/// [`DEFAULT_MARKER`] as the completion marker.
+#[derive(Debug)]
+pub struct PrdBuilder {
+    prd: Prd,
+}
+
+impl Default for PrdBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PrdBuilder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            prd: Prd {
+                project: Project {
+                    name: "test".into(),
+                    description: "Test project".into(),
+                    repository: None,
+                    progress_path: None,
+                    category_prompts: Default::default(),
+                    root: None,
+                    env: Default::default(),
+                    env_file: None,
+                    protected_paths: Vec::new(),
+                },
+                verification: Verification {
+                    commands: Vec::new(),
+                    run_after_each_feature: true,
+                    coverage: None,
+                },
+                features: Vec::new(),
+                completion: Completion {
+                    all_features_complete: true,
+                    all_verifications_passing: true,
+                    marker: DEFAULT_MARKER.into(),
+                    phase_markers: Default::default(),
+                },
+                analysis: None,
+            },
+        }
+    }
+
+    #[must_use]
+    pub fn name(mut self, name: &str) -> Self {
+        self.prd.project.name = name.into();
+        self
+    }
+
+    #[must_use]
+    pub fn progress_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.prd.project.progress_path = Some(path.into());
+        self
+    }
+
+    /// Adds a global verification command.
+    #[must_use]
+    pub fn command(mut self, name: &str, command: &str) -> Self {
+        self.prd.verification.commands.push(verify_command(name, command));
+        self
+    }
+
+    #[must_use]
+    pub fn run_after_each_feature(mut self, run: bool) -> Self {
+        self.prd.verification.run_after_each_feature = run;
+        self
+    }
+
+    #[must_use]
+    pub fn feature(mut self, feature: impl Into<Feature>) -> Self {
+        self.prd.features.push(feature.into());
+        self
+    }
+
+    #[must_use]
+    pub fn features<F: Into<Feature>>(mut self, features: impl IntoIterator<Item = F>) -> Self {
+        self.prd.features.extend(features.into_iter().map(Into::into));
+        self
+    }
+
+    #[must_use]
+    pub fn marker(mut self, marker: &str) -> Self {
+        self.prd.completion.marker = marker.into();
+        self
+    }
+
+    #[must_use]
+    pub fn phase_marker(mut self, phase: &str, marker: &str) -> Self {
+        self.prd.completion.phase_markers.insert(phase.into(), marker.into());
+        self
+    }
+
+    #[must_use]
+    pub fn build(self) -> Prd {
+        self.prd
+    }
+
+    /// Writes the PRD as pretty JSON, which [`Prd::load`] reads back.
+    pub fn write(self, path: &Path) -> Result<Prd> {
+        atomic::write(path, serde_json::to_string_pretty(&self.prd)? + "\n")?;
+        Ok(self.prd)
+    }
+}
+
+/// Builds a pending [`Feature`] with category "functional" and a description naming its id.
+#[derive(Debug)]
+pub struct FeatureBuilder {
+    feature: Feature,
+}
+
+impl FeatureBuilder {
+    #[must_use]
+    pub fn new(id: &str) -> Self {
+        Self {
+            feature: Feature {
+                id: id.into(),
+                category: "functional".into(),
+                description: format!("Implement {id}"),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[must_use]
+    pub fn status(mut self, status: Status) -> Self {
+        self.feature.status = status;
+        self
+    }
+
+    #[must_use]
+    pub fn category(mut self, category: &str) -> Self {
+        self.feature.category = category.into();
+        self
+    }
+
+    #[must_use]
+    pub fn description(mut self, description: &str) -> Self {
+        self.feature.description = description.into();
+        self
+    }
+
+    #[must_use]
+    pub fn step(mut self, step: &str) -> Self {
+        self.feature.steps.push(step.into());
+        self
+    }
+
+    #[must_use]
+    pub fn depends_on(mut self, id: &str) -> Self {
+        self.feature.depends_on.push(id.into());
+        self
+    }
+
+    #[must_use]
+    pub fn phase(mut self, phase: &str) -> Self {
+        self.feature.phase = Some(phase.into());
+        self
+    }
+
+    #[must_use]
+    pub fn priority(mut self, priority: u32) -> Self {
+        self.feature.priority = Some(priority);
+        self
+    }
+
+    #[must_use]
+    pub fn max_iterations(mut self, iterations: u32) -> Self {
+        self.feature.max_iterations = Some(iterations);
+        self
+    }
+
+    #[must_use]
+    pub fn timeout_secs(mut self, secs: u64) -> Self {
+        self.feature.timeout_secs = Some(secs);
+        self
+    }
+
+    #[must_use]
+    pub fn estimate(mut self, iterations: u32) -> Self {
+        self.feature.estimate = Some(iterations);
+        self
+    }
+
+    #[must_use]
+    pub fn notes(mut self, notes: &str) -> Self {
+        self.feature.notes = Some(notes.into());
+        self
+    }
+
+    #[must_use]
+    pub fn context(mut self, glob: &str) -> Self {
+        self.feature.context.push(glob.into());
+        self
+    }
+
+    /// Adds a feature-specific verification command.
+    #[must_use]
+    pub fn command(mut self, name: &str, command: &str) -> Self {
+        self.feature.verification.push(verify_command(name, command));
+        self
+    }
+
+    #[must_use]
+    pub fn build(self) -> Feature {
+        self.feature
+    }
+}
+
+impl From<FeatureBuilder> for Feature {
+    fn from(builder: FeatureBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// Builds an [`IterationArtifact`] for a successful ("continue") one-minute iteration in
+/// `acceptEdits` mode, logged to `iteration-<n>.log`.
+#[derive(Debug)]
+pub struct ArtifactBuilder {
+    artifact: IterationArtifact,
+}
+
+impl ArtifactBuilder {
+    #[must_use]
+    pub fn new(iteration: u32) -> Self {
+        Self {
+            artifact: IterationArtifact {
+                iteration,
+                run_id: None,
+                started_at: "2026-01-01T00:00:00+00:00".into(),
+                duration_secs: 60,
+                feature_id: None,
+                permission_mode: "acceptEdits".into(),
+                escalation: None,
+                result: "continue".into(),
+                coverage: None,
+                cost_usd: None,
+                resources: None,
+                error: None,
+                log_path: PathBuf::from(format!("iteration-{iteration}.log")),
+            },
+        }
+    }
+
+    #[must_use]
+    pub fn feature(mut self, id: &str) -> Self {
+        self.artifact.feature_id = Some(id.into());
+        self
+    }
+
+    /// An `IterationResult` string such as "complete" or "failed".
+    #[must_use]
+    pub fn result(mut self, result: &str) -> Self {
+        self.artifact.result = result.into();
+        self
+    }
+
+    /// Sets the run id, and names the log `<run id>-iteration-<n>.log` as runs do.
+    #[must_use]
+    pub fn run_id(mut self, id: &str) -> Self {
+        let name = format!("{id}-iteration-{}.log", self.artifact.iteration);
+        self.artifact.log_path = self.artifact.log_path.with_file_name(name);
+        self.artifact.run_id = Some(id.into());
+        self
+    }
+
+    #[must_use]
+    pub fn started_at(mut self, rfc3339: &str) -> Self {
+        self.artifact.started_at = rfc3339.into();
+        self
+    }
+
+    #[must_use]
+    pub fn duration_secs(mut self, secs: u64) -> Self {
+        self.artifact.duration_secs = secs;
+        self
+    }
+
+    #[must_use]
+    pub fn permission_mode(mut self, mode: &str) -> Self {
+        self.artifact.permission_mode = mode.into();
+        self
+    }
+
+    #[must_use]
+    pub fn cost_usd(mut self, cost: f64) -> Self {
+        self.artifact.cost_usd = Some(cost);
+        self
+    }
+
+    #[must_use]
+    pub fn resources(mut self, resources: ResourceUsage) -> Self {
+        self.artifact.resources = Some(resources);
+        self
+    }
+
+    #[must_use]
+    pub fn coverage(mut self, percent: f64) -> Self {
+        self.artifact.coverage = Some(percent);
+        self
+    }
+
+    /// Marks the iteration as errored; the result becomes "error".
+    #[must_use]
+    pub fn error(mut self, message: &str) -> Self {
+        self.artifact.result = "error".into();
+        self.artifact.error = Some(message.into());
+        self
+    }
+
+    /// Puts the log, and so the artifact, in `logs_dir`.
+    #[must_use]
+    pub fn in_dir(mut self, logs_dir: &Path) -> Self {
+        let name = self.artifact.log_path.file_name().map(PathBuf::from).unwrap_or_default();
+        self.artifact.log_path = logs_dir.join(name);
+        self
+    }
+
+    #[must_use]
+    pub fn build(self) -> IterationArtifact {
+        self.artifact
+    }
+
+    /// Writes the artifact beside its log path, creating the directory.
+    pub fn write(self) -> Result<IterationArtifact> {
+        if let Some(dir) = self.artifact.log_path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        self.artifact.write()?;
+        Ok(self.artifact)
+    }
+}
+
+fn verify_command(name: &str, command: &str) -> VerifyCommand {
+    VerifyCommand {
+        name: name.into(),
+        command: command.into(),
+        description: String::new(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn written_prd_loads_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("prd.jsonc");
+        PrdBuilder::new()
+            .command("test", "cargo test")
+            .feature(FeatureBuilder::new("a").phase("MVP").estimate(2))
+            .phase_marker("MVP", "<promise>MVP</promise>")
+            .write(&path)
+            .unwrap();
+        let prd = Prd::load(&path).unwrap();
+        assert_eq!(prd.features[0].phase.as_deref(), Some("MVP"));
+        assert_eq!(prd.completion.phase_markers["MVP"], "<promise>MVP</promise>");
+        assert_eq!(prd.verification.commands[0].command, "cargo test");
+    }
+
+    #[test]
+    fn written_artifacts_load_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let logs = dir.path().join(".ralph/logs");
+        ArtifactBuilder::new(2).feature("a").error("boom").in_dir(&logs).write().unwrap();
+        ArtifactBuilder::new(1).feature("a").cost_usd(0.5).in_dir(&logs).write().unwrap();
+        let all = IterationArtifact::load_all(&logs);
+        assert_eq!(all.iter().map(|a| a.result.as_str()).collect::<Vec<_>>(), vec!["continue", "error"]);
+        assert_eq!(all[0].cost_usd, Some(0.5));
+    }
+}
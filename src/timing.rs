@@ -0,0 +1,198 @@
+//! Wall-clock time per iteration and per phase (prompt build, agent, PRD validation,
+//! verification), saved to `.ralph/metrics.json` after every iteration and summed up in a
+//! table at the end of the run, to help tune timeouts and delays.
+
+use crate::{atomic, output};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Prompt,
+    Agent,
+    Validation,
+    Verification,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct IterationTiming {
+    pub iteration: u32,
+    /// From the start of the iteration to the end of its bookkeeping; with `--parallel`,
+    /// the whole round
+    pub wall_secs: f64,
+    /// Including the `--summarize-context` call
+    pub prompt_secs: f64,
+    pub agent_secs: f64,
+    pub validation_secs: f64,
+    pub verification_secs: f64,
+}
+
+impl IterationTiming {
+    fn phase_mut(&mut self, phase: Phase) -> &mut f64 {
+        match phase {
+            Phase::Prompt => &mut self.prompt_secs,
+            Phase::Agent => &mut self.agent_secs,
+            Phase::Validation => &mut self.validation_secs,
+            Phase::Verification => &mut self.verification_secs,
+        }
+    }
+}
+
+/// The contents of `.ralph/metrics.json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Metrics {
+    #[serde(default)]
+    pub run_id: Option<String>,
+    pub iterations: Vec<IterationTiming>,
+}
+
+#[must_use]
+pub fn path(ralph_dir: &Path) -> PathBuf {
+    ralph_dir.join("metrics.json")
+}
+
+/// Loads the saved metrics, or `None` if no run has saved any.
+pub fn load(ralph_dir: &Path) -> Result<Option<Metrics>> {
+    let path = path(ralph_dir);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content =
+        std::fs::read_to_string(&path).with_context(|| format!("Failed to read metrics: {}", path.display()))?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .with_context(|| format!("Failed to parse metrics: {}", path.display()))
+}
+
+/// Timing per iteration for the whole run. Shared by reference like
+/// [`CostTracker`](crate::metrics::CostTracker), so `--parallel` workers can record concurrently.
+#[derive(Debug, Default)]
+pub struct Timings {
+    iterations: Mutex<BTreeMap<u32, IterationTiming>>,
+}
+
+impl Timings {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts from the iterations of an earlier, resumed part of the run.
+    #[must_use]
+    pub fn with_earlier(iterations: Vec<IterationTiming>) -> Self {
+        Self {
+            iterations: Mutex::new(iterations.into_iter().map(|t| (t.iteration, t)).collect()),
+        }
+    }
+
+    /// Adds `elapsed` to a phase of `iteration`; phases can run more than once.
+    pub fn add(&self, iteration: u32, phase: Phase, elapsed: Duration) {
+        let mut iterations = self.iterations.lock().unwrap_or_else(|e| e.into_inner());
+        let timing = iterations.entry(iteration).or_insert_with(|| IterationTiming { iteration, ..Default::default() });
+        *timing.phase_mut(phase) += elapsed.as_secs_f64();
+    }
+
+    pub fn set_wall(&self, iteration: u32, elapsed: Duration) {
+        let mut iterations = self.iterations.lock().unwrap_or_else(|e| e.into_inner());
+        let timing = iterations.entry(iteration).or_insert_with(|| IterationTiming { iteration, ..Default::default() });
+        timing.wall_secs = elapsed.as_secs_f64();
+    }
+
+    #[must_use]
+    pub fn iterations(&self) -> Vec<IterationTiming> {
+        self.iterations.lock().unwrap_or_else(|e| e.into_inner()).values().copied().collect()
+    }
+
+    pub fn save(&self, ralph_dir: &Path, run_id: &str) -> Result<()> {
+        let metrics = Metrics { run_id: Some(run_id.to_string()), iterations: self.iterations() };
+        let path = path(ralph_dir);
+        atomic::write(&path, serde_json::to_string_pretty(&metrics)? + "\n")
+            .with_context(|| format!("Failed to write metrics: {}", path.display()))
+    }
+
+    /// Total, mean, and max of each phase and of the wall-clock time, as table rows with a
+    /// header; None if nothing was recorded.
+    #[must_use]
+    pub fn table(&self) -> Option<Vec<String>> {
+        let iterations = self.iterations();
+        if iterations.is_empty() {
+            return None;
+        }
+        let columns: [Column; 5] = [
+            ("Prompt build", |t| t.prompt_secs),
+            ("Agent", |t| t.agent_secs),
+            ("Validation", |t| t.validation_secs),
+            ("Verification", |t| t.verification_secs),
+            ("Iteration", |t| t.wall_secs),
+        ];
+        let mut rows = vec![format!("{:<14}{:>10}{:>10}{:>10}", "Phase", "Total", "Mean", "Max")];
+        for (name, secs) in columns {
+            let values: Vec<f64> = iterations.iter().map(secs).collect();
+            let total: f64 = values.iter().sum();
+            let max = values.iter().copied().fold(0.0, f64::max);
+            rows.push(format!(
+                "{name:<14}{:>10}{:>10}{:>10}",
+                format_secs(total),
+                format_secs(total / values.len() as f64),
+                format_secs(max)
+            ));
+        }
+        Some(rows)
+    }
+}
+
+type Column = (&'static str, fn(&IterationTiming) -> f64);
+
+/// Tenths of a second under a minute, where prompt and validation times fall.
+fn format_secs(secs: f64) -> String {
+    if secs < 60.0 {
+        format!("{secs:.1}s")
+    } else {
+        output::format_duration(Duration::from_secs_f64(secs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_phases_and_summarizes() {
+        let timings = Timings::new();
+        assert!(timings.table().is_none());
+        timings.add(1, Phase::Agent, Duration::from_secs(90));
+        timings.add(1, Phase::Verification, Duration::from_millis(1500));
+        timings.add(1, Phase::Verification, Duration::from_millis(500));
+        timings.set_wall(1, Duration::from_secs(100));
+        timings.add(2, Phase::Agent, Duration::from_secs(30));
+        timings.set_wall(2, Duration::from_secs(31));
+
+        assert_eq!(timings.iterations()[0].verification_secs, 2.0);
+        let table = timings.table().unwrap();
+        assert_eq!(table[0], "Phase              Total      Mean       Max");
+        assert_eq!(table[2], "Agent              2m 0s     1m 0s    1m 30s");
+        assert_eq!(table[4], "Verification        2.0s      1.0s      2.0s");
+    }
+
+    #[test]
+    fn saves_and_resumes() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(load(dir.path()).unwrap().is_none());
+        let timings = Timings::new();
+        timings.add(1, Phase::Prompt, Duration::from_millis(250));
+        timings.save(dir.path(), "run-1").unwrap();
+
+        let saved = load(dir.path()).unwrap().unwrap();
+        assert_eq!(saved.run_id.as_deref(), Some("run-1"));
+        let resumed = Timings::with_earlier(saved.iterations);
+        resumed.add(2, Phase::Agent, Duration::from_secs(1));
+        assert_eq!(resumed.iterations().iter().map(|t| t.iteration).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(resumed.iterations()[0].prompt_secs, 0.25);
+    }
+}
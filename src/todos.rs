@@ -0,0 +1,440 @@
+use crate::{
+    atomic,
+    config::{GroupBy, TodoImportArgs},
+    prd::{self, Feature, Status},
+};
+use anyhow::{bail, Context, Result};
+use ignore::WalkBuilder;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+const COMMENT_LEADERS: [&str; 5] = ["//", "#", "/*", "--", "<!--"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TodoKind {
+    Todo,
+    Fixme,
+    Hack,
+}
+
+impl TodoKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Todo => "TODO",
+            Self::Fixme => "FIXME",
+            Self::Hack => "HACK",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TodoItem {
+    pub path: PathBuf,
+    pub line: usize,
+    pub kind: TodoKind,
+    pub text: String,
+}
+
+/// Scans `args.dir` and writes a PRD with one feature per group of TODO comments.
+/// Returns the number of comments and features written.
+pub fn import(args: &TodoImportArgs, prd_path: &Path) -> Result<(usize, usize)> {
+    if prd_path.exists() && !args.force {
+        bail!(
+            "PRD file already exists: {} (use --force to overwrite)",
+            prd_path.display()
+        );
+    }
+
+    let items = scan(&args.dir)?;
+    if items.is_empty() {
+        bail!("No TODO/FIXME/HACK comments found in {}", args.dir.display());
+    }
+
+    let features = group(&items, args.group_by);
+    let feature_count = features.len();
+
+    let mut prd = prd::template()?;
+    prd.project.name = args
+        .dir
+        .canonicalize()
+        .ok()
+        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "my-project".into());
+    prd.project.description = "Resolve TODO/FIXME/HACK comments found in the codebase".into();
+    prd.features = features;
+
+    let json = serde_json::to_string_pretty(&prd)?;
+    atomic::write(prd_path, json + "\n")
+        .with_context(|| format!("Failed to write PRD file: {}", prd_path.display()))?;
+
+    Ok((items.len(), feature_count))
+}
+
+pub fn scan(dir: &Path) -> Result<Vec<TodoItem>> {
+    let mut items = Vec::new();
+
+    for entry in WalkBuilder::new(dir).build() {
+        let entry = entry.context("Failed to walk directory")?;
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        // Binary or non-UTF-8 files are skipped
+        let Ok(content) = std::fs::read_to_string(entry.path()) else {
+            continue;
+        };
+        let rel = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+        items.extend(content.lines().enumerate().filter_map(|(i, line)| {
+            parse_line(line).map(|(kind, text)| TodoItem {
+                path: rel.to_path_buf(),
+                line: i + 1,
+                kind,
+                text,
+            })
+        }));
+    }
+
+    Ok(items)
+}
+
+/// Extracts a TODO-style marker from a comment on `line`, if any.
+pub(crate) fn parse_line(line: &str) -> Option<(TodoKind, String)> {
+    [TodoKind::Todo, TodoKind::Fixme, TodoKind::Hack]
+        .into_iter()
+        .find_map(|kind| {
+            let marker = kind.as_str();
+            let pos = find_marker(line, marker)?;
+            let before = &line[..pos];
+            let in_comment = COMMENT_LEADERS.iter().any(|l| before.contains(l))
+                || before.trim_start().starts_with('*');
+            in_comment.then(|| (kind, clean_text(&line[pos + marker.len()..])))
+        })
+}
+
+fn find_marker(line: &str, marker: &str) -> Option<usize> {
+    line.match_indices(marker).map(|(i, _)| i).find(|&i| {
+        let prev_ok = line[..i]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !c.is_alphanumeric() && c != '_');
+        let next_ok = line[i + marker.len()..]
+            .chars()
+            .next()
+            .is_none_or(|c| c == ':' || c == '(' || c.is_whitespace());
+        prev_ok && next_ok
+    })
+}
+
+fn clean_text(rest: &str) -> String {
+    let rest = rest.trim_start();
+    // Drop an author tag such as `TODO(alice):`
+    let rest = match rest.strip_prefix('(') {
+        Some(r) => r.split_once(')').map_or(r, |(_, after)| after),
+        None => rest,
+    };
+    rest.trim_start_matches(':')
+        .trim()
+        .trim_end_matches("*/")
+        .trim_end_matches("-->")
+        .trim()
+        .to_string()
+}
+
+pub(crate) fn group(items: &[TodoItem], group_by: GroupBy) -> Vec<Feature> {
+    let mut groups: BTreeMap<String, Vec<&TodoItem>> = BTreeMap::new();
+    for item in items {
+        let key = match group_by {
+            GroupBy::File => item.path.clone(),
+            GroupBy::Module => item
+                .path
+                .parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .map_or_else(|| PathBuf::from("."), Path::to_path_buf),
+        };
+        groups
+            .entry(key.to_string_lossy().replace('\\', "/"))
+            .or_default()
+            .push(item);
+    }
+
+    let ids = unique_ids(groups.keys().map(|key| format!("todo-{}", slugify(key))).collect());
+    groups
+        .into_iter()
+        .zip(ids)
+        .map(|((key, items), id)| {
+            let category = if items.iter().any(|i| i.kind == TodoKind::Fixme) {
+                "bugfix"
+            } else {
+                "refactor"
+            };
+            let mut steps: Vec<String> = items
+                .iter()
+                .map(|i| {
+                    let location = format!("{}:{}", i.path.to_string_lossy().replace('\\', "/"), i.line);
+                    if i.text.is_empty() {
+                        format!("{location} {}", i.kind.as_str())
+                    } else {
+                        format!("{location} {}: {}", i.kind.as_str(), i.text)
+                    }
+                })
+                .collect();
+            steps.push("Remove resolved comments and run verification".into());
+
+            Feature {
+                id,
+                category: category.into(),
+                description: format!(
+                    "Resolve {} TODO/FIXME/HACK comment{} in {key}",
+                    items.len(),
+                    if items.len() == 1 { "" } else { "s" }
+                ),
+                steps,
+                status: Status::Pending,
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// `ids` with repeats numbered, e.g. `src/a-b.rs` and `src/a_b.rs` become `todo-src-a-b-rs`
+/// and `todo-src-a-b-rs-2`, skipping numbered ids another path already slugifies to.
+fn unique_ids(ids: Vec<String>) -> Vec<String> {
+    let natural: HashSet<String> = ids.iter().cloned().collect();
+    let mut taken = HashSet::new();
+    ids.into_iter()
+        .map(|id| {
+            let id = if taken.contains(&id) {
+                (2..)
+                    .map(|n| format!("{id}-{n}"))
+                    .find(|candidate| !taken.contains(candidate) && !natural.contains(candidate))
+                    .unwrap_or(id)
+            } else {
+                id
+            };
+            taken.insert(id.clone());
+            id
+        })
+        .collect()
+}
+
+pub(crate) fn slugify(s: &str) -> String {
+    let slug = s
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect::<String>()
+        .split('-')
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() {
+        "root".into()
+    } else {
+        slug
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(path: &str, line: usize, kind: TodoKind, text: &str) -> TodoItem {
+        TodoItem {
+            path: PathBuf::from(path),
+            line,
+            kind,
+            text: text.into(),
+        }
+    }
+
+    mod parse_line_tests {
+        use super::*;
+
+        #[test]
+        fn parses_rust_comment() {
+            assert_eq!(
+                parse_line("    // TODO: handle errors"),
+                Some((TodoKind::Todo, "handle errors".into()))
+            );
+        }
+
+        #[test]
+        fn parses_shell_comment() {
+            assert_eq!(
+                parse_line("# FIXME broken on macOS"),
+                Some((TodoKind::Fixme, "broken on macOS".into()))
+            );
+        }
+
+        #[test]
+        fn strips_author_tag() {
+            assert_eq!(
+                parse_line("// HACK(bob): temporary workaround"),
+                Some((TodoKind::Hack, "temporary workaround".into()))
+            );
+        }
+
+        #[test]
+        fn strips_block_comment_terminator() {
+            assert_eq!(
+                parse_line("/* TODO: remove */"),
+                Some((TodoKind::Todo, "remove".into()))
+            );
+        }
+
+        #[test]
+        fn parses_doc_block_continuation() {
+            assert_eq!(
+                parse_line("   * TODO document this"),
+                Some((TodoKind::Todo, "document this".into()))
+            );
+        }
+
+        #[test]
+        fn marker_without_text() {
+            assert_eq!(parse_line("// TODO"), Some((TodoKind::Todo, String::new())));
+        }
+
+        #[test]
+        fn ignores_marker_outside_comment() {
+            assert_eq!(parse_line("let TODO = 1;"), None);
+        }
+
+        #[test]
+        fn ignores_marker_inside_word() {
+            assert_eq!(parse_line("// TODOS are tracked elsewhere"), None);
+            assert_eq!(parse_line("// MY_TODO: no"), None);
+        }
+
+        #[test]
+        fn ignores_lowercase_marker() {
+            assert_eq!(parse_line("// todo: later"), None);
+        }
+    }
+
+    mod group_tests {
+        use super::*;
+
+        #[test]
+        fn groups_by_file() {
+            let items = vec![
+                item("src/a.rs", 1, TodoKind::Todo, "one"),
+                item("src/a.rs", 5, TodoKind::Todo, "two"),
+                item("src/b.rs", 2, TodoKind::Hack, "three"),
+            ];
+            let features = group(&items, GroupBy::File);
+            assert_eq!(features.len(), 2);
+            assert_eq!(features[0].id, "todo-src-a-rs");
+            assert_eq!(features[0].steps.len(), 3);
+            assert_eq!(features[0].steps[0], "src/a.rs:1 TODO: one");
+            assert_eq!(features[1].id, "todo-src-b-rs");
+        }
+
+        #[test]
+        fn colliding_slugs_get_numbered() {
+            let items = vec![
+                item("src/a_b.rs", 1, TodoKind::Todo, "one"),
+                item("src/a-b.rs", 2, TodoKind::Todo, "two"),
+                item("src/a.b.rs", 3, TodoKind::Todo, "three"),
+                item("src/a-b-rs-2", 4, TodoKind::Todo, "four"),
+            ];
+            let features = group(&items, GroupBy::File);
+            let ids: Vec<(&str, &str)> = features.iter().map(|f| (f.id.as_str(), f.steps[0].as_str())).collect();
+            assert_eq!(
+                ids,
+                [
+                    ("todo-src-a-b-rs-2", "src/a-b-rs-2:4 TODO: four"),
+                    ("todo-src-a-b-rs", "src/a-b.rs:2 TODO: two"),
+                    ("todo-src-a-b-rs-3", "src/a.b.rs:3 TODO: three"),
+                    ("todo-src-a-b-rs-4", "src/a_b.rs:1 TODO: one"),
+                ]
+            );
+        }
+
+        #[test]
+        fn groups_by_module() {
+            let items = vec![
+                item("src/a.rs", 1, TodoKind::Todo, "one"),
+                item("src/b.rs", 2, TodoKind::Todo, "two"),
+                item("main.rs", 3, TodoKind::Todo, "three"),
+            ];
+            let features = group(&items, GroupBy::Module);
+            assert_eq!(features.len(), 2);
+            assert_eq!(features[0].id, "todo-root");
+            assert_eq!(features[1].id, "todo-src");
+            assert_eq!(features[1].description, "Resolve 2 TODO/FIXME/HACK comments in src");
+        }
+
+        #[test]
+        fn fixme_makes_group_a_bugfix() {
+            let items = vec![
+                item("a.rs", 1, TodoKind::Todo, "one"),
+                item("a.rs", 2, TodoKind::Fixme, "two"),
+            ];
+            assert_eq!(group(&items, GroupBy::File)[0].category, "bugfix");
+        }
+
+        #[test]
+        fn features_start_pending() {
+            let items = vec![item("a.rs", 1, TodoKind::Todo, "x")];
+            assert_eq!(group(&items, GroupBy::File)[0].status, Status::Pending);
+        }
+    }
+
+    mod scan_tests {
+        use super::*;
+
+        #[test]
+        fn finds_comments_with_line_numbers() {
+            let dir = tempfile::tempdir().unwrap();
+            std::fs::create_dir(dir.path().join("src")).unwrap();
+            std::fs::write(
+                dir.path().join("src/lib.rs"),
+                "fn a() {}\n// TODO: first\nfn b() {} // FIXME: second\n",
+            )
+            .unwrap();
+
+            let mut items = scan(dir.path()).unwrap();
+            items.sort_by_key(|i| i.line);
+            assert_eq!(
+                items,
+                vec![
+                    item("src/lib.rs", 2, TodoKind::Todo, "first"),
+                    item("src/lib.rs", 3, TodoKind::Fixme, "second"),
+                ]
+            );
+        }
+
+        #[test]
+        fn import_refuses_to_overwrite_without_force() {
+            let dir = tempfile::tempdir().unwrap();
+            std::fs::write(dir.path().join("a.rs"), "// TODO: x\n").unwrap();
+            let prd_path = dir.path().join("prd.jsonc");
+            std::fs::write(&prd_path, "{}").unwrap();
+
+            let args = TodoImportArgs {
+                dir: dir.path().to_path_buf(),
+                group_by: GroupBy::File,
+                force: false,
+            };
+            assert!(import(&args, &prd_path).is_err());
+        }
+
+        #[test]
+        fn import_writes_loadable_prd() {
+            let dir = tempfile::tempdir().unwrap();
+            std::fs::write(dir.path().join("a.rs"), "// TODO: x\n// HACK: y\n").unwrap();
+            let out = tempfile::tempdir().unwrap();
+            let prd_path = out.path().join("prd.jsonc");
+
+            let args = TodoImportArgs {
+                dir: dir.path().to_path_buf(),
+                group_by: GroupBy::File,
+                force: false,
+            };
+            assert_eq!(import(&args, &prd_path).unwrap(), (2, 1));
+
+            let prd = prd::Prd::load(&prd_path).unwrap();
+            assert_eq!(prd.features.len(), 1);
+            assert_eq!(prd.features[0].id, "todo-a-rs");
+        }
+    }
+}
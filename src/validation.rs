@@ -1,5 +1,17 @@
 use crate::git;
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde_json::{Map, Value};
+use std::path::Path;
+
+const AGENT_NOTES_KEY: &str = "agentNotes";
+
+/// A note the agent appended to a feature's `agentNotes` during an iteration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentNote {
+    pub feature_id: String,
+    pub text: String,
+}
 
 pub(crate) fn is_diff_content_line(line: &str) -> bool {
     (line.starts_with('+') || line.starts_with('-'))
@@ -31,20 +43,555 @@ pub(crate) fn validate_diff_content(diff: &str) -> Result<()> {
     Ok(())
 }
 
-pub fn validate_prd_changes(prd_path: &str) -> Result<()> {
-    let diff = git::diff_file_from_head(prd_path)?;
+/// Diffs the PRD inside its own repository, which may differ from the agent's project dir.
+/// With `allow_agent_notes`, appends to `agentNotes` are also accepted and returned.
+pub fn validate_prd_changes(prd_path: &Path, allow_agent_notes: bool) -> Result<Vec<AgentNote>> {
+    let dir = prd_dir(prd_path);
+    let name = prd_path
+        .file_name()
+        .map_or_else(|| prd_path.to_string_lossy(), |n| n.to_string_lossy());
+    let diff = git::diff_file_from_head(dir, &name)?;
 
     if diff.is_empty() {
-        return Ok(());
+        return Ok(Vec::new());
+    }
+
+    if !allow_agent_notes {
+        validate_diff_content(&diff)?;
+        return Ok(Vec::new());
     }
 
-    validate_diff_content(&diff)
+    let Some(old) = git::show_file_from_head(dir, &name)? else {
+        return Ok(Vec::new());
+    };
+    let new = std::fs::read_to_string(prd_path)
+        .with_context(|| format!("Failed to read PRD file: {}", prd_path.display()))?;
+    validate_agent_note_changes(&old, &new)
+}
+
+/// Validates the PRD against a copy taken before the iteration, for projects without git (`--snapshot`).
+pub fn validate_prd_against(old: &str, prd_path: &Path, allow_agent_notes: bool) -> Result<Vec<AgentNote>> {
+    let new = std::fs::read_to_string(prd_path)
+        .with_context(|| format!("Failed to read PRD file: {}", prd_path.display()))?;
+    if new == old {
+        return Ok(Vec::new());
+    }
+    let notes = validate_agent_note_changes(old, &new)?;
+    if !allow_agent_notes && !notes.is_empty() {
+        bail!(
+            "Invalid PRD modification detected.\n\
+            Only 'status' field changes are allowed.\n\
+            agentNotes were added without --allow-agent-notes.\n\
+            Please revert non-status changes to the PRD."
+        );
+    }
+    Ok(notes)
+}
+
+/// Compares two PRD versions structurally: only `status` may change and `agentNotes` may
+/// only grow at the end. Returns the appended notes.
+pub(crate) fn validate_agent_note_changes(old: &str, new: &str) -> Result<Vec<AgentNote>> {
+    let mut old: Value = json5::from_str(old).context("Failed to parse PRD at HEAD")?;
+    let mut new: Value = json5::from_str(new).context("Failed to parse modified PRD")?;
+
+    let old_features = take_features(&mut old);
+    let new_features = take_features(&mut new);
+
+    if old != new {
+        bail!(
+            "Invalid PRD modification detected.\n\
+            Only 'status' and appended 'agentNotes' changes are allowed.\n\
+            Fields outside 'features' were modified.\n\
+            Please revert non-status changes to the PRD."
+        );
+    }
+    if old_features.len() != new_features.len() {
+        bail!(
+            "Invalid PRD modification detected.\n\
+            Features were added or removed ({} -> {}).\n\
+            Please revert non-status changes to the PRD.",
+            old_features.len(),
+            new_features.len()
+        );
+    }
+
+    let mut appended = Vec::new();
+    for (old, new) in old_features.into_iter().zip(new_features) {
+        let (mut old, mut new) = (into_object(old), into_object(new));
+        let id = old.get("id").and_then(Value::as_str).unwrap_or("?").to_string();
+        let old_notes = take_notes(&mut old, &id)?;
+        let new_notes = take_notes(&mut new, &id)?;
+        old.remove("status");
+        new.remove("status");
+
+        if let Some(key) = changed_key(&old, &new) {
+            bail!(
+                "Invalid PRD modification detected.\n\
+                Only 'status' and appended 'agentNotes' changes are allowed.\n\
+                Feature '{id}' field '{key}' was modified.\n\
+                Please revert non-status changes to the PRD."
+            );
+        }
+        if !new_notes.starts_with(&old_notes) {
+            bail!(
+                "Invalid PRD modification detected.\n\
+                Existing agentNotes of feature '{id}' were edited or removed; notes are append-only.\n\
+                Please restore the original notes."
+            );
+        }
+        appended.extend(new_notes[old_notes.len()..].iter().map(|text| AgentNote {
+            feature_id: id.clone(),
+            text: text.clone(),
+        }));
+    }
+
+    Ok(appended)
+}
+
+fn take_features(prd: &mut Value) -> Vec<Value> {
+    match prd.get_mut("features").map(Value::take) {
+        Some(Value::Array(features)) => features,
+        _ => Vec::new(),
+    }
+}
+
+fn into_object(value: Value) -> Map<String, Value> {
+    match value {
+        Value::Object(map) => map,
+        _ => Map::new(),
+    }
+}
+
+fn take_notes(feature: &mut Map<String, Value>, id: &str) -> Result<Vec<String>> {
+    match feature.remove(AGENT_NOTES_KEY) {
+        None => Ok(Vec::new()),
+        Some(Value::Array(notes)) => notes
+            .into_iter()
+            .map(|n| match n {
+                Value::String(s) => Ok(s),
+                _ => bail!("Feature '{id}' agentNotes must contain only strings"),
+            })
+            .collect(),
+        Some(_) => bail!("Feature '{id}' agentNotes must be an array of strings"),
+    }
+}
+
+fn changed_key(old: &Map<String, Value>, new: &Map<String, Value>) -> Option<String> {
+    old.keys()
+        .chain(new.keys())
+        .find(|k| old.get(*k) != new.get(*k))
+        .cloned()
+}
+
+/// Files no iteration may change: the PRD's `protectedPaths` and `--protected-path` globs,
+/// matched against paths relative to the project directory.
+#[derive(Debug)]
+pub struct ProtectedPaths {
+    globs: GlobSet,
+}
+
+impl ProtectedPaths {
+    /// None without any patterns, so there is nothing to check.
+    pub fn new<'a>(patterns: impl IntoIterator<Item = &'a str>) -> Result<Option<Self>> {
+        let mut builder = GlobSetBuilder::new();
+        let mut empty = true;
+        for pattern in patterns {
+            builder.add(Glob::new(pattern).with_context(|| format!("Invalid protected path '{pattern}'"))?);
+            empty = false;
+        }
+        if empty {
+            return Ok(None);
+        }
+        Ok(Some(Self { globs: builder.build().context("Failed to compile protected paths")? }))
+    }
+
+    /// The `changed` paths that are protected.
+    #[must_use]
+    pub fn violations<P: AsRef<Path>>(&self, changed: impl IntoIterator<Item = P>) -> Vec<String> {
+        changed
+            .into_iter()
+            .filter(|path| self.globs.is_match(path.as_ref()))
+            .map(|path| path.as_ref().to_string_lossy().replace('\\', "/"))
+            .collect()
+    }
+}
+
+/// A test the diff takes away: a `#[test]` function removed, renamed, or stripped of its
+/// attribute (`name` is set), or a file under a `tests` directory deleted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemovedTest {
+    pub path: String,
+    pub name: Option<String>,
+}
+
+impl std::fmt::Display for RemovedTest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{}: {name}", self.path),
+            None => write!(f, "{} (deleted)", self.path),
+        }
+    }
+}
+
+impl RemovedTest {
+    /// Whether an `--allow-test-removal` glob names it, by path or by function name.
+    #[must_use]
+    pub fn allowed_by(&self, allowlist: &GlobSet) -> bool {
+        allowlist.is_match(&self.path) || self.name.as_ref().is_some_and(|name| allowlist.is_match(name))
+    }
+}
+
+/// Compiles the `--allow-test-removal` globs.
+pub fn test_removal_allowlist(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).with_context(|| format!("Invalid test removal allowance '{pattern}'"))?);
+    }
+    builder.build().context("Failed to compile test removal allowances")
+}
+
+/// Tests removed by a unified `git diff -M`. A test function that reappears under the same
+/// name in another file was moved, not removed.
+#[must_use]
+pub fn removed_tests(diff: &str) -> Vec<RemovedTest> {
+    let mut removed = Vec::new();
+    let mut old_tests = Vec::new();
+    let mut new_tests = Vec::new();
+    let mut path = String::new();
+    let mut scan = false;
+    // Each side of the diff waits for the function after a test attribute
+    let (mut old_pending, mut new_pending) = (false, false);
+    for line in diff.lines() {
+        if line.starts_with("diff --git ") {
+            (path, scan, old_pending, new_pending) = (String::new(), false, false, false);
+            continue;
+        }
+        if let Some(name) = line.strip_prefix("+++ b/") {
+            path = name.to_string();
+            scan = path.ends_with(".rs");
+            continue;
+        }
+        if let Some(name) = line.strip_prefix("--- a/") {
+            path = name.to_string();
+            continue;
+        }
+        if line == "+++ /dev/null" {
+            if path.split('/').any(|part| part == "tests") {
+                removed.push(RemovedTest { path: path.clone(), name: None });
+            }
+            continue;
+        }
+        if !scan {
+            continue;
+        }
+        let (old_side, new_side, code) = match line.split_at_checked(1) {
+            Some((" ", code)) => (true, true, code),
+            Some(("-", code)) => (true, false, code),
+            Some(("+", code)) => (false, true, code),
+            _ => {
+                (old_pending, new_pending) = (false, false);
+                continue;
+            }
+        };
+        let code = code.trim();
+        let attribute = is_test_attribute(code);
+        let function = fn_name(code);
+        for (on_side, pending, tests) in
+            [(old_side, &mut old_pending, &mut old_tests), (new_side, &mut new_pending, &mut new_tests)]
+        {
+            if !on_side {
+                continue;
+            }
+            if attribute {
+                *pending = true;
+            } else if let Some(name) = function.filter(|_| *pending) {
+                tests.push((path.clone(), name.to_string()));
+                *pending = false;
+            } else if !code.starts_with("#[") && !code.starts_with("//") {
+                *pending = false;
+            }
+        }
+    }
+    for (path, name) in &old_tests {
+        let kept_or_moved = new_tests.iter().any(|new| new.1 == *name && (new.0 == *path || !old_tests.contains(new)));
+        if !kept_or_moved {
+            removed.push(RemovedTest { path: path.clone(), name: Some(name.clone()) });
+        }
+    }
+    removed
+}
+
+/// `#[test]`, or a runtime's `#[tokio::test]`, with or without arguments.
+fn is_test_attribute(code: &str) -> bool {
+    code.strip_prefix("#[")
+        .map(|rest| rest.split(['(', ']']).next().unwrap_or_default().trim())
+        .is_some_and(|attr| attr == "test" || attr.ends_with("::test"))
+}
+
+/// Name of the function `code` declares, if it starts a declaration.
+fn fn_name(code: &str) -> Option<&str> {
+    let mut words = code.split_whitespace();
+    let qualifiers = ["async", "unsafe", "const", "extern"];
+    let name = loop {
+        match words.next()? {
+            "fn" => break words.next()?,
+            word if word.starts_with("pub") || qualifiers.contains(&word) => {}
+            _ => return None,
+        }
+    };
+    name.split(['(', '<']).next().filter(|n| !n.is_empty())
+}
+
+#[must_use]
+pub fn prd_dir(prd_path: &Path) -> &Path {
+    prd_path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    mod prd_dir_tests {
+        use super::*;
+
+        #[test]
+        fn bare_file_name_uses_current_dir() {
+            assert_eq!(prd_dir(Path::new("prd.jsonc")), Path::new("."));
+        }
+
+        #[test]
+        fn nested_path_uses_parent() {
+            assert_eq!(prd_dir(Path::new("plans/prd.jsonc")), Path::new("plans"));
+        }
+    }
+
+    mod protected_paths_tests {
+        use super::*;
+
+        #[test]
+        fn none_without_patterns() {
+            assert!(ProtectedPaths::new([]).unwrap().is_none());
+        }
+
+        #[test]
+        fn reports_matching_paths() {
+            let protected = ProtectedPaths::new(["Cargo.lock", ".github/**", "migrations/*.sql"]).unwrap().unwrap();
+            let changed = ["src/main.rs", "Cargo.lock", ".github/workflows/ci.yml", "migrations/001.sql", "sub/Cargo.lock"];
+            assert_eq!(protected.violations(changed), ["Cargo.lock", ".github/workflows/ci.yml", "migrations/001.sql"]);
+        }
+
+        #[test]
+        fn invalid_glob_is_an_error() {
+            let err = ProtectedPaths::new(["src/[oops"]).unwrap_err();
+            assert!(err.to_string().contains("Invalid protected path 'src/[oops'"));
+        }
+    }
+
+    mod removed_tests_tests {
+        use super::*;
+
+        fn names(diff: &str) -> Vec<String> {
+            removed_tests(diff).iter().map(ToString::to_string).collect()
+        }
+
+        #[test]
+        fn removed_test_function_is_reported() {
+            let diff = "diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -10,9 +10,4 @@ mod tests {
+     #[test]
+     fn kept() {}
+-
+-    #[test]
+-    #[should_panic]
+-    fn removed() {
+-        panic!();
+-    }
+ }";
+            assert_eq!(names(diff), ["src/lib.rs: removed"]);
+        }
+
+        #[test]
+        fn renamed_or_disabled_tests_are_reported() {
+            let diff = "diff --git a/src/a.rs b/src/a.rs
+--- a/src/a.rs
++++ b/src/a.rs
+@@ -1,6 +1,6 @@
+     #[tokio::test]
+-    async fn old_name() {}
++    async fn new_name() {}
+-    #[test]
+     fn no_longer_a_test() {}";
+            assert_eq!(names(diff), ["src/a.rs: old_name", "src/a.rs: no_longer_a_test"]);
+        }
+
+        #[test]
+        fn moved_or_changed_tests_are_not_reported() {
+            let diff = "diff --git a/src/a.rs b/src/a.rs
+--- a/src/a.rs
++++ b/src/a.rs
+@@ -1,4 +1,2 @@
+-    #[test]
+-    fn moved() {}
+-    #[test]
++    #[tokio::test]
+     fn made_async() {}
+diff --git a/src/b.rs b/src/b.rs
+--- a/src/b.rs
++++ b/src/b.rs
+@@ -1,0 +1,2 @@
++    #[test]
++    fn moved() {}";
+            assert!(names(diff).is_empty());
+        }
+
+        #[test]
+        fn deleted_files_under_tests_are_reported() {
+            let diff = "diff --git a/tests/cli.rs b/tests/cli.rs
+deleted file mode 100644
+--- a/tests/cli.rs
++++ /dev/null
+@@ -1,2 +0,0 @@
+-#[test]
+-fn cli() {}
+diff --git a/src/old.rs b/src/old.rs
+deleted file mode 100644
+--- a/src/old.rs
++++ /dev/null
+@@ -1 +0,0 @@
+-pub fn old() {}";
+            assert_eq!(names(diff), ["tests/cli.rs (deleted)"]);
+        }
+
+        #[test]
+        fn allowlist_matches_path_or_name() {
+            let allowlist = test_removal_allowlist(&["tests/legacy/**".into(), "flaky_*".into()]).unwrap();
+            let by_path = RemovedTest { path: "tests/legacy/a.rs".into(), name: None };
+            let by_name = RemovedTest { path: "src/lib.rs".into(), name: Some("flaky_network".into()) };
+            let other = RemovedTest { path: "src/lib.rs".into(), name: Some("parses".into()) };
+            assert!(by_path.allowed_by(&allowlist));
+            assert!(by_name.allowed_by(&allowlist));
+            assert!(!other.allowed_by(&allowlist));
+        }
+    }
+
+    mod agent_notes_tests {
+        use super::*;
+
+        const BASE: &str = r#"{
+  // comments are fine
+  "project": { "name": "p", "description": "d" },
+  "features": [
+    { "id": "f1", "description": "one", "status": "pending" },
+    { "id": "f2", "description": "two", "status": "pending", "agentNotes": ["first"] }
+  ]
+}"#;
+
+        #[test]
+        fn unchanged_is_valid() {
+            assert_eq!(validate_agent_note_changes(BASE, BASE).unwrap(), vec![]);
+        }
+
+        #[test]
+        fn status_change_is_valid() {
+            let new = BASE.replacen(r#""status": "pending""#, r#""status": "in-progress""#, 1);
+            assert!(validate_agent_note_changes(BASE, &new).unwrap().is_empty());
+        }
+
+        #[test]
+        fn appended_notes_are_returned() {
+            let new = BASE
+                .replace(r#""one", "status": "pending""#, r#""one", "status": "pending", "agentNotes": ["new"]"#)
+                .replace(r#"["first"]"#, r#"["first", "second"]"#);
+            assert_eq!(
+                validate_agent_note_changes(BASE, &new).unwrap(),
+                vec![
+                    AgentNote { feature_id: "f1".into(), text: "new".into() },
+                    AgentNote { feature_id: "f2".into(), text: "second".into() },
+                ]
+            );
+        }
+
+        #[test]
+        fn edited_note_rejected() {
+            let new = BASE.replace(r#"["first"]"#, r#"["changed"]"#);
+            let err = validate_agent_note_changes(BASE, &new).unwrap_err().to_string();
+            assert!(err.contains("append-only"));
+        }
+
+        #[test]
+        fn removed_note_rejected() {
+            let new = BASE.replace(r#"["first"]"#, "[]");
+            assert!(validate_agent_note_changes(BASE, &new).is_err());
+        }
+
+        #[test]
+        fn prepended_note_rejected() {
+            let new = BASE.replace(r#"["first"]"#, r#"["zero", "first"]"#);
+            assert!(validate_agent_note_changes(BASE, &new).is_err());
+        }
+
+        #[test]
+        fn non_string_note_rejected() {
+            let new = BASE.replace(r#"["first"]"#, r#"["first", 2]"#);
+            assert!(validate_agent_note_changes(BASE, &new).is_err());
+        }
+
+        #[test]
+        fn description_change_rejected() {
+            let new = BASE.replace(r#""one""#, r#""uno""#);
+            let err = validate_agent_note_changes(BASE, &new).unwrap_err().to_string();
+            assert!(err.contains("Feature 'f1' field 'description'"));
+        }
+
+        #[test]
+        fn project_change_rejected() {
+            let new = BASE.replace(r#""name": "p""#, r#""name": "q""#);
+            assert!(validate_agent_note_changes(BASE, &new).is_err());
+        }
+
+        #[test]
+        fn feature_removal_rejected() {
+            let new = BASE.replace(r#",
+    { "id": "f2", "description": "two", "status": "pending", "agentNotes": ["first"] }"#, "");
+            assert!(validate_agent_note_changes(BASE, &new).is_err());
+        }
+    }
+
+    mod validate_prd_against_tests {
+        use super::*;
+        use tempfile::NamedTempFile;
+
+        const OLD: &str = r#"{ "features": [ { "id": "f1", "description": "one", "status": "pending" } ] }"#;
+
+        fn check(new: &str, allow_agent_notes: bool) -> Result<Vec<AgentNote>> {
+            let file = NamedTempFile::new().unwrap();
+            std::fs::write(file.path(), new).unwrap();
+            validate_prd_against(OLD, file.path(), allow_agent_notes)
+        }
+
+        #[test]
+        fn status_change_is_valid() {
+            assert!(check(&OLD.replace("pending", "complete"), false).unwrap().is_empty());
+        }
+
+        #[test]
+        fn other_change_is_rejected() {
+            assert!(check(&OLD.replace("one", "changed"), false).is_err());
+        }
+
+        #[test]
+        fn notes_need_opt_in() {
+            let new = OLD.replace(r#""pending""#, r#""pending", "agentNotes": ["hi"]"#);
+            assert!(check(&new, false).is_err());
+            assert_eq!(check(&new, true).unwrap().len(), 1);
+        }
+    }
+
     mod is_diff_content_line_tests {
         use super::*;
 
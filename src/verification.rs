@@ -0,0 +1,337 @@
+//! PRD verification commands run by ralph after each iteration, and their history in
+//! `.ralph/verification.jsonl` for the `{verification_history}` placeholder.
+
+use crate::prd::VerifyCommand;
+use crate::sandbox::Sandbox;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::Instrument;
+
+/// Lines of a failed command's output shown on the console.
+const TAIL_LINES: usize = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CommandResult {
+    pub name: String,
+    pub passed: bool,
+    /// More than one when the command has `retries`
+    #[serde(default = "one", skip_serializing_if = "is_one")]
+    pub attempts: u32,
+}
+
+impl CommandResult {
+    /// e.g. `test pass` or `test pass after 2 attempts`, for one-line summaries.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let outcome = if self.passed { "pass" } else { "FAIL" };
+        match self.attempts {
+            0 | 1 => format!("{} {outcome}", self.name),
+            n => format!("{} {outcome} after {n} attempts", self.name),
+        }
+    }
+}
+
+fn one() -> u32 {
+    1
+}
+
+fn is_one(n: &u32) -> bool {
+    *n == 1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationRun {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    pub iteration: u32,
+    pub finished_at: String,
+    pub results: Vec<CommandResult>,
+}
+
+/// Runs each command in `project_dir` through `sandbox`; a command that errors or times out
+/// fails.
+pub async fn run<'a>(
+    commands: impl IntoIterator<Item = &'a VerifyCommand>,
+    sandbox: &Sandbox,
+    project_dir: &Path,
+    timeout: Duration,
+) -> Vec<CommandResult> {
+    let mut results = Vec::new();
+    for cmd in commands {
+        crate::output::debug(&format!("Verification '{}': {}", cmd.name, cmd.command));
+        let span = tracing::info_span!("verification", command = cmd.name.as_str(), passed = tracing::field::Empty);
+        let (outcome, attempts) = run_with_retries(cmd, sandbox, project_dir, timeout).instrument(span.clone()).await;
+        let passed = matches!(outcome, Ok(true));
+        span.record("passed", passed);
+        results.push(CommandResult {
+            name: cmd.name.clone(),
+            passed,
+            attempts,
+        });
+    }
+    results
+}
+
+/// Runs `cmd` until it passes, up to `1 + retries` times with `retryDelaySecs` between
+/// attempts. Returns the last outcome and the attempts made; a command that can't be started
+/// isn't retried.
+pub async fn run_with_retries(
+    cmd: &VerifyCommand,
+    sandbox: &Sandbox,
+    project_dir: &Path,
+    timeout: Duration,
+) -> (Result<bool>, u32) {
+    let mut attempts = 1;
+    loop {
+        let outcome = sandbox.run(cmd, project_dir, timeout).await;
+        if !matches!(outcome, Ok(false)) || attempts > cmd.retries {
+            if matches!(outcome, Ok(false)) {
+                show_tail(sandbox, cmd);
+            }
+            return (outcome, attempts);
+        }
+        crate::output::warn(&format!(
+            "Verification '{}' failed; retrying ({attempts} of {} retries)",
+            cmd.name, cmd.retries
+        ));
+        tokio::time::sleep(Duration::from_secs(cmd.retry_delay_secs)).await;
+        attempts += 1;
+    }
+}
+
+/// Prints the end of a failed command's output, and where the rest is.
+fn show_tail(sandbox: &Sandbox, cmd: &VerifyCommand) {
+    let Some(path) = sandbox.output_log(cmd) else {
+        return;
+    };
+    let Ok(content) = std::fs::read(&path) else {
+        return;
+    };
+    let content = String::from_utf8_lossy(&content);
+    let lines = tail(&content, TAIL_LINES);
+    if lines.is_empty() {
+        return;
+    }
+    crate::output::dim(&format!("Output of '{}' ({}):", cmd.name, path.display()));
+    for line in lines {
+        crate::output::dim(&format!("  {line}"));
+    }
+}
+
+/// The last `n` lines of `text`, ignoring trailing blank lines.
+#[must_use]
+pub fn tail(text: &str, n: usize) -> Vec<&str> {
+    let lines: Vec<&str> = text.trim_end().lines().collect();
+    lines[lines.len().saturating_sub(n)..].to_vec()
+}
+
+#[must_use]
+pub fn history_path(ralph_dir: &Path) -> PathBuf {
+    ralph_dir.join("verification.jsonl")
+}
+
+pub fn append(ralph_dir: &Path, run: &VerificationRun) -> Result<()> {
+    let path = history_path(ralph_dir);
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open verification history: {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(run)?)?;
+    Ok(())
+}
+
+/// Loads all runs, oldest first. Unparseable lines are skipped.
+pub fn load(ralph_dir: &Path) -> Result<Vec<VerificationRun>> {
+    let path = history_path(ralph_dir);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read verification history: {}", path.display()))?;
+    Ok(content
+        .lines()
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect())
+}
+
+/// One line per command over the last `limit` runs, oldest first, with the current streak.
+#[must_use]
+pub fn summarize(runs: &[VerificationRun], limit: usize) -> String {
+    let recent = &runs[runs.len().saturating_sub(limit)..];
+    let mut names: Vec<&str> = Vec::new();
+    for result in recent.iter().flat_map(|r| &r.results) {
+        if !names.contains(&result.name.as_str()) {
+            names.push(&result.name);
+        }
+    }
+    if names.is_empty() {
+        return "No verification runs recorded yet.".to_string();
+    }
+
+    names
+        .iter()
+        .map(|name| {
+            let outcomes: Vec<bool> = recent
+                .iter()
+                .filter_map(|r| r.results.iter().find(|c| c.name == *name))
+                .map(|c| c.passed)
+                .collect();
+            let last = outcomes[outcomes.len() - 1];
+            let streak = outcomes.iter().rev().take_while(|&&p| p == last).count();
+            let trend = outcomes
+                .iter()
+                .map(|&p| if p { "pass" } else { "fail" })
+                .collect::<Vec<_>>()
+                .join(", ");
+            let state = match (last, streak) {
+                (true, _) => "passing".to_string(),
+                (false, 1) => "failing since the last run".to_string(),
+                (false, n) => format!("failing {n} runs in a row"),
+            };
+            format!("- {name}: {trend} ({state})")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_of(iteration: u32, results: &[(&str, bool)]) -> VerificationRun {
+        VerificationRun {
+            run_id: None,
+            iteration,
+            finished_at: "2026-01-01T00:00:00+00:00".into(),
+            results: results
+                .iter()
+                .map(|&(name, passed)| CommandResult {
+                    name: name.into(),
+                    passed,
+                    attempts: 1,
+                })
+                .collect(),
+        }
+    }
+
+    mod summarize_tests {
+        use super::*;
+
+        #[test]
+        fn empty_history() {
+            assert_eq!(summarize(&[], 5), "No verification runs recorded yet.");
+        }
+
+        #[test]
+        fn reports_trend_and_streak() {
+            let runs = vec![
+                run_of(1, &[("check", true), ("test", true)]),
+                run_of(2, &[("check", true), ("test", false)]),
+                run_of(3, &[("check", true), ("test", false)]),
+            ];
+            assert_eq!(
+                summarize(&runs, 5),
+                "- check: pass, pass, pass (passing)\n- test: pass, fail, fail (failing 2 runs in a row)"
+            );
+        }
+
+        #[test]
+        fn only_last_runs_are_shown() {
+            let runs = vec![
+                run_of(1, &[("test", false)]),
+                run_of(2, &[("test", true)]),
+                run_of(3, &[("test", false)]),
+            ];
+            assert_eq!(summarize(&runs, 2), "- test: pass, fail (failing since the last run)");
+        }
+
+        #[test]
+        fn commands_added_later_are_included() {
+            let runs = vec![run_of(1, &[("check", true)]), run_of(2, &[("check", true), ("lint", false)])];
+            assert!(summarize(&runs, 5).ends_with("- lint: fail (failing since the last run)"));
+        }
+    }
+
+    mod history_tests {
+        use super::*;
+
+        #[test]
+        fn append_and_load_round_trip() {
+            let dir = tempfile::tempdir().unwrap();
+            assert!(load(dir.path()).unwrap().is_empty());
+            append(dir.path(), &run_of(1, &[("test", true)])).unwrap();
+            append(dir.path(), &run_of(2, &[("test", false)])).unwrap();
+            std::fs::OpenOptions::new()
+                .append(true)
+                .open(history_path(dir.path()))
+                .unwrap()
+                .write_all(b"garbage\n")
+                .unwrap();
+            let runs = load(dir.path()).unwrap();
+            assert_eq!(runs.len(), 2);
+            assert!(!runs[1].results[0].passed);
+        }
+    }
+
+    mod run_tests {
+        use super::*;
+
+        fn command(name: &str, command: &str) -> VerifyCommand {
+            VerifyCommand {
+                name: name.into(),
+                command: command.into(),
+                description: String::new(),
+                ..Default::default()
+            }
+        }
+
+        #[tokio::test]
+        async fn records_exit_status_and_timeouts() {
+            let dir = tempfile::tempdir().unwrap();
+            let commands = [command("ok", "true"), command("bad", "false"), command("slow", "sleep 5")];
+            let results = run(&commands, &Sandbox::default(), dir.path(), Duration::from_millis(200)).await;
+            assert_eq!(
+                results.iter().map(|r| r.passed).collect::<Vec<_>>(),
+                vec![true, false, false]
+            );
+        }
+
+        #[tokio::test]
+        async fn retries_until_the_command_passes() {
+            let dir = tempfile::tempdir().unwrap();
+            // Fails until the third attempt
+            let flaky = VerifyCommand {
+                retries: 3,
+                ..command("flaky", "echo x >> tries; [ $(wc -l < tries) -ge 3 ]")
+            };
+            let broken = VerifyCommand { retries: 1, ..command("broken", "false") };
+            let results = run([&flaky, &broken], &Sandbox::default(), dir.path(), Duration::from_secs(5)).await;
+            assert!(results[0].passed);
+            assert_eq!(results[0].attempts, 3);
+            assert_eq!(results[0].summary(), "flaky pass after 3 attempts");
+            assert!(!results[1].passed);
+            assert_eq!(results[1].attempts, 2);
+        }
+
+        #[test]
+        fn tail_keeps_the_last_lines() {
+            assert_eq!(tail("a\nb\nc\n\n", 2), ["b", "c"]);
+            assert_eq!(tail("a\n", 5), ["a"]);
+            assert!(tail("", 5).is_empty());
+        }
+
+        #[test]
+        fn single_attempts_are_left_out_of_records() {
+            let result = CommandResult { name: "test".into(), passed: true, attempts: 1 };
+            let json = serde_json::to_string(&result).unwrap();
+            assert_eq!(json, r#"{"name":"test","passed":true}"#);
+            assert_eq!(serde_json::from_str::<CommandResult>(&json).unwrap(), result);
+            assert_eq!(result.summary(), "test pass");
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! `ralph watch`: re-runs the PRD's verification commands whenever a project file changes.
+//! `ralph watch --remote <url>` instead follows a `--serve` instance's live output from
+//! another machine.
+
+use crate::{
+    config::Args,
+    dry_run, output, prd,
+    serve::{parse_sse_event, LiveEvent},
+};
+use anyhow::{bail, Context, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+/// Directories whose changes never count: ralph's own state (verification logs included)
+/// and VCS metadata.
+const SKIPPED_DIRS: [&str; 2] = [".ralph", ".git"];
+/// Quiet time after a change before re-running, so a save touching several files runs once.
+const SETTLE: Duration = Duration::from_millis(300);
+
+/// Runs the verification commands, then again after every change in the project directory,
+/// until Ctrl-C. The PRD is re-read each time, so edits to its commands apply.
+pub async fn local(args: &Args) -> Result<()> {
+    if !args.prd.exists() {
+        bail!("PRD file not found: {}", args.prd.display());
+    }
+    let project_dir = prd::Prd::load(&args.prd)?.project_dir(&args.prd, args.project_dir.as_deref());
+    let root = project_dir
+        .canonicalize()
+        .with_context(|| format!("Project directory not found: {}", project_dir.display()))?;
+    let filter = ChangeFilter::new(&root);
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            let _ = tx.send(event);
+        }
+    })
+    .context("Failed to start the file watcher")?;
+    watcher
+        .watch(&root, RecursiveMode::Recursive)
+        .with_context(|| format!("Failed to watch {}", root.display()))?;
+    output::log(&format!("Watching {} (Ctrl+C to stop)", root.display()));
+
+    let mut changed = None;
+    loop {
+        verify(args, &project_dir, changed.as_deref()).await;
+        changed = tokio::select! {
+            path = next_change(&mut rx, &filter) => Some(path.context("The file watcher stopped")?),
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        };
+    }
+}
+
+async fn verify(args: &Args, project_dir: &Path, changed: Option<&Path>) {
+    output::blank();
+    match changed {
+        Some(path) => output::header(&format!("Verification Commands ({} changed)", path.display())),
+        None => output::header("Verification Commands"),
+    }
+    match prd::Prd::load(&args.prd) {
+        Ok(prd) if dry_run::verify_all(args, &prd, project_dir).await => output::success("All verifications passed"),
+        Ok(_) => output::warn("Some verifications failed"),
+        Err(e) => output::error(&format!("{e:#}")),
+    }
+}
+
+/// Waits for a change that counts and for things to settle, returning the first changed path
+/// relative to the project; None once the watcher is gone.
+async fn next_change(rx: &mut UnboundedReceiver<notify::Event>, filter: &ChangeFilter) -> Option<PathBuf> {
+    let changed = loop {
+        let event = rx.recv().await?;
+        // Reads by the commands themselves show up as access events
+        if matches!(event.kind, EventKind::Access(_)) {
+            continue;
+        }
+        if let Some(path) = event.paths.iter().find_map(|p| filter.relevant(p)) {
+            break path;
+        }
+    };
+    while let Ok(Some(_)) = tokio::time::timeout(SETTLE, rx.recv()).await {}
+    Some(changed)
+}
+
+/// Which changed paths count: not under [`SKIPPED_DIRS`], and not ignored by the project's
+/// top-level `.gitignore` or `.ignore`, which keeps build output from triggering runs.
+struct ChangeFilter {
+    root: PathBuf,
+    ignored: Gitignore,
+}
+
+impl ChangeFilter {
+    fn new(root: &Path) -> Self {
+        let mut builder = GitignoreBuilder::new(root);
+        for name in [".gitignore", ".ignore"] {
+            let path = root.join(name);
+            if path.exists() {
+                if let Some(e) = builder.add(&path) {
+                    output::warn(&format!("Failed to read {}: {e}", path.display()));
+                }
+            }
+        }
+        let ignored = builder.build().unwrap_or_else(|e| {
+            output::warn(&format!("Ignoring the project's ignore files: {e}"));
+            Gitignore::empty()
+        });
+        Self {
+            root: root.to_path_buf(),
+            ignored,
+        }
+    }
+
+    /// `path` relative to the project if a change to it counts.
+    fn relevant(&self, path: &Path) -> Option<PathBuf> {
+        let rel = path.strip_prefix(&self.root).ok()?;
+        if rel.components().any(|c| SKIPPED_DIRS.iter().any(|d| c.as_os_str() == *d)) {
+            return None;
+        }
+        let ignored = self.ignored.matched_path_or_any_parents(rel, path.is_dir()).is_ignore();
+        (!ignored).then(|| rel.to_path_buf())
+    }
+}
+
+pub async fn remote(url: &str) -> Result<()> {
+    let events_url = events_url(url);
+    let mut response = reqwest::get(&events_url)
+        .await
+        .with_context(|| format!("Failed to connect to {events_url}"))?;
+    if !response.status().is_success() {
+        bail!("{events_url} returned {}", response.status());
+    }
+    output::log(&format!("Watching {events_url} (Ctrl+C to stop)"));
+
+    let mut buffer = String::new();
+    while let Some(chunk) = response.chunk().await.context("Connection lost")? {
+        buffer.push_str(&String::from_utf8_lossy(&chunk));
+        while let Some(end) = buffer.find("\n\n") {
+            let block: String = buffer.drain(..end + 2).collect();
+            match parse_sse_event(block.trim_end_matches('\n')) {
+                Some(LiveEvent::Iteration(n)) => {
+                    println!();
+                    output::log(&format!("━━━ Iteration {n} ━━━"));
+                }
+                Some(LiveEvent::Line(line)) => println!("{line}"),
+                None => {}
+            }
+        }
+    }
+
+    output::warn("Remote closed the stream");
+    Ok(())
+}
+
+/// Accepts either the server root or the full events URL.
+fn events_url(url: &str) -> String {
+    let url = url.trim_end_matches('/');
+    if url.ends_with("/events") {
+        url.to_string()
+    } else {
+        format!("{url}/events")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn appends_events_path() {
+        assert_eq!(events_url("http://host:8080"), "http://host:8080/events");
+        assert_eq!(events_url("http://host:8080/"), "http://host:8080/events");
+    }
+
+    #[test]
+    fn keeps_explicit_events_path() {
+        assert_eq!(events_url("http://host:8080/events"), "http://host:8080/events");
+    }
+
+    #[test]
+    fn ignored_and_internal_changes_do_not_count() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+        std::fs::create_dir(root.join("target")).unwrap();
+        let filter = ChangeFilter::new(&root);
+
+        assert_eq!(filter.relevant(&root.join("src/lib.rs")), Some(PathBuf::from("src/lib.rs")));
+        assert_eq!(filter.relevant(&root.join("target/debug/app")), None);
+        assert_eq!(filter.relevant(&root.join("build.log")), None);
+        assert_eq!(filter.relevant(&root.join(".ralph/logs/verify-test.log")), None);
+        assert_eq!(filter.relevant(&root.join(".git/index")), None);
+        assert_eq!(filter.relevant(Path::new("/elsewhere/file")), None);
+    }
+}
@@ -1,12 +1,42 @@
-use crate::output;
+use crate::{
+    events::{EventSink, RunEvent},
+    output,
+    prd::Status,
+    redact,
+};
+use anyhow::{bail, Result};
 use chrono::Utc;
+use clap::ValueEnum;
 use serde::Serialize;
+use serde_json::{json, Value};
+use std::str::FromStr;
+use std::sync::{Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// How long `flush` waits for webhooks (and GitHub updates) still in flight when ralph exits.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where events go, set by [`configure`]; nothing is sent while it's empty.
+static TARGETS: RwLock<Vec<Target>> = RwLock::new(Vec::new());
+/// Events selected with `--webhook-events`; empty sends all of them.
+static EVENTS: RwLock<Vec<EventType>> = RwLock::new(Vec::new());
+static PENDING: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
 pub enum EventType {
     SessionStart,
     SessionComplete,
     SessionFailed,
+    ActionRequired,
+    MilestoneComplete,
+    SessionStopped,
+    IterationComplete,
+    IterationFailed,
+    FeatureComplete,
+    RateLimited,
 }
 
 impl EventType {
@@ -15,7 +45,90 @@ impl EventType {
             Self::SessionStart => "session_start",
             Self::SessionComplete => "session_complete",
             Self::SessionFailed => "session_failed",
+            Self::ActionRequired => "action_required",
+            Self::MilestoneComplete => "milestone_complete",
+            Self::SessionStopped => "session_stopped",
+            Self::IterationComplete => "iteration_complete",
+            Self::IterationFailed => "iteration_failed",
+            Self::FeatureComplete => "feature_complete",
+            Self::RateLimited => "rate_limited",
+        }
+    }
+
+    /// Heading for chat notifications.
+    fn title(self) -> &'static str {
+        match self {
+            Self::SessionStart => "Session started",
+            Self::SessionComplete => "Session complete",
+            Self::SessionFailed => "Session failed",
+            Self::ActionRequired => "Action required",
+            Self::MilestoneComplete => "Milestone reached",
+            Self::SessionStopped => "Session stopped",
+            Self::IterationComplete => "Iteration finished",
+            Self::IterationFailed => "Iteration failed",
+            Self::FeatureComplete => "Feature complete",
+            Self::RateLimited => "Rate limited",
+        }
+    }
+
+    /// Discord embed color: green for progress, red for failures, amber when someone should look.
+    fn color(self) -> u32 {
+        match self {
+            Self::SessionComplete | Self::MilestoneComplete | Self::FeatureComplete => 0x002E_B67D,
+            Self::SessionFailed | Self::IterationFailed => 0x00E0_1E5A,
+            Self::ActionRequired | Self::SessionStopped | Self::RateLimited => 0x00EC_B22E,
+            Self::SessionStart | Self::IterationComplete => 0x0036_C5F0,
+        }
+    }
+}
+
+/// How a target's request body is shaped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// ralph's own payload, for `--webhook`
+    Json,
+    /// Slack incoming webhook with blocks
+    Slack,
+    /// Discord webhook with an embed
+    Discord,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub format: Format,
+    pub url: String,
+}
+
+/// `--notify` values: `slack:<URL>` or `discord:<URL>`.
+impl FromStr for Target {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (kind, url) = s.split_once(':').unwrap_or((s, ""));
+        let format = match kind {
+            "slack" => Format::Slack,
+            "discord" => Format::Discord,
+            _ => bail!("expected slack:<URL> or discord:<URL>"),
+        };
+        if url.is_empty() {
+            bail!("missing URL after '{kind}:'");
         }
+        Ok(Self {
+            format,
+            url: url.to_string(),
+        })
+    }
+}
+
+impl Serialize for Target {
+    /// The URL is a secret; only the kind is kept.
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let kind = match self.format {
+            Format::Json => "webhook",
+            Format::Slack => "slack",
+            Format::Discord => "discord",
+        };
+        s.serialize_str(&format!("{kind}:<redacted>"))
     }
 }
 
@@ -24,36 +137,204 @@ struct WebhookPayload<'a> {
     event: &'a str,
     timestamp: String,
     message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    action: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iteration: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    feature: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    run_id: Option<String>,
 }
 
-pub fn send_webhook(url: &str, event: EventType, message: &str) {
-    let url = url.to_string();
-    let event_str = event.as_str();
-    let message = message.to_string();
+/// Sets where events go: the `--webhook` URL and any `--notify` targets.
+pub fn configure(webhook: Option<&str>, notify: &[Target], events: &[EventType]) {
+    let mut targets: Vec<Target> = webhook
+        .map(|url| Target {
+            format: Format::Json,
+            url: url.to_string(),
+        })
+        .into_iter()
+        .collect();
+    targets.extend_from_slice(notify);
+    *TARGETS.write().unwrap_or_else(|e| e.into_inner()) = targets;
+    *EVENTS.write().unwrap_or_else(|e| e.into_inner()) = events.to_vec();
+}
 
-    tokio::spawn(async move {
-        let payload = WebhookPayload {
-            event: event_str,
-            timestamp: Utc::now().to_rfc3339(),
-            message: &message,
-        };
+fn enabled(event: EventType) -> bool {
+    let events = EVENTS.read().unwrap_or_else(|e| e.into_inner());
+    events.is_empty() || events.contains(&event)
+}
 
-        let client = reqwest::Client::new();
-        match client.post(&url).json(&payload).send().await {
-            Ok(resp) if resp.status().is_success() => {
-                output::dim(&format!("Webhook sent: {event_str}"));
+/// Sends the run events webhooks announce; agent output and the like are skipped.
+pub struct WebhookSink;
+
+impl EventSink for WebhookSink {
+    fn handle(&self, event: &RunEvent) {
+        match event {
+            RunEvent::SessionStarted { project } => {
+                send(EventType::SessionStart, &format!("Starting session for {project}"), None, None, None);
+            }
+            // A stopped iteration is announced by the stop itself
+            RunEvent::IterationFinished(artifact) if artifact.result != "operator_stop" => {
+                let event = match artifact.result.as_str() {
+                    "continue" | "complete" => EventType::IterationComplete,
+                    "rate_limit" => EventType::RateLimited,
+                    _ => EventType::IterationFailed,
+                };
+                let message = format!("Iteration {} finished: {}", artifact.iteration, artifact.result);
+                send(event, &message, None, Some(artifact.iteration), artifact.feature_id.as_deref());
+            }
+            RunEvent::FeatureStatusChanged { iteration, feature, status: Status::Complete } => {
+                let message = format!("Feature '{feature}' complete in iteration {iteration}");
+                send(EventType::FeatureComplete, &message, None, Some(*iteration), Some(feature));
             }
-            Ok(resp) => {
-                output::warn(&format!(
-                    "Webhook returned {}: {event_str}",
-                    resp.status()
-                ));
+            RunEvent::MilestoneReached { iteration, phase } => {
+                let message = format!("Milestone '{phase}' reached in iteration {iteration}");
+                send(EventType::MilestoneComplete, &message, None, None, None);
             }
-            Err(e) => {
-                output::warn(&format!("Webhook failed: {e}"));
+            RunEvent::ActionRequired { message, action } => {
+                send(EventType::ActionRequired, message, Some(action), None, None);
+            }
+            RunEvent::Completed { iterations } => {
+                let message = format!("Session complete after {iterations} iterations");
+                send(EventType::SessionComplete, &message, None, None, None);
+            }
+            RunEvent::Stopped { iterations } => {
+                let message = format!("Stopped by operator after {iterations} iterations");
+                send(EventType::SessionStopped, &message, None, None, None);
+            }
+            RunEvent::Failed { iterations, reason } => {
+                let message = format!("Session failed after {iterations} iterations: {reason}");
+                send(EventType::SessionFailed, &message, None, None, None);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Waits for webhooks and [`track`]ed requests still in flight, so exiting doesn't drop them.
+pub async fn flush() {
+    let pending = std::mem::take(&mut *PENDING.lock().unwrap_or_else(|e| e.into_inner()));
+    if pending.is_empty() {
+        return;
+    }
+    if tokio::time::timeout(FLUSH_TIMEOUT, futures::future::join_all(pending)).await.is_err() {
+        output::warn("Gave up waiting for webhook delivery");
+    }
+}
+
+fn send(event: EventType, message: &str, action: Option<&str>, iteration: Option<u32>, feature: Option<&str>) {
+    if !enabled(event) {
+        return;
+    }
+    let targets = TARGETS.read().unwrap_or_else(|e| e.into_inner()).clone();
+    if targets.is_empty() {
+        return;
+    }
+    let payload = WebhookPayload {
+        event: event.as_str(),
+        timestamp: Utc::now().to_rfc3339(),
+        message,
+        action,
+        iteration,
+        feature,
+        run_id: crate::run_id::current(),
+    };
+    let bodies: Vec<(Target, Value)> = targets
+        .into_iter()
+        .map(|target| {
+            let mut body = match target.format {
+                Format::Json => json!(payload),
+                Format::Slack => slack_body(event, &payload),
+                Format::Discord => discord_body(event, &payload),
+            };
+            // Messages can quote agent output
+            redact::value(&mut body);
+            (target, body)
+        })
+        .collect();
+    let event_str = event.as_str();
+
+    let handle = tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        for (target, body) in bodies {
+            match client.post(&target.url).json(&body).send().await {
+                Ok(resp) if resp.status().is_success() => {
+                    output::dim(&format!("Webhook sent: {event_str}"));
+                }
+                Ok(resp) => {
+                    output::warn(&format!(
+                        "Webhook returned {}: {event_str}",
+                        resp.status()
+                    ));
+                }
+                Err(e) => {
+                    output::warn(&format!("Webhook failed: {e}"));
+                }
             }
         }
     });
+    track(handle);
+}
+
+/// Makes [`flush`] wait for `handle` too.
+pub(crate) fn track(handle: JoinHandle<()>) {
+    let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+    pending.retain(|h| !h.is_finished());
+    pending.push(handle);
+}
+
+/// e.g. `iteration 4 · feature auth · run ci-42`, for the footer of chat notifications.
+fn context_line(payload: &WebhookPayload<'_>) -> Option<String> {
+    let parts: Vec<String> = payload
+        .iteration
+        .map(|n| format!("iteration {n}"))
+        .into_iter()
+        .chain(payload.feature.map(|f| format!("feature {f}")))
+        .chain(payload.run_id.as_ref().map(|id| format!("run {id}")))
+        .collect();
+    (!parts.is_empty()).then(|| parts.join(" · "))
+}
+
+fn slack_body(event: EventType, payload: &WebhookPayload<'_>) -> Value {
+    let mut blocks = vec![
+        json!({ "type": "header", "text": { "type": "plain_text", "text": format!("ralph: {}", event.title()) } }),
+        json!({ "type": "section", "text": { "type": "mrkdwn", "text": payload.message } }),
+    ];
+    if let Some(action) = payload.action {
+        blocks.push(json!({ "type": "section", "text": { "type": "mrkdwn", "text": format!("*Next:* {action}") } }));
+    }
+    if let Some(context) = context_line(payload) {
+        blocks.push(json!({ "type": "context", "elements": [{ "type": "mrkdwn", "text": context }] }));
+    }
+    // `text` is the fallback shown in notifications
+    json!({ "text": format!("{}: {}", event.title(), payload.message), "blocks": blocks })
+}
+
+fn discord_body(event: EventType, payload: &WebhookPayload<'_>) -> Value {
+    let mut fields = Vec::new();
+    if let Some(iteration) = payload.iteration {
+        fields.push(json!({ "name": "Iteration", "value": iteration.to_string(), "inline": true }));
+    }
+    if let Some(feature) = payload.feature {
+        fields.push(json!({ "name": "Feature", "value": feature, "inline": true }));
+    }
+    if let Some(ref run_id) = payload.run_id {
+        fields.push(json!({ "name": "Run", "value": run_id, "inline": true }));
+    }
+    if let Some(action) = payload.action {
+        fields.push(json!({ "name": "Next", "value": action }));
+    }
+    json!({
+        "embeds": [{
+            "title": format!("ralph: {}", event.title()),
+            "description": payload.message,
+            "color": event.color(),
+            "timestamp": payload.timestamp,
+            "fields": fields,
+        }]
+    })
 }
 
 #[cfg(test)]
@@ -65,6 +346,8 @@ mod tests {
         assert_eq!(EventType::SessionStart.as_str(), "session_start");
         assert_eq!(EventType::SessionComplete.as_str(), "session_complete");
         assert_eq!(EventType::SessionFailed.as_str(), "session_failed");
+        assert_eq!(EventType::ActionRequired.as_str(), "action_required");
+        assert_eq!(EventType::MilestoneComplete.as_str(), "milestone_complete");
     }
 
     #[test]
@@ -79,10 +362,129 @@ mod tests {
             event: "session_start",
             timestamp: "2024-01-15T10:30:00Z".to_string(),
             message: "Starting session",
+            action: None,
+            iteration: None,
+            feature: None,
+            run_id: None,
         };
         let json = serde_json::to_string(&payload).unwrap();
         assert!(json.contains("\"event\":\"session_start\""));
         assert!(json.contains("\"timestamp\":\"2024-01-15T10:30:00Z\""));
         assert!(json.contains("\"message\":\"Starting session\""));
+        assert!(!json.contains("action"));
+    }
+
+    #[test]
+    fn action_required_payload_includes_action() {
+        let payload = WebhookPayload {
+            event: "action_required",
+            timestamp: "2024-01-15T10:30:00Z".to_string(),
+            message: "Feature blocked",
+            action: Some("ralph -p prd.jsonc"),
+            iteration: None,
+            feature: None,
+            run_id: None,
+        };
+        let json = serde_json::to_string(&payload).unwrap();
+        assert!(json.contains("\"action\":\"ralph -p prd.jsonc\""));
+    }
+
+    #[test]
+    fn iteration_payload_includes_iteration_and_feature() {
+        let payload = WebhookPayload {
+            event: "feature_complete",
+            timestamp: "2024-01-15T10:30:00Z".to_string(),
+            message: "Feature 'auth' complete",
+            action: None,
+            iteration: Some(4),
+            feature: Some("auth"),
+            run_id: Some("ci-42".into()),
+        };
+        let json = serde_json::to_string(&payload).unwrap();
+        assert!(json.contains("\"iteration\":4"));
+        assert!(json.contains("\"feature\":\"auth\""));
+        assert!(json.contains("\"run_id\":\"ci-42\""));
+    }
+
+    #[test]
+    fn value_names_match_payload_names() {
+        for event in EventType::value_variants() {
+            assert_eq!(event.to_possible_value().unwrap().get_name(), event.as_str());
+        }
+    }
+
+    #[test]
+    fn event_filter() {
+        configure(None, &[], &[EventType::SessionComplete]);
+        assert!(enabled(EventType::SessionComplete));
+        assert!(!enabled(EventType::IterationComplete));
+        configure(None, &[], &[]);
+        assert!(enabled(EventType::IterationComplete));
+    }
+
+    fn iteration_payload() -> WebhookPayload<'static> {
+        WebhookPayload {
+            event: "feature_complete",
+            timestamp: "2024-01-15T10:30:00Z".to_string(),
+            message: "Feature 'auth' complete in iteration 4",
+            action: None,
+            iteration: Some(4),
+            feature: Some("auth"),
+            run_id: None,
+        }
+    }
+
+    #[test]
+    fn parses_notify_targets() {
+        let slack: Target = "slack:https://hooks.slack.com/services/T/B/x".parse().unwrap();
+        assert_eq!(slack.format, Format::Slack);
+        assert_eq!(slack.url, "https://hooks.slack.com/services/T/B/x");
+        let discord: Target = "discord:https://discord.com/api/webhooks/1/x".parse().unwrap();
+        assert_eq!(discord.format, Format::Discord);
+        assert!("https://example.com".parse::<Target>().is_err());
+        assert!("slack:".parse::<Target>().is_err());
+    }
+
+    #[test]
+    fn target_serializes_without_url() {
+        let target: Target = "discord:https://discord.com/api/webhooks/1/secret".parse().unwrap();
+        assert_eq!(serde_json::to_string(&target).unwrap(), "\"discord:<redacted>\"");
+    }
+
+    #[test]
+    fn slack_body_has_header_message_and_context() {
+        let body = slack_body(EventType::FeatureComplete, &iteration_payload());
+        assert_eq!(body["text"], "Feature complete: Feature 'auth' complete in iteration 4");
+        let blocks = body["blocks"].as_array().unwrap();
+        assert_eq!(blocks[0]["text"]["text"], "ralph: Feature complete");
+        assert_eq!(blocks[1]["text"]["text"], "Feature 'auth' complete in iteration 4");
+        assert_eq!(blocks[2]["elements"][0]["text"], "iteration 4 · feature auth");
+    }
+
+    #[test]
+    fn slack_body_shows_action() {
+        let payload = WebhookPayload {
+            event: "action_required",
+            timestamp: "2024-01-15T10:30:00Z".to_string(),
+            message: "Feature blocked",
+            action: Some("ralph --resume"),
+            iteration: None,
+            feature: None,
+            run_id: None,
+        };
+        let blocks = slack_body(EventType::ActionRequired, &payload)["blocks"].clone();
+        assert_eq!(blocks.as_array().unwrap().len(), 3);
+        assert_eq!(blocks[2]["text"]["text"], "*Next:* ralph --resume");
+    }
+
+    #[test]
+    fn discord_body_is_an_embed() {
+        let body = discord_body(EventType::SessionFailed, &iteration_payload());
+        let embed = &body["embeds"][0];
+        assert_eq!(embed["title"], "ralph: Session failed");
+        assert_eq!(embed["color"], 0x00E0_1E5A);
+        assert_eq!(embed["timestamp"], "2024-01-15T10:30:00Z");
+        assert_eq!(embed["fields"][0]["value"], "4");
+        assert_eq!(embed["fields"][1]["value"], "auth");
     }
 }